@@ -1,9 +1,12 @@
 use firewheel::event::{InputEvent, PointerEvent};
 use firewheel::vg::{Color, Paint, Path};
-use firewheel::widgets::{LabelButton, LabelButtonEvent, LabelButtonStyle};
+use firewheel::widgets::{
+    resize_container_region, LabelButton, LabelButtonEvent, LabelButtonStyle, ResizeEdge,
+    ResizeHandle, ResizeHandleStyle,
+};
 use firewheel::{
-    Anchor, AppWindow, BackgroundNode, PaintRegionInfo, ParentAnchorType, PhysicalSize, Point,
-    RegionInfo, VG,
+    Anchor, AnchorOffset, AppWindow, AppWindowSettings, BackgroundNode, Edges, PaintRegionInfo,
+    ParentAnchorType, PhysicalSize, Point, RegionInfo, ScaleFactor, Size, VG,
 };
 use glutin::config::{ConfigSurfaceTypes, ConfigTemplateBuilder, GlConfig};
 use glutin::context::{ContextApi, ContextAttributesBuilder, NotCurrentGlContextSurfaceAccessor};
@@ -110,17 +113,23 @@ fn main() {
     let mut app_window = AppWindow::<MyAction>::new_from_glutin_display(
         window.scale_factor().into(),
         &gl_display,
+        None,
+        AppWindowSettings::default(),
         action_tx,
     );
+    app_window.set_clear_color(Color::rgb(30, 30, 30));
 
     let mut window_size = PhysicalSize::new(window.inner_size().width, window.inner_size().height);
-    let mut scale_factor = window.scale_factor().into();
+    let mut scale_factor: ScaleFactor = window.scale_factor().into();
     let window_logical_size = window_size.to_logical(scale_factor);
 
-    let main_font_id = app_window
-        .vg()
-        .add_font("examples/assets/Roboto-Regular.ttf")
-        .unwrap();
+    let main_font_id = match app_window.add_font("examples/assets/Roboto-Regular.ttf") {
+        Ok(font_id) => font_id,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     let mut test_background_node_ref = app_window.add_background_node(
         window_logical_size,
@@ -138,6 +147,14 @@ fn main() {
         true,
     );
 
+    // Both layers are resized along with the window in `commit_resize()`.
+    app_window
+        .set_background_node_fills_window(&mut test_background_node_ref, true)
+        .unwrap();
+    app_window
+        .set_widget_layer_fills_window(&mut widget_layer_ref, true)
+        .unwrap();
+
     let label_button_style = Rc::new(LabelButtonStyle::default());
 
     let mut button_msg_i: usize = 0;
@@ -163,12 +180,56 @@ fn main() {
                 internal_anchor: Anchor::center(),
                 parent_anchor: Anchor::center(),
                 parent_anchor_type: ParentAnchorType::Layer,
-                anchor_offset: Point::new(0.0, 0.0),
+                anchor_offset: AnchorOffset::default(),
+                normalized_parent_anchor: None,
             },
             true,
         )
         .unwrap();
 
+    // A side panel on the left of the window that can be resized by dragging
+    // the handle along its right edge.
+    let mut side_panel_ref = app_window
+        .add_container_region(
+            &widget_layer_ref,
+            RegionInfo::pinned(
+                Edges {
+                    left: Some(0.0),
+                    top: Some(0.0),
+                    bottom: Some(0.0),
+                    ..Edges::default()
+                },
+                Size::new(SIDE_PANEL_WIDTH, 0.0),
+                ParentAnchorType::Layer,
+            ),
+            true,
+        )
+        .unwrap();
+    app_window
+        .add_widget_node(
+            Box::new(ResizeHandle::new(
+                ResizeEdge::Right,
+                SIDE_PANEL_WIDTH,
+                SIDE_PANEL_MIN_WIDTH,
+                SIDE_PANEL_MAX_WIDTH,
+                Rc::new(ResizeHandleStyle::default()),
+                Box::new(MyAction::SidePanelResized),
+            )),
+            &widget_layer_ref,
+            RegionInfo::pinned(
+                Edges {
+                    right: Some(0.0),
+                    top: Some(0.0),
+                    bottom: Some(0.0),
+                    ..Edges::default()
+                },
+                Size::new(6.0, 0.0),
+                ParentAnchorType::ContainerRegion(side_panel_ref.clone()),
+            ),
+            true,
+        )
+        .unwrap();
+
     // --- Run event loop --------------------------------------------------------------
 
     let mut pointer_event_state = PointerEvent::default();
@@ -181,52 +242,28 @@ fn main() {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::Resized(physical_size) => {
                 if physical_size.width != 0 && physical_size.height != 0 {
-                    gl_surface.resize(
-                        &current_gl_context,
-                        NonZeroU32::new(physical_size.width).unwrap(),
-                        NonZeroU32::new(physical_size.height).unwrap(),
-                    );
-
-                    window_size = PhysicalSize::new(physical_size.width, physical_size.height);
-                    let window_logical_size = window_size.to_logical(scale_factor);
-
-                    app_window
-                        .set_background_node_size(
-                            &mut test_background_node_ref,
-                            window_logical_size,
-                        )
-                        .unwrap();
-                    app_window
-                        .set_widget_layer_size(&mut widget_layer_ref, window_logical_size)
-                        .unwrap();
+                    // The resize is applied once per frame in `RedrawRequested`.
+                    app_window.set_pending_window_size(PhysicalSize::new(
+                        physical_size.width,
+                        physical_size.height,
+                    ));
                 }
             }
             WindowEvent::ScaleFactorChanged {
                 scale_factor: window_scale_factor,
                 new_inner_size,
             } => {
+                scale_factor = (*window_scale_factor).into();
+                app_window.set_scale_factor(scale_factor);
+
+                // Relayout at the new scale factor even if the physical size
+                // of the window stayed the same.
+                app_window.set_pending_window_size(window_size);
                 if new_inner_size.width != 0 && new_inner_size.height != 0 {
-                    gl_surface.resize(
-                        &current_gl_context,
-                        NonZeroU32::new(new_inner_size.width).unwrap(),
-                        NonZeroU32::new(new_inner_size.height).unwrap(),
-                    );
-
-                    scale_factor = (*window_scale_factor).into();
-                    app_window.set_scale_factor(scale_factor);
-
-                    window_size = PhysicalSize::new(new_inner_size.width, new_inner_size.height);
-                    let window_logical_size = window_size.to_logical(scale_factor);
-
-                    app_window
-                        .set_background_node_size(
-                            &mut test_background_node_ref,
-                            window_logical_size,
-                        )
-                        .unwrap();
-                    app_window
-                        .set_widget_layer_size(&mut widget_layer_ref, window_logical_size)
-                        .unwrap();
+                    app_window.set_pending_window_size(PhysicalSize::new(
+                        new_inner_size.width,
+                        new_inner_size.height,
+                    ));
                 }
             }
             WindowEvent::ModifiersChanged(m) => {
@@ -236,6 +273,9 @@ fn main() {
                 pointer_event_state.update_from_winit_cursor_moved(*position, scale_factor);
                 app_window.handle_input_event(&InputEvent::Pointer(pointer_event_state.clone()));
             }
+            WindowEvent::CursorLeft { .. } => {
+                app_window.handle_input_event(&InputEvent::PointerLeftWindow);
+            }
             WindowEvent::MouseInput { state, button, .. } => {
                 pointer_event_state.update_from_winit_mouse_input(state, button);
                 app_window.handle_input_event(&InputEvent::Pointer(pointer_event_state.clone()));
@@ -247,7 +287,17 @@ fn main() {
             _ => {}
         },
         Event::RedrawRequested(window_id) if window_id == window.id() => {
-            app_window.render(window_size, Color::rgb(30, 30, 30));
+            if let Some(new_window_size) = app_window.commit_resize() {
+                gl_surface.resize(
+                    &current_gl_context,
+                    NonZeroU32::new(new_window_size.width).unwrap(),
+                    NonZeroU32::new(new_window_size.height).unwrap(),
+                );
+
+                window_size = new_window_size;
+            }
+
+            app_window.render(window_size);
 
             gl_surface.swap_buffers(&current_gl_context).unwrap();
 
@@ -289,6 +339,15 @@ fn main() {
                             )
                             .unwrap();
                     }
+                    MyAction::SidePanelResized(width) => {
+                        resize_container_region(
+                            &mut app_window,
+                            &mut side_panel_ref,
+                            ResizeEdge::Right,
+                            width,
+                        )
+                        .unwrap();
+                    }
                 }
             }
 
@@ -306,8 +365,13 @@ fn main() {
 #[derive(Debug, Clone)]
 enum MyAction {
     LabelButtonPressed,
+    SidePanelResized(f32),
 }
 
+const SIDE_PANEL_WIDTH: f32 = 200.0;
+const SIDE_PANEL_MIN_WIDTH: f32 = 100.0;
+const SIDE_PANEL_MAX_WIDTH: f32 = 400.0;
+
 const BUTTON_MESSAGES: [&str; 5] = [
     "Hello World!",
     "Click Me!",
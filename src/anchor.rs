@@ -1,3 +1,5 @@
+use crate::size::{Point, Rect, Size};
+
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct Anchor {
     pub h_align: HAlign,
@@ -73,6 +75,82 @@ impl Anchor {
     }
 }
 
+/// An anchor point at an arbitrary position within a rect, where `x` and `y`
+/// are normalized to the size of the rect (`(0.0, 0.0)` is the top-left
+/// corner and `(1.0, 1.0)` is the bottom-right corner).
+///
+/// Values outside of the range `[0.0, 1.0]` are allowed, and place the
+/// point outside of the rect.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct NormalizedAnchor {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl NormalizedAnchor {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// The point in the given rect that this anchor refers to.
+    pub fn resolve(&self, rect: Rect) -> Point {
+        Point::new(
+            rect.x() + (f64::from(rect.width()) * f64::from(self.x)),
+            rect.y() + (f64::from(rect.height()) * f64::from(self.y)),
+        )
+    }
+
+    /// The equivalent discrete `Anchor`, or `None` if this anchor is not
+    /// exactly on one of its alignments.
+    pub fn to_anchor(&self) -> Option<Anchor> {
+        let h_align = if self.x == 0.0 {
+            HAlign::Left
+        } else if self.x == 0.5 {
+            HAlign::Center
+        } else if self.x == 1.0 {
+            HAlign::Right
+        } else {
+            return None;
+        };
+        let v_align = if self.y == 0.0 {
+            VAlign::Top
+        } else if self.y == 0.5 {
+            VAlign::Center
+        } else if self.y == 1.0 {
+            VAlign::Bottom
+        } else {
+            return None;
+        };
+
+        Some(Anchor::new(h_align, v_align))
+    }
+
+    /// The anchor mirrored vertically (`y` becomes `1.0 - y`).
+    pub(crate) fn flipped_y(&self) -> Self {
+        Self {
+            x: self.x,
+            y: 1.0 - self.y,
+        }
+    }
+}
+
+impl From<Anchor> for NormalizedAnchor {
+    fn from(anchor: Anchor) -> Self {
+        let x = match anchor.h_align {
+            HAlign::Left => 0.0,
+            HAlign::Center => 0.5,
+            HAlign::Right => 1.0,
+        };
+        let y = match anchor.v_align {
+            VAlign::Top => 0.0,
+            VAlign::Center => 0.5,
+            VAlign::Bottom => 1.0,
+        };
+
+        Self { x, y }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HAlign {
     Left,
@@ -93,8 +171,156 @@ pub enum VAlign {
     Bottom,
 }
 
+impl VAlign {
+    /// The alignment on the opposite side (`Top` and `Bottom` are swapped).
+    pub(crate) fn flipped(&self) -> Self {
+        match self {
+            VAlign::Top => VAlign::Bottom,
+            VAlign::Center => VAlign::Center,
+            VAlign::Bottom => VAlign::Top,
+        }
+    }
+}
+
 impl Default for VAlign {
     fn default() -> Self {
         VAlign::Top
     }
 }
+
+/// The offset between a region's internal anchor point and its parent's
+/// anchor point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnchorOffset {
+    /// An offset in logical coordinates (points).
+    Points(Point),
+    /// An offset relative to the size of the parent region, where `x` and `y`
+    /// are percentages (in the range `[0.0..100.0]`) of the parent's width
+    /// and height.
+    Percent(Point),
+    /// Keep the margins to the pinned edges of the parent region (see
+    /// `RegionInfo::pinned()`).
+    Pinned(Edges),
+}
+
+impl AnchorOffset {
+    /// Resolve this offset into logical coordinates (points) given the size
+    /// of the parent region.
+    #[inline]
+    pub fn resolve(&self, parent_size: Size) -> Point {
+        match self {
+            AnchorOffset::Points(offset) => *offset,
+            AnchorOffset::Percent(percent) => Point::new(
+                f64::from(parent_size.width()) * percent.x / 100.0,
+                f64::from(parent_size.height()) * percent.y / 100.0,
+            ),
+            AnchorOffset::Pinned(edges) => edges.offset(),
+        }
+    }
+
+    #[inline]
+    pub fn partial_eq_with_epsilon(&self, other: AnchorOffset) -> bool {
+        match (self, other) {
+            (AnchorOffset::Points(a), AnchorOffset::Points(b)) => a.partial_eq_with_epsilon(b),
+            (AnchorOffset::Percent(a), AnchorOffset::Percent(b)) => a.partial_eq_with_epsilon(b),
+            (AnchorOffset::Pinned(a), AnchorOffset::Pinned(b)) => *a == b,
+            _ => false,
+        }
+    }
+}
+
+/// The margins (in points) between a region and the edges of its parent
+/// region that the region is pinned to (see `RegionInfo::pinned()`).
+///
+/// An edge that is `None` is not pinned.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Edges {
+    pub left: Option<f32>,
+    pub right: Option<f32>,
+    pub top: Option<f32>,
+    pub bottom: Option<f32>,
+}
+
+impl Edges {
+    /// Pin all four edges with the same margin.
+    pub fn all(margin: f32) -> Self {
+        Self {
+            left: Some(margin),
+            right: Some(margin),
+            top: Some(margin),
+            bottom: Some(margin),
+        }
+    }
+
+    /// The size of a region with these pinned edges inside a parent region
+    /// of the given size.
+    ///
+    /// A region that is pinned to both edges of an axis stretches to fill
+    /// the parent minus the margins on that axis, down to a size of zero if
+    /// the margins are larger than the parent. Otherwise the region keeps
+    /// its size on that axis.
+    pub fn pinned_size(&self, size: Size, parent_size: Size) -> Size {
+        let width = if let (Some(left), Some(right)) = (self.left, self.right) {
+            (parent_size.width() - left - right).max(0.0)
+        } else {
+            size.width()
+        };
+        let height = if let (Some(top), Some(bottom)) = (self.top, self.bottom) {
+            (parent_size.height() - top - bottom).max(0.0)
+        } else {
+            size.height()
+        };
+
+        Size::new(width, height)
+    }
+
+    /// The anchor (used as both the internal and the parent anchor) of a
+    /// region with these pinned edges.
+    ///
+    /// A region is anchored to the left (top) edge if it is pinned to it,
+    /// otherwise to the right (bottom) edge if it is pinned to that, and
+    /// otherwise it is centered on that axis.
+    pub fn anchor(&self) -> Anchor {
+        let h_align = match (self.left, self.right) {
+            (Some(_), _) => HAlign::Left,
+            (None, Some(_)) => HAlign::Right,
+            (None, None) => HAlign::Center,
+        };
+        let v_align = match (self.top, self.bottom) {
+            (Some(_), _) => VAlign::Top,
+            (None, Some(_)) => VAlign::Bottom,
+            (None, None) => VAlign::Center,
+        };
+
+        Anchor::new(h_align, v_align)
+    }
+
+    /// The offset from the anchor point of the parent region to the anchor
+    /// point of a region with these pinned edges (see `Edges::anchor()`).
+    pub fn offset(&self) -> Point {
+        let x = match (self.left, self.right) {
+            (Some(left), _) => f64::from(left),
+            (None, Some(right)) => -f64::from(right),
+            (None, None) => 0.0,
+        };
+        let y = match (self.top, self.bottom) {
+            (Some(top), _) => f64::from(top),
+            (None, Some(bottom)) => -f64::from(bottom),
+            (None, None) => 0.0,
+        };
+
+        Point::new(x, y)
+    }
+}
+
+impl Default for AnchorOffset {
+    fn default() -> Self {
+        AnchorOffset::Points(Point::default())
+    }
+}
+
+impl From<Point> for AnchorOffset {
+    fn from(p: Point) -> Self {
+        AnchorOffset::Points(p)
+    }
+}
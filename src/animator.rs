@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+/// The curve that an `Animator` follows from its start value to its end
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly and speeds up towards the end (cubic).
+    EaseIn,
+    /// Starts quickly and slows down towards the end (cubic). This is a
+    /// good fit for landing a knob or a fader on a new value.
+    EaseOut,
+    /// Starts and ends slowly (cubic).
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map the normalized time `t` in the range `[0.0, 1.0]` to the
+    /// normalized progress of the animation.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => {
+                let inv = 1.0 - t;
+                1.0 - inv * inv * inv
+            }
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let inv = -2.0 * t + 2.0;
+                    1.0 - inv * inv * inv / 2.0
+                }
+            }
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+/// Tweens a value from a start value to an end value over a duration.
+///
+/// An animator can be stepped manually, or it can be registered with
+/// `AppWindow::start_animator()` to be driven by the animation events of
+/// the window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animator {
+    from: f32,
+    to: f32,
+    duration: Duration,
+    easing: Easing,
+    elapsed: Duration,
+}
+
+impl Animator {
+    pub fn new(from: f32, to: f32, duration: Duration, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Advance the animation by the given amount of time and return the
+    /// new value.
+    pub fn step(&mut self, time_delta: Duration) -> f32 {
+        self.elapsed = (self.elapsed + time_delta).min(self.duration);
+        self.value()
+    }
+
+    /// The current value of the animation.
+    pub fn value(&self) -> f32 {
+        if self.is_finished() {
+            return self.to;
+        }
+
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    /// Whether the animation has reached its end value.
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn from(&self) -> f32 {
+        self.from
+    }
+
+    pub fn to(&self) -> f32 {
+        self.to
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn easing(&self) -> Easing {
+        self.easing
+    }
+}
+
+/// The ID of an animator registered with `AppWindow::start_animator()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnimatorId(pub u64);
+
+/// The user event sent to the target widget of an animator on every
+/// animation frame while the animator is running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatorUpdate {
+    pub id: AnimatorId,
+    pub value: f32,
+    /// Whether this is the last update of the animator. The value of the
+    /// last update is always the end value.
+    pub finished: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animator_reaches_target() {
+        let duration = Duration::from_millis(200);
+        let frame = Duration::from_millis(50);
+
+        let mut animator = Animator::new(10.0, 20.0, duration, Easing::Linear);
+        assert_eq!(animator.value(), 10.0);
+
+        assert!((animator.step(frame) - 12.5).abs() < 0.0001);
+        // The midpoint.
+        assert!((animator.step(frame) - 15.0).abs() < 0.0001);
+        animator.step(frame);
+        assert!(!animator.is_finished());
+        assert_eq!(animator.step(frame), 20.0);
+        assert!(animator.is_finished());
+
+        // Stepping past the end keeps the end value.
+        assert_eq!(animator.step(frame), 20.0);
+    }
+
+    #[test]
+    fn test_easing_curves() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert!((easing.apply(1.0) - 1.0).abs() < 0.0001);
+        }
+
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 0.0001);
+
+        // An ease-out animator is past the midpoint halfway through.
+        let mut animator = Animator::new(0.0, 1.0, Duration::from_secs(1), Easing::EaseOut);
+        assert!((animator.step(Duration::from_millis(500)) - 0.875).abs() < 0.0001);
+    }
+}
@@ -1,78 +1,209 @@
-use crossbeam_channel::Sender;
-use femtovg::Color;
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use femtovg::{Color, TextContext};
+use fnv::FnvHashMap;
 use std::any::Any;
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{Ref, RefCell};
 use std::ffi::c_void;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::anchor::Anchor;
+use crate::anchor::{Anchor, AnchorOffset, Edges};
+use crate::animator::{Animator, AnimatorId, AnimatorUpdate};
 use crate::error::FirewheelError;
-use crate::event::{InputEvent, KeyboardEventsListen};
+use crate::event::{
+    CompositionEvent, CompositionState, InputEvent, KeyboardEvent, KeyboardEventsListen,
+    PointerButton, PointerEvent, PointerSnap, WidgetGroupId,
+};
+#[cfg(feature = "input-recording")]
+use crate::input_recording::{replayed_events, InputLog, InputRecorder};
 use crate::layer::{
     BackgroundLayer, StrongBackgroundLayerEntry, StrongLayerEntry, StrongWidgetLayerEntry,
     WeakRegionTreeEntry, WidgetLayer, WidgetLayerRef,
 };
+use crate::loaded_assets::LoadedAssets;
 use crate::node::{
     BackgroundNodeRef, SetPointerLockType, StrongBackgroundNodeEntry, StrongWidgetNodeEntry,
-    WidgetNode, WidgetNodeRef,
+    WeakWidgetNodeEntry, WidgetNode, WidgetNodeRef,
+};
+use crate::pointer_capture::PointerCaptureStack;
+use crate::pointer_velocity::PointerVelocityTracker;
+use crate::popup::PopupPlacement;
+use crate::renderer::unpremultiply_rgba;
+use crate::renderer::{
+    capture_widget_image, BackgroundLayerRenderer, ColorSpace, Renderer, WidgetLayerRenderer,
 };
-use crate::renderer::{BackgroundLayerRenderer, Renderer, WidgetLayerRenderer};
+use crate::shared_resources::SharedResources;
+#[cfg(feature = "software-text")]
+use crate::software_text::{with_active_renderer, SoftwareTextRenderer};
+use crate::spatial_navigation::{nearest_in_direction, Direction};
+use crate::stack_layout::{Axis, StackAlign, StackLayout};
+use crate::text_layout_cache::TextLayoutCache;
 use crate::widget_node_set::WidgetNodeSet;
 use crate::{
-    BackgroundNode, ContainerRegionRef, EventCapturedStatus, PhysicalSize, Point, RegionInfo,
-    ScaleFactor, Size, WidgetNodeRequests, VG,
+    BackgroundNode, BlendMode, Compositing, ContainerRegionRef, CoordinateOrigin,
+    EventCapturedStatus, ParentAnchorType, PhysicalPoint, PhysicalRect, PhysicalSize, Point, Rect,
+    RegionInfo, ScaleFactor, ScaleFactor2D, ScrollBehavior, Size, WidgetNodeRequests, VG,
 };
 
 pub struct AppWindow<A: Clone + Send + Sync + 'static> {
     pub(crate) layers_ordered: Vec<(i32, Vec<StrongLayerEntry<A>>)>,
     pub(crate) widget_layer_renderers_to_clean_up: Vec<WidgetLayerRenderer>,
     pub(crate) background_layer_renderers_to_clean_up: Vec<BackgroundLayerRenderer>,
+    pub(crate) post_composite: Option<Box<dyn FnMut(&mut VG, PhysicalSize)>>,
+    pub(crate) window_mask: Option<femtovg::Path>,
 
+    /// The sender given to widgets. While an action queue limit is set,
+    /// this sends to a queue owned by the window instead of the host's.
     action_tx: Sender<A>,
+    action_queue_limit: Option<ActionQueueLimit<A>>,
+    action_queue_full: bool,
+    texture_memory_budget: Option<usize>,
+    hidden_layer_texture_timeout: Option<Duration>,
+    always_full_repaint: bool,
+    report_overlapping_dirty_widgets: bool,
 
     next_layer_id: u64,
     next_widget_id: u64,
 
     widget_with_pointer_lock: Option<(StrongWidgetNodeEntry<A>, SetPointerLockType)>,
+    /// Whether the widget holding the pointer lock was removed or hidden
+    /// since the last `InputEventResult` was returned.
+    pointer_lock_released: bool,
+    pointer_capture_stack: PointerCaptureStack<StrongWidgetNodeEntry<A>>,
     widgets_to_send_input_event: Vec<(StrongWidgetNodeEntry<A>, InputEvent)>,
     widget_with_text_comp_listen: Option<StrongWidgetNodeEntry<A>>,
     widgets_with_keyboard_listen: WidgetNodeSet<A>,
     widgets_scheduled_for_animation: WidgetNodeSet<A>,
     widgets_with_pointer_leave_listen: WidgetNodeSet<A>,
+    widgets_with_pre_frame_listen: WidgetNodeSet<A>,
+    focusable_widgets: WidgetNodeSet<A>,
+    focused_widget: Option<StrongWidgetNodeEntry<A>>,
     widgets_to_remove_from_animation: Vec<StrongWidgetNodeEntry<A>>,
+    animators: Vec<RunningAnimator<A>>,
+    next_animator_id: u64,
+    animations_paused: bool,
     widget_requests: Vec<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)>,
     widgets_just_shown: WidgetNodeSet<A>,
     widgets_just_hidden: WidgetNodeSet<A>,
+    widget_groups: FnvHashMap<WidgetGroupId, WidgetNodeSet<A>>,
+    widget_group_ids: FnvHashMap<u64, WidgetGroupId>,
+    hovered_group: Option<WidgetGroupId>,
+    injected_pointer: PointerEvent,
+    pointer_velocity: PointerVelocityTracker,
+    pointer_snap: PointerSnap,
+    input_filter: Option<Box<dyn FnMut(&mut InputEvent) -> bool>>,
+    #[cfg(feature = "input-recording")]
+    input_recorder: Option<InputRecorder>,
 
     renderer: Option<Renderer>,
-    scale_factor: ScaleFactor,
+    /// The fonts of the renderer's canvas. This outlives the renderer so that
+    /// font IDs stay valid across `suspend_gpu()` and `resume_gpu()`.
+    text_context: TextContext,
+    /// The fallback that the built-in widgets draw their text with when
+    /// femtovg can't draw glyphs on this window's GL context.
+    #[cfg(feature = "software-text")]
+    software_text: SoftwareTextRenderer,
+    /// Whether the current renderer's context has been checked with
+    /// `SoftwareTextRenderer::detect()`.
+    #[cfg(feature = "software-text")]
+    software_text_detected: bool,
+    color_space: ColorSpace,
+    clear_color: Color,
+    shared_resources: Option<SharedResources>,
+    #[cfg(feature = "image-loading")]
+    shared_image_ids: fnv::FnvHashMap<u64, femtovg::ImageId>,
+    loaded_assets: LoadedAssets,
+    text_layout_cache: TextLayoutCache,
+    scale_factor_2d: ScaleFactor2D,
+    dpi_scale_factor_2d: ScaleFactor2D,
+    ui_scale: f32,
+    /// The latest window size given to `set_pending_window_size()` that has
+    /// not been committed yet.
+    pending_window_size: Option<PhysicalSize>,
     window_visibility: bool,
+    pointer_lock_supported: bool,
+    /// Whether this window was created without a renderer (see
+    /// `new_headless()`).
+    headless: bool,
 
     do_repack_layers: bool,
 }
 
 impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
-    fn new(scale_factor: ScaleFactor, renderer: Renderer, action_tx: Sender<A>) -> Self {
+    fn new(
+        scale_factor: ScaleFactor,
+        renderer: Option<Renderer>,
+        text_context: TextContext,
+        shared_resources: Option<SharedResources>,
+        settings: AppWindowSettings,
+        action_tx: Sender<A>,
+    ) -> Self {
         Self {
             next_layer_id: 0,
             next_widget_id: 0,
             layers_ordered: Vec::new(),
             widget_with_pointer_lock: None,
+            pointer_lock_released: false,
+            pointer_capture_stack: PointerCaptureStack::new(),
             widgets_to_send_input_event: Vec::new(),
             widget_with_text_comp_listen: None,
             widgets_with_keyboard_listen: WidgetNodeSet::new(),
             widgets_scheduled_for_animation: WidgetNodeSet::new(),
             widgets_with_pointer_leave_listen: WidgetNodeSet::new(),
+            widgets_with_pre_frame_listen: WidgetNodeSet::new(),
+            focusable_widgets: WidgetNodeSet::new(),
+            focused_widget: None,
             widgets_to_remove_from_animation: Vec::new(),
+            animators: Vec::new(),
+            next_animator_id: 0,
+            animations_paused: false,
             widget_requests: Vec::new(),
             widgets_just_shown: WidgetNodeSet::new(),
             widgets_just_hidden: WidgetNodeSet::new(),
+            widget_groups: FnvHashMap::default(),
+            widget_group_ids: FnvHashMap::default(),
+            hovered_group: None,
+            injected_pointer: PointerEvent::default(),
+            pointer_velocity: PointerVelocityTracker::new(),
+            pointer_snap: PointerSnap::None,
+            input_filter: None,
+            #[cfg(feature = "input-recording")]
+            input_recorder: None,
             widget_layer_renderers_to_clean_up: Vec::new(),
             background_layer_renderers_to_clean_up: Vec::new(),
+            post_composite: None,
+            window_mask: None,
             action_tx,
-            renderer: Some(renderer),
-            scale_factor,
-            window_visibility: true,
+            action_queue_limit: None,
+            action_queue_full: false,
+            texture_memory_budget: None,
+            hidden_layer_texture_timeout: None,
+            always_full_repaint: false,
+            report_overlapping_dirty_widgets: false,
+            color_space: renderer
+                .as_ref()
+                .map(|r| r.color_space())
+                .unwrap_or_default(),
+            clear_color: Color::rgb(0, 0, 0),
+            headless: renderer.is_none(),
+            renderer,
+            text_context,
+            #[cfg(feature = "software-text")]
+            software_text: SoftwareTextRenderer::new(),
+            #[cfg(feature = "software-text")]
+            software_text_detected: false,
+            shared_resources,
+            #[cfg(feature = "image-loading")]
+            shared_image_ids: fnv::FnvHashMap::default(),
+            loaded_assets: LoadedAssets::new(),
+            text_layout_cache: TextLayoutCache::new(scale_factor),
+            scale_factor_2d: scale_factor.into(),
+            dpi_scale_factor_2d: scale_factor.into(),
+            ui_scale: 1.0,
+            pending_window_size: None,
+            window_visibility: !settings.initially_hidden,
+            pointer_lock_supported: settings.pointer_lock_supported,
             do_repack_layers: true,
         }
     }
@@ -81,11 +212,24 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
     pub fn new_from_glutin_display(
         scale_factor: ScaleFactor,
         display: &glutin::display::Display,
+        shared_resources: Option<&SharedResources>,
+        settings: AppWindowSettings,
         action_tx: Sender<A>,
     ) -> Self {
+        let text_context = shared_resources
+            .map(|r| r.text_context.clone())
+            .unwrap_or_default();
+
         Self::new(
             scale_factor,
-            Renderer::new_from_glutin_display(display),
+            Some(Renderer::new_from_glutin_display(
+                display,
+                Some(text_context.clone()),
+                settings.color_space,
+            )),
+            text_context,
+            shared_resources.cloned(),
+            settings,
             action_tx,
         )
     }
@@ -94,26 +238,444 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
     pub unsafe fn new_from_function<F>(
         scale_factor: ScaleFactor,
         load_fn: F,
+        shared_resources: Option<&SharedResources>,
+        settings: AppWindowSettings,
         action_tx: Sender<A>,
     ) -> Self
     where
         F: FnMut(&str) -> *const c_void,
     {
+        let text_context = shared_resources
+            .map(|r| r.text_context.clone())
+            .unwrap_or_default();
+
         Self::new(
             scale_factor,
-            Renderer::new_from_function(load_fn),
+            Some(Renderer::new_from_function(
+                load_fn,
+                Some(text_context.clone()),
+                settings.color_space,
+            )),
+            text_context,
+            shared_resources.cloned(),
+            settings,
             action_tx,
         )
     }
 
+    /// Create a window without a renderer, for testing layout without a GL
+    /// context.
+    ///
+    /// Layers, regions, and widgets can be added and modified as usual, and
+    /// their rects are resolved (see `widget_region_rect()`), but nothing is
+    /// ever painted. Input events are sent to widgets as usual, so widgets
+    /// can be tested by feeding them events with `handle_input_event()`.
+    /// `render()` does nothing, methods that need the canvas return
+    /// `FirewheelError::GpuSuspended` (and `vg()` panics), and `resume_gpu()`
+    /// does nothing.
+    pub fn new_headless(scale_factor: ScaleFactor, action_tx: Sender<A>) -> Self {
+        Self::new(
+            scale_factor,
+            None,
+            TextContext::default(),
+            None,
+            AppWindowSettings {
+                pointer_lock_supported: false,
+                ..Default::default()
+            },
+            action_tx,
+        )
+    }
+
+    /// Free all GPU resources (the textures of the layers and the renderer),
+    /// i.e. before moving the window to a different GL context. The layers
+    /// and widgets are kept as they are.
+    ///
+    /// The GL context that this window is currently using must still be
+    /// current when this is called.
+    ///
+    /// Until `resume_gpu()` is called, `render()` does nothing. Input events
+    /// are still sent to widgets, and widgets that are repainted in the
+    /// meantime are painted once the GPU resources are resumed. Methods that
+    /// need the canvas return `FirewheelError::GpuSuspended` (or `None`) in
+    /// the meantime, except for `vg()`, which panics.
+    pub fn suspend_gpu(&mut self) {
+        if self.renderer.is_none() {
+            return;
+        }
+
+        release_layer_renderers(
+            &mut self.layers_ordered,
+            &mut self.widget_layer_renderers_to_clean_up,
+            &mut self.background_layer_renderers_to_clean_up,
+        );
+
+        let mut renderer = self.renderer.take().unwrap();
+        #[cfg(feature = "software-text")]
+        self.software_text.free_images(&mut renderer.vg);
+        renderer.free(self);
+
+        // Shared images are uploaded again the next time they are requested.
+        #[cfg(feature = "image-loading")]
+        self.shared_image_ids.clear();
+        self.loaded_assets.clear_images();
+    }
+
+    /// Recreate the renderer with the GL context that is now current after
+    /// a call to `suspend_gpu()`. Every layer is fully repainted on the next
+    /// call to `render()`.
+    ///
+    /// Fonts added with `add_font()` keep their IDs. Images must be loaded
+    /// again (IDs from `shared_image_id()` must be requested again as well).
+    ///
+    /// # Safety
+    ///
+    /// The same as with `new_from_function()`, the GL context must be current
+    /// on the calling thread, and `load_fn` must return valid pointers to the
+    /// GL functions of that context (or null).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn resume_gpu<F>(&mut self, load_fn: F)
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        if self.renderer.is_some() || self.headless {
+            return;
+        }
+
+        self.renderer = Some(Renderer::new_from_function(
+            load_fn,
+            Some(self.text_context.clone()),
+            self.color_space,
+        ));
+        self.text_layout_cache.invalidate();
+        #[cfg(feature = "software-text")]
+        {
+            self.software_text_detected = false;
+        }
+    }
+
+    pub fn is_gpu_suspended(&self) -> bool {
+        self.renderer.is_none()
+    }
+
+    /// The canvas of this window.
+    ///
+    /// # Panics
+    ///
+    /// Panics while the GPU resources are suspended (see `suspend_gpu()`)
+    /// and for headless windows. Use `try_vg()` if that can be the case.
     pub fn vg(&mut self) -> &mut VG {
-        &mut self.renderer.as_mut().unwrap().vg
+        self.try_vg()
+            .expect("vg() was called while the GPU resources are suspended")
+    }
+
+    /// The canvas of this window, or `None` while the GPU resources are
+    /// suspended (see `suspend_gpu()`) and for headless windows.
+    pub fn try_vg(&mut self) -> Option<&mut VG> {
+        self.renderer.as_mut().map(|renderer| &mut renderer.vg)
+    }
+
+    fn vg_or_err(&mut self) -> Result<&mut VG, FirewheelError> {
+        self.try_vg().ok_or(FirewheelError::GpuSuspended)
+    }
+
+    pub fn add_font<P: AsRef<std::path::Path>>(
+        &mut self,
+        file_path: P,
+    ) -> Result<femtovg::FontId, FirewheelError> {
+        let file_path = file_path.as_ref();
+        self.text_layout_cache.invalidate();
+        let font_id = self
+            .text_context
+            .add_font_file(file_path)
+            .map_err(|e| FirewheelError::FontLoad(format!("{}: {:?}", file_path.display(), e)))?;
+        self.loaded_assets.add_font(font_id);
+
+        #[cfg(feature = "software-text")]
+        {
+            // The font is still usable with femtovg if the fallback can't
+            // load it.
+            let res = std::fs::read(file_path)
+                .map_err(|e| FirewheelError::FontLoad(format!("{}: {}", file_path.display(), e)))
+                .and_then(|data| self.software_text.add_font(font_id, &data));
+            if let Err(e) = res {
+                log::error!("Software text renderer: {}", e);
+            }
+        }
+
+        Ok(font_id)
+    }
+
+    pub fn add_font_from_bytes(&mut self, data: &[u8]) -> Result<femtovg::FontId, FirewheelError> {
+        self.text_layout_cache.invalidate();
+        let font_id = self
+            .text_context
+            .add_font_mem(data)
+            .map_err(|e| FirewheelError::FontLoad(format!("{:?}", e)))?;
+        self.loaded_assets.add_font(font_id);
+
+        // The font is still usable with femtovg if the fallback can't load
+        // it.
+        #[cfg(feature = "software-text")]
+        if let Err(e) = self.software_text.add_font(font_id, data) {
+            log::error!("Software text renderer: {}", e);
+        }
+
+        Ok(font_id)
+    }
+
+    /// Load a font from memory that can be looked up later by the given name
+    /// with `font_id_by_name()` (i.e. `"body"` or `"heading"`), so that the
+    /// rest of the app can refer to fonts by a stable name instead of passing
+    /// the `FontId` around.
+    ///
+    /// If a font was already loaded with this name, the name refers to the
+    /// new font from now on. The old font stays loaded.
+    pub fn add_font_named(
+        &mut self,
+        name: &str,
+        data: &[u8],
+    ) -> Result<femtovg::FontId, FirewheelError> {
+        let font_id = self.add_font_from_bytes(data)?;
+        self.loaded_assets.add_named_font(name, font_id);
+
+        Ok(font_id)
+    }
+
+    /// The font that was loaded with the given name with `add_font_named()`.
+    pub fn font_id_by_name(&self, name: &str) -> Option<femtovg::FontId> {
+        self.loaded_assets.font_id_by_name(name)
+    }
+
+    /// Load an encoded image (i.e. PNG or JPEG data) into this window's
+    /// canvas.
+    #[cfg(feature = "image-loading")]
+    pub fn load_image_from_bytes(
+        &mut self,
+        data: &[u8],
+        flags: femtovg::ImageFlags,
+    ) -> Result<femtovg::ImageId, FirewheelError> {
+        let image_id = self
+            .vg_or_err()?
+            .load_image_mem(data, flags)
+            .map_err(|e| FirewheelError::ImageLoad(format!("{:?}", e)))?;
+        self.loaded_assets.add_image(image_id);
+
+        Ok(image_id)
+    }
+
+    /// Unload an image from this window's canvas to free its memory.
+    ///
+    /// Returns `FirewheelError::ImageInUse` (and does nothing) if a visible
+    /// widget or background node reports that it uses the image (see
+    /// `WidgetNode::uses_image()`). The image must not be used again after
+    /// it has been unloaded.
+    ///
+    /// Fonts can't be unloaded, since femtovg has no way to remove a font
+    /// once it has been added.
+    ///
+    /// This does nothing while the GPU resources are suspended, since every
+    /// image was already destroyed along with the canvas.
+    pub fn unload_image(&mut self, image_id: femtovg::ImageId) -> Result<(), FirewheelError> {
+        check_image_not_in_use(&self.layers_ordered, image_id)?;
+
+        if let Some(vg) = self.try_vg() {
+            vg.delete_image(image_id);
+        }
+        self.loaded_assets.remove_image(image_id);
+        #[cfg(feature = "image-loading")]
+        self.shared_image_ids.retain(|_, id| *id != image_id);
+
+        Ok(())
+    }
+
+    /// The fonts that have been added with `add_font()` and
+    /// `add_font_from_bytes()`, in the order they were added.
+    ///
+    /// Fonts added with `vg().add_font()` directly or through
+    /// `SharedResources` are not included.
+    pub fn loaded_fonts(&self) -> Vec<femtovg::FontId> {
+        self.loaded_assets.fonts().to_vec()
+    }
+
+    /// The images that have been loaded with `load_image_from_bytes()` and
+    /// `shared_image_id()`, in the order they were loaded.
+    ///
+    /// Images loaded with `vg()` directly are not included. This is empty
+    /// after `suspend_gpu()`, since the images are destroyed along with the
+    /// canvas.
+    pub fn loaded_images(&self) -> Vec<femtovg::ImageId> {
+        self.loaded_assets.images().to_vec()
+    }
+
+    /// Measure the size of the given text (in logical points), reusing the
+    /// result of a previous measurement if the text, font, font size, and
+    /// scale factor are the same.
+    ///
+    /// Fonts loaded with `vg().add_font()` directly are not tracked by the
+    /// cache, so call `invalidate_text_layout_cache()` after loading those.
+    pub fn measure_text_cached(
+        &mut self,
+        text: &str,
+        font_id: femtovg::FontId,
+        font_size_pts: f32,
+    ) -> Size {
+        let scale_factor = self.scale_factor();
+        let text_context = &self.text_context;
+        self.text_layout_cache
+            .get_or_measure(text, font_id, font_size_pts, scale_factor, || {
+                // The text context is shared with the canvas, so this gives
+                // the same result as `compute_font_bounds()` without needing
+                // a renderer.
+                let font_paint = crate::font_paint(font_id, font_size_pts, scale_factor);
+                let font_metrics = text_context
+                    .measure_text(0.0, 0.0, text, &font_paint)
+                    .unwrap();
+
+                Size::new(
+                    font_metrics.width() / scale_factor.0,
+                    font_metrics.height() / scale_factor.0,
+                )
+            })
+    }
+
+    /// Rasterize the glyphs of the given text ahead of time so that the
+    /// first time it is painted doesn't cause a hitch (i.e. right before
+    /// opening a panel with lots of new text).
+    ///
+    /// The text is drawn with an empty scissor rect, so nothing appears on
+    /// the screen. Returns the size of the text, which is also stored in the
+    /// text layout cache (see `measure_text_cached()`).
+    pub fn warmup_text(
+        &mut self,
+        text: &str,
+        font_id: femtovg::FontId,
+        font_size_pts: f32,
+    ) -> Result<Size, FirewheelError> {
+        let font_paint = crate::font_paint(font_id, font_size_pts, self.scale_factor());
+
+        let vg = self.vg_or_err()?;
+        vg.save();
+        vg.scissor(0.0, 0.0, 0.0, 0.0);
+        let res = vg.fill_text(0.0, 0.0, text, &font_paint);
+        vg.restore();
+
+        res.map_err(|e| {
+            log::error!("Failed to draw text: {}", e);
+            FirewheelError::Text
+        })?;
+
+        Ok(self.measure_text_cached(text, font_id, font_size_pts))
+    }
+
+    pub fn invalidate_text_layout_cache(&mut self) {
+        self.text_layout_cache.invalidate();
+    }
+
+    /// The resources shared with other windows, if this window was created
+    /// with any.
+    pub fn shared_resources(&self) -> Option<&SharedResources> {
+        self.shared_resources.as_ref()
     }
 
+    /// Get the ID of a shared image in this window's canvas.
+    ///
+    /// The image is uploaded to this window's canvas the first time this is
+    /// called.
+    #[cfg(feature = "image-loading")]
+    pub fn shared_image_id(
+        &mut self,
+        image: &crate::SharedImageRef,
+    ) -> Result<femtovg::ImageId, FirewheelError> {
+        if let Some(image_id) = self.shared_image_ids.get(&image.id) {
+            return Ok(*image_id);
+        }
+
+        let (data, flags) = self
+            .shared_resources
+            .as_ref()
+            .and_then(|r| r.images.borrow().get(image.id))
+            .ok_or_else(|| {
+                FirewheelError::ImageLoad("image was removed from the shared resources".into())
+            })?;
+
+        let image_id = self
+            .vg_or_err()?
+            .load_image_mem(&data, flags)
+            .map_err(|e| FirewheelError::ImageLoad(format!("{:?}", e)))?;
+        self.shared_image_ids.insert(image.id, image_id);
+        self.loaded_assets.add_image(image_id);
+
+        Ok(image_id)
+    }
+
+    /// The scale factor used for layout and rendering, which includes the
+    /// UI scale (see `set_ui_scale()`).
     pub fn scale_factor(&self) -> ScaleFactor {
-        self.scale_factor
+        self.scale_factor_2d.scalar()
+    }
+
+    /// The scale factor of each axis used for layout and rendering, which
+    /// includes the UI scale (see `set_ui_scale()`).
+    pub fn scale_factor_2d(&self) -> ScaleFactor2D {
+        self.scale_factor_2d
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// Returns `Some(true)` if the renderer is running on OpenGL ES instead
+    /// of desktop OpenGL, or `None` while the GPU resources are suspended.
+    pub fn is_gles(&self) -> Option<bool> {
+        self.renderer.as_ref().map(|renderer| renderer.is_gles())
+    }
+
+    /// The `GL_VERSION` string reported by the OpenGL context, if it could
+    /// be queried. This is `None` while the GPU resources are suspended.
+    pub fn gl_version(&self) -> Option<&str> {
+        self.renderer
+            .as_ref()
+            .and_then(|renderer| renderer.gl_version())
+    }
+
+    /// Whether the platform supports locking the pointer in place (see
+    /// `AppWindowSettings::pointer_lock_supported`).
+    ///
+    /// When this is `false`, widgets requesting
+    /// `SetPointerLockType::LockInPlaceAndHideCursor` are locked to the widget
+    /// with `SetPointerLockType::LockToWidget` instead.
+    pub fn pointer_lock_supported(&self) -> bool {
+        self.pointer_lock_supported
+    }
+
+    /// The color space this window was created with.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Set the color that the window is cleared to before the layers are
+    /// composited in `render()`.
+    ///
+    /// By default this is black.
+    pub fn set_clear_color(&mut self, clear_color: Color) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
     }
 
+    /// Add a new widget layer.
+    ///
+    /// Layers are painted from the lowest to the highest `z_order`. Layers
+    /// that share the same `z_order` are painted from the lowest to the
+    /// highest paint order (see `set_widget_layer_paint_order()`), so by
+    /// default background nodes are painted behind widget layers. Layers
+    /// with the same paint order are painted in the order they were added
+    /// (so the most recently added layer is on top), unless they are
+    /// reordered with `move_widget_layer_within_z()` or
+    /// `move_background_node_within_z()`.
     pub fn add_widget_layer(
         &mut self,
         size: Size,
@@ -133,7 +695,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             inner_position,
             explicit_visibility,
             self.window_visibility,
-            self.scale_factor,
+            self.scale_factor_2d,
         ));
 
         let layer_ref = WidgetLayerRef {
@@ -142,24 +704,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
 
         let layer_entry = StrongLayerEntry::Widget(layer_entry);
 
-        let mut existing_z_order_i = None;
-        let mut insert_i = 0;
-        for (i, (z_order_2, _)) in self.layers_ordered.iter().enumerate() {
-            if z_order == *z_order_2 {
-                existing_z_order_i = Some(i);
-                break;
-            } else if z_order > *z_order_2 {
-                insert_i = i + 1;
-            }
-        }
-        if let Some(i) = existing_z_order_i {
-            self.layers_ordered[i].1.push(layer_entry);
-        } else {
-            self.layers_ordered
-                .insert(insert_i, (z_order, vec![layer_entry]));
-        }
-
-        self.do_repack_layers = true;
+        self.insert_layer_entry(z_order, layer_entry);
 
         layer_ref
     }
@@ -223,7 +768,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         if let Some(mut layer_entry) = layer.shared.upgrade() {
             layer_entry
                 .borrow_mut()
-                .set_outer_position(position, self.scale_factor);
+                .set_outer_position(position, self.scale_factor_2d);
         } else {
             return Err(FirewheelError::LayerRemoved);
         }
@@ -259,7 +804,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         if let Some(mut layer_entry) = layer.shared.upgrade() {
             layer_entry.borrow_mut().set_size(
                 size,
-                self.scale_factor,
+                self.scale_factor_2d,
                 &mut self.widgets_just_shown,
                 &mut self.widgets_just_hidden,
             );
@@ -272,14 +817,83 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
-    pub fn set_widget_layer_explicit_visibility(
+    /// Set whether this layer is resized to the logical size of the window
+    /// every time a new size is applied with `commit_resize()`.
+    ///
+    /// By default this is `false`.
+    pub fn set_widget_layer_fills_window(
         &mut self,
         layer: &mut WidgetLayerRef<A>,
-        explicit_visibility: bool,
+        fills_window: bool,
     ) -> Result<(), FirewheelError> {
         if let Some(mut layer_entry) = layer.shared.upgrade() {
-            layer_entry.borrow_mut().set_explicit_visibility(
-                explicit_visibility,
+            layer_entry.borrow_mut().fills_window = fills_window;
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        Ok(())
+    }
+
+    /// Set how this layer is composited onto the screen.
+    ///
+    /// See `Compositing` for the tradeoffs of each mode.
+    ///
+    /// Returns `FirewheelError::BlendModeNotSupported` if `compositing` is
+    /// `Compositing::Direct` and the layer uses a blend mode other than
+    /// `BlendMode::Normal`.
+    pub fn set_widget_layer_compositing(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        compositing: Compositing,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            let mut layer_entry = layer_entry.borrow_mut();
+            if compositing == Compositing::Direct && layer_entry.blend_mode != BlendMode::Normal {
+                return Err(FirewheelError::BlendModeNotSupported);
+            }
+
+            layer_entry.set_compositing(compositing);
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        Ok(())
+    }
+
+    /// Allocate the texture of the given layer at `max_size` (in logical
+    /// points) for as long as the layer fits inside of it, or `None` to
+    /// allocate it at the size of the layer (the default).
+    ///
+    /// Use this while animating the size of a layer (i.e. a sliding panel) so
+    /// that the texture isn't reallocated on every frame. Only the part of
+    /// the texture that covers the layer is blitted to the screen.
+    pub fn reserve_widget_layer_texture(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        max_size: Option<Size>,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_reserved_texture_size(max_size);
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        Ok(())
+    }
+
+    /// Set which corner of the given layer the coordinates of its widgets
+    /// start from.
+    ///
+    /// By default this is `CoordinateOrigin::TopLeft`.
+    pub fn set_widget_layer_coordinate_origin(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        coordinate_origin: CoordinateOrigin,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_coordinate_origin(
+                coordinate_origin,
                 &mut self.widgets_just_shown,
                 &mut self.widgets_just_hidden,
             );
@@ -292,76 +906,404 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
-    pub fn add_background_node(
+    /// Set how far (in points) a widget on the given layer may move past the
+    /// edges of the layer before it is hidden.
+    ///
+    /// A widget that straddles an edge of the layer during a smooth animation
+    /// would otherwise flicker in and out of visibility at sub-pixel
+    /// boundaries. By default this is a small fraction of a point.
+    pub fn set_widget_layer_bounds_epsilon(
         &mut self,
-        size: Size,
-        z_order: i32,
-        outer_position: Point,
-        explicit_visibility: bool,
-        background_node: Box<dyn BackgroundNode>,
-    ) -> BackgroundNodeRef {
-        let new_id = self.next_layer_id;
-        self.next_layer_id += 1;
-
-        let mut node_entry = StrongBackgroundNodeEntry::new(background_node, new_id);
+        layer: &mut WidgetLayerRef<A>,
+        epsilon: f64,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_layer_bounds_epsilon(
+                epsilon,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            );
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
 
-        let layer = BackgroundLayer::new(
-            new_id,
-            z_order,
-            size,
-            outer_position,
-            explicit_visibility,
-            self.window_visibility,
-            self.scale_factor,
-            node_entry.clone(),
-        );
+        self.handle_visibility_changes();
 
-        let layer_entry = StrongBackgroundLayerEntry::new(layer);
+        Ok(())
+    }
 
-        {
-            node_entry.set_assigned_layer(layer_entry.downgrade());
+    /// Zoom the content of the given layer in or out, i.e. for a zoomable
+    /// canvas.
+    ///
+    /// This scales the layout and painting of the layer's widgets on top of
+    /// the scale factor of the window, without changing the size of the
+    /// layer itself. The regions of the widgets keep their logical
+    /// coordinates, and pointer events are converted back into those
+    /// coordinates before they are sent to the widgets.
+    ///
+    /// By default this is `1.0`.
+    pub fn set_widget_layer_content_zoom(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        content_zoom: f32,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_content_zoom(
+                content_zoom,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            );
+        } else {
+            return Err(FirewheelError::LayerRemoved);
         }
 
-        let layer_entry = StrongLayerEntry::Background(layer_entry);
+        self.handle_visibility_changes();
 
-        let mut existing_z_order_i = None;
-        let mut insert_i = 0;
-        for (i, (z_order_2, _)) in self.layers_ordered.iter().enumerate() {
-            if z_order == *z_order_2 {
-                existing_z_order_i = Some(i);
-                break;
-            } else if z_order > *z_order_2 {
-                insert_i = i + 1;
+        Ok(())
+    }
+
+    /// Set how the given layer is blended with the layers beneath it when it
+    /// is composited onto the screen.
+    ///
+    /// Returns `FirewheelError::BlendModeNotSupported` if the layer uses
+    /// `Compositing::Direct` and `blend_mode` is not `BlendMode::Normal`.
+    ///
+    /// By default this is `BlendMode::Normal`.
+    pub fn set_widget_layer_blend_mode(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        blend_mode: BlendMode,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            let mut layer_entry = layer_entry.borrow_mut();
+            if layer_entry.compositing == Compositing::Direct && blend_mode != BlendMode::Normal {
+                return Err(FirewheelError::BlendModeNotSupported);
             }
-        }
-        if let Some(i) = existing_z_order_i {
-            self.layers_ordered[i].1.push(layer_entry);
+
+            layer_entry.set_blend_mode(blend_mode);
         } else {
-            self.layers_ordered
-                .insert(insert_i, (z_order, vec![layer_entry]));
+            return Err(FirewheelError::LayerRemoved);
         }
 
-        self.do_repack_layers = true;
+        Ok(())
+    }
 
-        BackgroundNodeRef {
-            shared: node_entry.downgrade(),
+    /// Set a version number for the content of the given layer, for layers
+    /// whose content rarely changes.
+    ///
+    /// While the version is the same as when the layer was last painted, and
+    /// no region in the layer has moved or been resized, the layer's texture
+    /// is reused without painting (or even iterating over) the widgets that
+    /// have been marked dirty, and the layer stops being reported as dirty.
+    /// Bumping the version marks the whole layer dirty so that it is painted
+    /// again on the next frame.
+    ///
+    /// This has no effect when the layer uses `Compositing::Direct`.
+    ///
+    /// By default no version is set, and dirty widgets are always painted.
+    pub fn set_widget_layer_content_version(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        version: u64,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            let mut layer = layer_entry.borrow_mut();
+            if layer.content_stale && layer.content_version != Some(version) {
+                layer.region_tree.mark_all_dirty();
+                layer.content_stale = false;
+            }
+            layer.content_version = Some(version);
+        } else {
+            return Err(FirewheelError::LayerRemoved);
         }
+
+        Ok(())
     }
 
-    pub fn remove_background_node(
+    /// Set the color that this layer's texture is cleared to before widgets
+    /// are painted into it, including the rects left behind by widgets that
+    /// have moved or have been hidden.
+    ///
+    /// Setting this to an opaque color matching the background behind the
+    /// layer avoids the window's clear color showing through vacated rects.
+    /// This has no effect when the layer uses `Compositing::Direct`.
+    ///
+    /// By default this is fully transparent.
+    pub fn set_widget_layer_clear_color(
         &mut self,
-        background_node: &mut BackgroundNodeRef,
+        layer: &mut WidgetLayerRef<A>,
+        clear_color: Color,
     ) -> Result<(), FirewheelError> {
-        let mut node_entry = background_node
-            .shared
-            .upgrade()
-            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?;
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            let mut layer_entry = layer_entry.borrow_mut();
+            if layer_entry.clear_color != clear_color {
+                layer_entry.clear_color = clear_color;
 
-        let layer_entry = node_entry.assigned_layer_mut().upgrade().unwrap();
+                // The layer's texture will need to be fully repainted.
+                layer_entry.region_tree.mark_all_dirty();
+            }
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
 
-        let (layer_id, layer_z_order) = {
-            let layer = layer_entry.borrow();
-            (layer.id, layer.z_order)
+        Ok(())
+    }
+
+    /// Assign a name to this layer which is shown in the `Debug` output of
+    /// its `WidgetLayerRef` and next to its ID in log messages (i.e. the
+    /// lifecycle trace and the overlapping dirty widgets report).
+    ///
+    /// The name is only stored when the `debug-names` feature is enabled.
+    pub fn set_layer_debug_name(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        name: &str,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_debug_name(name);
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        Ok(())
+    }
+
+    /// The rects that will be cleared or repainted in this layer on the next
+    /// call to `render()`, in physical screen coordinates.
+    ///
+    /// This is meant for hosts that only present the damaged parts of the
+    /// window (i.e. with `eglSwapBuffersWithDamageKHR`).
+    pub fn layer_damage(
+        &self,
+        layer: &WidgetLayerRef<A>,
+    ) -> Result<Vec<PhysicalRect>, FirewheelError> {
+        if let Some(layer_entry) = layer.shared.upgrade() {
+            Ok(layer_entry.borrow().damage())
+        } else {
+            Err(FirewheelError::LayerRemoved)
+        }
+    }
+
+    /// Whether the given layer is currently visible. A layer is visible if it
+    /// and the window are explicitly visible and it has at least one region.
+    ///
+    /// Returns `false` if the layer has been removed.
+    pub fn is_widget_layer_visible(&self, layer: &WidgetLayerRef<A>) -> bool {
+        layer
+            .shared
+            .upgrade()
+            .map(|layer_entry| layer_entry.borrow().is_visible())
+            .unwrap_or(false)
+    }
+
+    pub fn set_widget_layer_explicit_visibility(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        explicit_visibility: bool,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().set_explicit_visibility(
+                explicit_visibility,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            );
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        self.handle_visibility_changes();
+
+        Ok(())
+    }
+
+    /// Enable or disable pointer input for every widget in the given layer.
+    /// While disabled, pointer events pass through the layer to the layers
+    /// below it.
+    pub fn set_layer_input_enabled(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        enabled: bool,
+    ) -> Result<(), FirewheelError> {
+        if let Some(mut layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow_mut().input_enabled = enabled;
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        }
+
+        Ok(())
+    }
+
+    /// Add a widget layer that swallows every pointer event that reaches it,
+    /// anywhere in the window, so that nothing below it receives pointer
+    /// events (i.e. the backdrop behind a modal dialog).
+    ///
+    /// Put the modal in a layer with a higher `z_order` than the backdrop.
+    /// The backdrop stops blocking events while it is hidden or while its
+    /// input is disabled with `set_layer_input_enabled()`. Widgets can be
+    /// added to the backdrop (i.e. to dim the rest of the window, or to
+    /// close the modal when the backdrop is clicked), so `size` should
+    /// usually be the size of the window. The backdrop fills the window, so
+    /// `commit_resize()` resizes it along with the window.
+    pub fn add_backdrop_layer(
+        &mut self,
+        size: Size,
+        z_order: i32,
+        explicit_visibility: bool,
+    ) -> WidgetLayerRef<A> {
+        let layer = self.add_widget_layer(
+            size,
+            z_order,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            explicit_visibility,
+        );
+        {
+            let layer_entry = layer.shared.upgrade().unwrap();
+            let mut layer_entry = layer_entry.borrow_mut();
+            layer_entry.is_backdrop = true;
+            layer_entry.fills_window = true;
+        }
+
+        layer
+    }
+
+    /// Open a modal dialog in a new backdrop layer above every other layer.
+    ///
+    /// The backdrop covers the window (of the given logical size) with
+    /// `backdrop_color` and swallows every pointer event that the dialog
+    /// doesn't capture. `root_factory` is called with the new modal to add
+    /// the dialog's widgets to its container region, which has the given
+    /// `size` and is centered in the window. The backdrop is resized and the
+    /// dialog re-centered when the window is resized with `commit_resize()`.
+    ///
+    /// Close the modal with `close_modal()`.
+    pub fn open_modal<F>(
+        &mut self,
+        window_size: Size,
+        size: Size,
+        backdrop_color: Color,
+        root_factory: F,
+    ) -> ModalHandle<A>
+    where
+        F: FnOnce(&mut Self, &ModalHandle<A>),
+    {
+        let z_order = self
+            .layers_ordered
+            .last()
+            .map(|(z_order, _)| z_order.saturating_add(1))
+            .unwrap_or(0);
+
+        let layer = self.add_backdrop_layer(window_size, z_order, true);
+        layer.shared.upgrade().unwrap().borrow_mut().clear_color = backdrop_color;
+
+        let container = self
+            .add_container_region(
+                &layer,
+                RegionInfo::pinned(Edges::default(), size, ParentAnchorType::Layer),
+                true,
+            )
+            .unwrap();
+
+        let modal = ModalHandle { layer, container };
+        root_factory(self, &modal);
+
+        modal
+    }
+
+    /// Close a modal opened with `open_modal()`, removing everything inside
+    /// of its container region along with its layer.
+    ///
+    /// Any widgets that were added to the modal's layer outside of its
+    /// container region must be removed first.
+    pub fn close_modal(&mut self, modal: ModalHandle<A>) -> Result<(), FirewheelError> {
+        self.remove_container_region_recursive(modal.container)?;
+        self.remove_widget_layer(modal.layer)
+    }
+
+    /// Open a popup (i.e. a dropdown menu or a tooltip) in a new layer above
+    /// every other layer.
+    ///
+    /// The popup has the given size and is placed next to `anchor_widget`
+    /// according to `placement`, flipping and shifting it as needed so that
+    /// it stays within the window (of the given logical size). Add the
+    /// popup's widgets to the returned layer, and close the popup with
+    /// `remove_widget_layer()`.
+    pub fn open_popup(
+        &mut self,
+        anchor_widget: &WidgetNodeRef<A>,
+        size: Size,
+        placement: PopupPlacement,
+        window_size: Size,
+    ) -> Result<WidgetLayerRef<A>, FirewheelError> {
+        let target_rect = self.widget_window_rect(anchor_widget)?;
+        let rect = placement.place(target_rect, size, window_size);
+
+        let z_order = self
+            .layers_ordered
+            .last()
+            .map(|(z_order, _)| z_order.saturating_add(1))
+            .unwrap_or(0);
+
+        Ok(self.add_widget_layer(size, z_order, rect.pos(), Point::new(0.0, 0.0), true))
+    }
+
+    /// Add a new background node in its own layer.
+    ///
+    /// See `add_widget_layer()` for how layers with the same `z_order` are
+    /// ordered.
+    pub fn add_background_node(
+        &mut self,
+        size: Size,
+        z_order: i32,
+        outer_position: Point,
+        explicit_visibility: bool,
+        background_node: Box<dyn BackgroundNode>,
+    ) -> BackgroundNodeRef {
+        let new_id = self.next_layer_id;
+        self.next_layer_id += 1;
+
+        let mut node_entry = StrongBackgroundNodeEntry::new(background_node, new_id);
+
+        let layer = BackgroundLayer::new(
+            new_id,
+            z_order,
+            size,
+            outer_position,
+            explicit_visibility,
+            self.window_visibility,
+            self.scale_factor_2d,
+            node_entry.clone(),
+        );
+
+        let layer_entry = StrongBackgroundLayerEntry::new(layer);
+
+        {
+            node_entry.set_assigned_layer(layer_entry.downgrade());
+        }
+
+        let layer_entry = StrongLayerEntry::Background(layer_entry);
+
+        self.insert_layer_entry(z_order, layer_entry);
+
+        BackgroundNodeRef {
+            shared: node_entry.downgrade(),
+        }
+    }
+
+    pub fn remove_background_node(
+        &mut self,
+        background_node: &mut BackgroundNodeRef,
+    ) -> Result<(), FirewheelError> {
+        let mut node_entry = background_node
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?;
+
+        let layer_entry = node_entry.assigned_layer_mut().upgrade().unwrap();
+
+        let (layer_id, layer_z_order) = {
+            let layer = layer_entry.borrow();
+            (layer.id, layer.z_order)
         };
 
         let mut remove_z_order_i = None;
@@ -402,6 +1344,220 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
+    /// Move a widget layer to the given index among the layers that share
+    /// its `z_order`, where index `0` is painted first (at the bottom).
+    ///
+    /// An index past the end moves the layer to the top.
+    ///
+    /// If the paint order of the layer doesn't fit its new position, it is
+    /// changed to the closest paint order that does (the paint order of the
+    /// layer right below or above it), so that the layer keeps its position
+    /// when the paint order of another layer is changed later on.
+    pub fn move_widget_layer_within_z(
+        &mut self,
+        layer: &WidgetLayerRef<A>,
+        index: usize,
+    ) -> Result<(), FirewheelError> {
+        let (layer_id, layer_z_order) = if let Some(layer_entry) = layer.shared.upgrade() {
+            let layer = layer_entry.borrow();
+            (layer.id, layer.z_order)
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        };
+
+        self.move_layer_within_z(layer_id, layer_z_order, index);
+
+        Ok(())
+    }
+
+    /// Move a background node's layer to the given index among the layers
+    /// that share its `z_order`, where index `0` is painted first (at the
+    /// bottom).
+    ///
+    /// An index past the end moves the layer to the top.
+    ///
+    /// The paint order of the layer is changed as needed to keep it at this
+    /// index (see `move_widget_layer_within_z()`).
+    pub fn move_background_node_within_z(
+        &mut self,
+        background_node: &BackgroundNodeRef,
+        index: usize,
+    ) -> Result<(), FirewheelError> {
+        let mut node_entry = background_node
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?;
+
+        let layer_entry = node_entry.assigned_layer_mut().upgrade().unwrap();
+
+        let (layer_id, layer_z_order) = {
+            let layer = layer_entry.borrow();
+            (layer.id, layer.z_order)
+        };
+
+        self.move_layer_within_z(layer_id, layer_z_order, index);
+
+        Ok(())
+    }
+
+    /// Set the order of the given widget layer among the layers that share
+    /// its `z_order`. Layers with a lower paint order are painted first (at
+    /// the bottom).
+    ///
+    /// By default this is `0` for widget layers and `-1` for background
+    /// nodes, so that a background is painted behind the widget layers with
+    /// the same `z_order`.
+    pub fn set_widget_layer_paint_order(
+        &mut self,
+        layer: &mut WidgetLayerRef<A>,
+        paint_order: i32,
+    ) -> Result<(), FirewheelError> {
+        let mut layer_entry = layer.shared.upgrade().ok_or(FirewheelError::LayerRemoved)?;
+
+        let layer_z_order = {
+            let mut layer = layer_entry.borrow_mut();
+            if layer.paint_order == paint_order {
+                return Ok(());
+            }
+            layer.paint_order = paint_order;
+            layer.z_order
+        };
+
+        self.sort_layers_within_z(layer_z_order);
+
+        Ok(())
+    }
+
+    /// Set the order of the given background node's layer among the layers
+    /// that share its `z_order`.
+    ///
+    /// See `set_widget_layer_paint_order()` for more details.
+    pub fn set_background_node_paint_order(
+        &mut self,
+        background_node: &mut BackgroundNodeRef,
+        paint_order: i32,
+    ) -> Result<(), FirewheelError> {
+        let mut node_entry = background_node
+            .shared
+            .upgrade()
+            .ok_or(FirewheelError::BackgroundNodeRemoved)?;
+        let mut layer_entry = node_entry.assigned_layer_mut().upgrade().unwrap();
+
+        let layer_z_order = {
+            let mut layer = layer_entry.borrow_mut();
+            if layer.paint_order == paint_order {
+                return Ok(());
+            }
+            layer.paint_order = paint_order;
+            layer.z_order
+        };
+
+        self.sort_layers_within_z(layer_z_order);
+
+        Ok(())
+    }
+
+    /// Insert a new layer above the layers with the same `z_order` and a
+    /// lower or equal paint order.
+    fn insert_layer_entry(&mut self, z_order: i32, layer_entry: StrongLayerEntry<A>) {
+        let mut existing_z_order_i = None;
+        let mut insert_i = 0;
+        for (i, (z_order_2, _)) in self.layers_ordered.iter().enumerate() {
+            if z_order == *z_order_2 {
+                existing_z_order_i = Some(i);
+                break;
+            } else if z_order > *z_order_2 {
+                insert_i = i + 1;
+            }
+        }
+        if let Some(i) = existing_z_order_i {
+            let layers = &mut self.layers_ordered[i].1;
+            let paint_order = layer_entry.paint_order();
+            let index = layers
+                .iter()
+                .position(|l| l.paint_order() > paint_order)
+                .unwrap_or(layers.len());
+            layers.insert(index, layer_entry);
+        } else {
+            self.layers_ordered
+                .insert(insert_i, (z_order, vec![layer_entry]));
+        }
+
+        self.do_repack_layers = true;
+    }
+
+    /// Sort the layers with the given `z_order` by their paint order, keeping
+    /// the order of the layers with the same paint order.
+    fn sort_layers_within_z(&mut self, layer_z_order: i32) {
+        if let Some((_, layers)) = self
+            .layers_ordered
+            .iter_mut()
+            .find(|(z_order, _)| *z_order == layer_z_order)
+        {
+            layers.sort_by_key(|layer_entry| layer_entry.paint_order());
+
+            // Make sure the window gets redrawn with the new order.
+            for layer_entry in layers.iter_mut() {
+                match layer_entry {
+                    StrongLayerEntry::Widget(layer_entry) => {
+                        layer_entry.borrow_mut().region_tree.mark_all_dirty();
+                    }
+                    StrongLayerEntry::Background(layer_entry) => {
+                        layer_entry.borrow_mut().mark_dirty();
+                    }
+                }
+            }
+        }
+    }
+
+    fn move_layer_within_z(&mut self, layer_id: u64, layer_z_order: i32, index: usize) {
+        for (z_order, layers) in self.layers_ordered.iter_mut() {
+            if layer_z_order == *z_order {
+                let from_i = layers.iter().position(|layer_entry| match layer_entry {
+                    StrongLayerEntry::Widget(layer_entry) => layer_entry.borrow().id == layer_id,
+                    StrongLayerEntry::Background(layer_entry) => {
+                        layer_entry.borrow().id == layer_id
+                    }
+                });
+
+                if let Some(from_i) = from_i {
+                    if move_item(layers, from_i, index) {
+                        let to_i = index.min(layers.len() - 1);
+
+                        // Keep the layers sorted by their paint order, so that
+                        // sorting them again doesn't undo the move.
+                        let min = if to_i > 0 {
+                            layers[to_i - 1].paint_order()
+                        } else {
+                            i32::MIN
+                        };
+                        let max = layers
+                            .get(to_i + 1)
+                            .map(|layer_entry| layer_entry.paint_order())
+                            .unwrap_or(i32::MAX);
+                        let paint_order = layers[to_i].paint_order();
+                        let new_paint_order = paint_order.max(min).min(max);
+                        if new_paint_order != paint_order {
+                            layers[to_i].set_paint_order(new_paint_order);
+                        }
+
+                        // Make sure the window gets redrawn with the new order.
+                        match &mut layers[to_i] {
+                            StrongLayerEntry::Widget(layer_entry) => {
+                                layer_entry.borrow_mut().region_tree.mark_all_dirty();
+                            }
+                            StrongLayerEntry::Background(layer_entry) => {
+                                layer_entry.borrow_mut().mark_dirty();
+                            }
+                        }
+                    }
+                }
+
+                break;
+            }
+        }
+    }
+
     pub fn set_background_node_outer_position(
         &mut self,
         background_node: &mut BackgroundNodeRef,
@@ -415,7 +1571,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .set_outer_position(position, self.scale_factor);
+            .set_outer_position(position, self.scale_factor_2d);
 
         Ok(())
     }
@@ -433,15 +1589,20 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .set_size(size, self.scale_factor);
+            .set_size(size, self.scale_factor_2d);
 
         Ok(())
     }
 
-    pub fn set_background_node_explicit_visibility(
+    /// Set whether the layer of this background node is resized to the
+    /// logical size of the window every time a new size is applied with
+    /// `commit_resize()`.
+    ///
+    /// By default this is `false`.
+    pub fn set_background_node_fills_window(
         &mut self,
         background_node: &mut BackgroundNodeRef,
-        explicit_visibility: bool,
+        fills_window: bool,
     ) -> Result<(), FirewheelError> {
         background_node
             .shared
@@ -451,14 +1612,15 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .set_explicit_visibility(explicit_visibility);
+            .fills_window = fills_window;
 
         Ok(())
     }
 
-    pub fn mark_background_node_dirty(
+    pub fn set_background_node_explicit_visibility(
         &mut self,
         background_node: &mut BackgroundNodeRef,
+        explicit_visibility: bool,
     ) -> Result<(), FirewheelError> {
         background_node
             .shared
@@ -468,20 +1630,91 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .mark_dirty();
+            .set_explicit_visibility(explicit_visibility);
 
         Ok(())
     }
 
-    pub fn send_user_event_to_background_node(
+    pub fn mark_background_node_dirty(
         &mut self,
         background_node: &mut BackgroundNodeRef,
-        event: Box<dyn Any>,
     ) -> Result<(), FirewheelError> {
-        let mut node_entry = background_node
+        background_node
             .shared
             .upgrade()
-            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?;
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .mark_dirty();
+
+        Ok(())
+    }
+
+    /// Limit how often the given background node is repainted (i.e. for an
+    /// animated background that is marked dirty every frame).
+    ///
+    /// While the limit is set, the node is repainted at most `max_fps` times
+    /// per second no matter how often it is marked dirty. In the frames in
+    /// between, the layer keeps showing its last painted contents. Widget
+    /// layers are not affected.
+    ///
+    /// Set to `None` to remove the limit (the default).
+    pub fn set_background_node_max_fps(
+        &mut self,
+        background_node: &mut BackgroundNodeRef,
+        max_fps: Option<f32>,
+    ) -> Result<(), FirewheelError> {
+        background_node
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set_max_fps(max_fps);
+
+        Ok(())
+    }
+
+    /// Hint that the given background node covers its whole layer with
+    /// opaque pixels (i.e. a solid or textured window background).
+    ///
+    /// An opaque layer's texture is allocated without an alpha channel,
+    /// which saves memory and bandwidth, and it is blitted to the screen
+    /// without alpha blending. Any transparent pixels the node paints will
+    /// show up as black.
+    ///
+    /// By default this is set to `false`.
+    pub fn set_background_node_opaque(
+        &mut self,
+        background_node: &mut BackgroundNodeRef,
+        opaque: bool,
+    ) -> Result<(), FirewheelError> {
+        background_node
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set_opaque(opaque);
+
+        Ok(())
+    }
+
+    pub fn send_user_event_to_background_node(
+        &mut self,
+        background_node: &mut BackgroundNodeRef,
+        event: Box<dyn Any>,
+    ) -> Result<(), FirewheelError> {
+        let mut node_entry = background_node
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::BackgroundNodeRemoved)?;
 
         let mark_dirty = { node_entry.borrow_mut().on_user_event(event) };
 
@@ -497,6 +1730,19 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
+    pub fn window_visibility(&self) -> bool {
+        self.window_visibility
+    }
+
+    /// Set whether the window is visible.
+    ///
+    /// Nothing is rendered while the window is hidden. When the window becomes
+    /// visible again, every layer is fully repainted so that the first visible
+    /// frame is complete.
+    ///
+    /// To avoid showing an unpopulated window on the first frame, create the
+    /// `AppWindow` with `initially_hidden` set to `true`, populate it, and then
+    /// call this with `true`.
     pub fn set_window_visibility(&mut self, visible: bool) {
         if self.window_visibility != visible {
             self.window_visibility = visible;
@@ -552,6 +1798,47 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             })
     }
 
+    /// Add a container region that lays out its direct children one after
+    /// the other along the given axis with `spacing` points between them
+    /// (i.e. for a toolbar), and is sized to fit them. `item_align` aligns
+    /// the children on the other axis.
+    ///
+    /// The children are laid out again whenever one of them is added,
+    /// removed, or resized, and their anchors are overridden. The size in
+    /// `region_info` is ignored.
+    pub fn add_stack_container(
+        &mut self,
+        layer: &WidgetLayerRef<A>,
+        region_info: RegionInfo<A>,
+        axis: Axis,
+        spacing: f32,
+        item_align: StackAlign,
+        explicit_visibility: bool,
+    ) -> Result<ContainerRegionRef<A>, FirewheelError> {
+        let weak_layer_entry = layer.shared.clone();
+        let mut layer_entry = weak_layer_entry
+            .upgrade()
+            .ok_or_else(|| FirewheelError::LayerRemoved)?;
+
+        let res = layer_entry
+            .borrow_mut()
+            .add_stack_container(
+                region_info,
+                StackLayout::new(axis, spacing, item_align),
+                explicit_visibility,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            )
+            .map(|mut container_ref| {
+                container_ref.assigned_layer = weak_layer_entry;
+                container_ref
+            });
+
+        self.handle_visibility_changes();
+
+        res
+    }
+
     pub fn remove_container_region(
         &mut self,
         region: ContainerRegionRef<A>,
@@ -564,13 +1851,41 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             .remove_container_region(region)
     }
 
+    /// Remove the given container region along with everything inside of
+    /// it.
+    ///
+    /// All descendant widgets are removed (in depth-first order) the same
+    /// as with `remove_widget()`, then all descendant container regions,
+    /// and then the container region itself.
+    pub fn remove_container_region_recursive(
+        &mut self,
+        region: ContainerRegionRef<A>,
+    ) -> Result<(), FirewheelError> {
+        let removed_widgets = region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow_mut()
+            .remove_container_region_recursive(
+                region,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            )?;
+
+        for mut widget_entry in removed_widgets {
+            self.forget_removed_widget(&mut widget_entry);
+        }
+
+        Ok(())
+    }
+
     pub fn modify_container_region(
         &mut self,
         region: &mut ContainerRegionRef<A>,
         new_size: Option<Size>,
         new_internal_anchor: Option<Anchor>,
         new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        new_anchor_offset: Option<AnchorOffset>,
     ) -> Result<(), FirewheelError> {
         region
             .assigned_layer
@@ -592,6 +1907,140 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
+    /// Make a container region scrollable with the given scroll offset (in
+    /// logical points), or make it not scrollable with `None`.
+    ///
+    /// The children of a scrollable container region are positioned relative
+    /// to the container's rect shifted by `-scroll_offset`.
+    pub fn set_container_region_scroll_offset(
+        &mut self,
+        region: &mut ContainerRegionRef<A>,
+        scroll_offset: Option<Point>,
+    ) -> Result<(), FirewheelError> {
+        region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow_mut()
+            .set_container_region_scroll_offset(
+                region,
+                scroll_offset,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            )?;
+
+        self.handle_visibility_changes();
+
+        Ok(())
+    }
+
+    /// Limit the scroll offset of a scrollable container region to between
+    /// zero and `max_scroll_offset` (i.e. the size of its content minus the
+    /// size of the container), with the given behavior at the bounds. Use
+    /// `None` to not limit the scroll offset (the default).
+    ///
+    /// In `ScrollBehavior::Bounce` mode, the host must keep sending
+    /// `InputEvent::Animation` events while `is_scroll_bouncing()` is `true`.
+    pub fn set_container_region_scroll_behavior(
+        &mut self,
+        region: &mut ContainerRegionRef<A>,
+        behavior: Option<(ScrollBehavior, Point)>,
+    ) -> Result<(), FirewheelError> {
+        region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow_mut()
+            .set_container_region_scroll_behavior(
+                region,
+                behavior,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            )?;
+
+        self.handle_visibility_changes();
+
+        Ok(())
+    }
+
+    /// Whether any scrollable container regions are springing back from
+    /// being scrolled past their bounds (see `ScrollBehavior::Bounce`).
+    pub fn is_scroll_bouncing(&self) -> bool {
+        self.layers_ordered.iter().any(|(_, layers)| {
+            layers.iter().any(|layer_entry| match layer_entry {
+                StrongLayerEntry::Widget(layer_entry) => layer_entry.borrow().is_scroll_bouncing(),
+                StrongLayerEntry::Background(_) => false,
+            })
+        })
+    }
+
+    /// Set the widget which receives the pointer events inside of the given
+    /// container region that none of the container's children captured
+    /// (via `WidgetNode::on_child_event_bubbled()`), or `None` to stop
+    /// bubbling events (the default).
+    ///
+    /// Events bubble up through nested container regions until one of their
+    /// handlers captures the event.
+    pub fn set_container_region_bubble_handler(
+        &mut self,
+        region: &mut ContainerRegionRef<A>,
+        handler: Option<&WidgetNodeRef<A>>,
+    ) -> Result<(), FirewheelError> {
+        let handler = if let Some(handler) = handler {
+            Some(
+                handler
+                    .shared
+                    .upgrade()
+                    .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?
+                    .downgrade(),
+            )
+        } else {
+            None
+        };
+
+        region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow_mut()
+            .set_container_region_bubble_handler(region, handler)
+    }
+
+    /// The scroll offset of a container region, or `None` if it is not
+    /// scrollable.
+    pub fn container_region_scroll_offset(
+        &self,
+        region: &ContainerRegionRef<A>,
+    ) -> Result<Option<Point>, FirewheelError> {
+        region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow()
+            .container_region_scroll_offset(region)
+    }
+
+    /// The rect of a container region in the coordinates of its layer.
+    pub fn container_region_rect(
+        &self,
+        region: &ContainerRegionRef<A>,
+    ) -> Result<Rect, FirewheelError> {
+        let layer_rect = region
+            .assigned_layer
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?
+            .borrow()
+            .region_tree
+            .layer_rect();
+        let entry = region
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+        let rect = entry.borrow().region.widget_rect(layer_rect);
+
+        Ok(rect)
+    }
+
     pub fn set_container_region_explicit_visibility(
         &mut self,
         region: &mut ContainerRegionRef<A>,
@@ -628,10 +2077,35 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
 
     pub fn add_widget_node(
         &mut self,
-        mut widget_node: Box<dyn WidgetNode<A>>,
+        widget_node: Box<dyn WidgetNode<A>>,
         layer: &WidgetLayerRef<A>,
         region_info: RegionInfo<A>,
         explicit_visibility: bool,
+    ) -> Result<WidgetNodeRef<A>, FirewheelError> {
+        self.add_widget_node_inner(widget_node, layer, Some(region_info), explicit_visibility)
+    }
+
+    /// Add a widget node whose region always covers the whole layer.
+    ///
+    /// This is useful for widgets which conceptually span the whole layer
+    /// (i.e. connection lines or a selection marquee). The widget's region
+    /// is resized along with the layer, and the widget is repainted whenever
+    /// the layer changes.
+    pub fn add_overlay_widget(
+        &mut self,
+        widget_node: Box<dyn WidgetNode<A>>,
+        layer: &WidgetLayerRef<A>,
+    ) -> Result<WidgetNodeRef<A>, FirewheelError> {
+        self.add_widget_node_inner(widget_node, layer, None, true)
+    }
+
+    /// If `region_info` is `None`, then the widget is added as an overlay.
+    fn add_widget_node_inner(
+        &mut self,
+        mut widget_node: Box<dyn WidgetNode<A>>,
+        layer: &WidgetLayerRef<A>,
+        region_info: Option<RegionInfo<A>>,
+        explicit_visibility: bool,
     ) -> Result<WidgetNodeRef<A>, FirewheelError> {
         if layer.shared.upgrade().is_none() {
             return Err(FirewheelError::LayerRemoved);
@@ -651,18 +2125,28 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             new_id,
         );
 
-        weak_layer_entry
-            .upgrade()
-            .unwrap()
-            .borrow_mut()
-            .add_widget_region(
-                &mut widget_entry,
-                region_info,
-                node_type,
-                explicit_visibility,
-                &mut self.widgets_just_shown,
-                &mut self.widgets_just_hidden,
-            )?;
+        {
+            let mut layer_entry = weak_layer_entry.upgrade().unwrap();
+            let mut layer_entry = layer_entry.borrow_mut();
+
+            if let Some(region_info) = region_info {
+                layer_entry.add_widget_region(
+                    &mut widget_entry,
+                    region_info,
+                    node_type,
+                    explicit_visibility,
+                    &mut self.widgets_just_shown,
+                    &mut self.widgets_just_hidden,
+                )?;
+            } else {
+                layer_entry.add_overlay_widget_region(
+                    &mut widget_entry,
+                    node_type,
+                    &mut self.widgets_just_shown,
+                    &mut self.widgets_just_hidden,
+                )?;
+            }
+        }
 
         //self.widgets.insert(widget_entry.clone());
 
@@ -675,13 +2159,74 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         })
     }
 
+    /// The rect of the given widget's region in the coordinates of its layer
+    /// (the same rect that is passed to `WidgetNode::on_region_changed()`).
+    pub fn widget_region_rect(
+        &self,
+        widget_node_ref: &WidgetNodeRef<A>,
+    ) -> Result<Rect, FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        let layer_rect = widget_entry
+            .assigned_layer_mut()
+            .upgrade()
+            .ok_or_else(|| FirewheelError::LayerRemoved)?
+            .borrow()
+            .region_tree
+            .layer_rect();
+        let region = widget_entry
+            .assigned_region()
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+        let rect = region.borrow().region.widget_rect(layer_rect);
+
+        Ok(rect)
+    }
+
+    /// The rect of the given widget's region in window coordinates, taking
+    /// the position and the content zoom of its layer into account.
+    pub fn widget_window_rect(
+        &self,
+        widget_node_ref: &WidgetNodeRef<A>,
+    ) -> Result<Rect, FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        widget_entry_window_rect(&mut widget_entry)
+    }
+
+    /// Add a widget node with the given rect in layer coordinates.
+    ///
+    /// This is a shorthand for `add_widget_node()` with a region that is
+    /// anchored to the top-left corner of the layer (see
+    /// `RegionInfo::from_layer_rect()`).
+    pub fn add_widget_node_at_rect(
+        &mut self,
+        widget_node: Box<dyn WidgetNode<A>>,
+        layer: &WidgetLayerRef<A>,
+        rect: Rect,
+        explicit_visibility: bool,
+    ) -> Result<WidgetNodeRef<A>, FirewheelError> {
+        self.add_widget_node(
+            widget_node,
+            layer,
+            RegionInfo::from_layer_rect(rect),
+            explicit_visibility,
+        )
+    }
+
     pub fn modify_widget_region(
         &mut self,
         widget_node_ref: &mut WidgetNodeRef<A>,
         new_size: Option<Size>,
         new_internal_anchor: Option<Anchor>,
         new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        new_anchor_offset: Option<AnchorOffset>,
     ) -> Result<(), FirewheelError> {
         let mut widget_entry = widget_node_ref
             .shared
@@ -708,87 +2253,340 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
-    pub fn set_widget_explicit_visibility(
+    /// Move a widget to a different container region (or to the root of its
+    /// layer) without removing and re-adding it.
+    ///
+    /// The widget keeps all of its internal state. The new parent is taken
+    /// from `region_info.parent_anchor_type` and must be in the same layer
+    /// as the widget.
+    pub fn reparent_widget(
         &mut self,
         widget_node_ref: &mut WidgetNodeRef<A>,
-        visible: bool,
+        region_info: RegionInfo<A>,
     ) -> Result<(), FirewheelError> {
         let mut widget_entry = widget_node_ref
             .shared
             .upgrade()
             .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
 
-        widget_entry
+        let res = widget_entry
             .assigned_layer_mut()
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .set_widget_explicit_visibility(
-                &mut widget_entry,
-                visible,
+            .reparent_widget_region(
+                &widget_entry,
+                region_info,
                 &mut self.widgets_just_shown,
                 &mut self.widgets_just_hidden,
             );
 
         self.handle_visibility_changes();
 
-        Ok(())
+        res
     }
 
-    pub fn remove_widget(
+    /// Whether the given widget is currently visible, taking into account its
+    /// explicit visibility, the visibility of the container regions it is
+    /// nested in, its layer, and the window, and whether it has been scrolled
+    /// or moved outside of its layer.
+    ///
+    /// Returns `false` if the widget has been removed.
+    pub fn is_widget_visible(&self, widget_node_ref: &WidgetNodeRef<A>) -> bool {
+        if let Some(mut widget_entry) = widget_node_ref.shared.upgrade() {
+            if let Some(layer_entry) = widget_entry.assigned_layer_mut().upgrade() {
+                return layer_entry.borrow().is_widget_visible(&widget_entry);
+            }
+        }
+
+        false
+    }
+
+    /// The container regions that the given widget's region is nested in,
+    /// starting with its direct parent and ending with the root container.
+    ///
+    /// This is useful for understanding why a widget is positioned where it
+    /// is. The list is empty if the widget's region is not nested in a
+    /// container or if the widget was removed.
+    pub fn region_ancestors(
+        &self,
+        widget_node_ref: &WidgetNodeRef<A>,
+    ) -> Vec<ContainerRegionRef<A>> {
+        if let Some(mut widget_entry) = widget_node_ref.shared.upgrade() {
+            if let Some(region_entry) = widget_entry.assigned_region().upgrade() {
+                let assigned_layer = widget_entry.assigned_layer_mut().clone();
+                if let Some(layer_entry) = assigned_layer.upgrade() {
+                    let layer_id = layer_entry.borrow().id;
+                    return region_entry.borrow().ancestors(&assigned_layer, layer_id);
+                }
+            }
+        }
+
+        Vec::new()
+    }
+
+    pub fn set_widget_explicit_visibility(
         &mut self,
         widget_node_ref: &mut WidgetNodeRef<A>,
+        visible: bool,
     ) -> Result<(), FirewheelError> {
         let mut widget_entry = widget_node_ref
             .shared
             .upgrade()
             .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
 
-        // Remove this widget from its assigned layer.
         widget_entry
             .assigned_layer_mut()
             .upgrade()
             .unwrap()
             .borrow_mut()
-            .remove_widget_region(
+            .set_widget_explicit_visibility(
                 &mut widget_entry,
+                visible,
                 &mut self.widgets_just_shown,
                 &mut self.widgets_just_hidden,
             );
 
-        // Remove this widget from all active event listeners.
-        self.widgets_scheduled_for_animation.remove(&widget_entry);
-        self.widgets_with_keyboard_listen.remove(&widget_entry);
-        self.widgets_with_pointer_leave_listen.remove(&widget_entry);
-        if let Some(w) = self.widget_with_pointer_lock.take() {
-            if w.0.unique_id() != widget_node_ref.unique_id() {
-                self.widget_with_pointer_lock = Some(w);
-            }
-        }
-        if let Some(w) = self.widget_with_text_comp_listen.take() {
-            if w.unique_id() != widget_node_ref.unique_id() {
-                self.widget_with_text_comp_listen = Some(w);
-            }
-        }
+        self.handle_visibility_changes();
 
         Ok(())
     }
 
-    pub fn send_user_event_to_widget(
+    /// Set whether the given widget is skipped entirely when hit-testing
+    /// pointer events (i.e. for a purely decorative widget drawn over other
+    /// widgets).
+    ///
+    /// Pointer events over an input-transparent widget fall through to the
+    /// widgets beneath it, even if the widget has requested to listen to
+    /// pointer events. This does not affect how the widget is painted, and
+    /// it does not affect a widget that currently has the pointer locked.
+    ///
+    /// By default this is `false`.
+    pub fn set_widget_input_transparent(
         &mut self,
         widget_node_ref: &mut WidgetNodeRef<A>,
-        event: Box<dyn Any>,
+        input_transparent: bool,
     ) -> Result<(), FirewheelError> {
         let mut widget_entry = widget_node_ref
             .shared
             .upgrade()
             .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
 
-        let res = {
-            widget_entry
-                .borrow_mut()
-                .on_user_event(event, &mut self.action_tx)
-        };
+        widget_entry
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set_widget_input_transparent(&widget_entry, input_transparent);
+
+        Ok(())
+    }
+
+    /// Set whether the given widget counts toward the content bounds of its
+    /// layer (see `layer_content_bounds()`).
+    ///
+    /// Use this to leave out purely decorative widgets such as backgrounds
+    /// and shadows, which would otherwise make the content bounds as large
+    /// as the layer.
+    ///
+    /// By default this is `true`.
+    pub fn set_widget_counts_toward_bounds(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+        counts_toward_bounds: bool,
+    ) -> Result<(), FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        widget_entry
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set_widget_counts_toward_bounds(&widget_entry, counts_toward_bounds);
+
+        Ok(())
+    }
+
+    /// The smallest rect (in the coordinates of the layer's widgets) that
+    /// contains every explicitly visible widget in the given layer, or
+    /// `None` if the layer has no such widgets.
+    ///
+    /// Widgets that lie outside of the layer's rect are included, and
+    /// widgets that don't count toward the bounds (see
+    /// `set_widget_counts_toward_bounds()`) are left out.
+    pub fn layer_content_bounds(
+        &self,
+        layer: &WidgetLayerRef<A>,
+    ) -> Result<Option<Rect>, FirewheelError> {
+        let layer_entry = layer
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::LayerRemoved)?;
+        let bounds = layer_entry.borrow().content_bounds();
+
+        Ok(bounds)
+    }
+
+    /// Find every visible widget in the given layer whose rect overlaps with
+    /// `rect` (in the coordinates of the layer's widgets), i.e. for a
+    /// rubber-band selection. Widgets that only touch the edge of `rect` are
+    /// left out.
+    pub fn widgets_intersecting_rect(
+        &self,
+        rect: Rect,
+        layer: &WidgetLayerRef<A>,
+    ) -> Result<Vec<WidgetNodeRef<A>>, FirewheelError> {
+        let layer_entry = layer
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::LayerRemoved)?;
+
+        let mut widgets = Vec::new();
+        layer_entry
+            .borrow()
+            .widgets_intersecting_rect(rect, &mut widgets);
+
+        Ok(widgets
+            .iter()
+            .map(|widget_entry| WidgetNodeRef {
+                shared: widget_entry.downgrade(),
+            })
+            .collect())
+    }
+
+    pub fn remove_widget(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+    ) -> Result<(), FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        // Remove this widget from its assigned layer.
+        widget_entry
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .remove_widget_region(
+                &mut widget_entry,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            );
+
+        self.forget_removed_widget(&mut widget_entry);
+
+        Ok(())
+    }
+
+    /// Remove a widget whose region has been removed from all active event
+    /// listeners, and notify it that it has been removed.
+    fn forget_removed_widget(&mut self, widget_entry: &mut StrongWidgetNodeEntry<A>) {
+        let unique_id = widget_entry.unique_id();
+
+        self.widgets_scheduled_for_animation.remove(widget_entry);
+        self.widgets_with_keyboard_listen.remove(widget_entry);
+        self.widgets_with_pointer_leave_listen.remove(widget_entry);
+        self.widgets_with_pre_frame_listen.remove(widget_entry);
+        self.release_pointer_from_widget(unique_id);
+        self.widgets_to_send_input_event
+            .retain(|(w, _)| w.unique_id() != unique_id);
+        if let Some(w) = self.widget_with_text_comp_listen.take() {
+            if w.unique_id() != unique_id {
+                self.widget_with_text_comp_listen = Some(w);
+            }
+        }
+        self.focusable_widgets.remove(widget_entry);
+        if let Some(w) = self.focused_widget.take() {
+            if w.unique_id() != unique_id {
+                self.focused_widget = Some(w);
+            }
+        }
+
+        self.remove_widget_from_group(widget_entry);
+
+        widget_entry.borrow_mut().on_removed(&mut self.action_tx);
+    }
+
+    /// Assign a widget to a group, or remove it from its group if `group` is
+    /// `None`.
+    ///
+    /// When the pointer moves over a widget in a group, every visible member
+    /// of that group receives an `InputEvent::GroupHoverChanged` event with
+    /// `hovered: true`. When the pointer moves off of the group, every visible
+    /// member receives the same event with `hovered: false`.
+    ///
+    /// The pointer is considered to be over a widget when that widget
+    /// captures the pointer event.
+    pub fn set_widget_group(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+        group: Option<WidgetGroupId>,
+    ) -> Result<(), FirewheelError> {
+        let widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        self.remove_widget_from_group(&widget_entry);
+
+        if let Some(group) = group {
+            self.widget_group_ids
+                .insert(widget_entry.unique_id(), group);
+            self.widget_groups
+                .entry(group)
+                .or_insert_with(WidgetNodeSet::new)
+                .insert(&widget_entry);
+        }
+
+        Ok(())
+    }
+
+    /// Assign a name to this widget which is shown in the `Debug` output of
+    /// its `WidgetNodeRef` and next to its ID in log messages (i.e. the
+    /// lifecycle trace and the overlapping dirty widgets report).
+    ///
+    /// The name is only stored when the `debug-names` feature is enabled.
+    pub fn set_widget_debug_name(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+        name: &str,
+    ) -> Result<(), FirewheelError> {
+        let widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        widget_entry.set_debug_name(name);
+
+        Ok(())
+    }
+
+    pub fn widget_group(&self, widget_node_ref: &WidgetNodeRef<A>) -> Option<WidgetGroupId> {
+        self.widget_group_ids
+            .get(&widget_node_ref.unique_id())
+            .copied()
+    }
+
+    pub fn send_user_event_to_widget(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+        event: Box<dyn Any>,
+    ) -> Result<(), FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        let res = {
+            widget_entry
+                .borrow_mut()
+                .on_user_event(event, &mut self.action_tx)
+        };
         if let Some(requests) = res {
             self.handle_widget_requests(&mut widget_entry, requests);
         }
@@ -796,6 +2594,126 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
+    /// Run the given animator on every animation event, sending an
+    /// `AnimatorUpdate` user event with the new value to the given widget.
+    ///
+    /// The animator is stopped once it is finished or once the widget is
+    /// removed.
+    pub fn start_animator(
+        &mut self,
+        animator: Animator,
+        widget_node_ref: &WidgetNodeRef<A>,
+    ) -> Result<AnimatorId, FirewheelError> {
+        let widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        Ok(self.push_animator(animator, AnimatorTarget::Widget(widget_entry.downgrade())))
+    }
+
+    /// Run the given animator on every animation event, calling `on_update`
+    /// with the new value and whether the animator has finished.
+    pub fn start_animator_with_callback<F: FnMut(f32, bool) + 'static>(
+        &mut self,
+        animator: Animator,
+        on_update: F,
+    ) -> AnimatorId {
+        self.push_animator(animator, AnimatorTarget::Callback(Box::new(on_update)))
+    }
+
+    /// Stop the given animator before it has finished. No further updates
+    /// are delivered for it.
+    ///
+    /// Returns `false` if the animator has already finished or was stopped.
+    pub fn stop_animator(&mut self, id: AnimatorId) -> bool {
+        let num_animators = self.animators.len();
+        self.animators.retain(|running| running.id != id);
+        self.animators.len() != num_animators
+    }
+
+    /// Pause or resume every animation in the window (i.e. while the
+    /// window is hidden or the host is not playing).
+    ///
+    /// While paused, `InputEvent::Animation` events are ignored: no widgets
+    /// receive them, and animators and scroll bounces don't advance. Widgets
+    /// stay scheduled for animation, so they receive the next animation
+    /// event after the animations are resumed.
+    ///
+    /// By default this is set to `false`.
+    pub fn set_animations_paused(&mut self, paused: bool) {
+        self.animations_paused = paused;
+    }
+
+    pub fn animations_paused(&self) -> bool {
+        self.animations_paused
+    }
+
+    /// Whether the given animator is still running.
+    pub fn is_animator_running(&self, id: AnimatorId) -> bool {
+        self.animators.iter().any(|running| running.id == id)
+    }
+
+    fn push_animator(&mut self, animator: Animator, target: AnimatorTarget<A>) -> AnimatorId {
+        let id = AnimatorId(self.next_animator_id);
+        self.next_animator_id += 1;
+
+        self.animators.push(RunningAnimator {
+            id,
+            animator,
+            target,
+        });
+
+        id
+    }
+
+    /// Send a user event to every widget for which `predicate` returns
+    /// `true` (i.e. to notify all widgets of a change to the theme).
+    ///
+    /// A new event is constructed with `make_event` for each widget that
+    /// matches. The requests returned by the widgets are handled after the
+    /// event has been sent to all of them.
+    ///
+    /// Returns the number of widgets that the event was sent to.
+    pub fn broadcast_user_event<F, P>(&mut self, make_event: F, predicate: P) -> usize
+    where
+        F: Fn() -> Box<dyn Any>,
+        P: Fn(&WidgetNodeRef<A>) -> bool,
+    {
+        let mut widgets: Vec<StrongWidgetNodeEntry<A>> = Vec::new();
+        for (_z_order, layers) in self.layers_ordered.iter() {
+            for layer_entry in layers.iter() {
+                if let StrongLayerEntry::Widget(layer_entry) = layer_entry {
+                    layer_entry.borrow().collect_widgets(&mut widgets);
+                }
+            }
+        }
+
+        let mut num_sent = 0;
+        for mut widget_entry in widgets.drain(..) {
+            let widget_node_ref = WidgetNodeRef {
+                shared: widget_entry.downgrade(),
+            };
+            if !predicate(&widget_node_ref) {
+                continue;
+            }
+
+            num_sent += 1;
+            let res = {
+                widget_entry
+                    .borrow_mut()
+                    .on_user_event(make_event(), &mut self.action_tx)
+            };
+            if let Some(requests) = res {
+                self.widget_requests.push((widget_entry, requests));
+            }
+        }
+
+        self.handle_queued_widget_requests();
+
+        num_sent
+    }
+
     pub fn mark_widget_dirty(
         &mut self,
         widget_node_ref: &mut WidgetNodeRef<A>,
@@ -815,9 +2733,136 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         Ok(())
     }
 
+    /// Re-resolve the rect and visibility of the widget's region (i.e. after
+    /// changing state that its layout depends on) without necessarily
+    /// repainting it.
+    ///
+    /// Unlike `mark_widget_dirty()`, the widget is only repainted (and
+    /// notified with `WidgetNode::on_region_changed()`) if its rect or its
+    /// visibility actually changed.
+    pub fn mark_widget_layout_dirty(
+        &mut self,
+        widget_node_ref: &mut WidgetNodeRef<A>,
+    ) -> Result<(), FirewheelError> {
+        let mut widget_entry = widget_node_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+        widget_entry
+            .assigned_layer_mut()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .mark_widget_region_layout_dirty(
+                &widget_entry,
+                &mut self.widgets_just_shown,
+                &mut self.widgets_just_hidden,
+            );
+
+        self.handle_visibility_changes();
+
+        Ok(())
+    }
+
+    /// Store the latest physical size of the window while it is being
+    /// resized, without resizing anything yet.
+    ///
+    /// Dragging the edge of a window sends many resize events per frame.
+    /// Only the last size per frame matters, so apply it once with
+    /// `commit_resize()` right before rendering instead of laying out the
+    /// layers and reallocating their textures for every event.
+    pub fn set_pending_window_size(&mut self, window_size: PhysicalSize) {
+        self.pending_window_size = Some(window_size);
+    }
+
+    /// Apply the size given to the last call to `set_pending_window_size()`,
+    /// if any, since the last time this was called.
+    ///
+    /// The layers set to fill the window (see
+    /// `set_widget_layer_fills_window()` and
+    /// `set_background_node_fills_window()`) are resized to the logical size
+    /// of the window. The physical size is returned so that the host can
+    /// resize the window's surface.
+    ///
+    /// Call this once per frame, right before rendering.
+    pub fn commit_resize(&mut self) -> Option<PhysicalSize> {
+        let window_size = self.pending_window_size.take()?;
+        let logical_size = window_size.to_logical_2d(self.scale_factor_2d);
+
+        for (_z_order, layers) in self.layers_ordered.iter_mut() {
+            for layer_entry in layers.iter_mut() {
+                match layer_entry {
+                    StrongLayerEntry::Widget(layer_entry) => {
+                        let mut layer = layer_entry.borrow_mut();
+                        if layer.fills_window {
+                            layer.set_size(
+                                logical_size,
+                                self.scale_factor_2d,
+                                &mut self.widgets_just_shown,
+                                &mut self.widgets_just_hidden,
+                            );
+                        }
+                    }
+                    StrongLayerEntry::Background(layer_entry) => {
+                        let mut layer = layer_entry.borrow_mut();
+                        if layer.fills_window {
+                            layer.set_size(logical_size, self.scale_factor_2d);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.handle_visibility_changes();
+
+        Some(window_size)
+    }
+
     pub fn set_scale_factor(&mut self, scale_factor: ScaleFactor) {
-        if self.scale_factor != scale_factor {
-            self.scale_factor = scale_factor;
+        self.set_scale_factor_2d(scale_factor.into());
+    }
+
+    /// Set separate horizontal and vertical scale factors, for displays with
+    /// a different DPI on each axis.
+    ///
+    /// The physical rects of layers and widgets are scaled per axis. Things
+    /// that can't be scaled per axis (i.e. font sizes) use the larger of the
+    /// two factors (see `ScaleFactor2D::scalar()`), which is what
+    /// `scale_factor()` returns.
+    pub fn set_scale_factor_2d(&mut self, scale_factor: ScaleFactor2D) {
+        self.dpi_scale_factor_2d = scale_factor;
+        self.update_scale_factor();
+    }
+
+    /// Set a multiplier for the size of the whole UI (i.e. from an
+    /// accessibility setting), which is applied on top of the scale factor
+    /// of the display.
+    ///
+    /// Everything is laid out and painted at the combined scale factor, so
+    /// text stays crisp. The host must use `scale_factor()` (and not the
+    /// scale factor of the display) when converting between logical and
+    /// physical coordinates, i.e. for pointer positions and the logical size
+    /// of the window.
+    ///
+    /// Returns `FirewheelError::InvalidScale` (and leaves the UI scale as it
+    /// was) if `ui_scale` is zero, negative, or not finite.
+    pub fn set_ui_scale(&mut self, ui_scale: f32) -> Result<(), FirewheelError> {
+        if !(ui_scale.is_finite() && ui_scale > 0.0) {
+            return Err(FirewheelError::InvalidScale);
+        }
+
+        self.ui_scale = ui_scale;
+        self.update_scale_factor();
+
+        Ok(())
+    }
+
+    fn update_scale_factor(&mut self) {
+        let scale_factor = effective_scale_factor(self.dpi_scale_factor_2d, self.ui_scale);
+        if self.scale_factor_2d != scale_factor {
+            self.scale_factor_2d = scale_factor;
+            self.text_layout_cache.invalidate();
 
             for (_z_order, layers) in self.layers_ordered.iter_mut() {
                 for layer_entry in layers.iter_mut() {
@@ -845,46 +2890,325 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         }
     }
 
-    pub fn handle_input_event(&mut self, event: &InputEvent) -> InputEventResult {
-        match event {
-            InputEvent::Animation(_) => {
-                let mut widgets_to_remove_from_animation: Vec<StrongWidgetNodeEntry<A>> =
-                    Vec::new();
-                let mut widget_requests: Vec<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)> =
-                    Vec::new();
-                std::mem::swap(
-                    &mut widgets_to_remove_from_animation,
-                    &mut self.widgets_to_remove_from_animation,
-                );
-                std::mem::swap(&mut widget_requests, &mut self.widget_requests);
+    /// The total amount of GPU memory used by the textures of all layers in
+    /// bytes (as of the last frame).
+    pub fn texture_memory_usage(&self) -> usize {
+        self.layers_ordered
+            .iter()
+            .flat_map(|(_z_order, layer_entries)| layer_entries.iter())
+            .map(|layer_entry| match layer_entry {
+                StrongLayerEntry::Widget(layer_entry) => layer_entry
+                    .borrow()
+                    .renderer
+                    .as_ref()
+                    .map(|r| r.texture_memory_bytes())
+                    .unwrap_or(0),
+                StrongLayerEntry::Background(layer_entry) => layer_entry
+                    .borrow()
+                    .renderer
+                    .as_ref()
+                    .map(|r| r.texture_memory_bytes())
+                    .unwrap_or(0),
+            })
+            .sum()
+    }
 
-                for widget_entry in self.widgets_scheduled_for_animation.iter_mut() {
-                    let res = {
-                        widget_entry
-                            .borrow_mut()
-                            .on_input_event(event, &mut self.action_tx)
-                    };
-                    if let EventCapturedStatus::Captured(requests) = res {
-                        widget_requests.push((widget_entry.clone(), requests));
-                    } else {
-                        widgets_to_remove_from_animation.push(widget_entry.clone());
+    /// Limit the total amount of GPU memory used by the textures of all
+    /// layers, in bytes.
+    ///
+    /// When the usage is over the budget at the start of `render()`, the
+    /// textures of hidden layers are freed (the largest first) until it is
+    /// within budget. A freed texture is recreated and repainted once its
+    /// layer is shown again. The textures of visible layers are never freed,
+    /// so the usage can still be over budget if the visible layers alone
+    /// exceed it.
+    ///
+    /// Set this to `None` to remove the budget (the default).
+    pub fn set_texture_memory_budget(&mut self, budget: Option<usize>) {
+        self.texture_memory_budget = budget;
+    }
+
+    pub(crate) fn texture_memory_budget(&self) -> Option<usize> {
+        self.texture_memory_budget
+    }
+
+    /// Free the texture of a layer once it has been hidden for longer than
+    /// the given timeout (i.e. a settings page that was closed a while ago).
+    ///
+    /// The timeout is checked at the start of `render()`, so a layer's
+    /// texture is freed on the first frame after the timeout has passed. A
+    /// freed texture is recreated and repainted once its layer is shown
+    /// again. This can be combined with `set_texture_memory_budget()`.
+    ///
+    /// Set this to `None` to keep the textures of hidden layers (the
+    /// default).
+    pub fn set_hidden_layer_texture_timeout(&mut self, timeout: Option<Duration>) {
+        self.hidden_layer_texture_timeout = timeout;
+    }
+
+    pub(crate) fn hidden_layer_texture_timeout(&self) -> Option<Duration> {
+        self.hidden_layer_texture_timeout
+    }
+
+    /// Check the invariants of the region tree of every widget layer (i.e.
+    /// that the children of each container region reference it as their
+    /// parent, and that the visibility of each region matches its rect).
+    ///
+    /// This is meant for debugging the layout engine. With the
+    /// `validate-tree` feature enabled, this is run after every change to
+    /// the layout in debug builds.
+    ///
+    /// # Panics
+    ///
+    /// This panics with a description of the invariant if one is violated.
+    pub fn validate_tree(&self) {
+        for (_z_order, layers) in self.layers_ordered.iter() {
+            for layer_entry in layers.iter() {
+                if let StrongLayerEntry::Widget(layer_entry) = layer_entry {
+                    let layer = layer_entry.borrow();
+                    if let Err(e) = layer.validate_region_tree() {
+                        panic!(
+                            "the region tree of widget layer {} is invalid: {}",
+                            layer.id, e
+                        );
                     }
                 }
+            }
+        }
+    }
 
-                for (mut widget_entry, requests) in widget_requests.drain(..) {
-                    self.handle_widget_requests(&mut widget_entry, requests);
-                }
-                for widget_entry in widgets_to_remove_from_animation.drain(..) {
-                    self.widgets_scheduled_for_animation.remove(&widget_entry);
-                }
+    /// Clear and repaint every layer fully on every frame, instead of only
+    /// repainting the widgets that are dirty and clearing the rects that
+    /// they have vacated.
+    ///
+    /// This is meant for debugging. If a visual glitch disappears with this
+    /// enabled, then the bug is in the incremental repainting and not in the
+    /// widget's `paint()` method.
+    ///
+    /// By default this is disabled.
+    pub fn set_always_full_repaint(&mut self, always_full_repaint: bool) {
+        self.always_full_repaint = always_full_repaint;
+    }
 
-                std::mem::swap(
-                    &mut widgets_to_remove_from_animation,
+    /// Log a warning whenever two dirty widgets whose regions overlap are
+    /// painted in the same frame.
+    ///
+    /// This is meant for debugging. The widget that is painted last covers
+    /// the other one, which can cause flickering if they are painted in a
+    /// different order in the next frame. This usually means that widgets
+    /// overlap by mistake.
+    ///
+    /// By default this is disabled.
+    pub fn set_report_overlapping_dirty_widgets(&mut self, report: bool) {
+        self.report_overlapping_dirty_widgets = report;
+    }
+
+    pub(crate) fn report_overlapping_dirty_widgets(&self) -> bool {
+        self.report_overlapping_dirty_widgets
+    }
+
+    /// Set how the positions of pointer events are rounded before they are
+    /// sent to widgets, i.e. to the nearest pixel for pixel-art tools.
+    ///
+    /// The unsnapped position is still available to widgets as
+    /// `PointerEvent::raw_position`. The velocity of the pointer is computed
+    /// from the unsnapped positions.
+    ///
+    /// By default this is `PointerSnap::None`.
+    pub fn set_pointer_snap(&mut self, pointer_snap: PointerSnap) {
+        self.pointer_snap = pointer_snap;
+    }
+
+    /// Limit the number of actions that can be waiting in the action queue
+    /// (the channel passed to the constructor), for hosts that only process
+    /// a certain number of actions per frame.
+    ///
+    /// While a limit is set, the actions sent by widgets are held by the
+    /// window and moved to the action queue at the end of
+    /// `handle_input_event()` and `render()`, until the queue holds `limit`
+    /// actions. Actions that are already in the queue are never removed, so
+    /// `overflow` only decides which of the new actions are dropped. A
+    /// bounded queue is never filled past its capacity, so the window never
+    /// blocks on it.
+    pub fn set_action_queue_limit(&mut self, limit: usize, overflow: ActionQueueOverflow) {
+        if let Some(queue_limit) = &mut self.action_queue_limit {
+            queue_limit.limit = limit;
+            queue_limit.overflow = overflow;
+        } else {
+            let (pending_tx, pending_rx) = crossbeam_channel::unbounded();
+            let host_action_tx = std::mem::replace(&mut self.action_tx, pending_tx);
+            self.action_queue_limit = Some(ActionQueueLimit {
+                limit,
+                overflow,
+                host_action_tx,
+                pending_rx,
+            });
+        }
+        self.enforce_action_queue_limit();
+    }
+
+    /// Remove the limit set with `set_action_queue_limit()`. The actions
+    /// held by the window are moved to the action queue (the ones that don't
+    /// fit in a bounded queue are dropped).
+    pub fn clear_action_queue_limit(&mut self) {
+        if let Some(queue_limit) = self.action_queue_limit.take() {
+            let overflowed = try_send_actions(
+                &queue_limit.host_action_tx,
+                queue_limit.pending_rx.try_iter(),
+            );
+            if !overflowed.is_empty() {
+                log::warn!(
+                    "Dropped {} actions: the action queue is full",
+                    overflowed.len()
+                );
+            }
+            self.action_tx = queue_limit.host_action_tx;
+        }
+        self.action_queue_full = false;
+    }
+
+    /// Whether the action queue is over its limit when using
+    /// `ActionQueueOverflow::Error`.
+    pub fn action_queue_full(&self) -> bool {
+        self.action_queue_full
+    }
+
+    fn enforce_action_queue_limit(&mut self) {
+        self.action_queue_full = false;
+
+        if let Some(queue_limit) = &self.action_queue_limit {
+            let pending: Vec<A> = queue_limit.pending_rx.try_iter().collect();
+            let queued = queue_limit.host_action_tx.len();
+            let mut room = queue_limit.limit.saturating_sub(queued);
+            if let Some(capacity) = queue_limit.host_action_tx.capacity() {
+                room = room.min(capacity.saturating_sub(queued));
+            }
+            let overflowed = try_send_actions(
+                &queue_limit.host_action_tx,
+                limit_action_queue(pending, room, queue_limit.overflow),
+            );
+
+            if queue_limit.overflow == ActionQueueOverflow::Error {
+                // The actions that don't fit in a bounded queue are held
+                // until the next time the limit is enforced.
+                self.action_queue_full =
+                    !overflowed.is_empty() || queue_limit.host_action_tx.len() > queue_limit.limit;
+                for action in overflowed {
+                    // The window owns the receiver, so this can't fail.
+                    self.action_tx.send(action).unwrap();
+                }
+            } else if !overflowed.is_empty() {
+                log::warn!(
+                    "Dropped {} actions: the action queue is full",
+                    overflowed.len()
+                );
+            }
+        }
+    }
+
+    /// Find the widget that a pointer event at this position would be sent
+    /// to, without sending any events.
+    pub fn widget_at_point(&self, point: Point) -> Option<WidgetNodeRef<A>> {
+        self.widgets_at_points(&[point]).pop().flatten()
+    }
+
+    /// The same as `widget_at_point()` for several points at once (i.e. for
+    /// multiple cursors). The returned `Vec` has one entry per point.
+    pub fn widgets_at_points(&self, points: &[Point]) -> Vec<Option<WidgetNodeRef<A>>> {
+        let layers: Vec<Ref<'_, WidgetLayer<A>>> = self
+            .layers_ordered
+            .iter()
+            .rev()
+            .flat_map(|(_z_index, layers)| layers.iter())
+            .filter_map(|layer_entry| {
+                if let StrongLayerEntry::Widget(layer_entry) = layer_entry {
+                    Some(layer_entry.borrow())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let layers: Vec<&WidgetLayer<A>> = layers.iter().map(|layer| &**layer).collect();
+
+        widgets_at_points_in_layers(&layers, points)
+            .into_iter()
+            .map(|widget_entry| {
+                widget_entry.map(|widget_entry| WidgetNodeRef {
+                    shared: widget_entry.downgrade(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn handle_input_event(&mut self, event: &InputEvent) -> InputEventResult {
+        trace_lifecycle!("input event: {:?}", event);
+
+        #[cfg(feature = "input-recording")]
+        if let Some(input_recorder) = &mut self.input_recorder {
+            input_recorder.record(event, Instant::now());
+        }
+
+        let event = match apply_input_filter(self.input_filter.as_deref_mut(), event) {
+            Some(event) => event,
+            None => {
+                trace_lifecycle!("input event dropped: input filter");
+                return self.input_event_result();
+            }
+        };
+        let event = event.as_ref();
+
+        // Fill in the pointer's timestamp and velocity and snap its position
+        // before sending the event to widgets.
+        let pointer_event_with_velocity;
+        let event = if let InputEvent::Pointer(e) = event {
+            let mut e = *e;
+            fill_pointer_event_timing(&mut e, &mut self.pointer_velocity, Instant::now());
+            snap_pointer_event(&mut e, self.pointer_snap, self.scale_factor_2d);
+            pointer_event_with_velocity = InputEvent::Pointer(e);
+            &pointer_event_with_velocity
+        } else {
+            event
+        };
+
+        match event {
+            InputEvent::Animation(e) => {
+                if !self.step_animation_frame(e.time_delta) {
+                    return self.input_event_result();
+                }
+
+                let mut widgets_to_remove_from_animation: Vec<StrongWidgetNodeEntry<A>> =
+                    Vec::new();
+                std::mem::swap(
+                    &mut widgets_to_remove_from_animation,
+                    &mut self.widgets_to_remove_from_animation,
+                );
+
+                for widget_entry in self.widgets_scheduled_for_animation.iter_mut() {
+                    let res = {
+                        widget_entry.borrow_mut().on_input_event(
+                            event,
+                            &self.text_context,
+                            &mut self.action_tx,
+                        )
+                    };
+                    if let EventCapturedStatus::Captured(requests) = res {
+                        self.widget_requests.push((widget_entry.clone(), requests));
+                    } else {
+                        widgets_to_remove_from_animation.push(widget_entry.clone());
+                    }
+                }
+
+                self.handle_queued_widget_requests();
+                for widget_entry in widgets_to_remove_from_animation.drain(..) {
+                    self.widgets_scheduled_for_animation.remove(&widget_entry);
+                }
+
+                std::mem::swap(
+                    &mut widgets_to_remove_from_animation,
                     &mut self.widgets_to_remove_from_animation,
                 );
-                std::mem::swap(&mut widget_requests, &mut self.widget_requests);
             }
-            InputEvent::Pointer(mut e) => {
+            InputEvent::Pointer(e) => {
                 let pointer_locked_in_place = self
                     .widget_with_pointer_lock
                     .as_ref()
@@ -894,63 +3218,93 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                     .unwrap_or(false);
 
                 if pointer_locked_in_place {
-                    // Remove the position data when the pointer is locked in place.
-                    e.position = Point::default();
-
                     let mut widget_entry =
                         self.widget_with_pointer_lock.as_ref().unwrap().0.clone();
+                    let mut widget_event = widget_pointer_event(&mut widget_entry, event);
+
+                    // Remove the position data when the pointer is locked in place.
+                    if let InputEvent::Pointer(e) = &mut widget_event {
+                        e.position = Point::default();
+                    }
                     let res = {
-                        widget_entry
-                            .borrow_mut()
-                            .on_input_event(event, &mut self.action_tx)
+                        widget_entry.borrow_mut().on_input_event(
+                            &widget_event,
+                            &self.text_context,
+                            &mut self.action_tx,
+                        )
                     };
                     if let EventCapturedStatus::Captured(requests) = res {
                         self.handle_widget_requests(&mut widget_entry, requests);
                     }
-                } else {
-                    if !self.widgets_with_pointer_leave_listen.is_empty() {
-                        let mut widget_requests: Vec<(
-                            StrongWidgetNodeEntry<A>,
-                            WidgetNodeRequests,
-                        )> = Vec::new();
-                        std::mem::swap(&mut widget_requests, &mut self.widget_requests);
-
-                        for widget_entry in self.widgets_with_pointer_leave_listen.iter_mut() {
-                            let res = {
-                                widget_entry
-                                    .borrow_mut()
-                                    .on_input_event(event, &mut self.action_tx)
-                            };
-                            if let EventCapturedStatus::Captured(requests) = res {
-                                widget_requests.push((widget_entry.clone(), requests));
-                            }
+                } else if !self.pointer_capture_stack.is_empty() {
+                    // Send the event to the widgets that have captured the
+                    // pointer, from the top of the stack down, until one of
+                    // them captures it.
+                    let captured_widgets: Vec<StrongWidgetNodeEntry<A>> = self
+                        .pointer_capture_stack
+                        .iter_top_down()
+                        .cloned()
+                        .collect();
+
+                    for mut widget_entry in captured_widgets {
+                        // A widget may have released the capture or lost it
+                        // to another widget while handling this event.
+                        if !self
+                            .pointer_capture_stack
+                            .contains(widget_entry.unique_id())
+                        {
+                            continue;
                         }
 
-                        for (mut widget_entry, requests) in widget_requests.drain(..) {
+                        let widget_event = widget_pointer_event(&mut widget_entry, event);
+                        let res = {
+                            widget_entry.borrow_mut().on_input_event(
+                                &widget_event,
+                                &self.text_context,
+                                &mut self.action_tx,
+                            )
+                        };
+                        if let EventCapturedStatus::Captured(requests) = res {
                             self.handle_widget_requests(&mut widget_entry, requests);
+                            break;
                         }
-
-                        std::mem::swap(&mut widget_requests, &mut self.widget_requests);
                     }
+                } else {
+                    self.send_pointer_event_to_leave_listeners(event);
 
                     let mut widget_requests = None;
+                    let mut blocked = false;
                     for (_z_index, layers) in self.layers_ordered.iter_mut().rev() {
                         for layer_entry in layers.iter_mut() {
                             if let StrongLayerEntry::Widget(layer_entry) = layer_entry {
-                                if let Some(captured_res) = layer_entry
-                                    .borrow_mut()
-                                    .handle_pointer_event(e, &mut self.action_tx)
-                                {
+                                let mut layer = layer_entry.borrow_mut();
+                                if let Some(captured_res) = layer.handle_pointer_event(
+                                    *e,
+                                    &self.text_context,
+                                    &mut self.action_tx,
+                                ) {
                                     widget_requests = Some(captured_res);
+                                    blocked = true;
+                                    break;
+                                }
+                                if layer.blocks_pointer_events() {
+                                    blocked = true;
                                     break;
                                 }
                             }
                         }
-                        if widget_requests.is_some() {
+                        if blocked {
                             break;
                         }
                     }
 
+                    let hovered_group = widget_requests.as_ref().and_then(|(widget_entry, _)| {
+                        self.widget_group_ids
+                            .get(&widget_entry.unique_id())
+                            .copied()
+                    });
+                    self.set_hovered_group(hovered_group);
+
                     if let Some((mut widget_entry, requests)) = widget_requests {
                         self.handle_widget_requests(&mut widget_entry, requests);
                     }
@@ -960,9 +3314,11 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                 let mut requests = None;
                 if let Some((mut last_widget, _lock_type)) = self.widget_with_pointer_lock.take() {
                     let res = {
-                        last_widget
-                            .borrow_mut()
-                            .on_input_event(event, &mut self.action_tx)
+                        last_widget.borrow_mut().on_input_event(
+                            event,
+                            &self.text_context,
+                            &mut self.action_tx,
+                        )
                     };
                     if let EventCapturedStatus::Captured(r) = res {
                         requests = Some((last_widget.clone(), r));
@@ -973,35 +3329,53 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                     self.handle_widget_requests(&mut widget_entry, requests);
                 }
             }
+            InputEvent::PointerLeftWindow => {
+                // A widget with a pointer lock or capture keeps receiving
+                // pointer events even when the pointer is outside of the
+                // window.
+                if self.widget_with_pointer_lock.is_none() && self.pointer_capture_stack.is_empty()
+                {
+                    // The pointer can't be over any widget anymore, so the
+                    // widgets listening to pointer leave events receive a
+                    // pointer event that is outside of their bounds.
+                    let position = Point::new(f64::MIN, f64::MIN);
+                    self.send_pointer_event_to_leave_listeners(&InputEvent::Pointer(
+                        PointerEvent {
+                            position,
+                            raw_position: position,
+                            ..Default::default()
+                        },
+                    ));
+
+                    self.set_hovered_group(None);
+                    self.pointer_velocity.reset();
+                }
+            }
             InputEvent::Keyboard(_) => {
-                let mut widget_requests: Vec<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)> =
-                    Vec::new();
-                std::mem::swap(&mut widget_requests, &mut self.widget_requests);
-
                 for widget_entry in self.widgets_with_keyboard_listen.iter_mut() {
                     let res = {
-                        widget_entry
-                            .borrow_mut()
-                            .on_input_event(event, &mut self.action_tx)
+                        widget_entry.borrow_mut().on_input_event(
+                            event,
+                            &self.text_context,
+                            &mut self.action_tx,
+                        )
                     };
                     if let EventCapturedStatus::Captured(requests) = res {
-                        widget_requests.push((widget_entry.clone(), requests));
+                        self.widget_requests.push((widget_entry.clone(), requests));
                     }
                 }
 
-                for (mut widget_entry, requests) in widget_requests.drain(..) {
-                    self.handle_widget_requests(&mut widget_entry, requests);
-                }
-
-                std::mem::swap(&mut widget_requests, &mut self.widget_requests);
+                self.handle_queued_widget_requests();
             }
             InputEvent::TextComposition(_) => {
                 let mut requests = None;
                 if let Some(widget_entry) = &mut self.widget_with_text_comp_listen {
                     let res = {
-                        widget_entry
-                            .borrow_mut()
-                            .on_input_event(event, &mut self.action_tx)
+                        widget_entry.borrow_mut().on_input_event(
+                            event,
+                            &self.text_context,
+                            &mut self.action_tx,
+                        )
                     };
                     if let EventCapturedStatus::Captured(r) = res {
                         requests = Some((widget_entry.clone(), r));
@@ -1017,31 +3391,329 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             }
         }
 
-        // Handle any extra events that have occurred as a result of handling
-        // widget requests.
+        self.send_queued_input_events();
+
+        self.enforce_action_queue_limit();
+
+        self.input_event_result()
+    }
+
+    /// Handle any extra events that have occurred as a result of handling
+    /// widget requests.
+    fn send_queued_input_events(&mut self) {
         while let Some((mut widget_entry, event)) = self.widgets_to_send_input_event.pop() {
             let res = {
-                widget_entry
-                    .borrow_mut()
-                    .on_input_event(&event, &mut self.action_tx)
+                widget_entry.borrow_mut().on_input_event(
+                    &event,
+                    &self.text_context,
+                    &mut self.action_tx,
+                )
+            };
+            if let EventCapturedStatus::Captured(requests) = res {
+                self.handle_widget_requests(&mut widget_entry, requests);
+            }
+        }
+    }
+
+    /// Handle any widget requests that are still queued, along with the
+    /// input events that they result in.
+    ///
+    /// Requests returned from event handlers are handled right away, so this
+    /// only needs to be called after queuing requests outside of an event
+    /// (i.e. from a timer).
+    pub fn flush_widget_requests(&mut self) {
+        self.handle_queued_widget_requests();
+        self.send_queued_input_events();
+    }
+
+    /// Send a pointer event to every widget that is listening to pointer
+    /// leave events, so they can tell when the pointer is no longer over them.
+    fn send_pointer_event_to_leave_listeners(&mut self, event: &InputEvent) {
+        if self.widgets_with_pointer_leave_listen.is_empty() {
+            return;
+        }
+
+        for widget_entry in self.widgets_with_pointer_leave_listen.iter_mut() {
+            let widget_event = widget_pointer_event(widget_entry, event);
+            let res = {
+                widget_entry.borrow_mut().on_input_event(
+                    &widget_event,
+                    &self.text_context,
+                    &mut self.action_tx,
+                )
             };
             if let EventCapturedStatus::Captured(requests) = res {
+                self.widget_requests.push((widget_entry.clone(), requests));
+            }
+        }
+
+        self.handle_queued_widget_requests();
+    }
+
+    fn handle_queued_widget_requests(&mut self) {
+        // Handling a request can queue more requests, so the queue is swapped
+        // out while it is being drained.
+        let mut widget_requests = std::mem::take(&mut self.widget_requests);
+        while !widget_requests.is_empty() {
+            for (mut widget_entry, requests) in widget_requests.drain(..) {
                 self.handle_widget_requests(&mut widget_entry, requests);
             }
+            std::mem::swap(&mut widget_requests, &mut self.widget_requests);
+        }
+        self.widget_requests = widget_requests;
+    }
+
+    /// Release the pointer lock and the pointer capture of a widget that has
+    /// been removed or hidden in the middle of a gesture.
+    ///
+    /// If the widget held the pointer lock, the host is told to release the
+    /// pointer with `InputEventResult::pointer_lock_released` the next time
+    /// an event is handled, since the widget will never request an unlock.
+    fn release_pointer_from_widget(&mut self, unique_id: u64) {
+        if let Some((last_widget, lock_type)) = self.widget_with_pointer_lock.take() {
+            if last_widget.unique_id() != unique_id {
+                self.widget_with_pointer_lock = Some((last_widget, lock_type));
+            } else {
+                self.pointer_lock_released = true;
+            }
         }
+        self.pointer_capture_stack.release(unique_id);
+    }
 
-        let lock_pointer_in_place = self
+    fn input_event_result(&mut self) -> InputEventResult {
+        let pointer_lock = self
             .widget_with_pointer_lock
             .as_ref()
-            .map(|(_, lock_type)| *lock_type == SetPointerLockType::LockInPlaceAndHideCursor)
-            .unwrap_or(false);
+            .map(|(_, lock_type)| *lock_type)
+            .unwrap_or(SetPointerLockType::Unlock);
 
         InputEventResult {
-            lock_pointer_in_place,
+            lock_pointer_in_place: pointer_lock == SetPointerLockType::LockInPlaceAndHideCursor,
+            pointer_lock,
+            action_queue_full: self.action_queue_full,
+            pointer_lock_released: std::mem::take(&mut self.pointer_lock_released),
+        }
+    }
+
+    /// Set a filter that is called on every event passed to
+    /// `handle_input_event()` (including the events from the `inject_*`
+    /// methods) before it is dispatched to any widgets.
+    ///
+    /// The filter may modify the event (i.e. to remap the pointer position
+    /// for a scaled render target). If it returns `false`, the event is
+    /// dropped.
+    ///
+    /// The filter is owned by this `AppWindow`, so it cannot call back into
+    /// it. To record events or generate new ones, send them through a
+    /// channel and handle them after `handle_input_event()` returns. Events
+    /// that are generated internally and sent directly to widgets (i.e.
+    /// `InputEvent::TextCompositionFocused`) do not pass through the filter.
+    pub fn set_input_filter(&mut self, filter: Box<dyn FnMut(&mut InputEvent) -> bool>) {
+        self.input_filter = Some(filter);
+    }
+
+    pub fn clear_input_filter(&mut self) {
+        self.input_filter = None;
+    }
+
+    /// Start recording every input event sent to `handle_input_event()`
+    /// (including the events sent by the `inject_*` methods). This replaces
+    /// the recording in progress, if there is one.
+    #[cfg(feature = "input-recording")]
+    pub fn start_input_recording(&mut self) {
+        self.input_recorder = Some(InputRecorder::new(Instant::now()));
+    }
+
+    /// Stop recording input events and return the log, or `None` if there
+    /// was no recording in progress.
+    #[cfg(feature = "input-recording")]
+    pub fn stop_input_recording(&mut self) -> Option<InputLog> {
+        self.input_recorder.take().map(|recorder| recorder.finish())
+    }
+
+    /// Send the events in the given log through `handle_input_event()` at
+    /// the same cadence as when they were recorded.
+    ///
+    /// Each event is sent once its recorded time (relative to the start of
+    /// the log) has passed, so this sleeps on the calling thread for the
+    /// whole length of the log. The window is not rendered and the host's
+    /// own events are not handled in the meantime, so call this from a test
+    /// or a debugging tool rather than from the window's event loop.
+    ///
+    /// Keyboard events carry no timestamp, so this is the only way that
+    /// widgets which time key gestures see the same spacing. The timestamps
+    /// of the pointer events are set to the recorded times as well. For the
+    /// events to reach the same widgets, the window should have the same
+    /// layout as when the log was recorded.
+    #[cfg(feature = "input-recording")]
+    pub fn replay_input(&mut self, log: &InputLog) {
+        let start = Instant::now();
+        for (recorded, event) in log.events.iter().zip(replayed_events(log, start)) {
+            let due = start + recorded.time;
+            let now = Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+
+            self.handle_input_event(&event);
+        }
+    }
+
+    /// Move the pointer to the given position (in logical coordinates) as if
+    /// the user had moved it.
+    ///
+    /// The `inject_*` methods are meant for driving the UI deterministically
+    /// in automated tests. They keep track of their own pointer state, so
+    /// they should not be mixed with real pointer events from the window.
+    pub fn inject_pointer_move(&mut self, position: Point) -> InputEventResult {
+        self.injected_pointer.update_position(position);
+        self.handle_input_event(&InputEvent::Pointer(self.injected_pointer))
+    }
+
+    /// Press the given pointer button at the given position (in logical
+    /// coordinates) as if the user had clicked there.
+    ///
+    /// Any actions emitted by the widgets are sent to the action channel
+    /// that this window was created with.
+    ///
+    /// ```ignore
+    /// app_window.inject_pointer_down(Point::new(50.0, 20.0), PointerButton::Left);
+    /// app_window.inject_pointer_up(Point::new(50.0, 20.0), PointerButton::Left);
+    ///
+    /// assert!(matches!(
+    ///     action_rx.try_recv(),
+    ///     Ok(MyAction::LabelButtonPressed)
+    /// ));
+    /// ```
+    pub fn inject_pointer_down(
+        &mut self,
+        position: Point,
+        button: PointerButton,
+    ) -> InputEventResult {
+        self.injected_pointer.update_position(position);
+        self.injected_pointer.update_button(button, true);
+        self.handle_input_event(&InputEvent::Pointer(self.injected_pointer))
+    }
+
+    /// Release the given pointer button at the given position (in logical
+    /// coordinates).
+    pub fn inject_pointer_up(
+        &mut self,
+        position: Point,
+        button: PointerButton,
+    ) -> InputEventResult {
+        self.injected_pointer.update_position(position);
+        self.injected_pointer.update_button(button, false);
+        self.handle_input_event(&InputEvent::Pointer(self.injected_pointer))
+    }
+
+    /// Send a key press or release to the widgets listening to keys.
+    pub fn inject_key(&mut self, event: KeyboardEvent) -> InputEventResult {
+        self.injected_pointer.modifiers = event.modifiers;
+        self.handle_input_event(&InputEvent::Keyboard(event))
+    }
+
+    /// Send a committed piece of text to the widget listening to text
+    /// composition.
+    pub fn inject_text(&mut self, text: &str) -> InputEventResult {
+        self.handle_input_event(&InputEvent::TextComposition(CompositionEvent {
+            state: CompositionState::End,
+            data: text.into(),
+        }))
+    }
+
+    /// Move the keyboard focus to the nearest focusable widget in the given
+    /// direction from the currently focused widget (i.e. for arrow key or
+    /// gamepad navigation).
+    ///
+    /// Widgets opt in to receiving focus with
+    /// `WidgetNodeRequests::set_focusable`. If no widget is currently focused,
+    /// then the top-left-most focusable widget is focused.
+    ///
+    /// Returns `true` if the focus moved.
+    pub fn focus_in_direction(&mut self, direction: Direction) -> bool {
+        let mut candidates: Vec<(u64, Rect)> = Vec::new();
+        for widget_entry in self.focusable_widgets.iter_mut() {
+            if let Ok(rect) = widget_entry_window_rect(widget_entry) {
+                candidates.push((widget_entry.unique_id(), rect));
+            }
+        }
+
+        let focused_rect = self
+            .focused_widget
+            .as_mut()
+            .and_then(|widget_entry| widget_entry_window_rect(widget_entry).ok());
+        let focused_id = self.focused_widget.as_ref().map(|w| w.unique_id());
+
+        let next_id = if let Some(focused_rect) = focused_rect {
+            nearest_in_direction(
+                focused_rect,
+                direction,
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|(id, _)| Some(*id) != focused_id),
+            )
+        } else {
+            candidates
+                .iter()
+                .min_by(|(a_id, a), (b_id, b)| {
+                    a.y()
+                        .total_cmp(&b.y())
+                        .then(a.x().total_cmp(&b.x()))
+                        .then(a_id.cmp(b_id))
+                })
+                .map(|(id, _)| *id)
+        };
+
+        let next_widget = next_id.and_then(|next_id| {
+            self.focusable_widgets
+                .iter_mut()
+                .find(|w| w.unique_id() == next_id)
+                .cloned()
+        });
+
+        if let Some(next_widget) = next_widget {
+            // The events are sent in reverse order.
+            self.widgets_to_send_input_event
+                .push((next_widget.clone(), InputEvent::KeyboardFocused));
+            if let Some(last_widget) = self.focused_widget.replace(next_widget) {
+                self.widgets_to_send_input_event
+                    .push((last_widget, InputEvent::KeyboardUnfocused));
+            }
+
+            self.send_queued_input_events();
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove the keyboard focus from the currently focused widget (if
+    /// there is one).
+    pub fn clear_keyboard_focus(&mut self) {
+        if let Some(last_widget) = self.focused_widget.take() {
+            self.widgets_to_send_input_event
+                .push((last_widget, InputEvent::KeyboardUnfocused));
+
+            self.send_queued_input_events();
         }
     }
 
+    /// Whether any layer needs to be repainted in the next call to
+    /// `render()`.
+    ///
+    /// A dirty background node whose repaint is throttled (see
+    /// `set_background_node_max_fps()`) is not reported until its repaint
+    /// is due.
     pub fn is_dirty(&self) -> bool {
+        if !self.window_visibility {
+            return false;
+        }
+
+        let now = Instant::now();
+
         for (_z_order, layers) in self.layers_ordered.iter() {
             for layer_entry in layers.iter() {
                 match layer_entry {
@@ -1051,7 +3723,7 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                         }
                     }
                     StrongLayerEntry::Background(layer_entry) => {
-                        if layer_entry.borrow().is_dirty {
+                        if layer_entry.borrow().should_repaint(now) {
                             return true;
                         }
                     }
@@ -1062,12 +3734,248 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         false
     }
 
-    pub fn render(&mut self, window_size: PhysicalSize, clear_color: Color) {
+    /// Render a frame, clearing the window to the color set with
+    /// `set_clear_color()`.
+    pub fn render(&mut self, window_size: PhysicalSize) {
+        self.render_with_clear_color(window_size, self.clear_color);
+    }
+
+    /// Render a frame, clearing the window to the given color instead of the
+    /// color set with `set_clear_color()`.
+    pub fn render_with_clear_color(&mut self, window_size: PhysicalSize, clear_color: Color) {
+        if self.renderer.is_none() {
+            // The GPU resources are suspended.
+            return;
+        }
+
+        self.handle_pre_frame();
+
+        if self.always_full_repaint {
+            mark_layers_for_full_repaint(&mut self.layers_ordered);
+        }
+
+        let mut renderer = self.renderer.take().unwrap();
+
+        self.render_frame(&mut renderer, window_size, clear_color, None);
+
+        self.renderer = Some(renderer);
+
+        self.enforce_action_queue_limit();
+    }
+
+    /// Render a frame with the renderer, which has been taken out of `self`
+    /// while it renders.
+    fn render_frame(
+        &mut self,
+        renderer: &mut Renderer,
+        window_size: PhysicalSize,
+        clear_color: Color,
+        only_layer: Option<u64>,
+    ) {
+        let scale_factor_2d = self.scale_factor_2d;
+
+        #[cfg(feature = "software-text")]
+        {
+            // Check the context once it is known which font to draw with.
+            if !self.software_text_detected {
+                if let Some(font_id) = self.software_text.first_font() {
+                    self.software_text.detect(&mut renderer.vg, font_id);
+                    self.software_text_detected = true;
+                }
+            }
+
+            let mut software_text = std::mem::take(&mut self.software_text);
+            with_active_renderer(&mut software_text, || {
+                renderer.render(self, window_size, scale_factor_2d, clear_color, only_layer)
+            });
+            software_text.free_unused_images(&mut renderer.vg);
+            self.software_text = software_text;
+        }
+
+        #[cfg(not(feature = "software-text"))]
+        renderer.render(self, window_size, scale_factor_2d, clear_color, only_layer);
+    }
+
+    /// The fallback that the built-in widgets draw their text with when
+    /// femtovg can't draw glyphs on this window's GL context.
+    ///
+    /// It is checked whether the fallback is needed on the first frame
+    /// after the renderer is created (and after `resume_gpu()`) that has a
+    /// font to check with. Fonts added with `add_font()`,
+    /// `add_font_from_bytes()`, and `add_font_named()` are added to the
+    /// fallback as well, but fonts added to `SharedResources` are not.
+    #[cfg(feature = "software-text")]
+    pub fn software_text_mut(&mut self) -> &mut SoftwareTextRenderer {
+        &mut self.software_text
+    }
+
+    /// Set a callback that draws over the whole window after all of the
+    /// layers have been composited, on every call to `render()` (i.e. for a
+    /// vignette or CRT effect over the whole UI).
+    ///
+    /// The callback is given the canvas and the size of the window in
+    /// physical pixels. It draws directly to the screen (not to a layer's
+    /// texture) with an identity transform, before the canvas is flushed. It
+    /// is not called by `render_single_layer()`.
+    pub fn set_post_composite(&mut self, post_composite: Box<dyn FnMut(&mut VG, PhysicalSize)>) {
+        self.post_composite = Some(post_composite);
+    }
+
+    pub fn clear_post_composite(&mut self) {
+        self.post_composite = None;
+    }
+
+    /// Mask the whole window with the given path (in logical coordinates),
+    /// i.e. for a circular plugin window.
+    ///
+    /// After the layers have been composited (and after the post-composite
+    /// callback), the alpha of every pixel in the window is multiplied by the
+    /// coverage of the path, so everything outside of it is fully
+    /// transparent. The host can then shape its window to match. The mask is
+    /// not applied by `render_single_layer()`.
+    ///
+    /// Set this to `None` to remove the mask.
+    pub fn set_window_mask(&mut self, mask: Option<femtovg::Path>) {
+        self.window_mask = mask;
+    }
+
+    /// Render only the given layer over the clear color, skipping all other
+    /// layers.
+    ///
+    /// This is meant for debugging the compositing of a layer and for
+    /// testing layers in isolation. The other layers are left untouched, so
+    /// they will be painted as usual on the next call to `render()`.
+    ///
+    /// Returns `FirewheelError::GpuSuspended` if the GPU resources are
+    /// suspended (or if this window was created with `new_headless()`).
+    pub fn render_single_layer(
+        &mut self,
+        layer: &WidgetLayerRef<A>,
+        window_size: PhysicalSize,
+        clear_color: Color,
+    ) -> Result<(), FirewheelError> {
+        let layer_id = if let Some(layer_entry) = layer.shared.upgrade() {
+            layer_entry.borrow().id
+        } else {
+            return Err(FirewheelError::LayerRemoved);
+        };
+
+        if self.renderer.is_none() {
+            return Err(FirewheelError::GpuSuspended);
+        }
+
+        self.handle_pre_frame();
+
+        if self.always_full_repaint {
+            mark_layers_for_full_repaint(&mut self.layers_ordered);
+        }
+
         let mut renderer = self.renderer.take().unwrap();
 
-        renderer.render(self, window_size, self.scale_factor, clear_color);
+        self.render_frame(&mut renderer, window_size, clear_color, Some(layer_id));
 
         self.renderer = Some(renderer);
+
+        self.enforce_action_queue_limit();
+
+        Ok(())
+    }
+
+    /// Read back the RGBA color of a single pixel in the window (i.e. for a
+    /// color picker or for asserting the rendered output in tests).
+    ///
+    /// The point is in physical coordinates (pixels) relative to the
+    /// top-left corner of the window, the same as pointer events. (OpenGL's
+    /// origin is the bottom-left corner, so the y axis is flipped
+    /// internally.)
+    ///
+    /// This must be called after `render()` and before the buffers are
+    /// swapped, while the GL context is still current. The color is
+    /// returned exactly as it is stored in the framebuffer.
+    ///
+    /// Returns `None` if the point lies outside of the window that was last
+    /// rendered or if the GPU resources are suspended.
+    pub fn read_pixel(&self, point: PhysicalPoint) -> Option<[u8; 4]> {
+        self.renderer.as_ref()?.read_pixel(point)
+    }
+
+    /// Render a frame and read it back as RGBA pixels with straight alpha,
+    /// row by row starting from the top-left corner of the window (i.e. for
+    /// comparing against reference images with
+    /// `testing::assert_image_matches()`).
+    ///
+    /// The same as with `read_pixel()`, this must be called while the GL
+    /// context is current.
+    pub fn render_to_rgba(
+        &mut self,
+        window_size: PhysicalSize,
+        clear_color: Color,
+    ) -> Result<Vec<u8>, FirewheelError> {
+        if self.renderer.is_none() {
+            return Err(FirewheelError::GpuSuspended);
+        }
+
+        self.render_with_clear_color(window_size, clear_color);
+
+        let mut pixels = self
+            .renderer
+            .as_ref()
+            .and_then(|renderer| renderer.read_frame())
+            .ok_or_else(|| {
+                log::error!("Failed to save image: could not read the frame");
+                FirewheelError::ImageSave
+            })?;
+        unpremultiply_rgba(&mut pixels);
+
+        Ok(pixels)
+    }
+
+    /// Render a frame and save it to a PNG file (i.e. for attaching to bug
+    /// reports).
+    ///
+    /// The same as with `read_pixel()`, this must be called while the GL
+    /// context is current. The colors are converted from the premultiplied
+    /// alpha in the framebuffer to the straight alpha that PNG expects.
+    #[cfg(feature = "image")]
+    pub fn save_frame_png<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        window_size: PhysicalSize,
+        clear_color: Color,
+    ) -> Result<(), FirewheelError> {
+        let pixels = self.render_to_rgba(window_size, clear_color)?;
+
+        write_png(path.as_ref(), &pixels, window_size)
+    }
+
+    fn handle_pre_frame(&mut self) {
+        poll_pre_frame(
+            &mut self.widgets_with_pre_frame_listen,
+            &mut self.action_tx,
+            &mut self.widget_requests,
+        );
+
+        self.flush_widget_requests();
+    }
+
+    /// Paint the given widget into a new image the size of its physical
+    /// region (i.e. to draw a preview of the widget while it is being dragged).
+    ///
+    /// The image is owned by the caller, who should free it with
+    /// `vg().delete_image()` once it is no longer needed.
+    ///
+    /// Returns `None` if the widget has been removed, if its region is
+    /// empty, or while the GPU resources are suspended.
+    pub fn capture_widget_image(
+        &mut self,
+        widget_node_ref: &WidgetNodeRef<A>,
+    ) -> Option<femtovg::ImageId> {
+        let mut widget_entry = widget_node_ref.shared.upgrade()?;
+        let layer_entry = widget_entry.assigned_layer_mut().upgrade()?;
+        let vg = self.try_vg()?;
+
+        let layer = layer_entry.borrow();
+        capture_widget_image(&layer, &mut widget_entry, vg)
     }
 
     fn handle_widget_requests(
@@ -1075,6 +3983,28 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
         widget_entry: &mut StrongWidgetNodeEntry<A>,
         requests: WidgetNodeRequests,
     ) {
+        trace_lifecycle!(
+            "widget requests: widget={} layer={:?} {:?}",
+            widget_entry.debug_label(),
+            widget_layer_id(widget_entry),
+            requests
+        );
+
+        if let Some(align) = requests.scroll_into_view {
+            widget_entry
+                .assigned_layer_mut()
+                .upgrade()
+                .unwrap()
+                .borrow_mut()
+                .scroll_widget_into_view(
+                    widget_entry,
+                    align,
+                    &mut self.widgets_just_shown,
+                    &mut self.widgets_just_hidden,
+                );
+
+            self.handle_visibility_changes();
+        }
         if requests.repaint {
             // Note, the widget won't actually get marked dirty if it is
             // currently hidden.
@@ -1084,6 +4014,13 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                 .unwrap()
                 .borrow_mut()
                 .mark_widget_region_dirty(widget_entry);
+        } else if let Some(repaint_rect) = requests.repaint_rect {
+            widget_entry
+                .assigned_layer_mut()
+                .upgrade()
+                .unwrap()
+                .borrow_mut()
+                .mark_widget_region_rect_dirty(widget_entry, repaint_rect);
         }
         if let Some(receive_next_animation_event) = requests.set_receive_next_animation_event {
             if receive_next_animation_event {
@@ -1103,9 +4040,62 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                 self.widgets_scheduled_for_animation.remove(widget_entry);
             }
         }
-        if let Some(listens) = requests.set_pointer_events_listen {
-            widget_entry
-                .assigned_layer_mut()
+        if let Some(focusable) = requests.set_focusable {
+            let is_visible = {
+                let assigned_region = widget_entry.assigned_region().upgrade().unwrap();
+                let mut assigned_region = assigned_region.borrow_mut();
+                assigned_region.set_widget_focusable(focusable);
+                assigned_region.region.is_visible()
+            };
+            if focusable && is_visible {
+                self.focusable_widgets.insert(widget_entry);
+            } else {
+                self.focusable_widgets.remove(widget_entry);
+                if let Some(last_widget) = self.focused_widget.take() {
+                    if last_widget.unique_id() == widget_entry.unique_id() {
+                        self.widgets_to_send_input_event
+                            .push((last_widget, InputEvent::KeyboardUnfocused));
+                    } else {
+                        self.focused_widget = Some(last_widget);
+                    }
+                }
+            }
+        }
+        if let Some(pre_frame_listen) = requests.set_pre_frame_listen {
+            let is_visible = {
+                widget_entry
+                    .assigned_region()
+                    .upgrade()
+                    .unwrap()
+                    .borrow()
+                    .region
+                    .is_visible()
+            };
+            if pre_frame_listen && is_visible {
+                self.widgets_with_pre_frame_listen.insert(widget_entry);
+            } else {
+                self.widgets_with_pre_frame_listen.remove(widget_entry);
+            }
+        }
+        if let Some(render_scale) = requests.set_render_scale {
+            widget_entry
+                .assigned_layer_mut()
+                .upgrade()
+                .unwrap()
+                .borrow_mut()
+                .set_widget_render_scale(widget_entry, render_scale);
+        }
+        if let Some(hit_shape) = requests.set_hit_shape {
+            widget_entry
+                .assigned_layer_mut()
+                .upgrade()
+                .unwrap()
+                .borrow_mut()
+                .set_widget_hit_shape(widget_entry, hit_shape);
+        }
+        if let Some(listens) = requests.set_pointer_events_listen {
+            widget_entry
+                .assigned_layer_mut()
                 .upgrade()
                 .unwrap()
                 .borrow_mut()
@@ -1175,6 +4165,9 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             }
         }
         if let Some(set_lock_type) = requests.set_pointer_lock {
+            let set_lock_type =
+                supported_pointer_lock_type(set_lock_type, self.pointer_lock_supported);
+
             let is_visible = {
                 widget_entry
                     .assigned_region()
@@ -1209,6 +4202,9 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                 }
             }
         }
+        if let Some(raw_scroll) = requests.set_raw_scroll {
+            widget_entry.set_receives_raw_scroll(raw_scroll);
+        }
         if let Some(set_pointer_leave_listen) = requests.set_pointer_leave_listen {
             let is_visible = {
                 widget_entry
@@ -1226,15 +4222,198 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
                 self.widgets_with_pointer_leave_listen.remove(&widget_entry);
             }
         }
+        if requests.set_pointer_capture.is_some() || requests.steal_pointer_capture {
+            let is_visible = {
+                widget_entry
+                    .assigned_region()
+                    .upgrade()
+                    .unwrap()
+                    .borrow()
+                    .region
+                    .is_visible()
+            };
+
+            if let Some(set_pointer_capture) = requests.set_pointer_capture {
+                if set_pointer_capture && is_visible {
+                    self.pointer_capture_stack
+                        .capture(widget_entry.unique_id(), widget_entry.clone());
+                } else {
+                    self.pointer_capture_stack.release(widget_entry.unique_id());
+                }
+            }
+            if requests.steal_pointer_capture && is_visible {
+                for lost_widget in self
+                    .pointer_capture_stack
+                    .steal(widget_entry.unique_id(), widget_entry.clone())
+                {
+                    self.widgets_to_send_input_event
+                        .push((lost_widget, InputEvent::PointerCaptureLost));
+                }
+            }
+        }
+    }
+
+    fn remove_widget_from_group(&mut self, widget_entry: &StrongWidgetNodeEntry<A>) {
+        if let Some(group) = self.widget_group_ids.remove(&widget_entry.unique_id()) {
+            let mut remove_group = false;
+            if let Some(members) = self.widget_groups.get_mut(&group) {
+                members.remove(widget_entry);
+                remove_group = members.is_empty();
+            }
+            if remove_group {
+                self.widget_groups.remove(&group);
+
+                if self.hovered_group == Some(group) {
+                    self.hovered_group = None;
+                }
+            }
+        }
+    }
+
+    fn set_hovered_group(&mut self, group: Option<WidgetGroupId>) {
+        if self.hovered_group == group {
+            return;
+        }
+
+        if let Some(old_group) = self.hovered_group.take() {
+            self.send_group_hover_changed(old_group, false);
+        }
+        if let Some(new_group) = group {
+            self.send_group_hover_changed(new_group, true);
+        }
+
+        self.hovered_group = group;
+    }
+
+    fn send_group_hover_changed(&mut self, group: WidgetGroupId, hovered: bool) {
+        if let Some(members) = self.widget_groups.get_mut(&group) {
+            for widget_entry in members.iter_mut() {
+                let is_visible = widget_entry
+                    .assigned_region()
+                    .upgrade()
+                    .map(|r| r.borrow().region.is_visible())
+                    .unwrap_or(false);
+
+                if is_visible {
+                    self.widgets_to_send_input_event.push((
+                        widget_entry.clone(),
+                        InputEvent::GroupHoverChanged { group, hovered },
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Advance the scroll bounces and animators by one animation frame.
+    ///
+    /// Returns `false` if animations are paused, in which case the frame
+    /// should not be sent to the widgets scheduled for animation either.
+    fn step_animation_frame(&mut self, time_delta: Duration) -> bool {
+        if self.animations_paused {
+            trace_lifecycle!("animation event dropped: animations paused");
+            return false;
+        }
+
+        self.step_scroll_bounce(time_delta.as_secs_f32());
+        self.step_animators(time_delta);
+        true
+    }
+
+    fn step_animators(&mut self, time_delta: Duration) {
+        if self.animators.is_empty() {
+            return;
+        }
+
+        let mut animators = std::mem::take(&mut self.animators);
+        animators.retain_mut(|running| {
+            let value = running.animator.step(time_delta);
+            let finished = running.animator.is_finished();
+
+            match &mut running.target {
+                AnimatorTarget::Widget(widget) => {
+                    if let Some(mut widget_entry) = widget.upgrade() {
+                        let res = {
+                            widget_entry.borrow_mut().on_user_event(
+                                Box::new(AnimatorUpdate {
+                                    id: running.id,
+                                    value,
+                                    finished,
+                                }),
+                                &mut self.action_tx,
+                            )
+                        };
+                        if let Some(requests) = res {
+                            self.widget_requests.push((widget_entry, requests));
+                        }
+                    } else {
+                        // The widget was removed, so there is nothing left to
+                        // animate.
+                        return false;
+                    }
+                }
+                AnimatorTarget::Callback(callback) => callback(value, finished),
+            }
+
+            !finished
+        });
+        self.animators = animators;
+
+        self.handle_queued_widget_requests();
+    }
+
+    fn step_scroll_bounce(&mut self, time_delta: f32) {
+        for (_z_order, layers) in self.layers_ordered.iter_mut() {
+            for layer_entry in layers.iter_mut() {
+                if let StrongLayerEntry::Widget(layer_entry) = layer_entry {
+                    layer_entry.borrow_mut().step_scroll_bounce(
+                        time_delta,
+                        &mut self.widgets_just_shown,
+                        &mut self.widgets_just_hidden,
+                    );
+                }
+            }
+        }
+
+        self.handle_visibility_changes();
     }
 
     fn handle_visibility_changes(&mut self) {
+        #[cfg(all(debug_assertions, feature = "validate-tree"))]
+        self.validate_tree();
+
+        if !self.widgets_just_shown.is_empty() || !self.widgets_just_hidden.is_empty() {
+            trace_lifecycle!(
+                "visibility changes: shown={} hidden={}",
+                self.widgets_just_shown.len(),
+                self.widgets_just_hidden.len()
+            );
+        }
+
         // Handle widgets that have just been shown.
         while let Some(mut widget_entry) = self.widgets_just_shown.pop() {
+            trace_lifecycle!(
+                "widget shown: widget={} layer={:?}",
+                widget_entry.debug_label(),
+                widget_layer_id(&mut widget_entry)
+            );
+
+            // Widgets that were made focusable while hidden (or before they
+            // were last hidden) can receive the focus again.
+            let focusable = widget_entry
+                .assigned_region()
+                .upgrade()
+                .map(|region| region.borrow().widget_is_focusable())
+                .unwrap_or(false);
+            if focusable {
+                self.focusable_widgets.insert(&widget_entry);
+            }
+
             let status = {
-                widget_entry
-                    .borrow_mut()
-                    .on_input_event(&InputEvent::VisibilityShown, &mut self.action_tx)
+                widget_entry.borrow_mut().on_input_event(
+                    &InputEvent::VisibilityShown,
+                    &self.text_context,
+                    &mut self.action_tx,
+                )
             };
             if let EventCapturedStatus::Captured(requests) = status {
                 self.handle_widget_requests(&mut widget_entry, requests);
@@ -1244,6 +4423,11 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
 
         // Handle widgets that have just been hidden.
         while let Some(mut widget_entry) = self.widgets_just_hidden.pop() {
+            trace_lifecycle!(
+                "widget hidden: widget={} layer={:?}",
+                widget_entry.debug_label(),
+                widget_layer_id(&mut widget_entry)
+            );
             {
                 widget_entry
                     .borrow_mut()
@@ -1256,14 +4440,17 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
             self.widgets_scheduled_for_animation.remove(&widget_entry);
             self.widgets_with_keyboard_listen.remove(&widget_entry);
             self.widgets_with_pointer_leave_listen.remove(&widget_entry);
-            if let Some((last_widget, lock_type)) = self.widget_with_pointer_lock.take() {
+            self.widgets_with_pre_frame_listen.remove(&widget_entry);
+            self.release_pointer_from_widget(widget_entry.unique_id());
+            if let Some(last_widget) = self.widget_with_text_comp_listen.take() {
                 if last_widget.unique_id() != widget_entry.unique_id() {
-                    self.widget_with_pointer_lock = Some((last_widget, lock_type));
+                    self.widget_with_text_comp_listen = Some(last_widget);
                 }
             }
-            if let Some(last_widget) = self.widget_with_text_comp_listen.take() {
+            self.focusable_widgets.remove(&widget_entry);
+            if let Some(last_widget) = self.focused_widget.take() {
                 if last_widget.unique_id() != widget_entry.unique_id() {
-                    self.widget_with_text_comp_listen = Some(last_widget);
+                    self.focused_widget = Some(last_widget);
                 }
             }
         }
@@ -1271,6 +4458,107 @@ impl<A: Clone + Send + Sync + 'static> AppWindow<A> {
     }
 }
 
+/// The id of the layer that the given widget is on.
+fn widget_layer_id<A: Clone + Send + Sync + 'static>(
+    widget_entry: &mut StrongWidgetNodeEntry<A>,
+) -> Option<u64> {
+    widget_entry
+        .assigned_layer_mut()
+        .upgrade()
+        .map(|layer| layer.borrow().id)
+}
+
+/// The rect of the widget's region in window coordinates, taking the
+/// position and the content zoom of its layer into account (see
+/// `AppWindow::widget_window_rect()`).
+fn widget_entry_window_rect<A: Clone + Send + Sync + 'static>(
+    widget_entry: &mut StrongWidgetNodeEntry<A>,
+) -> Result<Rect, FirewheelError> {
+    let layer_entry = widget_entry
+        .assigned_layer_mut()
+        .upgrade()
+        .ok_or_else(|| FirewheelError::LayerRemoved)?;
+    let region = widget_entry
+        .assigned_region()
+        .upgrade()
+        .ok_or_else(|| FirewheelError::WidgetNodeRemoved)?;
+
+    let layer = layer_entry.borrow();
+    let zoom = layer.content_zoom();
+    let layer_rect = layer.region_tree.layer_rect();
+    let rect = region.borrow().region.rect;
+
+    Ok(Rect::new(
+        Point::new(
+            layer.outer_position.x + (rect.x() - layer_rect.x()) * f64::from(zoom),
+            layer.outer_position.y + (rect.y() - layer_rect.y()) * f64::from(zoom),
+        ),
+        Size::new(rect.width() * zoom, rect.height() * zoom),
+    ))
+}
+
+/// Run the given input filter on a copy of the event, returning `None` if the
+/// filter dropped it. The event is only copied if there is a filter.
+fn apply_input_filter<'a>(
+    input_filter: Option<&mut (dyn FnMut(&mut InputEvent) -> bool + 'static)>,
+    event: &'a InputEvent,
+) -> Option<Cow<'a, InputEvent>> {
+    let input_filter = match input_filter {
+        Some(input_filter) => input_filter,
+        None => return Some(Cow::Borrowed(event)),
+    };
+
+    let mut event = event.clone();
+    if input_filter(&mut event) {
+        Some(Cow::Owned(event))
+    } else {
+        None
+    }
+}
+
+/// Call `WidgetNode::pre_frame()` on every widget in the given set, collecting
+/// any requests they return.
+fn poll_pre_frame<A: Clone + Send + Sync + 'static>(
+    widgets: &mut WidgetNodeSet<A>,
+    action_tx: &mut Sender<A>,
+    widget_requests: &mut Vec<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)>,
+) {
+    for widget_entry in widgets.iter_mut() {
+        let res = { widget_entry.borrow_mut().pre_frame(action_tx) };
+        if let Some(requests) = res {
+            widget_requests.push((widget_entry.clone(), requests));
+        }
+    }
+}
+
+/// The pointer lock mode to use for the requested mode, falling back to
+/// locking the pointer to the widget when the platform can't lock the pointer
+/// in place.
+fn supported_pointer_lock_type(
+    requested: SetPointerLockType,
+    pointer_lock_supported: bool,
+) -> SetPointerLockType {
+    if requested == SetPointerLockType::LockInPlaceAndHideCursor && !pointer_lock_supported {
+        SetPointerLockType::LockToWidget
+    } else {
+        requested
+    }
+}
+
+/// Move the item at `from` to `to` (clamped to the last index), shifting the
+/// items in between. Returns `true` if the order changed.
+fn move_item<T>(items: &mut Vec<T>, from: usize, to: usize) -> bool {
+    let to = to.min(items.len() - 1);
+    if from == to {
+        return false;
+    }
+
+    let item = items.remove(from);
+    items.insert(to, item);
+
+    true
+}
+
 impl<A: Clone + Send + Sync + 'static> Drop for AppWindow<A> {
     fn drop(&mut self) {
         for (_z_order, layers) in self.layers_ordered.iter_mut() {
@@ -1290,13 +4578,4261 @@ impl<A: Clone + Send + Sync + 'static> Drop for AppWindow<A> {
             }
         }
 
-        let mut renderer = self.renderer.take().unwrap();
-
-        renderer.free(self);
+        if let Some(mut renderer) = self.renderer.take() {
+            renderer.free(self);
+        }
     }
 }
 
 pub struct InputEventResult {
     pub lock_pointer_in_place: bool,
+    /// The pointer lock mode that is currently active. If the platform does
+    /// not support locking the pointer in place, a request for
+    /// `LockInPlaceAndHideCursor` falls back to `LockToWidget`.
+    pub pointer_lock: SetPointerLockType,
+    /// Whether the action queue is over its limit when using
+    /// `ActionQueueOverflow::Error` (see `AppWindow::set_action_queue_limit()`).
+    pub action_queue_full: bool,
+    /// The widget that held the pointer lock was removed or hidden since the
+    /// last event. The host should release the pointer (i.e. show the cursor
+    /// again) if it was locked in place.
+    pub pointer_lock_released: bool,
     // TODO: cursor icon
 }
+
+/// A modal dialog opened with `AppWindow::open_modal()`.
+pub struct ModalHandle<A: Clone + Send + Sync + 'static> {
+    layer: WidgetLayerRef<A>,
+    container: ContainerRegionRef<A>,
+}
+
+impl<A: Clone + Send + Sync + 'static> ModalHandle<A> {
+    /// The backdrop layer of the modal.
+    pub fn layer(&self) -> &WidgetLayerRef<A> {
+        &self.layer
+    }
+
+    /// The container region in the center of the modal that holds the
+    /// dialog's widgets.
+    pub fn container(&self) -> &ContainerRegionRef<A> {
+        &self.container
+    }
+}
+
+struct RunningAnimator<A: Clone + Send + Sync + 'static> {
+    id: AnimatorId,
+    animator: Animator,
+    target: AnimatorTarget<A>,
+}
+
+enum AnimatorTarget<A: Clone + Send + Sync + 'static> {
+    Widget(WeakWidgetNodeEntry<A>),
+    Callback(Box<dyn FnMut(f32, bool)>),
+}
+
+/// The settings an `AppWindow` is created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppWindowSettings {
+    /// Create the window as hidden, so that nothing is shown until the host
+    /// calls `AppWindow::set_window_visibility(true)` once the UI has been
+    /// populated. This avoids showing an empty window on the first frame.
+    pub initially_hidden: bool,
+    /// How colors are written to the window's framebuffer.
+    pub color_space: ColorSpace,
+    /// Whether the platform supports locking the pointer in place (see
+    /// `SetPointerLockType::LockInPlaceAndHideCursor`). If not, widgets that
+    /// request it are given `SetPointerLockType::LockToWidget` instead.
+    pub pointer_lock_supported: bool,
+}
+
+impl Default for AppWindowSettings {
+    fn default() -> Self {
+        Self {
+            initially_hidden: false,
+            color_space: ColorSpace::default(),
+            pointer_lock_supported: true,
+        }
+    }
+}
+
+/// What to do when the action queue grows past the limit set with
+/// `AppWindow::set_action_queue_limit()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionQueueOverflow {
+    /// Drop the oldest of the new actions so that only the newest ones are
+    /// kept.
+    DropOldest,
+    /// Drop the newest of the new actions so that only the oldest ones are
+    /// kept.
+    DropNewest,
+    /// Keep every action, but report that the queue is full with
+    /// `AppWindow::action_queue_full()`.
+    Error,
+}
+
+struct ActionQueueLimit<A> {
+    limit: usize,
+    overflow: ActionQueueOverflow,
+    /// The action queue passed to the constructor.
+    host_action_tx: Sender<A>,
+    /// The actions sent by widgets that haven't been moved to the action
+    /// queue yet.
+    pending_rx: Receiver<A>,
+}
+
+/// Send the given actions to the host's action queue without blocking, and
+/// return the ones that didn't fit because the queue is full.
+///
+/// If the host has dropped its receiver, the actions are dropped.
+fn try_send_actions<A>(host_action_tx: &Sender<A>, actions: impl IntoIterator<Item = A>) -> Vec<A> {
+    let mut actions = actions.into_iter();
+    while let Some(action) = actions.next() {
+        match host_action_tx.try_send(action) {
+            Ok(()) => {}
+            Err(TrySendError::Full(action)) => {
+                return std::iter::once(action).chain(actions).collect();
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::warn!("Dropped actions: the action queue was disconnected");
+                break;
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+/// Return the actions that are kept from the given queue (ordered from
+/// oldest to newest) after applying the limit. The number of dropped actions
+/// is logged.
+fn limit_action_queue<A>(
+    mut queued: Vec<A>,
+    limit: usize,
+    overflow: ActionQueueOverflow,
+) -> Vec<A> {
+    let excess = queued.len().saturating_sub(limit);
+    if excess == 0 {
+        return queued;
+    }
+
+    match overflow {
+        ActionQueueOverflow::DropOldest => {
+            queued.drain(..excess);
+        }
+        ActionQueueOverflow::DropNewest => queued.truncate(limit),
+        ActionQueueOverflow::Error => return queued,
+    }
+    log::warn!(
+        "Dropped {} actions: the action queue is over its limit",
+        excess
+    );
+
+    queued
+}
+
+/// Replace the renderer of every layer with a new one (which has no textures
+/// yet), and queue the old renderers to be cleaned up. Every layer is marked
+/// to be fully repainted.
+fn release_layer_renderers<A: Clone + Send + Sync + 'static>(
+    layers_ordered: &mut [(i32, Vec<StrongLayerEntry<A>>)],
+    widget_layer_renderers_to_clean_up: &mut Vec<WidgetLayerRenderer>,
+    background_layer_renderers_to_clean_up: &mut Vec<BackgroundLayerRenderer>,
+) {
+    for (_z_order, layers) in layers_ordered.iter_mut() {
+        for layer_entry in layers.iter_mut() {
+            match layer_entry {
+                StrongLayerEntry::Widget(layer_entry) => {
+                    let mut layer = layer_entry.borrow_mut();
+                    if let Some(renderer) = layer.renderer.replace(WidgetLayerRenderer::new()) {
+                        widget_layer_renderers_to_clean_up.push(renderer);
+                    }
+                    layer.region_tree.mark_all_dirty();
+                }
+                StrongLayerEntry::Background(layer_entry) => {
+                    let mut layer = layer_entry.borrow_mut();
+                    if let Some(renderer) = layer.renderer.replace(BackgroundLayerRenderer::new()) {
+                        background_layer_renderers_to_clean_up.push(renderer);
+                    }
+                    layer.mark_dirty();
+                }
+            }
+        }
+    }
+}
+
+/// Mark every layer to be cleared and fully repainted on the next frame.
+fn mark_layers_for_full_repaint<A: Clone + Send + Sync + 'static>(
+    layers_ordered: &mut [(i32, Vec<StrongLayerEntry<A>>)],
+) {
+    for (_z_order, layers) in layers_ordered.iter_mut() {
+        for layer_entry in layers.iter_mut() {
+            match layer_entry {
+                StrongLayerEntry::Widget(layer_entry) => {
+                    layer_entry.borrow_mut().region_tree.mark_all_dirty();
+                }
+                StrongLayerEntry::Background(layer_entry) => {
+                    layer_entry.borrow_mut().mark_dirty();
+                }
+            }
+        }
+    }
+}
+
+/// Return an error if a visible widget or background node uses the given
+/// image.
+fn check_image_not_in_use<A: Clone + Send + Sync + 'static>(
+    layers_ordered: &[(i32, Vec<StrongLayerEntry<A>>)],
+    image_id: femtovg::ImageId,
+) -> Result<(), FirewheelError> {
+    let mut visible_widgets = Vec::new();
+    for (_z_order, layers) in layers_ordered.iter() {
+        for layer_entry in layers.iter() {
+            match layer_entry {
+                StrongLayerEntry::Widget(layer_entry) => {
+                    let mut layer_entry = layer_entry.clone();
+                    let mut layer = layer_entry.borrow_mut();
+                    if layer.is_visible() {
+                        layer
+                            .region_tree
+                            .collect_visible_painted_widgets(&mut visible_widgets);
+                    }
+                }
+                StrongLayerEntry::Background(layer_entry) => {
+                    let mut layer_entry = layer_entry.clone();
+                    let mut layer = layer_entry.borrow_mut();
+                    if layer.is_visible() && layer.assigned_node.borrow_mut().uses_image(image_id) {
+                        return Err(FirewheelError::ImageInUse);
+                    }
+                }
+            }
+        }
+    }
+
+    for widget_entry in visible_widgets.iter_mut() {
+        if widget_entry.borrow_mut().uses_image(image_id) {
+            return Err(FirewheelError::ImageInUse);
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert a pointer event into the coordinates of the layer that the given
+/// widget is on (see `CoordinateOrigin`), and remove the raw scroll data if
+/// the widget hasn't opted in to it.
+fn widget_pointer_event<A: Clone + Send + Sync + 'static>(
+    widget_entry: &mut StrongWidgetNodeEntry<A>,
+    event: &InputEvent,
+) -> InputEvent {
+    let raw_scroll = widget_entry.receives_raw_scroll();
+    let strip = |e: PointerEvent| {
+        if raw_scroll {
+            e
+        } else {
+            e.without_raw_scroll()
+        }
+    };
+
+    match (event, widget_entry.assigned_layer_mut().upgrade()) {
+        (InputEvent::Pointer(e), Some(layer)) => {
+            InputEvent::Pointer(strip(layer.borrow().widget_pointer_event(*e)))
+        }
+        (InputEvent::Pointer(e), None) => InputEvent::Pointer(strip(*e)),
+        _ => event.clone(),
+    }
+}
+
+/// Fill in the timestamp of the given pointer event (unless the host has
+/// already provided one) and its velocity.
+fn fill_pointer_event_timing(
+    event: &mut PointerEvent,
+    pointer_velocity: &mut PointerVelocityTracker,
+    now: Instant,
+) {
+    let timestamp = *event.timestamp.get_or_insert(now);
+    event.velocity = pointer_velocity.update(event, timestamp);
+}
+
+fn snap_pointer_event(
+    event: &mut PointerEvent,
+    pointer_snap: PointerSnap,
+    scale_factor: ScaleFactor2D,
+) {
+    event.raw_position = event.position;
+    event.position = pointer_snap.snap(event.position, scale_factor);
+}
+
+/// Write RGBA pixels with straight alpha to a PNG file.
+#[cfg(feature = "image")]
+pub(crate) fn write_png(
+    path: &std::path::Path,
+    pixels: &[u8],
+    size: PhysicalSize,
+) -> Result<(), FirewheelError> {
+    image::save_buffer(
+        path,
+        pixels,
+        size.width,
+        size.height,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|e| {
+        log::error!("Failed to save image {}: {}", path.display(), e);
+        FirewheelError::ImageSave
+    })
+}
+
+/// Combine the scale factor of the display with the UI scale.
+fn effective_scale_factor(dpi_scale_factor: ScaleFactor2D, ui_scale: f32) -> ScaleFactor2D {
+    ScaleFactor2D::new(dpi_scale_factor.x * ui_scale, dpi_scale_factor.y * ui_scale)
+}
+
+/// Hit-test each point against the given widget layers, which are ordered
+/// from top to bottom.
+fn widgets_at_points_in_layers<A: Clone + Send + Sync + 'static>(
+    layers: &[&WidgetLayer<A>],
+    points: &[Point],
+) -> Vec<Option<StrongWidgetNodeEntry<A>>> {
+    points
+        .iter()
+        .map(|point| {
+            for layer in layers.iter() {
+                if let Some(widget_entry) = layer.widget_at_point(*point) {
+                    return Some(widget_entry);
+                }
+                if layer.blocks_pointer_events() {
+                    return None;
+                }
+            }
+
+            None
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::PointerButtonState;
+    use crate::layer::WeakWidgetLayerEntry;
+    use crate::test_utils::{add_layer_with_widget, GlTestWindow, TestWidget};
+    use crate::{Easing, HitShape, PaintRegionInfo, PhysicalPoint, WidgetNodeType};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pointer_lock_fallback() {
+        assert_eq!(
+            supported_pointer_lock_type(SetPointerLockType::LockInPlaceAndHideCursor, true),
+            SetPointerLockType::LockInPlaceAndHideCursor
+        );
+
+        // Locking in place falls back to locking to the widget when it isn't
+        // supported.
+        assert_eq!(
+            supported_pointer_lock_type(SetPointerLockType::LockInPlaceAndHideCursor, false),
+            SetPointerLockType::LockToWidget
+        );
+
+        // The other modes are always supported.
+        for supported in [true, false] {
+            assert_eq!(
+                supported_pointer_lock_type(SetPointerLockType::LockToWidget, supported),
+                SetPointerLockType::LockToWidget
+            );
+            assert_eq!(
+                supported_pointer_lock_type(SetPointerLockType::Unlock, supported),
+                SetPointerLockType::Unlock
+            );
+        }
+    }
+
+    #[test]
+    fn test_move_item() {
+        // Layers with the same z order are painted from first to last.
+        let mut layers = vec!["a", "b", "c"];
+
+        // Moving the bottom layer to the top.
+        assert!(move_item(&mut layers, 0, 2));
+        assert_eq!(layers, vec!["b", "c", "a"]);
+
+        // Moving a layer back down.
+        assert!(move_item(&mut layers, 2, 1));
+        assert_eq!(layers, vec!["b", "a", "c"]);
+
+        // An index past the end moves the layer to the top.
+        assert!(move_item(&mut layers, 0, 10));
+        assert_eq!(layers, vec!["a", "c", "b"]);
+
+        // Moving a layer to its current index does nothing.
+        assert!(!move_item(&mut layers, 1, 1));
+        assert!(!move_item(&mut layers, 2, 5));
+        assert_eq!(layers, vec!["a", "c", "b"]);
+    }
+
+    #[test]
+    fn test_poll_pre_frame() {
+        let (mut action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let shared_value = Arc::new(AtomicU32::new(0));
+
+        let meter_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(
+                TestWidget::new().with_value(Arc::clone(&shared_value)),
+            ))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+
+        let mut widgets = WidgetNodeSet::new();
+        widgets.insert(&meter_entry);
+
+        let mut widget_requests = Vec::new();
+
+        // Nothing changed, so no repaint is requested.
+        poll_pre_frame(&mut widgets, &mut action_tx, &mut widget_requests);
+        assert!(widget_requests.is_empty());
+
+        // The meter requests a repaint once the shared value changes.
+        shared_value.store(5, Ordering::Relaxed);
+        poll_pre_frame(&mut widgets, &mut action_tx, &mut widget_requests);
+        assert_eq!(widget_requests.len(), 1);
+        assert_eq!(widget_requests[0].0.unique_id(), meter_entry.unique_id());
+        assert!(widget_requests[0].1.repaint);
+        widget_requests.clear();
+
+        poll_pre_frame(&mut widgets, &mut action_tx, &mut widget_requests);
+        assert!(widget_requests.is_empty());
+    }
+
+    /// The RGBA color of the pixel at the given point of a frame read with
+    /// `render_to_rgba()`.
+    fn pixel_at(pixels: &[u8], window_size: PhysicalSize, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * window_size.width + x) * 4) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    }
+
+    #[test]
+    fn test_direct_compositing() {
+        let window_size = PhysicalSize::new(200, 100);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let mut layer = app_window.add_widget_layer(
+            Size::new(150.0, 80.0),
+            0,
+            Point::new(20.0, 10.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        app_window
+            .set_widget_layer_compositing(&mut layer, Compositing::Direct)
+            .unwrap();
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new().with_color(Color::rgb(0, 0, 255))),
+                &layer,
+                Rect::new(Point::new(30.0, 10.0), Size::new(40.0, 30.0)),
+                true,
+            )
+            .unwrap();
+
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+
+        // The widget is painted at its position in the layer, offset by the
+        // position of the layer in the window.
+        let blue = [0, 0, 255, 255];
+        let black = [0, 0, 0, 255];
+        assert_eq!(pixel_at(&pixels, window_size, 50, 20), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 89, 49), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 49, 20), black);
+        assert_eq!(pixel_at(&pixels, window_size, 50, 19), black);
+        assert_eq!(pixel_at(&pixels, window_size, 90, 49), black);
+        assert_eq!(pixel_at(&pixels, window_size, 89, 50), black);
+    }
+
+    #[test]
+    fn test_direct_compositing_blend_mode() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let mut layer = app_window.add_widget_layer(
+            Size::new(100.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let compositing =
+            |layer: &WidgetLayerRef<()>| layer.shared.upgrade().unwrap().borrow().compositing;
+        let blend_mode =
+            |layer: &WidgetLayerRef<()>| layer.shared.upgrade().unwrap().borrow().blend_mode;
+
+        // A layer with a blend mode can't switch to direct compositing.
+        app_window
+            .set_widget_layer_blend_mode(&mut layer, BlendMode::Add)
+            .unwrap();
+        assert_eq!(
+            app_window.set_widget_layer_compositing(&mut layer, Compositing::Direct),
+            Err(FirewheelError::BlendModeNotSupported)
+        );
+        assert_eq!(compositing(&layer), Compositing::Texture);
+
+        // A layer with direct compositing can't use a blend mode.
+        app_window
+            .set_widget_layer_blend_mode(&mut layer, BlendMode::Normal)
+            .unwrap();
+        app_window
+            .set_widget_layer_compositing(&mut layer, Compositing::Direct)
+            .unwrap();
+        assert_eq!(
+            app_window.set_widget_layer_blend_mode(&mut layer, BlendMode::Screen),
+            Err(FirewheelError::BlendModeNotSupported)
+        );
+        assert_eq!(blend_mode(&layer), BlendMode::Normal);
+    }
+
+    #[test]
+    fn test_layer_repainted_in_resize_frame() {
+        let window_size = PhysicalSize::new(200, 100);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let (mut layer, _widget) = add_layer_with_widget(
+            app_window,
+            Size::new(100.0, 50.0),
+            TestWidget::new().with_color(Color::rgb(0, 0, 255)),
+            Rect::new(Point::new(10.0, 10.0), Size::new(30.0, 20.0)),
+        );
+        app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+
+        // Resizing the layer reallocates its texture. The new texture is
+        // painted in the same frame, so the widget never disappears.
+        for size in [Size::new(180.0, 90.0), Size::new(60.0, 40.0)] {
+            app_window.set_widget_layer_size(&mut layer, size).unwrap();
+
+            let pixels = app_window
+                .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+                .unwrap();
+
+            let blue = [0, 0, 255, 255];
+            let black = [0, 0, 0, 255];
+            assert_eq!(pixel_at(&pixels, window_size, 10, 10), blue, "{:?}", size);
+            assert_eq!(pixel_at(&pixels, window_size, 39, 29), blue, "{:?}", size);
+            assert_eq!(pixel_at(&pixels, window_size, 40, 29), black, "{:?}", size);
+            assert_eq!(pixel_at(&pixels, window_size, 9, 10), black, "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn test_capture_widget_image() {
+        let window_size = PhysicalSize::new(200, 100);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(2.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let (_layer, mut widget) = add_layer_with_widget(
+            app_window,
+            Size::new(100.0, 50.0),
+            TestWidget::new().with_color(Color::rgb(0, 0, 255)),
+            Rect::new(Point::new(10.0, 5.0), Size::new(15.0, 20.0)),
+        );
+
+        // The image has the physical size of the widget.
+        let image_id = app_window.capture_widget_image(&widget).unwrap();
+        assert_eq!(app_window.vg().image_size(image_id).unwrap(), (30, 40));
+
+        // Draw the image somewhere else in the window after removing the
+        // widget, to check that it holds what the widget painted.
+        app_window.remove_widget(&mut widget).unwrap();
+        app_window.set_post_composite(Box::new(move |vg, _| {
+            let mut path = femtovg::Path::new();
+            path.rect(100.0, 50.0, 30.0, 40.0);
+            vg.fill_path(
+                &mut path,
+                &femtovg::Paint::image(image_id, 100.0, 50.0, 30.0, 40.0, 0.0, 1.0),
+            );
+        }));
+
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+
+        let blue = [0, 0, 255, 255];
+        let black = [0, 0, 0, 255];
+        assert_eq!(pixel_at(&pixels, window_size, 100, 50), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 129, 89), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 115, 70), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 99, 50), black);
+        assert_eq!(pixel_at(&pixels, window_size, 130, 89), black);
+        assert_eq!(pixel_at(&pixels, window_size, 20, 10), black);
+    }
+
+    #[test]
+    fn test_render_scale() {
+        let window_size = PhysicalSize::new(200, 100);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let (_layer, mut widget) = add_layer_with_widget(
+            app_window,
+            Size::new(200.0, 100.0),
+            TestWidget::new().with_color(Color::rgb(0, 0, 255)),
+            Rect::new(Point::new(40.0, 20.0), Size::new(60.0, 40.0)),
+        );
+        app_window.render_with_clear_color(window_size, Color::rgb(0, 0, 0));
+        let layer_memory = app_window.texture_memory_usage();
+
+        let mut widget_entry = widget.shared.upgrade().unwrap();
+        app_window.handle_widget_requests(
+            &mut widget_entry,
+            WidgetNodeRequests {
+                set_render_scale: Some(0.5),
+                ..Default::default()
+            },
+        );
+
+        // The widget is stretched over its whole region from a half-size
+        // image.
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        let blue = [0, 0, 255, 255];
+        let black = [0, 0, 0, 255];
+        assert_eq!(pixel_at(&pixels, window_size, 41, 21), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 98, 58), blue);
+        assert_eq!(pixel_at(&pixels, window_size, 38, 40), black);
+        assert_eq!(pixel_at(&pixels, window_size, 102, 40), black);
+        assert_eq!(
+            app_window.texture_memory_usage(),
+            layer_memory + 30 * 20 * 4
+        );
+
+        // The image is freed along with the widget.
+        drop(widget_entry);
+        app_window.remove_widget(&mut widget).unwrap();
+        app_window.render_with_clear_color(window_size, Color::rgb(0, 0, 0));
+        assert_eq!(app_window.texture_memory_usage(), layer_memory);
+    }
+
+    #[test]
+    fn test_warmup_text() {
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(2.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let size = app_window.warmup_text("Warm me up", font_id, 14.0).unwrap();
+        assert!(size.width() > 0.0 && size.height() > 0.0);
+        assert_eq!(
+            app_window.measure_text_cached("Warm me up", font_id, 14.0),
+            size
+        );
+
+        // The glyphs are drawn with an empty scissor rect, so the next frame
+        // doesn't show them.
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        assert!(pixels.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_background_node_max_fps() {
+        struct ColorBackgroundNode {
+            color: Rc<std::cell::Cell<Color>>,
+        }
+
+        impl BackgroundNode for ColorBackgroundNode {
+            fn paint(&mut self, vg: &mut VG, region: &PaintRegionInfo) {
+                let rect = region.physical_rect;
+                let mut path = femtovg::Path::new();
+                path.rect(
+                    rect.pos.x as f32,
+                    rect.pos.y as f32,
+                    rect.size.width as f32,
+                    rect.size.height as f32,
+                );
+                vg.fill_path(&mut path, &femtovg::Paint::color(self.color.get()));
+            }
+        }
+
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let color = Rc::new(std::cell::Cell::new(Color::rgb(255, 0, 0)));
+        let mut background = app_window.add_background_node(
+            Size::new(50.0, 50.0),
+            0,
+            Point::new(0.0, 0.0),
+            true,
+            Box::new(ColorBackgroundNode {
+                color: Rc::clone(&color),
+            }),
+        );
+        app_window
+            .set_background_node_max_fps(&mut background, Some(1.0))
+            .unwrap();
+
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        assert_eq!(pixel_at(&pixels, window_size, 10, 10), [255, 0, 0, 255]);
+
+        // The repaint is throttled, so the window is not reported as dirty
+        // and the layer keeps its last painted contents.
+        color.set(Color::rgb(0, 255, 0));
+        app_window
+            .mark_background_node_dirty(&mut background)
+            .unwrap();
+        assert!(!app_window.is_dirty());
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        assert_eq!(pixel_at(&pixels, window_size, 10, 10), [255, 0, 0, 255]);
+
+        // The texture is reallocated when the layer is resized, so it is
+        // painted right away.
+        app_window
+            .set_background_node_size(&mut background, Size::new(80.0, 50.0))
+            .unwrap();
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        assert_eq!(pixel_at(&pixels, window_size, 10, 10), [0, 255, 0, 255]);
+        assert_eq!(pixel_at(&pixels, window_size, 70, 10), [0, 255, 0, 255]);
+        assert!(!app_window.is_dirty());
+    }
+
+    #[test]
+    fn test_suspend_gpu() {
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let test_widget = TestWidget::new()
+            .with_color(Color::rgb(0, 0, 255))
+            .with_pointer_listen();
+        let events = test_widget.events();
+        let (layer, widget) = add_layer_with_widget(
+            app_window,
+            Size::new(100.0, 50.0),
+            test_widget,
+            Rect::new(Point::new(10.0, 10.0), Size::new(30.0, 20.0)),
+        );
+        app_window.render_with_clear_color(window_size, Color::rgb(0, 0, 0));
+        let image_id = app_window.capture_widget_image(&widget).unwrap();
+
+        app_window.suspend_gpu();
+
+        assert!(app_window.try_vg().is_none());
+        assert_eq!(app_window.is_gles(), None);
+        assert_eq!(app_window.gl_version(), None);
+        assert!(matches!(
+            app_window.warmup_text("Warm me up", font_id, 14.0),
+            Err(FirewheelError::GpuSuspended)
+        ));
+        assert!(app_window.capture_widget_image(&widget).is_none());
+        assert!(app_window.unload_image(image_id).is_ok());
+        #[cfg(feature = "image-loading")]
+        assert!(matches!(
+            app_window.load_image_from_bytes(&[], femtovg::ImageFlags::empty()),
+            Err(FirewheelError::GpuSuspended)
+        ));
+        assert!(matches!(
+            app_window.render_to_rgba(window_size, Color::rgb(0, 0, 0)),
+            Err(FirewheelError::GpuSuspended)
+        ));
+        assert!(matches!(
+            app_window.render_single_layer(&layer, window_size, Color::rgb(0, 0, 0)),
+            Err(FirewheelError::GpuSuspended)
+        ));
+
+        // Rendering is skipped, but input is still handled.
+        app_window.render_with_clear_color(window_size, Color::rgb(0, 0, 0));
+        app_window.inject_pointer_down(Point::new(20.0, 20.0), PointerButton::Left);
+        assert!(!events.borrow().is_empty());
+
+        window.gl.resume_gpu(app_window);
+
+        assert!(app_window.try_vg().is_some());
+        assert!(app_window.is_gles().is_some());
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+            .unwrap();
+        assert_eq!(pixel_at(&pixels, window_size, 20, 20), [0, 0, 255, 255]);
+        assert_eq!(pixel_at(&pixels, window_size, 60, 20), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_unchanged_content_version_skips_paint() {
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        let test_widget = TestWidget::new();
+        let paint_count = test_widget.paint_count();
+        let (mut layer, mut widget) = add_layer_with_widget(
+            app_window,
+            Size::new(100.0, 50.0),
+            test_widget,
+            Rect::new(Point::new(10.0, 10.0), Size::new(30.0, 20.0)),
+        );
+        let clear_color = Color::rgb(0, 0, 0);
+
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 1);
+
+        // A new version is painted once...
+        app_window
+            .set_widget_layer_content_version(&mut layer, 1)
+            .unwrap();
+        app_window.mark_widget_dirty(&mut widget).unwrap();
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 2);
+        assert!(!app_window.is_dirty());
+
+        // ...and skipped while it stays the same. The layer doesn't stay
+        // dirty, so the host isn't asked to redraw on every frame.
+        app_window.mark_widget_dirty(&mut widget).unwrap();
+        assert!(app_window.is_dirty());
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 2);
+        assert!(!app_window.is_dirty());
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 2);
+
+        // Setting the same version again changes nothing.
+        app_window
+            .set_widget_layer_content_version(&mut layer, 1)
+            .unwrap();
+        assert!(!app_window.is_dirty());
+
+        // A moved region forces a repaint.
+        app_window
+            .modify_widget_region(
+                &mut widget,
+                None,
+                None,
+                None,
+                Some(AnchorOffset::Points(Point::new(50.0, 10.0))),
+            )
+            .unwrap();
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 3);
+        assert!(!app_window.is_dirty());
+
+        // Bumping the version repaints the widgets that were skipped.
+        app_window.mark_widget_dirty(&mut widget).unwrap();
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 3);
+        assert!(!app_window.is_dirty());
+        app_window
+            .set_widget_layer_content_version(&mut layer, 2)
+            .unwrap();
+        assert!(app_window.is_dirty());
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(paint_count.get(), 4);
+        assert!(!app_window.is_dirty());
+    }
+
+    #[test]
+    fn test_framebuffer_srgb_only_enabled_while_rendering() {
+        const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
+
+        let window_size = PhysicalSize::new(100, 100);
+        let mut window = match GlTestWindow::with_settings(
+            window_size,
+            ScaleFactor(1.0),
+            AppWindowSettings {
+                color_space: ColorSpace::Linear,
+                ..Default::default()
+            },
+        ) {
+            Some(window) => window,
+            None => return,
+        };
+        assert_eq!(window.app_window.color_space(), ColorSpace::Linear);
+        assert!(!window.gl.is_enabled(GL_FRAMEBUFFER_SRGB));
+
+        window
+            .app_window
+            .render_with_clear_color(window_size, Color::rgb(0, 0, 0));
+
+        // The state of the host's context is left as it was.
+        assert!(!window.gl.is_enabled(GL_FRAMEBUFFER_SRGB));
+    }
+
+    #[test]
+    fn test_scrolled_children_are_clipped() {
+        let window_size = PhysicalSize::new(200, 100);
+        for compositing in [Compositing::Texture, Compositing::Direct] {
+            let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+                Some(window) => window,
+                None => return,
+            };
+            let app_window = &mut window.app_window;
+
+            let mut layer = app_window.add_widget_layer(
+                Size::new(200.0, 100.0),
+                0,
+                Point::new(0.0, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+            );
+            app_window
+                .set_widget_layer_compositing(&mut layer, compositing)
+                .unwrap();
+
+            let mut container = app_window
+                .add_container_region(
+                    &layer,
+                    RegionInfo::from_layer_rect(Rect::new(
+                        Point::new(20.0, 10.0),
+                        Size::new(100.0, 50.0),
+                    )),
+                    true,
+                )
+                .unwrap();
+            app_window
+                .set_container_region_scroll_offset(&mut container, Some(Point::new(0.0, 20.0)))
+                .unwrap();
+
+            // The widget is taller than the container, and it is scrolled up
+            // by 20 points.
+            app_window
+                .add_widget_node(
+                    Box::new(TestWidget::new().with_color(Color::rgb(0, 0, 255))),
+                    &layer,
+                    RegionInfo {
+                        parent_anchor_type: ParentAnchorType::ContainerRegion(container.clone()),
+                        ..RegionInfo::from_layer_rect(Rect::new(
+                            Point::new(0.0, 0.0),
+                            Size::new(100.0, 100.0),
+                        ))
+                    },
+                    true,
+                )
+                .unwrap();
+
+            let pixels = app_window
+                .render_to_rgba(window_size, Color::rgb(0, 0, 0))
+                .unwrap();
+
+            let blue = [0, 0, 255, 255];
+            let black = [0, 0, 0, 255];
+            assert_eq!(
+                pixel_at(&pixels, window_size, 20, 10),
+                blue,
+                "{:?}",
+                compositing
+            );
+            assert_eq!(
+                pixel_at(&pixels, window_size, 119, 59),
+                blue,
+                "{:?}",
+                compositing
+            );
+            // Nothing is painted outside of the container.
+            assert_eq!(
+                pixel_at(&pixels, window_size, 50, 9),
+                black,
+                "{:?}",
+                compositing
+            );
+            assert_eq!(
+                pixel_at(&pixels, window_size, 50, 60),
+                black,
+                "{:?}",
+                compositing
+            );
+            assert_eq!(
+                pixel_at(&pixels, window_size, 50, 75),
+                black,
+                "{:?}",
+                compositing
+            );
+        }
+    }
+
+    #[test]
+    fn test_shared_font_in_two_windows() {
+        let resources = SharedResources::new();
+        let font_id = resources
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let new_window = || {
+            let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+            AppWindow::<()>::new(
+                ScaleFactor(1.0),
+                None,
+                resources.text_context.clone(),
+                Some(resources.clone()),
+                AppWindowSettings::default(),
+                action_tx,
+            )
+        };
+        let mut main_window = new_window();
+        let mut meter_window = new_window();
+
+        // The font was never added to either window, but both can measure
+        // text with it.
+        let main_size = main_window.measure_text_cached("-12.5 dB", font_id, 14.0);
+        let meter_size = meter_window.measure_text_cached("-12.5 dB", font_id, 14.0);
+        assert!(main_size.width() > 0.0);
+        assert_eq!(main_size, meter_size);
+    }
+
+    /// A text field that places its caret at the character boundary closest
+    /// to a pointer click.
+    struct CaretWidget {
+        text: &'static str,
+        font_id: femtovg::FontId,
+        caret: Rc<std::cell::Cell<Option<usize>>>,
+    }
+
+    impl WidgetNode<()> for CaretWidget {
+        fn on_added(
+            &mut self,
+            _action_tx: &mut Sender<()>,
+        ) -> (WidgetNodeType, WidgetNodeRequests) {
+            (
+                WidgetNodeType::Painted,
+                WidgetNodeRequests {
+                    set_pointer_events_listen: Some(true),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn on_input_event(
+            &mut self,
+            event: &InputEvent,
+            text_context: &TextContext,
+            _action_tx: &mut Sender<()>,
+        ) -> EventCapturedStatus {
+            if let InputEvent::Pointer(e) = event {
+                if e.left_button.just_pressed() {
+                    let paint = crate::font_paint(self.font_id, 14.0, ScaleFactor(1.0));
+                    let caret = self
+                        .text
+                        .char_indices()
+                        .map(|(i, _)| i)
+                        .chain(std::iter::once(self.text.len()))
+                        .min_by(|a, b| {
+                            let distance = |i: &usize| {
+                                let width = text_context
+                                    .measure_text(0.0, 0.0, &self.text[..*i], &paint)
+                                    .unwrap()
+                                    .width();
+                                (f64::from(width) - e.position.x).abs()
+                            };
+                            distance(a).total_cmp(&distance(b))
+                        });
+                    self.caret.set(caret);
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests::default());
+                }
+            }
+            EventCapturedStatus::NotCaptured
+        }
+    }
+
+    #[test]
+    fn test_measure_text_in_input_event() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let caret = Rc::new(std::cell::Cell::new(None));
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(CaretWidget {
+                    text: "Hello world",
+                    font_id,
+                    caret: Rc::clone(&caret),
+                }),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(400.0, 30.0)),
+                true,
+            )
+            .unwrap();
+
+        // Click slightly to the right of the end of "Hel".
+        let paint = crate::font_paint(font_id, 14.0, ScaleFactor(1.0));
+        let hel_width = app_window
+            .text_context
+            .measure_text(0.0, 0.0, "Hel", &paint)
+            .unwrap()
+            .width();
+        app_window.handle_input_event(&InputEvent::Pointer(PointerEvent {
+            position: Point::new(f64::from(hel_width) + 1.0, 10.0),
+            left_button: PointerButtonState::JustPressed,
+            ..PointerEvent::default()
+        }));
+
+        assert_eq!(caret.get(), Some(3));
+    }
+
+    #[test]
+    fn test_input_filter() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        let button_a = TestWidget::new().with_pointer_listen();
+        let button_a_events = button_a.events();
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(button_a),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(50.0, 20.0)),
+                true,
+            )
+            .unwrap();
+        let button_b = TestWidget::new().with_pointer_listen();
+        let button_b_events = button_b.events();
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(button_b),
+                &layer,
+                Rect::new(Point::new(100.0, 0.0), Size::new(50.0, 20.0)),
+                true,
+            )
+            .unwrap();
+
+        button_a_events.borrow_mut().clear();
+        button_b_events.borrow_mut().clear();
+
+        // A filter which offsets the pointer, i.e. for a render target that
+        // is drawn 100 points to the right of the window's origin, and which
+        // drops keyboard events.
+        let (filtered_tx, filtered_rx) = crossbeam_channel::unbounded();
+        app_window.set_input_filter(Box::new(move |event| {
+            filtered_tx.send(event.clone()).unwrap();
+            match event {
+                InputEvent::Pointer(e) => {
+                    e.position.x += 100.0;
+                    true
+                }
+                InputEvent::Keyboard(_) => false,
+                _ => true,
+            }
+        }));
+
+        app_window.inject_pointer_down(Point::new(10.0, 10.0), PointerButton::Left);
+        assert!(button_a_events.borrow().is_empty());
+        assert_eq!(button_b_events.borrow().len(), 1);
+        if let InputEvent::Pointer(e) = &button_b_events.borrow()[0] {
+            assert_eq!(e.position, Point::new(110.0, 10.0));
+            assert_eq!(e.left_button, PointerButtonState::JustPressed);
+        } else {
+            panic!("expected a pointer event");
+        }
+        app_window.inject_pointer_up(Point::new(10.0, 10.0), PointerButton::Left);
+
+        app_window.inject_key(KeyboardEvent::default());
+        assert_eq!(filtered_rx.try_iter().count(), 3);
+
+        // Without the filter, events are dispatched as they are.
+        app_window.clear_input_filter();
+        button_b_events.borrow_mut().clear();
+        app_window.inject_pointer_down(Point::new(10.0, 10.0), PointerButton::Left);
+        assert_eq!(button_a_events.borrow().len(), 1);
+        assert!(button_b_events.borrow().is_empty());
+        assert_eq!(filtered_rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_focusable_while_hidden() {
+        use crate::widgets::{LabelButton, LabelButtonStyle};
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        // The button asks to be focusable when it is added, while it is
+        // still hidden.
+        let mut button = app_window
+            .add_widget_node_at_rect(
+                Box::new(LabelButton::new(
+                    "Button".into(),
+                    font_id,
+                    Rc::new(LabelButtonStyle::default()),
+                    None,
+                    false,
+                )),
+                &layer,
+                Rect::new(Point::new(20.0, 20.0), Size::new(100.0, 30.0)),
+                false,
+            )
+            .unwrap();
+        assert!(!app_window.focus_in_direction(Direction::Right));
+
+        app_window
+            .set_widget_explicit_visibility(&mut button, true)
+            .unwrap();
+        assert!(app_window.focus_in_direction(Direction::Right));
+
+        // Hiding the button takes away its focus, and showing it again makes
+        // it focusable again.
+        app_window
+            .set_widget_explicit_visibility(&mut button, false)
+            .unwrap();
+        assert!(!app_window.focus_in_direction(Direction::Right));
+        app_window
+            .set_widget_explicit_visibility(&mut button, true)
+            .unwrap();
+        assert!(app_window.focus_in_direction(Direction::Right));
+    }
+
+    #[test]
+    fn test_focus_in_direction_on_zoomed_layer() {
+        use crate::widgets::{LabelButton, LabelButtonStyle};
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+        let add_layer = |app_window: &mut AppWindow<()>, z_order| {
+            app_window.add_widget_layer(
+                Size::new(400.0, 100.0),
+                z_order,
+                Point::new(0.0, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+            )
+        };
+        let layer = add_layer(&mut app_window, 0);
+        let mut zoomed_layer = add_layer(&mut app_window, 1);
+        app_window
+            .set_widget_layer_content_zoom(&mut zoomed_layer, 2.0)
+            .unwrap();
+
+        let add_button = |app_window: &mut AppWindow<()>, layer: &WidgetLayerRef<()>, rect| {
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(LabelButton::new(
+                        "Button".into(),
+                        font_id,
+                        Rc::new(LabelButtonStyle::default()),
+                        None,
+                        false,
+                    )),
+                    layer,
+                    rect,
+                    true,
+                )
+                .unwrap()
+        };
+        let first = add_button(
+            &mut app_window,
+            &layer,
+            Rect::new(Point::new(0.0, 0.0), Size::new(40.0, 20.0)),
+        );
+        let near = add_button(
+            &mut app_window,
+            &layer,
+            Rect::new(Point::new(160.0, 0.0), Size::new(40.0, 20.0)),
+        );
+        // At (100, 0) in the coordinates of the layer's content, but at
+        // (200, 0) in the window, which is further away than `near`.
+        let zoomed = add_button(
+            &mut app_window,
+            &zoomed_layer,
+            Rect::new(Point::new(100.0, 0.0), Size::new(20.0, 10.0)),
+        );
+        assert!(app_window
+            .widget_window_rect(&zoomed)
+            .unwrap()
+            .partial_eq_with_epsilon(Rect::new(Point::new(200.0, 0.0), Size::new(40.0, 20.0))));
+
+        let focused_id = |app_window: &AppWindow<()>| {
+            app_window
+                .focused_widget
+                .as_ref()
+                .map(|widget_entry| widget_entry.unique_id())
+        };
+
+        assert!(app_window.focus_in_direction(Direction::Right));
+        assert_eq!(focused_id(&app_window), Some(first.unique_id()));
+        assert!(app_window.focus_in_direction(Direction::Right));
+        assert_eq!(focused_id(&app_window), Some(near.unique_id()));
+        assert!(app_window.focus_in_direction(Direction::Right));
+        assert_eq!(focused_id(&app_window), Some(zoomed.unique_id()));
+    }
+
+    #[cfg(feature = "input-recording")]
+    #[test]
+    fn test_replay_input_keyboard_cadence() {
+        /// Records the time at which each key event is received.
+        struct KeyTimingWidget {
+            received_at: Rc<RefCell<Vec<Instant>>>,
+        }
+
+        impl WidgetNode<()> for KeyTimingWidget {
+            fn on_added(
+                &mut self,
+                _action_tx: &mut Sender<()>,
+            ) -> (WidgetNodeType, WidgetNodeRequests) {
+                (
+                    WidgetNodeType::PointerOnly,
+                    WidgetNodeRequests {
+                        set_keyboard_events_listen: Some(KeyboardEventsListen::Keys),
+                        ..Default::default()
+                    },
+                )
+            }
+
+            fn on_input_event(
+                &mut self,
+                event: &InputEvent,
+                _text_context: &TextContext,
+                _action_tx: &mut Sender<()>,
+            ) -> EventCapturedStatus {
+                if let InputEvent::Keyboard(_) = event {
+                    self.received_at.borrow_mut().push(Instant::now());
+                }
+                EventCapturedStatus::NotCaptured
+            }
+        }
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let layer = app_window.add_widget_layer(
+            Size::new(100.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let received_at = Rc::new(RefCell::new(Vec::new()));
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(KeyTimingWidget {
+                    received_at: Rc::clone(&received_at),
+                }),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(10.0, 10.0)),
+                true,
+            )
+            .unwrap();
+
+        // Press and release a key 50ms apart.
+        app_window.start_input_recording();
+        app_window.inject_key(KeyboardEvent {
+            state: crate::event::KeyState::Down,
+            ..KeyboardEvent::default()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        app_window.inject_key(KeyboardEvent {
+            state: crate::event::KeyState::Up,
+            ..KeyboardEvent::default()
+        });
+        let log = app_window.stop_input_recording().unwrap();
+        let recorded_gap = log.events[1].time - log.events[0].time;
+        assert!(recorded_gap >= Duration::from_millis(50));
+
+        // The replayed key events are spaced out the same way.
+        received_at.borrow_mut().clear();
+        app_window.replay_input(&log);
+        let received_at = received_at.borrow();
+        assert_eq!(received_at.len(), 2);
+        assert!(received_at[1] - received_at[0] >= recorded_gap);
+    }
+
+    #[cfg(feature = "input-recording")]
+    #[test]
+    fn test_replay_click() {
+        use crate::widgets::{LabelButton, LabelButtonStyle};
+
+        let (action_tx, action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<&'static str> =
+            AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let style = Rc::new(LabelButtonStyle::default());
+        for (label, x) in [("ok", 0.0), ("cancel", 100.0)] {
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(LabelButton::new(
+                        label.into(),
+                        font_id,
+                        Rc::clone(&style),
+                        Some(label),
+                        true,
+                    )),
+                    &layer,
+                    Rect::new(Point::new(x, 0.0), Size::new(100.0, 40.0)),
+                    true,
+                )
+                .unwrap();
+        }
+
+        // Click both buttons while recording.
+        app_window.start_input_recording();
+        for x in [50.0, 150.0] {
+            let position = Point::new(x, 20.0);
+            app_window.inject_pointer_move(position);
+            app_window.inject_pointer_down(position, PointerButton::Left);
+            app_window.inject_pointer_up(position, PointerButton::Left);
+        }
+        let log = app_window.stop_input_recording().unwrap();
+        let recorded_actions: Vec<&str> = action_rx.try_iter().collect();
+        assert_eq!(recorded_actions, vec!["ok", "cancel"]);
+
+        // Replaying the log emits the same actions.
+        app_window.replay_input(&log);
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), recorded_actions);
+    }
+
+    #[test]
+    fn test_apply_input_filter_borrows_without_filter() {
+        let event = InputEvent::Keyboard(KeyboardEvent::default());
+        assert!(matches!(
+            apply_input_filter(None, &event),
+            Some(Cow::Borrowed(_))
+        ));
+
+        let mut drop_all = |_: &mut InputEvent| false;
+        assert!(apply_input_filter(Some(&mut drop_all), &event).is_none());
+    }
+
+    #[test]
+    fn test_widgets_at_points() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut add_widget = |layer: &mut WidgetLayer<()>, rect: Rect, unique_id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            layer.set_widget_region_listens_to_pointer_events(&widget_entry, true);
+            widget_entry
+        };
+
+        // A widget in the bottom layer, partially covered by a widget in a
+        // top layer which is offset by (50, 50).
+        let mut bottom_layer = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+        let mut top_layer = WidgetLayer::new(
+            1,
+            1,
+            Size::new(100.0, 100.0),
+            Point::new(50.0, 50.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+        let _bottom_widget = add_widget(
+            &mut bottom_layer,
+            Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)),
+            0,
+        );
+        let _top_widget = add_widget(
+            &mut top_layer,
+            Rect::new(Point::new(0.0, 0.0), Size::new(40.0, 40.0)),
+            1,
+        );
+
+        let layers = [&top_layer, &bottom_layer];
+        let points = [
+            Point::new(10.0, 10.0),
+            Point::new(60.0, 60.0),
+            Point::new(95.0, 95.0),
+            Point::new(120.0, 120.0),
+            Point::new(500.0, 500.0),
+        ];
+
+        let ids = |widgets: Vec<Option<StrongWidgetNodeEntry<()>>>| -> Vec<Option<u64>> {
+            widgets
+                .iter()
+                .map(|w| w.as_ref().map(|w| w.unique_id()))
+                .collect()
+        };
+
+        let batched = ids(widgets_at_points_in_layers(&layers, &points));
+        assert_eq!(batched, vec![Some(0), Some(1), Some(0), None, None]);
+
+        // The batched results match querying each point on its own.
+        for (point, batched_id) in points.iter().zip(batched.iter()) {
+            assert_eq!(
+                ids(widgets_at_points_in_layers(&layers, &[*point])),
+                vec![*batched_id]
+            );
+        }
+    }
+
+    #[test]
+    fn test_modal_backdrop_blocks_pointer_events() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let new_layer = |id: u64, size: Size, outer_position: Point| {
+            WidgetLayer::new(
+                id,
+                id as i32,
+                size,
+                outer_position,
+                Point::new(0.0, 0.0),
+                true,
+                true,
+                scale_factor,
+            )
+        };
+        let mut add_widget = |layer: &mut WidgetLayer<()>, rect: Rect, unique_id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            layer.set_widget_region_listens_to_pointer_events(&widget_entry, true);
+            widget_entry
+        };
+
+        // A button in the main layer, and a modal dialog with an "OK" button
+        // above a backdrop.
+        let mut main_layer = new_layer(0, Size::new(400.0, 400.0), Point::new(0.0, 0.0));
+        let mut backdrop_layer = new_layer(1, Size::new(400.0, 400.0), Point::new(0.0, 0.0));
+        backdrop_layer.is_backdrop = true;
+        let mut modal_layer = new_layer(2, Size::new(100.0, 100.0), Point::new(150.0, 150.0));
+        let _main_button = add_widget(
+            &mut main_layer,
+            Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)),
+            0,
+        );
+        let _ok_button = add_widget(
+            &mut modal_layer,
+            Rect::new(Point::new(0.0, 0.0), Size::new(50.0, 50.0)),
+            2,
+        );
+
+        let click_outside_modal = Point::new(10.0, 10.0);
+        let click_ok = Point::new(160.0, 160.0);
+        let target = |layers: &[&WidgetLayer<()>], point: Point| {
+            widgets_at_points_in_layers(layers, &[point])[0]
+                .as_ref()
+                .map(|w| w.unique_id())
+        };
+
+        // The click outside of the modal doesn't reach the main button.
+        let layers = [&modal_layer, &backdrop_layer, &main_layer];
+        assert_eq!(target(&layers, click_ok), Some(2));
+        assert_eq!(target(&layers, click_outside_modal), None);
+
+        // Once the modal is closed, clicks reach the main layer again.
+        backdrop_layer.set_explicit_visibility(
+            false,
+            &mut WidgetNodeSet::new(),
+            &mut WidgetNodeSet::new(),
+        );
+        let layers = [&modal_layer, &backdrop_layer, &main_layer];
+        assert_eq!(target(&layers, click_outside_modal), Some(0));
+
+        // A layer with its input disabled lets events pass through.
+        modal_layer.input_enabled = false;
+        main_layer.input_enabled = false;
+        let layers = [&modal_layer, &backdrop_layer, &main_layer];
+        assert_eq!(target(&layers, click_ok), None);
+        assert_eq!(target(&layers, click_outside_modal), None);
+    }
+
+    #[test]
+    fn test_limit_action_queue() {
+        let (action_tx, action_rx) = crossbeam_channel::unbounded::<u32>();
+        for i in 0..10 {
+            action_tx.send(i).unwrap();
+        }
+
+        // Exceeding the limit with `DropOldest` keeps the newest actions.
+        let queued: Vec<u32> = action_rx.try_iter().collect();
+        let kept = limit_action_queue(queued, 4, ActionQueueOverflow::DropOldest);
+        assert_eq!(kept, vec![6, 7, 8, 9]);
+
+        assert_eq!(
+            limit_action_queue(kept.clone(), 2, ActionQueueOverflow::DropNewest),
+            vec![6, 7]
+        );
+        assert_eq!(
+            limit_action_queue(kept.clone(), 2, ActionQueueOverflow::Error),
+            kept
+        );
+
+        // Nothing is dropped when under the limit.
+        assert_eq!(
+            limit_action_queue(vec![1, 2], 4, ActionQueueOverflow::DropOldest),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_action_queue_limit() {
+        /// Sends three actions every time it is pressed.
+        struct BurstWidget {
+            presses: u32,
+        }
+
+        impl WidgetNode<u32> for BurstWidget {
+            fn on_added(
+                &mut self,
+                _action_tx: &mut Sender<u32>,
+            ) -> (WidgetNodeType, WidgetNodeRequests) {
+                (
+                    WidgetNodeType::Painted,
+                    WidgetNodeRequests {
+                        set_pointer_events_listen: Some(true),
+                        ..Default::default()
+                    },
+                )
+            }
+
+            fn on_input_event(
+                &mut self,
+                event: &InputEvent,
+                _text_context: &TextContext,
+                action_tx: &mut Sender<u32>,
+            ) -> EventCapturedStatus {
+                if let InputEvent::Pointer(e) = event {
+                    if e.left_button.just_pressed() {
+                        self.presses += 1;
+                        for i in 1..=3 {
+                            action_tx.send(self.presses * 10 + i).unwrap();
+                        }
+                    }
+                }
+                EventCapturedStatus::Captured(WidgetNodeRequests::default())
+            }
+        }
+
+        let new_app_window = |action_tx: Sender<u32>| {
+            let mut app_window: AppWindow<u32> =
+                AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+            let layer = app_window.add_widget_layer(
+                Size::new(400.0, 300.0),
+                0,
+                Point::new(0.0, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+            );
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(BurstWidget { presses: 0 }),
+                    &layer,
+                    Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 30.0)),
+                    true,
+                )
+                .unwrap();
+            app_window
+        };
+        let (action_tx, action_rx) = crossbeam_channel::unbounded();
+        let mut app_window = new_app_window(action_tx.clone());
+        let press = |app_window: &mut AppWindow<u32>| {
+            app_window.inject_pointer_down(Point::new(50.0, 15.0), PointerButton::Left);
+            app_window.inject_pointer_up(Point::new(50.0, 15.0), PointerButton::Left);
+        };
+
+        // The host already has an action waiting, which is never removed.
+        action_tx.send(0).unwrap();
+        app_window.set_action_queue_limit(3, ActionQueueOverflow::DropOldest);
+        press(&mut app_window);
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![0, 12, 13]);
+
+        app_window.set_action_queue_limit(2, ActionQueueOverflow::DropNewest);
+        press(&mut app_window);
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![21, 22]);
+
+        app_window.set_action_queue_limit(2, ActionQueueOverflow::Error);
+        press(&mut app_window);
+        assert!(app_window.action_queue_full());
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![31, 32, 33]);
+
+        // Without a limit, actions go straight to the host's queue.
+        app_window.clear_action_queue_limit();
+        assert!(!app_window.action_queue_full());
+        press(&mut app_window);
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![41, 42, 43]);
+
+        // The window never blocks on a bounded queue. Only as many actions
+        // as fit are moved to it...
+        let (action_tx, action_rx) = crossbeam_channel::bounded(2);
+        let mut app_window = new_app_window(action_tx);
+        app_window.set_action_queue_limit(4, ActionQueueOverflow::DropOldest);
+        press(&mut app_window);
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![12, 13]);
+
+        // ...and with `ActionQueueOverflow::Error`, the rest are held until
+        // there is room.
+        app_window.set_action_queue_limit(4, ActionQueueOverflow::Error);
+        press(&mut app_window);
+        assert!(app_window.action_queue_full());
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![21, 22]);
+        press(&mut app_window);
+        assert!(app_window.action_queue_full());
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![23, 31]);
+        app_window.clear_action_queue_limit();
+        assert_eq!(action_rx.try_iter().collect::<Vec<_>>(), vec![32, 33]);
+
+        // The actions are dropped once the host drops its receiver.
+        let (action_tx, action_rx) = crossbeam_channel::unbounded();
+        let mut app_window = new_app_window(action_tx);
+        app_window.set_action_queue_limit(2, ActionQueueOverflow::Error);
+        drop(action_rx);
+        press(&mut app_window);
+        app_window.clear_action_queue_limit();
+    }
+
+    #[test]
+    fn test_layer_damage() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut layer: WidgetLayer<()> = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 300.0),
+            Point::new(50.0, 50.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+
+        let mut widgets = Vec::new();
+        for (unique_id, x) in [(0, 0.0), (1, 100.0)] {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(Rect::new(
+                        Point::new(x, 10.0),
+                        Size::new(50.0, 20.0),
+                    )),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            widgets.push(widget_entry);
+        }
+
+        // Pretend that the layer was just rendered.
+        layer.region_tree.clear_whole_layer = false;
+        layer.region_tree.texture_rects_to_clear.clear();
+        layer.region_tree.dirty_widgets.clear();
+        assert!(layer.damage().is_empty());
+
+        layer.region_tree.mark_widget_dirty(&widgets[1]);
+
+        assert_eq!(
+            layer.damage(),
+            vec![PhysicalRect::new(
+                PhysicalPoint::new(150, 60),
+                PhysicalSize::new(50, 20)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_set_ui_scale() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.5), action_tx);
+
+        app_window.set_ui_scale(2.0).unwrap();
+        assert_eq!(app_window.scale_factor(), ScaleFactor(3.0));
+
+        for ui_scale in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                app_window.set_ui_scale(ui_scale),
+                Err(FirewheelError::InvalidScale)
+            );
+            assert_eq!(app_window.scale_factor(), ScaleFactor(3.0));
+        }
+    }
+
+    #[test]
+    fn test_ui_scale() {
+        let dpi_scale_factor = ScaleFactor2D::new(1.5, 1.5);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut layer: WidgetLayer<()> = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            effective_scale_factor(dpi_scale_factor, 1.0),
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        layer
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 20.0),
+                    Size::new(50.0, 30.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let physical_rect = |widget_entry: &StrongWidgetNodeEntry<()>| {
+            widget_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .physical_rect
+        };
+        assert_eq!(
+            physical_rect(&widget_entry),
+            PhysicalRect::new(PhysicalPoint::new(15, 30), PhysicalSize::new(75, 45))
+        );
+
+        // Doubling the UI scale at the same DPI doubles the physical rects.
+        let size = layer.size();
+        layer.set_size(
+            size,
+            effective_scale_factor(dpi_scale_factor, 2.0),
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(
+            physical_rect(&widget_entry),
+            PhysicalRect::new(PhysicalPoint::new(30, 60), PhysicalSize::new(150, 90))
+        );
+    }
+
+    #[test]
+    fn test_widget_group_hover() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        // Two toolbars with two buttons each, and a button on its own.
+        let groups = [
+            Some(WidgetGroupId(0)),
+            Some(WidgetGroupId(0)),
+            Some(WidgetGroupId(1)),
+            Some(WidgetGroupId(1)),
+            None,
+        ];
+        let mut events = Vec::new();
+        for (i, group) in groups.iter().enumerate() {
+            let widget = TestWidget::new().with_pointer_listen();
+            events.push(widget.events());
+            let mut widget_ref = app_window
+                .add_widget_node_at_rect(
+                    Box::new(widget),
+                    &layer,
+                    Rect::new(Point::new(i as f64 * 50.0, 0.0), Size::new(40.0, 40.0)),
+                    true,
+                )
+                .unwrap();
+            app_window
+                .set_widget_group(&mut widget_ref, *group)
+                .unwrap();
+        }
+
+        let move_pointer = |app_window: &mut AppWindow<()>, x: f64| {
+            app_window.handle_input_event(&InputEvent::Pointer(PointerEvent {
+                position: Point::new(x, 20.0),
+                ..PointerEvent::default()
+            }));
+
+            // Take the hover changes each widget received.
+            events
+                .iter()
+                .map(|events| {
+                    let hover_changes = events
+                        .borrow()
+                        .iter()
+                        .filter_map(|e| match e {
+                            InputEvent::GroupHoverChanged { group, hovered } => {
+                                Some((group.0, *hovered))
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    events.borrow_mut().clear();
+                    hover_changes
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Hovering one button of the first toolbar notifies both of its
+        // buttons, and nobody else.
+        assert_eq!(
+            move_pointer(&mut app_window, 10.0),
+            vec![vec![(0, true)], vec![(0, true)], vec![], vec![], vec![]]
+        );
+
+        // Moving within the same group changes nothing.
+        assert_eq!(move_pointer(&mut app_window, 60.0), vec![vec![]; 5]);
+
+        // Moving to the other toolbar notifies both groups.
+        assert_eq!(
+            move_pointer(&mut app_window, 110.0),
+            vec![
+                vec![(0, false)],
+                vec![(0, false)],
+                vec![(1, true)],
+                vec![(1, true)],
+                vec![]
+            ]
+        );
+
+        // A button outside of any group leaves the hovered group.
+        assert_eq!(
+            move_pointer(&mut app_window, 210.0),
+            vec![vec![], vec![], vec![(1, false)], vec![(1, false)], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_inject_click() {
+        use crate::widgets::{LabelButton, LabelButtonStyle};
+
+        let (action_tx, action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<u32> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(LabelButton::new(
+                    "Click me".into(),
+                    font_id,
+                    Rc::new(LabelButtonStyle::default()),
+                    Some(7),
+                    true,
+                )),
+                &layer,
+                Rect::new(Point::new(20.0, 20.0), Size::new(100.0, 30.0)),
+                true,
+            )
+            .unwrap();
+
+        // Clicking next to the button does nothing.
+        app_window.inject_pointer_down(Point::new(200.0, 35.0), PointerButton::Left);
+        app_window.inject_pointer_up(Point::new(200.0, 35.0), PointerButton::Left);
+        assert!(action_rx.try_recv().is_err());
+
+        // The button emits its action once it is released.
+        app_window.inject_pointer_move(Point::new(50.0, 35.0));
+        app_window.inject_pointer_down(Point::new(50.0, 35.0), PointerButton::Left);
+        assert!(action_rx.try_recv().is_err());
+        app_window.inject_pointer_up(Point::new(50.0, 35.0), PointerButton::Left);
+        assert_eq!(action_rx.try_recv(), Ok(7));
+        assert!(action_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_debug_names() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let mut layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let mut widget = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new()),
+                &layer,
+                Rect::new(Point::new(20.0, 20.0), Size::new(100.0, 30.0)),
+                true,
+            )
+            .unwrap();
+
+        app_window
+            .set_layer_debug_name(&mut layer, "transport bar")
+            .unwrap();
+        app_window
+            .set_widget_debug_name(&mut widget, "play button")
+            .unwrap();
+
+        let layer_entry = layer.shared.upgrade().unwrap();
+        let layer_id = layer_entry.borrow().id;
+        let widget_entry = widget.shared.upgrade().unwrap();
+
+        #[cfg(feature = "debug-names")]
+        {
+            assert!(format!("{:?}", layer).contains("\"transport bar\""));
+            assert_eq!(
+                layer_entry.borrow().debug_label().to_string(),
+                format!("{} (\"transport bar\")", layer_id)
+            );
+            assert_eq!(
+                widget_entry.debug_label().to_string(),
+                format!("{} (\"play button\")", widget.unique_id())
+            );
+        }
+        #[cfg(not(feature = "debug-names"))]
+        {
+            assert_eq!(
+                layer_entry.borrow().debug_label().to_string(),
+                layer_id.to_string()
+            );
+            assert_eq!(
+                widget_entry.debug_label().to_string(),
+                widget.unique_id().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_pointer_left_window_clears_hover() {
+        use crate::widgets::{LabelButton, LabelButtonStyle};
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let button = app_window
+            .add_widget_node_at_rect(
+                Box::new(LabelButton::new(
+                    "Hover me".into(),
+                    font_id,
+                    Rc::new(LabelButtonStyle::default()),
+                    None,
+                    true,
+                )),
+                &layer,
+                Rect::new(Point::new(20.0, 20.0), Size::new(100.0, 30.0)),
+                true,
+            )
+            .unwrap();
+        let test_widget = TestWidget::new();
+        let events = test_widget.events();
+        let other = app_window
+            .add_widget_node_at_rect(
+                Box::new(test_widget),
+                &layer,
+                Rect::new(Point::new(200.0, 20.0), Size::new(100.0, 30.0)),
+                true,
+            )
+            .unwrap();
+        let button_entry = button.shared.upgrade().unwrap();
+        let mut other_entry = other.shared.upgrade().unwrap();
+
+        // Hovering the button makes it listen to pointer leave events.
+        app_window.inject_pointer_move(Point::new(50.0, 35.0));
+        assert!(app_window
+            .widgets_with_pointer_leave_listen
+            .contains(&button_entry));
+
+        app_window.handle_widget_requests(
+            &mut other_entry,
+            WidgetNodeRequests {
+                set_pointer_leave_listen: Some(true),
+                ..Default::default()
+            },
+        );
+        events.borrow_mut().clear();
+
+        // The pointer leaves the window without passing over anything else.
+        app_window.handle_input_event(&InputEvent::PointerLeftWindow);
+
+        // The button sees a pointer event outside of its bounds, so it is no
+        // longer hovered and stops listening.
+        assert!(!app_window
+            .widgets_with_pointer_leave_listen
+            .contains(&button_entry));
+
+        // Listeners receive a plain pointer event rather than the host event.
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            InputEvent::Pointer(e) => {
+                assert!(!Rect::new(Point::new(0.0, 0.0), Size::new(400.0, 300.0))
+                    .contains_point(e.position));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        drop(events);
+
+        // The button is hovered again once the pointer comes back.
+        app_window.inject_pointer_move(Point::new(50.0, 35.0));
+        assert!(app_window
+            .widgets_with_pointer_leave_listen
+            .contains(&button_entry));
+    }
+
+    #[test]
+    fn test_headless_layout() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(2.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        // A sidebar pinned to the right edge, with a button centered in it.
+        let sidebar = app_window
+            .add_container_region(
+                &layer,
+                RegionInfo::pinned(
+                    Edges {
+                        top: Some(0.0),
+                        bottom: Some(0.0),
+                        right: Some(0.0),
+                        ..Edges::default()
+                    },
+                    Size::new(100.0, 0.0),
+                    ParentAnchorType::Layer,
+                ),
+                true,
+            )
+            .unwrap();
+
+        let add_meter = |app_window: &mut AppWindow<()>, region_info| {
+            app_window
+                .add_widget_node(Box::new(TestWidget::new()), &layer, region_info, true)
+                .unwrap()
+        };
+        let button = add_meter(
+            &mut app_window,
+            RegionInfo {
+                size: Size::new(80.0, 30.0),
+                internal_anchor: Anchor::center(),
+                parent_anchor: Anchor::center(),
+                parent_anchor_type: ParentAnchorType::ContainerRegion(sidebar),
+                anchor_offset: AnchorOffset::default(),
+                normalized_parent_anchor: None,
+            },
+        );
+        let header = add_meter(
+            &mut app_window,
+            RegionInfo::from_layer_rect(Rect::new(Point::new(10.0, 10.0), Size::new(200.0, 40.0))),
+        );
+
+        let rect = |app_window: &AppWindow<()>, widget: &WidgetNodeRef<()>| {
+            app_window.widget_region_rect(widget).unwrap()
+        };
+        assert!(rect(&app_window, &button)
+            .partial_eq_with_epsilon(Rect::new(Point::new(310.0, 135.0), Size::new(80.0, 30.0))));
+        assert!(rect(&app_window, &header)
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 10.0), Size::new(200.0, 40.0))));
+        assert!(app_window.is_widget_visible(&button));
+
+        // Resizing the layer moves the sidebar and the button along with it.
+        let mut layer = layer;
+        app_window
+            .set_widget_layer_size(&mut layer, Size::new(600.0, 300.0))
+            .unwrap();
+        assert!(rect(&app_window, &button)
+            .partial_eq_with_epsilon(Rect::new(Point::new(510.0, 135.0), Size::new(80.0, 30.0))));
+
+        // Rendering does nothing without a renderer.
+        app_window.render(PhysicalSize::new(1200, 600));
+        assert!(matches!(
+            app_window.render_single_layer(
+                &layer,
+                PhysicalSize::new(1200, 600),
+                Color::rgb(0, 0, 0)
+            ),
+            Err(FirewheelError::GpuSuspended)
+        ));
+    }
+
+    #[test]
+    fn test_stored_clear_color() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        assert_eq!(app_window.clear_color(), Color::rgb(0, 0, 0));
+
+        // The color-less `render()` clears to the stored color.
+        app_window.set_clear_color(Color::rgb(30, 30, 30));
+        assert_eq!(app_window.clear_color(), Color::rgb(30, 30, 30));
+        app_window.render(PhysicalSize::new(400, 300));
+
+        // An explicit color doesn't replace the stored one.
+        app_window.render_with_clear_color(PhysicalSize::new(400, 300), Color::rgb(255, 0, 0));
+        assert_eq!(app_window.clear_color(), Color::rgb(30, 30, 30));
+    }
+
+    #[test]
+    fn test_broadcast_user_event() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let container = app_window
+            .add_container_region(
+                &layer,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(0.0, 0.0),
+                    Size::new(100.0, 100.0),
+                )),
+                true,
+            )
+            .unwrap();
+
+        let mut meters = Vec::new();
+        for i in 0..3 {
+            let shared_value = Arc::new(AtomicU32::new(0));
+            let meter = app_window
+                .add_widget_node(
+                    Box::new(TestWidget::new().with_value(Arc::clone(&shared_value))),
+                    &layer,
+                    RegionInfo::pinned(
+                        Edges::all(10.0 * i as f32),
+                        Size::new(10.0, 10.0),
+                        ParentAnchorType::ContainerRegion(container.clone()),
+                    ),
+                    true,
+                )
+                .unwrap();
+            meters.push((meter, shared_value));
+        }
+
+        // Only the first and last meters match.
+        let matching_ids = [meters[0].0.unique_id(), meters[2].0.unique_id()];
+        let num_sent = app_window.broadcast_user_event(
+            || Box::new(7u32),
+            |widget| matching_ids.contains(&widget.unique_id()),
+        );
+
+        assert_eq!(num_sent, 2);
+        assert_eq!(meters[0].1.load(Ordering::Relaxed), 7);
+        assert_eq!(meters[1].1.load(Ordering::Relaxed), 0);
+        assert_eq!(meters[2].1.load(Ordering::Relaxed), 7);
+    }
+
+    /// Records the lifecycle log messages along with the thread that logged
+    /// them, so that tests running in parallel don't see each other's
+    /// messages.
+    struct LifecycleLogger {
+        messages: std::sync::Mutex<Vec<(std::thread::ThreadId, String)>>,
+    }
+
+    impl log::Log for LifecycleLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == crate::LIFECYCLE_LOG_TARGET
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.messages
+                    .lock()
+                    .unwrap()
+                    .push((std::thread::current().id(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LIFECYCLE_LOGGER: LifecycleLogger = LifecycleLogger {
+        messages: std::sync::Mutex::new(Vec::new()),
+    };
+
+    /// Take the lifecycle log messages logged by this thread so far.
+    fn take_lifecycle_messages() -> Vec<String> {
+        let thread_id = std::thread::current().id();
+        let mut messages = LIFECYCLE_LOGGER.messages.lock().unwrap();
+        let (ours, others): (Vec<_>, Vec<_>) =
+            messages.drain(..).partition(|(id, _)| *id == thread_id);
+        *messages = others;
+        ours.into_iter().map(|(_, message)| message).collect()
+    }
+
+    #[test]
+    fn test_lifecycle_trace_logging() {
+        let _ = log::set_logger(&LIFECYCLE_LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let mut meter = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new()),
+                &layer,
+                Rect::new(Point::new(10.0, 10.0), Size::new(50.0, 50.0)),
+                true,
+            )
+            .unwrap();
+        let mut meter_entry = meter.shared.upgrade().unwrap();
+        let widget_id = meter_entry.unique_id();
+        let layer_id = layer.shared.upgrade().unwrap().borrow().id;
+        take_lifecycle_messages();
+
+        // A headless window has no renderer to send the click to widgets
+        // with, so the widget's response to the click is handled directly.
+        app_window.inject_pointer_down(Point::new(20.0, 20.0), PointerButton::Left);
+        app_window.inject_pointer_up(Point::new(20.0, 20.0), PointerButton::Left);
+        app_window.handle_widget_requests(
+            &mut meter_entry,
+            WidgetNodeRequests {
+                repaint: true,
+                ..Default::default()
+            },
+        );
+        app_window
+            .set_widget_explicit_visibility(&mut meter, false)
+            .unwrap();
+
+        let messages = take_lifecycle_messages();
+        assert_eq!(messages.len(), 7, "{:#?}", messages);
+        assert!(messages[0].starts_with("input event: Pointer("));
+        assert_eq!(messages[1], "input event dropped: renderer suspended");
+        assert!(messages[2].starts_with("input event: Pointer("));
+        assert_eq!(messages[3], "input event dropped: renderer suspended");
+        assert!(messages[4].starts_with(&format!(
+            "widget requests: widget={} layer=Some({}) WidgetNodeRequests {{ repaint: true,",
+            widget_id, layer_id
+        )));
+        assert_eq!(messages[5], "visibility changes: shown=0 hidden=1");
+        assert_eq!(
+            messages[6],
+            format!(
+                "widget hidden: widget={} layer=Some({})",
+                widget_id, layer_id
+            )
+        );
+    }
+
+    #[test]
+    fn test_modal_blocks_underlying_input() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let new_meter = || Box::new(TestWidget::new().with_pointer_listen());
+
+        let window_size = Size::new(400.0, 300.0);
+        let layer = app_window.add_widget_layer(
+            window_size,
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let knob_widget = new_meter();
+        let knob_events = knob_widget.events();
+        let knob = app_window
+            .add_widget_node_at_rect(
+                knob_widget,
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), window_size),
+                true,
+            )
+            .unwrap();
+
+        let hit_id = |app_window: &AppWindow<()>, point: Point| {
+            app_window
+                .widget_at_point(point)
+                .map(|w| w.shared.upgrade().unwrap().unique_id())
+        };
+        let knob_id = knob.shared.upgrade().unwrap().unique_id();
+        assert_eq!(hit_id(&app_window, Point::new(20.0, 20.0)), Some(knob_id));
+
+        let mut dialog = None;
+        let modal = app_window.open_modal(
+            window_size,
+            Size::new(100.0, 80.0),
+            Color::rgba(0, 0, 0, 128),
+            |app_window, modal| {
+                dialog = Some(
+                    app_window
+                        .add_widget_node(
+                            new_meter(),
+                            modal.layer(),
+                            RegionInfo::pinned(
+                                Edges::all(0.0),
+                                Size::default(),
+                                ParentAnchorType::ContainerRegion(modal.container().clone()),
+                            ),
+                            true,
+                        )
+                        .unwrap(),
+                );
+            },
+        );
+        let dialog_id = dialog.unwrap().shared.upgrade().unwrap().unique_id();
+
+        // The dialog is centered, and everything outside of it is blocked.
+        assert_eq!(
+            app_window.container_region_rect(modal.container()).unwrap(),
+            Rect::new(Point::new(150.0, 110.0), Size::new(100.0, 80.0))
+        );
+        assert_eq!(
+            hit_id(&app_window, Point::new(200.0, 150.0)),
+            Some(dialog_id)
+        );
+        assert_eq!(hit_id(&app_window, Point::new(20.0, 20.0)), None);
+
+        let click = InputEvent::Pointer(PointerEvent {
+            position: Point::new(20.0, 20.0),
+            left_button: PointerButtonState::JustPressed,
+            ..PointerEvent::default()
+        });
+        let knob_pointer_events = |events: &Rc<RefCell<Vec<InputEvent>>>| {
+            events
+                .borrow()
+                .iter()
+                .filter(|e| matches!(e, InputEvent::Pointer(_)))
+                .count()
+        };
+        app_window.handle_input_event(&click);
+        assert_eq!(knob_pointer_events(&knob_events), 0);
+
+        // Closing the modal restores input to the layer below.
+        app_window.close_modal(modal).unwrap();
+        assert_eq!(app_window.layers_ordered.len(), 1);
+        assert_eq!(hit_id(&app_window, Point::new(20.0, 20.0)), Some(knob_id));
+        assert_eq!(hit_id(&app_window, Point::new(200.0, 150.0)), Some(knob_id));
+        app_window.handle_input_event(&click);
+        assert_eq!(knob_pointer_events(&knob_events), 1);
+    }
+
+    #[test]
+    fn test_modal_backdrop_follows_window_resize() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let mut layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        app_window
+            .set_widget_layer_fills_window(&mut layer, true)
+            .unwrap();
+        let knob_widget = TestWidget::new().with_pointer_listen();
+        let knob_events = knob_widget.events();
+        app_window
+            .add_widget_node(
+                Box::new(knob_widget),
+                &layer,
+                RegionInfo::pinned(Edges::all(0.0), Size::default(), ParentAnchorType::Layer),
+                true,
+            )
+            .unwrap();
+
+        let modal = app_window.open_modal(
+            Size::new(400.0, 300.0),
+            Size::new(100.0, 80.0),
+            Color::rgba(0, 0, 0, 128),
+            |_, _| {},
+        );
+
+        // Grow the window while the modal is open.
+        app_window.set_pending_window_size(PhysicalSize::new(800, 600));
+        app_window.commit_resize();
+
+        // The dialog is re-centered in the new window size.
+        assert_eq!(
+            app_window.container_region_rect(modal.container()).unwrap(),
+            Rect::new(Point::new(350.0, 260.0), Size::new(100.0, 80.0))
+        );
+
+        // A click in the newly exposed area is still blocked by the backdrop.
+        app_window.handle_input_event(&InputEvent::Pointer(PointerEvent {
+            position: Point::new(700.0, 500.0),
+            left_button: PointerButtonState::JustPressed,
+            ..PointerEvent::default()
+        }));
+        assert!(!knob_events
+            .borrow()
+            .iter()
+            .any(|e| matches!(e, InputEvent::Pointer(_))));
+    }
+
+    #[test]
+    fn test_widget_node_ref_is_alive() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let window_size = Size::new(400.0, 300.0);
+        let mut dialog = None;
+        let modal = app_window.open_modal(
+            window_size,
+            Size::new(100.0, 80.0),
+            Color::rgba(0, 0, 0, 128),
+            |app_window, modal| {
+                dialog = Some(
+                    app_window
+                        .add_widget_node(
+                            Box::new(TestWidget::new()),
+                            modal.layer(),
+                            RegionInfo::pinned(
+                                Edges::all(0.0),
+                                Size::default(),
+                                ParentAnchorType::ContainerRegion(modal.container().clone()),
+                            ),
+                            true,
+                        )
+                        .unwrap(),
+                );
+            },
+        );
+        let mut dialog = dialog.unwrap();
+        assert!(dialog.is_alive());
+
+        // Closing the modal removes its layer, which leaves the ref stale.
+        app_window.close_modal(modal).unwrap();
+        assert!(!dialog.is_alive());
+        assert!(matches!(
+            app_window.set_widget_counts_toward_bounds(&mut dialog, false),
+            Err(FirewheelError::WidgetNodeRemoved)
+        ));
+    }
+
+    #[test]
+    fn test_background_paint_order() {
+        struct EmptyBackgroundNode {}
+        impl BackgroundNode for EmptyBackgroundNode {}
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        // The widget layer is added first, but the background is still
+        // composited behind it.
+        let mut layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let mut background = app_window.add_background_node(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            true,
+            Box::new(EmptyBackgroundNode {}),
+        );
+
+        let is_background_order = |app_window: &AppWindow<()>| {
+            assert_eq!(app_window.layers_ordered.len(), 1);
+            app_window.layers_ordered[0]
+                .1
+                .iter()
+                .map(|layer_entry| matches!(layer_entry, StrongLayerEntry::Background(_)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(is_background_order(&app_window), vec![true, false]);
+
+        // A later widget layer with the same z order goes on top.
+        let _top_layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        assert_eq!(is_background_order(&app_window), vec![true, false, false]);
+
+        // The paint order can be overridden to put the background in front.
+        app_window
+            .set_background_node_paint_order(&mut background, 1)
+            .unwrap();
+        assert_eq!(is_background_order(&app_window), vec![false, false, true]);
+
+        // And a widget layer can be raised above it again.
+        app_window
+            .set_widget_layer_paint_order(&mut layer, 2)
+            .unwrap();
+        assert_eq!(is_background_order(&app_window), vec![false, true, false]);
+        if let StrongLayerEntry::Widget(layer_entry) = &app_window.layers_ordered[0].1[2] {
+            assert_eq!(
+                layer_entry.borrow().id,
+                layer.shared.upgrade().unwrap().borrow().id
+            );
+        } else {
+            panic!("expected a widget layer");
+        }
+    }
+
+    #[test]
+    fn test_move_within_z_survives_paint_order_change() {
+        struct EmptyBackgroundNode {}
+        impl BackgroundNode for EmptyBackgroundNode {}
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let add_layer = |app_window: &mut AppWindow<()>| {
+            app_window.add_widget_layer(
+                Size::new(200.0, 200.0),
+                0,
+                Point::new(0.0, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+            )
+        };
+        let mut bottom_layer = add_layer(&mut app_window);
+        let middle_layer = add_layer(&mut app_window);
+        let top_layer = add_layer(&mut app_window);
+        let background = app_window.add_background_node(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            true,
+            Box::new(EmptyBackgroundNode {}),
+        );
+
+        // The ID of each widget layer from the bottom up, with `None` for the
+        // background.
+        let layer_ids = |app_window: &AppWindow<()>| {
+            app_window.layers_ordered[0]
+                .1
+                .iter()
+                .map(|layer_entry| match layer_entry {
+                    StrongLayerEntry::Widget(layer_entry) => Some(layer_entry.borrow().id),
+                    StrongLayerEntry::Background(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let id = |layer: &WidgetLayerRef<()>| Some(layer.shared.upgrade().unwrap().borrow().id);
+        assert_eq!(
+            layer_ids(&app_window),
+            vec![None, id(&bottom_layer), id(&middle_layer), id(&top_layer)]
+        );
+
+        // Move the top layer behind the background, and the background above
+        // every widget layer.
+        app_window
+            .move_widget_layer_within_z(&top_layer, 0)
+            .unwrap();
+        app_window
+            .move_background_node_within_z(&background, usize::MAX)
+            .unwrap();
+        assert_eq!(
+            layer_ids(&app_window),
+            vec![id(&top_layer), id(&bottom_layer), id(&middle_layer), None]
+        );
+
+        // Raising another layer sorts the layers again, which keeps the moved
+        // layers where they were put relative to the rest.
+        app_window
+            .set_widget_layer_paint_order(&mut bottom_layer, 1)
+            .unwrap();
+        assert_eq!(
+            layer_ids(&app_window),
+            vec![id(&top_layer), id(&middle_layer), None, id(&bottom_layer)]
+        );
+    }
+
+    #[test]
+    fn test_raw_scroll() {
+        use crate::event::{RawScroll, RawScrollDelta, ScrollPhase};
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let map = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new()),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 200.0)),
+                true,
+            )
+            .unwrap();
+        let mut map_entry = map.shared.upgrade().unwrap();
+
+        // A trackpad gesture followed by momentum scrolling.
+        let gesture = [
+            (ScrollPhase::Started, 0.0),
+            (ScrollPhase::Moved, 4.0),
+            (ScrollPhase::Moved, 10.0),
+            (ScrollPhase::Moved, 2.5),
+            (ScrollPhase::Ended, 0.0),
+        ];
+        let events: Vec<InputEvent> = gesture
+            .iter()
+            .map(|&(phase, dy)| {
+                InputEvent::Pointer(PointerEvent {
+                    scroll_delta_y: dy as f32,
+                    raw_scroll: Some(RawScroll {
+                        delta: RawScrollDelta::Pixels { x: 0.0, y: dy },
+                        phase,
+                    }),
+                    ..PointerEvent::default()
+                })
+            })
+            .collect();
+
+        let received = |map_entry: &mut StrongWidgetNodeEntry<()>| -> Vec<Option<RawScroll>> {
+            events
+                .iter()
+                .map(|event| match widget_pointer_event(map_entry, event) {
+                    InputEvent::Pointer(e) => e.raw_scroll,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        // Widgets don't receive the raw scroll data by default.
+        assert!(received(&mut map_entry).iter().all(|raw| raw.is_none()));
+
+        app_window.handle_widget_requests(
+            &mut map_entry,
+            WidgetNodeRequests {
+                set_raw_scroll: Some(true),
+                ..Default::default()
+            },
+        );
+
+        // Every individual delta arrives with its phase.
+        let received = received(&mut map_entry);
+        assert_eq!(received.len(), gesture.len());
+        for (raw, &(phase, dy)) in received.iter().zip(gesture.iter()) {
+            assert_eq!(
+                *raw,
+                Some(RawScroll {
+                    delta: RawScrollDelta::Pixels { x: 0.0, y: dy },
+                    phase,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_flush_widget_requests() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let meter = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new()),
+                &layer,
+                Rect::new(Point::new(10.0, 10.0), Size::new(50.0, 50.0)),
+                true,
+            )
+            .unwrap();
+        let meter_entry = meter.shared.upgrade().unwrap();
+
+        // A timer queues a request outside of any event, so nothing handles
+        // it until the queue is flushed.
+        app_window.widget_requests.push((
+            meter_entry.clone(),
+            WidgetNodeRequests {
+                set_receive_next_animation_event: Some(true),
+                ..Default::default()
+            },
+        ));
+        assert!(!app_window
+            .widgets_scheduled_for_animation
+            .contains(&meter_entry));
+
+        app_window.flush_widget_requests();
+        assert!(app_window
+            .widgets_scheduled_for_animation
+            .contains(&meter_entry));
+        assert!(app_window.widget_requests.is_empty());
+        assert!(app_window.widgets_to_send_input_event.is_empty());
+
+        // Flushing an empty queue does nothing.
+        app_window.flush_widget_requests();
+        assert!(app_window
+            .widgets_scheduled_for_animation
+            .contains(&meter_entry));
+    }
+
+    #[test]
+    fn test_animator_updates() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let shared_value = Arc::new(AtomicU32::new(0));
+        let meter = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new().with_value(Arc::clone(&shared_value))),
+                &layer,
+                Rect::new(Point::new(10.0, 10.0), Size::new(50.0, 50.0)),
+                true,
+            )
+            .unwrap();
+
+        let duration = Duration::from_millis(200);
+        let frame = Duration::from_millis(50);
+
+        let meter_animator = app_window
+            .start_animator(Animator::new(0.0, 100.0, duration, Easing::Linear), &meter)
+            .unwrap();
+
+        let callback_values = Rc::new(RefCell::new(Vec::new()));
+        let callback_animator = {
+            let callback_values = Rc::clone(&callback_values);
+            app_window.start_animator_with_callback(
+                Animator::new(1.0, 0.0, duration, Easing::Linear),
+                move |value, finished| callback_values.borrow_mut().push((value, finished)),
+            )
+        };
+
+        app_window.step_animators(frame);
+        app_window.step_animators(frame);
+        // The midpoint.
+        assert_eq!(shared_value.load(Ordering::Relaxed), 50);
+        assert!(app_window.is_animator_running(meter_animator));
+
+        app_window.step_animators(frame);
+        app_window.step_animators(frame);
+        assert_eq!(shared_value.load(Ordering::Relaxed), 100);
+        assert!(!app_window.is_animator_running(meter_animator));
+        assert!(!app_window.is_animator_running(callback_animator));
+
+        let expected = [(0.75, false), (0.5, false), (0.25, false), (0.0, true)];
+        assert_eq!(callback_values.borrow().len(), expected.len());
+        for (&(value, finished), &(expected_value, expected_finished)) in
+            callback_values.borrow().iter().zip(expected.iter())
+        {
+            assert!((value - expected_value).abs() < 0.0001);
+            assert_eq!(finished, expected_finished);
+        }
+
+        // Finished animators are not stepped again.
+        shared_value.store(7, Ordering::Relaxed);
+        app_window.step_animators(frame);
+        assert_eq!(shared_value.load(Ordering::Relaxed), 7);
+        assert_eq!(callback_values.borrow().len(), 4);
+
+        // Stopping an animator early.
+        let meter_animator = app_window
+            .start_animator(Animator::new(0.0, 100.0, duration, Easing::Linear), &meter)
+            .unwrap();
+        assert!(app_window.stop_animator(meter_animator));
+        assert!(!app_window.stop_animator(meter_animator));
+        app_window.step_animators(frame);
+        assert_eq!(shared_value.load(Ordering::Relaxed), 7);
+    }
+
+    #[test]
+    fn test_animations_paused() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let meter = app_window
+            .add_widget_node_at_rect(
+                Box::new(TestWidget::new()),
+                &layer,
+                Rect::new(Point::new(10.0, 10.0), Size::new(50.0, 50.0)),
+                true,
+            )
+            .unwrap();
+        let meter_entry = meter.shared.upgrade().unwrap();
+        app_window.widget_requests.push((
+            meter_entry.clone(),
+            WidgetNodeRequests {
+                set_receive_next_animation_event: Some(true),
+                ..Default::default()
+            },
+        ));
+        app_window.flush_widget_requests();
+
+        let frame = Duration::from_millis(50);
+        let callback_values = Rc::new(RefCell::new(Vec::new()));
+        let animator = {
+            let callback_values = Rc::clone(&callback_values);
+            app_window.start_animator_with_callback(
+                Animator::new(0.0, 1.0, Duration::from_millis(100), Easing::Linear),
+                move |value, _| callback_values.borrow_mut().push(value),
+            )
+        };
+
+        // While paused, animation frames are dropped without losing the
+        // widgets scheduled for animation or the running animators.
+        app_window.set_animations_paused(true);
+        assert!(!app_window.step_animation_frame(frame));
+        assert!(!app_window.step_animation_frame(frame));
+        assert!(callback_values.borrow().is_empty());
+        assert!(app_window.is_animator_running(animator));
+        assert!(app_window
+            .widgets_scheduled_for_animation
+            .contains(&meter_entry));
+
+        // Resuming picks up where the animations left off.
+        app_window.set_animations_paused(false);
+        assert!(app_window.step_animation_frame(frame));
+        assert_eq!(callback_values.borrow().len(), 1);
+        assert!((callback_values.borrow()[0] - 0.5).abs() < 0.0001);
+        assert!(app_window.is_animator_running(animator));
+    }
+
+    #[test]
+    fn test_region_ancestors() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        // A toolbar inside of a panel.
+        let panel = app_window
+            .add_container_region(
+                &layer,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 10.0),
+                    Size::new(300.0, 200.0),
+                )),
+                true,
+            )
+            .unwrap();
+        let panel_id = panel.unique_id();
+        let toolbar = app_window
+            .add_container_region(
+                &layer,
+                RegionInfo::pinned(
+                    Edges {
+                        top: Some(0.0),
+                        left: Some(0.0),
+                        right: Some(0.0),
+                        ..Edges::default()
+                    },
+                    Size::new(0.0, 40.0),
+                    ParentAnchorType::ContainerRegion(panel),
+                ),
+                true,
+            )
+            .unwrap();
+        let toolbar_id = toolbar.unique_id();
+
+        let add_meter = |app_window: &mut AppWindow<()>, region_info| {
+            app_window
+                .add_widget_node(Box::new(TestWidget::new()), &layer, region_info, true)
+                .unwrap()
+        };
+        let mut button = add_meter(
+            &mut app_window,
+            RegionInfo::pinned(
+                Edges {
+                    top: Some(5.0),
+                    left: Some(5.0),
+                    ..Edges::default()
+                },
+                Size::new(30.0, 30.0),
+                ParentAnchorType::ContainerRegion(toolbar),
+            ),
+        );
+        let header = add_meter(
+            &mut app_window,
+            RegionInfo::from_layer_rect(Rect::new(Point::new(0.0, 250.0), Size::new(100.0, 40.0))),
+        );
+
+        // The direct parent comes first.
+        assert_eq!(
+            app_window
+                .region_ancestors(&button)
+                .iter()
+                .map(|container| container.unique_id())
+                .collect::<Vec<_>>(),
+            vec![toolbar_id, panel_id]
+        );
+        assert!(app_window.region_ancestors(&header).is_empty());
+
+        app_window.remove_widget(&mut button).unwrap();
+        assert!(app_window.region_ancestors(&button).is_empty());
+    }
+
+    #[test]
+    fn test_popup_flips_above_widget() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let window_size = Size::new(400.0, 300.0);
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 200.0),
+            0,
+            Point::new(0.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        let add_dropdown = |app_window: &mut AppWindow<()>, rect| {
+            app_window
+                .add_widget_node_at_rect(Box::new(TestWidget::new()), &layer, rect, true)
+                .unwrap()
+        };
+        let top_dropdown = add_dropdown(
+            &mut app_window,
+            Rect::new(Point::new(10.0, 0.0), Size::new(80.0, 20.0)),
+        );
+        // Near the bottom edge of the window.
+        let bottom_dropdown = add_dropdown(
+            &mut app_window,
+            Rect::new(Point::new(10.0, 170.0), Size::new(80.0, 20.0)),
+        );
+        assert!(app_window
+            .widget_window_rect(&bottom_dropdown)
+            .unwrap()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 270.0), Size::new(80.0, 20.0))));
+
+        let menu_size = Size::new(100.0, 120.0);
+        let popup_pos = |app_window: &mut AppWindow<()>, dropdown: &WidgetNodeRef<()>| {
+            let popup = app_window
+                .open_popup(dropdown, menu_size, PopupPlacement::default(), window_size)
+                .unwrap();
+            let popup_entry = popup.shared.upgrade().unwrap();
+            let layer = popup_entry.borrow();
+            layer.outer_position
+        };
+
+        // There is room below the top dropdown.
+        assert!(popup_pos(&mut app_window, &top_dropdown)
+            .partial_eq_with_epsilon(Point::new(10.0, 120.0)));
+        // The menu of the bottom dropdown opens upward instead.
+        assert!(popup_pos(&mut app_window, &bottom_dropdown)
+            .partial_eq_with_epsilon(Point::new(10.0, 150.0)));
+    }
+
+    #[test]
+    fn test_coalesce_window_resize() {
+        /// Counts the number of times its region is laid out.
+        struct LayoutCounter {
+            layouts: Rc<std::cell::Cell<u32>>,
+        }
+
+        impl WidgetNode<()> for LayoutCounter {
+            fn on_added(
+                &mut self,
+                _action_tx: &mut Sender<()>,
+            ) -> (WidgetNodeType, WidgetNodeRequests) {
+                (WidgetNodeType::Painted, WidgetNodeRequests::default())
+            }
+
+            fn on_region_changed(&mut self, _assigned_rect: Rect) {
+                self.layouts.set(self.layouts.get() + 1);
+            }
+        }
+
+        struct EmptyBackgroundNode {}
+        impl BackgroundNode for EmptyBackgroundNode {}
+
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(2.0), action_tx);
+
+        let mut layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let overlay = app_window.add_widget_layer(
+            Size::new(100.0, 100.0),
+            1,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let mut background = app_window.add_background_node(
+            Size::new(400.0, 300.0),
+            -1,
+            Point::new(0.0, 0.0),
+            true,
+            Box::new(EmptyBackgroundNode {}),
+        );
+        app_window
+            .set_widget_layer_fills_window(&mut layer, true)
+            .unwrap();
+        app_window
+            .set_background_node_fills_window(&mut background, true)
+            .unwrap();
+
+        // A widget pinned to the edges of the layer is laid out again every
+        // time the layer is resized.
+        let layouts = Rc::new(std::cell::Cell::new(0));
+        app_window
+            .add_widget_node(
+                Box::new(LayoutCounter {
+                    layouts: Rc::clone(&layouts),
+                }),
+                &layer,
+                RegionInfo::pinned(
+                    Edges {
+                        top: Some(0.0),
+                        bottom: Some(0.0),
+                        left: Some(0.0),
+                        right: Some(0.0),
+                    },
+                    Size::new(0.0, 0.0),
+                    ParentAnchorType::Layer,
+                ),
+                true,
+            )
+            .unwrap();
+        layouts.set(0);
+
+        // The window edge is dragged across several sizes within one frame,
+        // which results in a single layout on commit.
+        app_window.set_pending_window_size(PhysicalSize::new(820, 600));
+        app_window.set_pending_window_size(PhysicalSize::new(840, 610));
+        app_window.set_pending_window_size(PhysicalSize::new(860, 620));
+        assert_eq!(layouts.get(), 0);
+        assert_eq!(
+            app_window.commit_resize(),
+            Some(PhysicalSize::new(860, 620))
+        );
+        assert_eq!(layouts.get(), 1);
+
+        // Nothing changes in the next frame.
+        assert_eq!(app_window.commit_resize(), None);
+        assert_eq!(layouts.get(), 1);
+
+        let widget_layer_size = |layer: &WidgetLayerRef<()>| {
+            layer
+                .shared
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region_tree
+                .layer_size()
+        };
+        assert_eq!(widget_layer_size(&layer), Size::new(430.0, 310.0));
+        // Layers that don't fill the window keep their size.
+        assert_eq!(widget_layer_size(&overlay), Size::new(100.0, 100.0));
+
+        let background_size = app_window
+            .layers_ordered
+            .iter()
+            .flat_map(|(_z_order, layers)| layers.iter())
+            .find_map(|layer_entry| match layer_entry {
+                StrongLayerEntry::Background(layer_entry) => Some(layer_entry.borrow().size),
+                StrongLayerEntry::Widget(_) => None,
+            })
+            .unwrap();
+        assert_eq!(background_size, Size::new(430.0, 310.0));
+    }
+
+    #[test]
+    fn test_widget_counts_toward_bounds() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        assert_eq!(app_window.layer_content_bounds(&layer).unwrap(), None);
+
+        let add_meter = |app_window: &mut AppWindow<()>, rect| {
+            app_window
+                .add_widget_node_at_rect(Box::new(TestWidget::new()), &layer, rect, true)
+                .unwrap()
+        };
+        let mut background = add_meter(
+            &mut app_window,
+            Rect::new(Point::new(0.0, 0.0), Size::new(400.0, 300.0)),
+        );
+        add_meter(
+            &mut app_window,
+            Rect::new(Point::new(20.0, 30.0), Size::new(50.0, 20.0)),
+        );
+        add_meter(
+            &mut app_window,
+            Rect::new(Point::new(100.0, 40.0), Size::new(60.0, 60.0)),
+        );
+
+        // The background covers the whole layer.
+        assert!(app_window
+            .layer_content_bounds(&layer)
+            .unwrap()
+            .unwrap()
+            .partial_eq_with_epsilon(Rect::new(Point::new(0.0, 0.0), Size::new(400.0, 300.0))));
+
+        app_window
+            .set_widget_counts_toward_bounds(&mut background, false)
+            .unwrap();
+        assert!(app_window
+            .layer_content_bounds(&layer)
+            .unwrap()
+            .unwrap()
+            .partial_eq_with_epsilon(Rect::new(Point::new(20.0, 30.0), Size::new(140.0, 70.0))));
+    }
+
+    #[test]
+    fn test_widgets_intersecting_rect() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(400.0, 300.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let add_meter = |app_window: &mut AppWindow<()>, x, y, visible| {
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(TestWidget::new()),
+                    &layer,
+                    Rect::new(Point::new(x, y), Size::new(50.0, 50.0)),
+                    visible,
+                )
+                .unwrap()
+                .unique_id()
+        };
+        let a = add_meter(&mut app_window, 0.0, 0.0, true);
+        let b = add_meter(&mut app_window, 60.0, 0.0, true);
+        // Only touches the right edge of the selection.
+        let c = add_meter(&mut app_window, 150.0, 0.0, true);
+        // Only touches the bottom edge of the selection.
+        let d = add_meter(&mut app_window, 0.0, 100.0, true);
+        let hidden = add_meter(&mut app_window, 70.0, 20.0, false);
+
+        let selection = Rect::new(Point::new(40.0, 10.0), Size::new(110.0, 90.0));
+        let mut selected: Vec<u64> = app_window
+            .widgets_intersecting_rect(selection, &layer)
+            .unwrap()
+            .iter()
+            .map(|widget| widget.unique_id())
+            .collect();
+        selected.sort_unstable();
+        assert_eq!(selected, vec![a, b]);
+        for id in [c, d, hidden] {
+            assert!(!selected.contains(&id));
+        }
+
+        assert!(app_window
+            .widgets_intersecting_rect(
+                Rect::new(Point::new(300.0, 200.0), Size::new(10.0, 10.0)),
+                &layer
+            )
+            .unwrap()
+            .is_empty());
+    }
+
+    /// A widget that captures the pointer when it is pressed, but lets the
+    /// widgets below it in the capture stack watch the drag.
+    struct PressableChild {
+        events: Rc<RefCell<Vec<InputEvent>>>,
+    }
+
+    impl WidgetNode<()> for PressableChild {
+        fn on_added(
+            &mut self,
+            _action_tx: &mut Sender<()>,
+        ) -> (WidgetNodeType, WidgetNodeRequests) {
+            (
+                WidgetNodeType::PointerOnly,
+                WidgetNodeRequests {
+                    set_pointer_events_listen: Some(true),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn on_input_event(
+            &mut self,
+            event: &InputEvent,
+            _text_context: &TextContext,
+            _action_tx: &mut Sender<()>,
+        ) -> EventCapturedStatus {
+            self.events.borrow_mut().push(event.clone());
+
+            match event {
+                InputEvent::Pointer(e) if e.left_button.just_pressed() => {
+                    EventCapturedStatus::Captured(WidgetNodeRequests {
+                        set_pointer_capture: Some(true),
+                        ..Default::default()
+                    })
+                }
+                InputEvent::Pointer(e) if e.left_button.just_unpressed() => {
+                    EventCapturedStatus::Captured(WidgetNodeRequests {
+                        set_pointer_capture: Some(false),
+                        ..Default::default()
+                    })
+                }
+                _ => EventCapturedStatus::NotCaptured,
+            }
+        }
+    }
+
+    /// A scroll container that watches every pointer event, and takes over a
+    /// drag once it has moved past a threshold.
+    struct DragScrollContainer {
+        press_y: Option<f64>,
+        scrolling: bool,
+        events: Rc<RefCell<Vec<InputEvent>>>,
+    }
+
+    impl DragScrollContainer {
+        const DRAG_THRESHOLD: f64 = 8.0;
+    }
+
+    impl WidgetNode<()> for DragScrollContainer {
+        fn on_added(
+            &mut self,
+            _action_tx: &mut Sender<()>,
+        ) -> (WidgetNodeType, WidgetNodeRequests) {
+            (
+                WidgetNodeType::PointerOnly,
+                WidgetNodeRequests {
+                    // Receive the press before it is hit tested, so that
+                    // this container captures the pointer below the child
+                    // that was pressed.
+                    set_pointer_leave_listen: Some(true),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn on_input_event(
+            &mut self,
+            event: &InputEvent,
+            _text_context: &TextContext,
+            _action_tx: &mut Sender<()>,
+        ) -> EventCapturedStatus {
+            let e = match event {
+                InputEvent::Pointer(e) => e,
+                _ => return EventCapturedStatus::NotCaptured,
+            };
+            self.events.borrow_mut().push(event.clone());
+
+            if e.left_button.just_pressed() {
+                self.press_y = Some(e.position.y);
+                return EventCapturedStatus::Captured(WidgetNodeRequests {
+                    set_pointer_capture: Some(true),
+                    ..Default::default()
+                });
+            }
+            if e.left_button.just_unpressed() {
+                self.press_y = None;
+                self.scrolling = false;
+                return EventCapturedStatus::Captured(WidgetNodeRequests {
+                    set_pointer_capture: Some(false),
+                    ..Default::default()
+                });
+            }
+
+            match self.press_y {
+                Some(_) if self.scrolling => {
+                    EventCapturedStatus::Captured(WidgetNodeRequests::default())
+                }
+                Some(press_y) if (e.position.y - press_y).abs() > Self::DRAG_THRESHOLD => {
+                    self.scrolling = true;
+                    EventCapturedStatus::Captured(WidgetNodeRequests {
+                        steal_pointer_capture: true,
+                        ..Default::default()
+                    })
+                }
+                _ => EventCapturedStatus::NotCaptured,
+            }
+        }
+    }
+
+    #[test]
+    fn test_scroll_container_steals_drag() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        let container_events = Rc::new(RefCell::new(Vec::new()));
+        let child_events = Rc::new(RefCell::new(Vec::new()));
+        let container = app_window
+            .add_widget_node_at_rect(
+                Box::new(DragScrollContainer {
+                    press_y: None,
+                    scrolling: false,
+                    events: Rc::clone(&container_events),
+                }),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 200.0)),
+                true,
+            )
+            .unwrap();
+        let child = app_window
+            .add_widget_node_at_rect(
+                Box::new(PressableChild {
+                    events: Rc::clone(&child_events),
+                }),
+                &layer,
+                Rect::new(Point::new(50.0, 50.0), Size::new(100.0, 100.0)),
+                true,
+            )
+            .unwrap();
+
+        let drag = |app_window: &mut AppWindow<()>, y: f64, left_button| {
+            app_window.handle_input_event(&InputEvent::Pointer(PointerEvent {
+                position: Point::new(100.0, y),
+                left_button,
+                ..PointerEvent::default()
+            }));
+        };
+
+        // Both widgets capture the pointer on press, with the child on top.
+        drag(&mut app_window, 100.0, PointerButtonState::JustPressed);
+        assert_eq!(
+            app_window
+                .pointer_capture_stack
+                .iter_top_down()
+                .map(|w| w.unique_id())
+                .collect::<Vec<_>>(),
+            vec![child.unique_id(), container.unique_id()]
+        );
+        assert_eq!(child_events.borrow().len(), 1);
+        assert_eq!(container_events.borrow().len(), 1);
+
+        // Small moves reach both widgets, since the child doesn't capture
+        // them.
+        for y in [102.0, 104.0, 106.0] {
+            drag(&mut app_window, y, PointerButtonState::StayedPressed);
+        }
+        assert_eq!(child_events.borrow().len(), 4);
+        assert_eq!(container_events.borrow().len(), 4);
+
+        // Once the drag passes the threshold, the container steals the
+        // capture and the child is told that it lost it.
+        drag(&mut app_window, 110.0, PointerButtonState::StayedPressed);
+        assert!(matches!(
+            child_events.borrow().last(),
+            Some(InputEvent::PointerCaptureLost)
+        ));
+        let child_events_len = child_events.borrow().len();
+
+        // Later moves, even outside of the child, only reach the container.
+        for y in [130.0, 170.0] {
+            drag(&mut app_window, y, PointerButtonState::StayedPressed);
+        }
+        drag(&mut app_window, 170.0, PointerButtonState::JustUnpressed);
+        assert_eq!(child_events.borrow().len(), child_events_len);
+        assert_eq!(container_events.borrow().len(), 8);
+        assert!(app_window.pointer_capture_stack.is_empty());
+    }
+
+    #[test]
+    fn test_remove_widget_during_capture() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 200.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let add_knob = |app_window: &mut AppWindow<()>| {
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(TestWidget::new()),
+                    &layer,
+                    Rect::new(Point::new(10.0, 10.0), Size::new(50.0, 50.0)),
+                    true,
+                )
+                .unwrap()
+        };
+        let mut knob = add_knob(&mut app_window);
+        let mut other_knob = add_knob(&mut app_window);
+
+        // The knob locks the pointer in place and captures it while it is
+        // being dragged.
+        let knob_entry = knob.shared.upgrade().unwrap();
+        app_window.handle_widget_requests(
+            &mut knob_entry.clone(),
+            WidgetNodeRequests {
+                set_pointer_lock: Some(SetPointerLockType::LockInPlaceAndHideCursor),
+                set_pointer_capture: Some(true),
+                ..Default::default()
+            },
+        );
+        let result = app_window.input_event_result();
+        assert_eq!(result.pointer_lock, SetPointerLockType::LockToWidget);
+        assert!(!result.pointer_lock_released);
+        assert!(app_window
+            .pointer_capture_stack
+            .contains(knob_entry.unique_id()));
+
+        app_window.remove_widget(&mut knob).unwrap();
+
+        // Nothing refers to the removed widget anymore (including the
+        // `PointerLocked` event that was queued for it).
+        assert!(app_window.widget_with_pointer_lock.is_none());
+        assert!(app_window.pointer_capture_stack.is_empty());
+        assert!(app_window.widgets_to_send_input_event.is_empty());
+
+        // The host is told to release the pointer on the next event, and
+        // only once.
+        let result = app_window.handle_input_event(&InputEvent::PointerLeftWindow);
+        assert_eq!(result.pointer_lock, SetPointerLockType::Unlock);
+        assert!(result.pointer_lock_released);
+        assert!(!app_window.input_event_result().pointer_lock_released);
+
+        // Removing a widget that doesn't hold the lock doesn't report a
+        // release.
+        app_window.remove_widget(&mut other_knob).unwrap();
+        assert!(!app_window.input_event_result().pointer_lock_released);
+    }
+
+    #[test]
+    fn test_always_full_repaint() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut layer_entry = StrongWidgetLayerEntry::new(WidgetLayer::<()>::new(
+            0,
+            0,
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        ));
+
+        let mut widgets = Vec::new();
+        for (unique_id, x) in [(0, 0.0), (1, 100.0), (2, 200.0)] {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer_entry
+                .borrow_mut()
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(Rect::new(
+                        Point::new(x, 10.0),
+                        Size::new(50.0, 20.0),
+                    )),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            widgets.push(widget_entry);
+        }
+
+        let mut layers_ordered = vec![(0, vec![StrongLayerEntry::Widget(layer_entry.clone())])];
+
+        for _ in 0..3 {
+            // Pretend that the layer was just rendered.
+            {
+                let mut layer = layer_entry.borrow_mut();
+                layer.region_tree.clear_whole_layer = false;
+                layer.region_tree.texture_rects_to_clear.clear();
+                layer.region_tree.dirty_widgets.clear();
+                assert!(!layer.is_dirty());
+            }
+
+            mark_layers_for_full_repaint(&mut layers_ordered);
+
+            // Every widget is painted on the next frame, even though none of
+            // them have changed.
+            let layer = layer_entry.borrow();
+            assert!(layer.region_tree.clear_whole_layer);
+            for widget_entry in widgets.iter() {
+                assert!(layer.region_tree.dirty_widgets.contains(widget_entry));
+            }
+        }
+    }
+
+    struct ImageWidget {
+        image_id: femtovg::ImageId,
+    }
+
+    impl WidgetNode<()> for ImageWidget {
+        fn on_added(
+            &mut self,
+            _action_tx: &mut Sender<()>,
+        ) -> (WidgetNodeType, WidgetNodeRequests) {
+            (WidgetNodeType::Painted, WidgetNodeRequests::default())
+        }
+
+        fn on_input_event(
+            &mut self,
+            _event: &InputEvent,
+            _text_context: &TextContext,
+            _action_tx: &mut Sender<()>,
+        ) -> EventCapturedStatus {
+            EventCapturedStatus::NotCaptured
+        }
+
+        fn uses_image(&self, image_id: femtovg::ImageId) -> bool {
+            image_id == self.image_id
+        }
+    }
+
+    #[test]
+    fn test_unload_image_in_use() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut vg = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let mut load_image = || {
+            vg.create_image_empty(
+                4,
+                4,
+                femtovg::PixelFormat::Rgba8,
+                femtovg::ImageFlags::empty(),
+            )
+            .unwrap()
+        };
+        let used_image = load_image();
+        let unused_image = load_image();
+
+        let mut layer_entry = StrongWidgetLayerEntry::new(WidgetLayer::<()>::new(
+            0,
+            0,
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        ));
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(ImageWidget {
+                image_id: used_image,
+            }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        layer_entry
+            .borrow_mut()
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 20.0),
+                    Size::new(50.0, 30.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let layers_ordered = vec![(0, vec![StrongLayerEntry::Widget(layer_entry.clone())])];
+
+        assert_eq!(
+            check_image_not_in_use(&layers_ordered, unused_image),
+            Ok(())
+        );
+        assert_eq!(
+            check_image_not_in_use(&layers_ordered, used_image),
+            Err(FirewheelError::ImageInUse)
+        );
+
+        // Once the widget is hidden, its image can be unloaded.
+        layer_entry.borrow_mut().set_widget_explicit_visibility(
+            &mut widget_entry,
+            false,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(check_image_not_in_use(&layers_ordered, used_image), Ok(()));
+    }
+
+    #[test]
+    fn test_release_layer_renderers() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+        let mut widgets_just_shown = WidgetNodeSet::new();
+        let mut widgets_just_hidden = WidgetNodeSet::new();
+
+        let mut layer_entry = StrongWidgetLayerEntry::new(WidgetLayer::<()>::new(
+            0,
+            0,
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        ));
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        let widget_rect = Rect::new(Point::new(10.0, 20.0), Size::new(50.0, 30.0));
+        {
+            let mut layer = layer_entry.borrow_mut();
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(widget_rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+
+            // Pretend that the layer was just rendered.
+            layer.region_tree.clear_whole_layer = false;
+            layer.region_tree.dirty_widgets.clear();
+            assert!(!layer.is_dirty());
+        }
+
+        let mut layers_ordered = vec![(0, vec![StrongLayerEntry::Widget(layer_entry.clone())])];
+        let mut widget_layer_renderers_to_clean_up = Vec::new();
+        let mut background_layer_renderers_to_clean_up = Vec::new();
+
+        release_layer_renderers(
+            &mut layers_ordered,
+            &mut widget_layer_renderers_to_clean_up,
+            &mut background_layer_renderers_to_clean_up,
+        );
+
+        // The old renderer is queued to free its textures, and the layer has
+        // a new one.
+        assert_eq!(widget_layer_renderers_to_clean_up.len(), 1);
+        assert!(background_layer_renderers_to_clean_up.is_empty());
+        assert!(layer_entry.borrow().renderer.is_some());
+
+        // The layout is kept, and the whole layer will be repainted.
+        let layer = layer_entry.borrow();
+        assert!(!layer.is_empty());
+        assert!(widget_entry
+            .assigned_region()
+            .upgrade()
+            .unwrap()
+            .borrow()
+            .region
+            .rect
+            .partial_eq_with_epsilon(widget_rect));
+        assert!(layer.region_tree.clear_whole_layer);
+        assert!(layer.region_tree.dirty_widgets.contains(&widget_entry));
+    }
+
+    #[test]
+    fn test_input_transparent_widget() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut layer: WidgetLayer<()> = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+
+        // A decorative widget that is hit-tested first, covering a button.
+        let mut add_widget = |rect: Rect, unique_id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            layer.set_widget_region_listens_to_pointer_events(&widget_entry, true);
+            widget_entry
+        };
+        let decoration = add_widget(Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)), 0);
+        let _button = add_widget(Rect::new(Point::new(20.0, 20.0), Size::new(40.0, 40.0)), 1);
+
+        let hit = |layer: &WidgetLayer<()>, point: Point| {
+            layer.widget_at_point(point).map(|w| w.unique_id())
+        };
+
+        // The decoration blocks the button while it receives input.
+        assert_eq!(hit(&layer, Point::new(30.0, 30.0)), Some(0));
+
+        // Once it is input-transparent, a click over it reaches the button.
+        layer.set_widget_input_transparent(&decoration, true);
+        assert_eq!(hit(&layer, Point::new(30.0, 30.0)), Some(1));
+        assert_eq!(hit(&layer, Point::new(80.0, 80.0)), None);
+
+        // The widget asking to listen to pointer events does not undo this.
+        layer.set_widget_region_listens_to_pointer_events(&decoration, true);
+        assert_eq!(hit(&layer, Point::new(30.0, 30.0)), Some(1));
+
+        layer.set_widget_input_transparent(&decoration, false);
+        assert_eq!(hit(&layer, Point::new(30.0, 30.0)), Some(0));
+    }
+
+    #[test]
+    fn test_circle_hit_shape() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut layer: WidgetLayer<()> = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+
+        // A round knob on top of a panel.
+        let mut add_widget = |rect: Rect, unique_id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            layer.set_widget_region_listens_to_pointer_events(&widget_entry, true);
+            widget_entry
+        };
+        let knob = add_widget(Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)), 0);
+        let _panel = add_widget(Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 200.0)), 1);
+
+        let hit = |layer: &WidgetLayer<()>, point: Point| {
+            layer.widget_at_point(point).map(|w| w.unique_id())
+        };
+
+        // With the default rect shape, the corner of the knob's region hits
+        // the knob.
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(0));
+
+        layer.set_widget_hit_shape(&knob, HitShape::Circle);
+
+        // A click in the corner misses the knob and reaches the panel.
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(1));
+        assert_eq!(hit(&layer, Point::new(95.0, 95.0)), Some(1));
+        // Clicks inside of the circle still hit the knob.
+        assert_eq!(hit(&layer, Point::new(50.0, 50.0)), Some(0));
+        assert_eq!(hit(&layer, Point::new(50.0, 2.0)), Some(0));
+
+        layer.set_widget_hit_shape(
+            &knob,
+            HitShape::RoundedRect {
+                corner_radius: 10.0,
+            },
+        );
+        assert_eq!(hit(&layer, Point::new(1.0, 1.0)), Some(1));
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(0));
+
+        // Only the left half of the knob.
+        layer.set_widget_hit_shape(
+            &knob,
+            HitShape::Custom(Box::new(|point, size| {
+                point.x < f64::from(size.width()) / 2.0
+            })),
+        );
+        assert_eq!(hit(&layer, Point::new(25.0, 50.0)), Some(0));
+        assert_eq!(hit(&layer, Point::new(75.0, 50.0)), Some(1));
+    }
+
+    #[cfg(feature = "alpha-hit-testing")]
+    #[test]
+    fn test_painted_alpha_hit_shape() {
+        use crate::node::AlphaMask;
+        use crate::PhysicalSize;
+
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut layer: WidgetLayer<()> = WidgetLayer::new(
+            0,
+            0,
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+        );
+
+        // A partially transparent sprite on top of a panel.
+        let mut add_widget = |rect: Rect, unique_id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(TestWidget::new()))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                unique_id,
+            );
+            layer
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::from_layer_rect(rect),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            layer.set_widget_region_listens_to_pointer_events(&widget_entry, true);
+            widget_entry
+        };
+        let sprite = add_widget(Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)), 0);
+        let _panel = add_widget(Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 200.0)), 1);
+
+        let hit = |layer: &WidgetLayer<()>, point: Point| {
+            layer.widget_at_point(point).map(|w| w.unique_id())
+        };
+
+        layer.set_widget_hit_shape(&sprite, HitShape::PaintedAlpha);
+
+        // Until the sprite has been painted, its whole rect is hit.
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(0));
+
+        // The top-left quadrant of the sprite is fully transparent.
+        #[rustfmt::skip]
+        let rgba = [
+            0, 0, 0, 0,         255, 255, 255, 255,
+            255, 255, 255, 255, 255, 255, 255, 255,
+        ];
+        sprite
+            .assigned_region()
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set_alpha_mask(AlphaMask::from_rgba(PhysicalSize::new(2, 2), &rgba));
+
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(1));
+        assert_eq!(hit(&layer, Point::new(75.0, 5.0)), Some(0));
+        assert_eq!(hit(&layer, Point::new(75.0, 75.0)), Some(0));
+
+        // Changing the hit shape discards the mask.
+        layer.set_widget_hit_shape(&sprite, HitShape::Rect);
+        assert_eq!(hit(&layer, Point::new(5.0, 5.0)), Some(0));
+    }
+
+    #[test]
+    fn test_pointer_event_timestamps() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let layer = app_window.add_widget_layer(
+            Size::new(100.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+        let widget = TestWidget::new().with_pointer_listen();
+        let events = widget.events();
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(widget),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 100.0)),
+                true,
+            )
+            .unwrap();
+
+        let received_timestamps = |events: &Rc<RefCell<Vec<InputEvent>>>| -> Vec<Instant> {
+            events
+                .borrow_mut()
+                .drain(..)
+                .filter_map(|event| match event {
+                    InputEvent::Pointer(e) if e.any_button_just_pressed() => e.timestamp,
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Two clicks with timestamps injected by the host, 300ms apart. The
+        // time that the events are received at does not matter.
+        let start = Instant::now();
+        for time_ms in [100, 400] {
+            for pressed in [true, false] {
+                let mut e = PointerEvent {
+                    timestamp: Some(start + Duration::from_millis(time_ms)),
+                    ..PointerEvent::default()
+                };
+                e.update_position(Point::new(50.0, 50.0));
+                e.update_button(PointerButton::Left, pressed);
+                app_window.handle_input_event(&InputEvent::Pointer(e));
+            }
+        }
+        let timestamps = received_timestamps(&events);
+        assert_eq!(timestamps.len(), 2);
+        assert_eq!(timestamps[1] - timestamps[0], Duration::from_millis(300));
+
+        // Events without a timestamp are stamped with the time they were
+        // received.
+        let before = Instant::now();
+        app_window.inject_pointer_down(Point::new(50.0, 50.0), PointerButton::Left);
+        let after = Instant::now();
+        let timestamps = received_timestamps(&events);
+        assert_eq!(timestamps.len(), 1);
+        assert!(before <= timestamps[0] && timestamps[0] <= after);
+    }
+
+    #[test]
+    fn test_pointer_snap() {
+        let scale_factor = ScaleFactor2D::new(2.0, 2.0);
+        let mut e = PointerEvent::default();
+        e.update_position(Point::new(10.4, 20.6));
+
+        let mut snapped = e;
+        snap_pointer_event(&mut snapped, PointerSnap::LogicalPixel, scale_factor);
+        assert_eq!(snapped.position, Point::new(10.0, 21.0));
+        // Widgets that need the full precision can still get it.
+        assert_eq!(snapped.raw_position, Point::new(10.4, 20.6));
+
+        let mut snapped = e;
+        snap_pointer_event(&mut snapped, PointerSnap::PhysicalPixel, scale_factor);
+        assert_eq!(snapped.position, Point::new(10.5, 20.5));
+
+        let mut snapped = e;
+        snap_pointer_event(
+            &mut snapped,
+            PointerSnap::Grid(Point::new(8.0, 8.0)),
+            scale_factor,
+        );
+        assert_eq!(snapped.position, Point::new(8.0, 24.0));
+
+        let mut snapped = e;
+        snap_pointer_event(&mut snapped, PointerSnap::None, scale_factor);
+        assert_eq!(snapped.position, Point::new(10.4, 20.6));
+        assert_eq!(snapped.raw_position, snapped.position);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_write_png() {
+        // A 3x2 image with a red top-left corner on a transparent background.
+        let size = PhysicalSize::new(3, 2);
+        let mut pixels = vec![0u8; 3 * 2 * 4];
+        pixels[0..4].copy_from_slice(&[255, 0, 0, 255]);
+
+        // Include the process ID so that concurrent test runs don't write
+        // to the same file.
+        let path = std::env::temp_dir().join(format!(
+            "firewheel_test_write_png_{}.png",
+            std::process::id()
+        ));
+        write_png(&path, &pixels, size).unwrap();
+
+        let image = image::open(&path).unwrap().into_rgba8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(2, 1).0, [0, 0, 0, 0]);
+    }
+}
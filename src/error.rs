@@ -1,7 +1,7 @@
 use std::error::Error;
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FirewheelError {
     LayerRemoved,
     LayerNotEmpty,
@@ -11,6 +11,15 @@ pub enum FirewheelError {
     ContainerRegionNotEmpty,
     BackgroundNodeRemoved,
     WidgetNodeRemoved,
+    FontLoad(String),
+    ImageLoad(String),
+    ImageSave,
+    ImageInUse,
+    Text,
+    DuplicateListKey,
+    InvalidScale,
+    GpuSuspended,
+    BlendModeNotSupported,
 }
 
 impl Error for FirewheelError {}
@@ -48,6 +57,45 @@ impl fmt::Display for FirewheelError {
             Self::WidgetNodeRemoved => {
                 write!(f, "Widget node is invalid because it has been removed")
             }
+            Self::FontLoad(msg) => {
+                write!(f, "Failed to load font: {}", msg)
+            }
+            Self::ImageLoad(msg) => {
+                write!(f, "Failed to load image: {}", msg)
+            }
+            Self::ImageSave => {
+                write!(f, "Failed to save image")
+            }
+            Self::ImageInUse => {
+                write!(
+                    f,
+                    "Could not unload image: image is in use by a visible widget or background node"
+                )
+            }
+            Self::Text => {
+                write!(f, "Failed to draw text")
+            }
+            Self::DuplicateListKey => {
+                write!(
+                    f,
+                    "Could not update list: the same key appears more than once"
+                )
+            }
+            Self::InvalidScale => {
+                write!(f, "Scale must be a finite number greater than zero")
+            }
+            Self::GpuSuspended => {
+                write!(
+                    f,
+                    "The GPU resources of the window are suspended (or it is headless)"
+                )
+            }
+            Self::BlendModeNotSupported => {
+                write!(
+                    f,
+                    "Layers that use direct compositing only support the normal blend mode"
+                )
+            }
         }
     }
 }
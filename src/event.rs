@@ -1,6 +1,6 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::{Point, ScaleFactor};
+use crate::{Point, ScaleFactor, ScaleFactor2D};
 
 pub use keyboard_types::{
     Code, CompositionEvent, CompositionState, Key, KeyState, KeyboardEvent, Location, Modifiers,
@@ -26,20 +26,63 @@ pub fn from_winit_modifiers(modifiers: &winit::event::ModifiersState) -> Modifie
     m
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum InputEvent {
     Animation(AnimationEvent),
     Pointer(PointerEvent),
     PointerLocked,
     PointerUnlocked,
+    /// Another widget has stolen the pointer capture from this widget (see
+    /// `WidgetNodeRequests::steal_pointer_capture`). The widget should cancel
+    /// the gesture it was handling.
+    PointerCaptureLost,
+    /// A key was pressed or released.
+    ///
+    /// Unlike pointer events, keyboard events don't carry a timestamp, since
+    /// `KeyboardEvent` is the type from the `keyboard_types` crate. Widgets
+    /// that time key gestures (i.e. key repeat) need to measure the time
+    /// themselves.
     Keyboard(KeyboardEvent),
     TextComposition(CompositionEvent),
     TextCompositionFocused,
     TextCompositionUnfocused,
+    /// This widget has received the keyboard focus (see
+    /// `AppWindow::focus_in_direction()`).
+    KeyboardFocused,
+    KeyboardUnfocused,
     VisibilityShown,
+    /// The pointer has left the window (i.e. winit's `WindowEvent::CursorLeft`).
+    ///
+    /// The host should forward this to `AppWindow::handle_input_event()`.
+    /// Every widget that is listening to pointer leave events then receives
+    /// a pointer event that is outside of its bounds, so it can clear its
+    /// hover state. This event itself is never sent to widgets.
+    PointerLeftWindow,
+    /// The pointer has moved onto (`hovered: true`) or off of (`hovered: false`)
+    /// a group of widgets that this widget is a member of.
+    GroupHoverChanged {
+        group: WidgetGroupId,
+        hovered: bool,
+    },
 }
 
+/// The ID of a group of related widgets (i.e. the buttons in a toolbar).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct WidgetGroupId(pub u64);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum PointerButtonState {
     StayedUnpressed,
     StayedPressed,
@@ -71,19 +114,121 @@ impl Default for PointerButtonState {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerButton {
+    Left,
+    Middle,
+    Right,
+}
+
+/// How the positions of pointer events are rounded before they are sent to
+/// widgets (see `AppWindow::set_pointer_snap()`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointerSnap {
+    /// Don't round pointer positions.
+    None,
+    /// Round pointer positions to the nearest logical pixel (point).
+    LogicalPixel,
+    /// Round pointer positions to the nearest physical pixel.
+    PhysicalPixel,
+    /// Round pointer positions to the nearest point on a grid with the given
+    /// spacing in logical coordinates (points). The grid starts at the
+    /// origin of the window.
+    Grid(Point),
+}
+
+impl Default for PointerSnap {
+    fn default() -> Self {
+        PointerSnap::None
+    }
+}
+
+impl PointerSnap {
+    /// Snap the position in logical coordinates, where `scale_factor` is the
+    /// scale factor of the window.
+    pub fn snap(&self, position: Point, scale_factor: ScaleFactor2D) -> Point {
+        let round_to = |value: f64, step: f64| {
+            if step > 0.0 {
+                (value / step).round() * step
+            } else {
+                value
+            }
+        };
+
+        match self {
+            PointerSnap::None => position,
+            PointerSnap::LogicalPixel => Point::new(position.x.round(), position.y.round()),
+            PointerSnap::PhysicalPixel => Point::new(
+                round_to(position.x, 1.0 / f64::from(scale_factor.x)),
+                round_to(position.y, 1.0 / f64::from(scale_factor.y)),
+            ),
+            PointerSnap::Grid(spacing) => Point::new(
+                round_to(position.x, spacing.x),
+                round_to(position.y, spacing.y),
+            ),
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct PointerEvent {
+    /// The position of the pointer in logical coordinates, snapped with the
+    /// window's `PointerSnap` (see `AppWindow::set_pointer_snap()`).
     pub position: Point,
+    /// The position of the pointer before it was snapped, for widgets that
+    /// need the full precision of the pointer (i.e. knobs and sliders).
+    ///
+    /// This is filled in by `AppWindow`, and it is the same as `position`
+    /// when snapping is disabled.
+    #[cfg_attr(feature = "input-recording", serde(skip))]
+    pub raw_position: Point,
     pub delta: Point,
+    /// The velocity of the pointer in logical points per second.
+    ///
+    /// This is computed by `AppWindow` from the pointer positions in the last
+    /// 100 milliseconds, and it is reset to zero when a button is pressed.
+    pub velocity: Point,
     pub left_button: PointerButtonState,
     pub middle_button: PointerButtonState,
     pub right_button: PointerButtonState,
     pub scroll_delta_x: f32,
     pub scroll_delta_y: f32,
+    /// The scroll delta exactly as the host received it, along with the
+    /// phase of the gesture (i.e. for trackpad momentum scrolling).
+    ///
+    /// This is only sent to widgets that have opted in with
+    /// `WidgetNodeRequests::set_raw_scroll`, and it is `None` for every other
+    /// widget and for events that aren't scroll events.
+    pub raw_scroll: Option<RawScroll>,
     pub modifiers: Modifiers,
+    /// The time at which this event occurred, for widgets that time their
+    /// own gestures (i.e. double-clicks and long-presses).
+    ///
+    /// If this is `None`, then `AppWindow` fills it in with the time the
+    /// event was received. A host which knows the actual time of the event
+    /// (or a test which wants to inject a time) can set it before passing
+    /// the event to `AppWindow::handle_input_event()`.
+    ///
+    /// This is not serialized. An `InputLog` stores the time of each event
+    /// separately.
+    ///
+    /// Only pointer events carry a timestamp (see `InputEvent::Keyboard`).
+    #[cfg_attr(feature = "input-recording", serde(skip))]
+    pub timestamp: Option<Instant>,
 }
 
 impl PointerEvent {
+    /// This event without the raw scroll data, for widgets that haven't
+    /// opted in to receiving it.
+    pub fn without_raw_scroll(mut self) -> Self {
+        self.raw_scroll = None;
+        self
+    }
+
     pub fn any_button_just_pressed(&self) -> bool {
         self.left_button == PointerButtonState::JustPressed
             || self.right_button == PointerButtonState::JustPressed
@@ -98,6 +243,7 @@ impl PointerEvent {
     ) {
         self.scroll_delta_x = 0.0;
         self.scroll_delta_y = 0.0;
+        self.raw_scroll = None;
 
         let new_pos = Point::new(
             position.x / scale_factor.as_f64(),
@@ -108,16 +254,22 @@ impl PointerEvent {
         self.position = new_pos;
     }
 
-    #[cfg(feature = "winit")]
-    pub fn update_from_winit_mouse_input(
-        &mut self,
-        state: &winit::event::ElementState,
-        button: &winit::event::MouseButton,
-    ) {
+    /// Move the pointer to the given position in logical coordinates.
+    pub fn update_position(&mut self, position: Point) {
         self.scroll_delta_x = 0.0;
         self.scroll_delta_y = 0.0;
+        self.raw_scroll = None;
 
-        let is_down = *state == winit::event::ElementState::Pressed;
+        self.delta = position - self.position;
+        self.position = position;
+    }
+
+    /// Update the state of the given button after it was pressed
+    /// (`is_down: true`) or released (`is_down: false`).
+    pub fn update_button(&mut self, button: PointerButton, is_down: bool) {
+        self.scroll_delta_x = 0.0;
+        self.scroll_delta_y = 0.0;
+        self.raw_scroll = None;
 
         let handle_button = |button_state: &mut PointerButtonState| match button_state {
             PointerButtonState::StayedUnpressed => {
@@ -147,9 +299,24 @@ impl PointerEvent {
         };
 
         match button {
-            winit::event::MouseButton::Left => handle_button(&mut self.left_button),
-            winit::event::MouseButton::Middle => handle_button(&mut self.middle_button),
-            winit::event::MouseButton::Right => handle_button(&mut self.right_button),
+            PointerButton::Left => handle_button(&mut self.left_button),
+            PointerButton::Middle => handle_button(&mut self.middle_button),
+            PointerButton::Right => handle_button(&mut self.right_button),
+        }
+    }
+
+    #[cfg(feature = "winit")]
+    pub fn update_from_winit_mouse_input(
+        &mut self,
+        state: &winit::event::ElementState,
+        button: &winit::event::MouseButton,
+    ) {
+        let is_down = *state == winit::event::ElementState::Pressed;
+
+        match button {
+            winit::event::MouseButton::Left => self.update_button(PointerButton::Left, is_down),
+            winit::event::MouseButton::Middle => self.update_button(PointerButton::Middle, is_down),
+            winit::event::MouseButton::Right => self.update_button(PointerButton::Right, is_down),
             _ => (),
         }
     }
@@ -158,13 +325,14 @@ impl PointerEvent {
     pub fn update_from_winit_mouse_wheel(
         &mut self,
         delta: &winit::event::MouseScrollDelta,
-        _phase: &winit::event::TouchPhase,
+        phase: &winit::event::TouchPhase,
         scale_factor: ScaleFactor,
     ) {
         const PIXELS_PER_LINE: f32 = 12.0;
 
         self.scroll_delta_x = 0.0;
         self.scroll_delta_y = 0.0;
+        self.raw_scroll = None;
 
         match delta {
             winit::event::MouseScrollDelta::LineDelta(x, y) => {
@@ -176,9 +344,68 @@ impl PointerEvent {
                 self.scroll_delta_y = delta.y as f32 / scale_factor.as_f32();
             }
         }
+
+        self.raw_scroll = Some(RawScroll {
+            delta: match delta {
+                winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                    RawScrollDelta::Lines { x: *x, y: *y }
+                }
+                winit::event::MouseScrollDelta::PixelDelta(delta) => RawScrollDelta::Pixels {
+                    x: delta.x,
+                    y: delta.y,
+                },
+            },
+            phase: match phase {
+                winit::event::TouchPhase::Started => ScrollPhase::Started,
+                winit::event::TouchPhase::Moved => ScrollPhase::Moved,
+                winit::event::TouchPhase::Ended => ScrollPhase::Ended,
+                winit::event::TouchPhase::Cancelled => ScrollPhase::Cancelled,
+            },
+        });
     }
 }
 
+/// An unprocessed scroll event (see `PointerEvent::raw_scroll`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct RawScroll {
+    pub delta: RawScrollDelta,
+    pub phase: ScrollPhase,
+}
+
+/// A scroll delta in the units that the host received it in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum RawScrollDelta {
+    /// Lines to scroll by, i.e. from the notches of a mouse wheel.
+    Lines { x: f32, y: f32 },
+    /// Physical pixels to scroll by, i.e. from a trackpad.
+    Pixels { x: f64, y: f64 },
+}
+
+/// The phase of a scroll gesture (the same as winit's `TouchPhase`).
+///
+/// A mouse wheel only sends `Moved`. A trackpad sends `Started` when the
+/// fingers touch down, and `Ended` once the gesture (including any momentum
+/// scrolling after the fingers are lifted) is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum ScrollPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyboardEventsListen {
     None,
@@ -194,6 +421,74 @@ impl Default for KeyboardEventsListen {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct AnimationEvent {
     pub time_delta: Duration,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_button_updates() {
+        let mut pointer = PointerEvent::default();
+
+        pointer.update_position(Point::new(10.0, 20.0));
+        pointer.update_button(PointerButton::Left, true);
+        assert_eq!(pointer.left_button, PointerButtonState::JustPressed);
+        assert_eq!(pointer.right_button, PointerButtonState::StayedUnpressed);
+
+        pointer.update_button(PointerButton::Left, true);
+        assert_eq!(pointer.left_button, PointerButtonState::StayedPressed);
+
+        pointer.update_position(Point::new(15.0, 20.0));
+        assert!(pointer.delta.partial_eq_with_epsilon(Point::new(5.0, 0.0)));
+        pointer.update_button(PointerButton::Left, false);
+        assert_eq!(pointer.left_button, PointerButtonState::JustUnpressed);
+
+        pointer.update_button(PointerButton::Left, false);
+        assert_eq!(pointer.left_button, PointerButtonState::StayedUnpressed);
+    }
+
+    #[cfg(feature = "winit")]
+    #[test]
+    fn test_winit_raw_scroll() {
+        let mut pointer = PointerEvent::default();
+
+        pointer.update_from_winit_mouse_wheel(
+            &winit::event::MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(
+                0.0, 30.0,
+            )),
+            &winit::event::TouchPhase::Moved,
+            ScaleFactor(2.0),
+        );
+        // The normalized delta is in logical points, while the raw delta is
+        // left as is.
+        assert_eq!(pointer.scroll_delta_y, 15.0);
+        assert_eq!(
+            pointer.raw_scroll,
+            Some(RawScroll {
+                delta: RawScrollDelta::Pixels { x: 0.0, y: 30.0 },
+                phase: ScrollPhase::Moved,
+            })
+        );
+
+        pointer.update_from_winit_mouse_wheel(
+            &winit::event::MouseScrollDelta::LineDelta(0.0, 0.0),
+            &winit::event::TouchPhase::Ended,
+            ScaleFactor(2.0),
+        );
+        assert_eq!(
+            pointer.raw_scroll.map(|raw| raw.phase),
+            Some(ScrollPhase::Ended)
+        );
+
+        // Moving the pointer is not a scroll event.
+        pointer.update_position(Point::new(10.0, 10.0));
+        assert_eq!(pointer.raw_scroll, None);
+    }
+}
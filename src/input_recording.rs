@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::event::InputEvent;
+
+/// An input event in an `InputLog`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedInputEvent {
+    /// The time of the event relative to the start of the recording.
+    pub time: Duration,
+    pub event: InputEvent,
+}
+
+/// A log of the input events sent to an `AppWindow` (see
+/// `AppWindow::start_input_recording()`).
+///
+/// This can be serialized with any `serde` format, i.e. to attach it to a
+/// bug report.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputLog {
+    pub events: Vec<RecordedInputEvent>,
+}
+
+pub(crate) struct InputRecorder {
+    start: Instant,
+    log: InputLog,
+}
+
+impl InputRecorder {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            log: InputLog::default(),
+        }
+    }
+
+    pub fn record(&mut self, event: &InputEvent, now: Instant) {
+        let time = match event {
+            InputEvent::Pointer(e) => e.timestamp.unwrap_or(now),
+            _ => now,
+        };
+
+        self.log.events.push(RecordedInputEvent {
+            time: time.saturating_duration_since(self.start),
+            event: event.clone(),
+        });
+    }
+
+    pub fn finish(self) -> InputLog {
+        self.log
+    }
+}
+
+/// The events of the log with the timestamps of the pointer events set to
+/// the recorded times (relative to `start`), so that widgets which time
+/// their gestures see the same cadence as when the log was recorded.
+pub(crate) fn replayed_events(log: &InputLog, start: Instant) -> Vec<InputEvent> {
+    log.events
+        .iter()
+        .map(|recorded| match &recorded.event {
+            InputEvent::Pointer(e) => {
+                let mut e = *e;
+                e.timestamp = Some(start + recorded.time);
+                InputEvent::Pointer(e)
+            }
+            event => event.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PointerButton, PointerEvent};
+    use crate::Point;
+
+    #[test]
+    fn test_replayed_event_timestamps() {
+        let start = Instant::now();
+        let mut recorder = InputRecorder::new(start);
+
+        let mut pointer = PointerEvent::default();
+        for (button_down, millis) in [(true, 10), (false, 110)] {
+            pointer.update_position(Point::new(20.0, 20.0));
+            pointer.update_button(PointerButton::Left, button_down);
+            pointer.timestamp = Some(start + Duration::from_millis(millis));
+
+            recorder.record(
+                &InputEvent::Pointer(pointer),
+                start + Duration::from_millis(millis + 1),
+            );
+        }
+
+        let log = recorder.finish();
+        assert_eq!(log.events.len(), 2);
+        assert_eq!(log.events[1].time, Duration::from_millis(110));
+
+        // The replayed events keep the recorded spacing.
+        let replay_start = start + Duration::from_secs(60);
+        let replayed = replayed_events(&log, replay_start);
+        if let InputEvent::Pointer(e) = &replayed[1] {
+            assert_eq!(e.timestamp, Some(replay_start + Duration::from_millis(110)));
+        } else {
+            panic!("expected a pointer event");
+        }
+    }
+}
@@ -1,22 +1,35 @@
+use super::DEFAULT_BACKGROUND_PAINT_ORDER;
 use crate::node::StrongBackgroundNodeEntry;
 use crate::renderer::BackgroundLayerRenderer;
-use crate::size::{PhysicalPoint, PhysicalSize, Point, ScaleFactor, Size};
+use crate::size::{PhysicalPoint, PhysicalSize, Point, ScaleFactor2D, Size};
+use std::time::{Duration, Instant};
 
 pub(crate) struct BackgroundLayer {
     pub id: u64,
     pub z_order: i32,
+    /// The order of this layer among the layers that share its `z_order`.
+    pub paint_order: i32,
     pub renderer: Option<BackgroundLayerRenderer>,
     pub is_dirty: bool,
     pub physical_outer_position: PhysicalPoint,
     pub size: Size,
     pub physical_size: PhysicalSize,
+    /// Whether the background node promises to cover the whole layer with
+    /// opaque pixels. The layer's texture is then allocated without an
+    /// alpha channel and blitted without alpha blending.
+    pub opaque: bool,
+    /// Whether this layer is resized to the size of the window by
+    /// `AppWindow::commit_resize()`.
+    pub fills_window: bool,
 
     pub assigned_node: StrongBackgroundNodeEntry,
 
     outer_position: Point,
     explicit_visibility: bool,
     window_visibility: bool,
-    scale_factor: ScaleFactor,
+    scale_factor: ScaleFactor2D,
+    min_repaint_interval: Option<Duration>,
+    last_repaint: Option<Instant>,
 }
 
 impl BackgroundLayer {
@@ -27,28 +40,33 @@ impl BackgroundLayer {
         outer_position: Point,
         explicit_visibility: bool,
         window_visibility: bool,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         assigned_node: StrongBackgroundNodeEntry,
     ) -> Self {
         Self {
             id,
             z_order,
+            paint_order: DEFAULT_BACKGROUND_PAINT_ORDER,
             renderer: Some(BackgroundLayerRenderer::new()),
             size,
-            physical_size: size.to_physical(scale_factor),
+            physical_size: size.to_physical_2d(scale_factor),
             outer_position,
-            physical_outer_position: outer_position.to_physical(scale_factor),
+            physical_outer_position: outer_position.to_physical_2d(scale_factor),
             explicit_visibility,
             window_visibility,
             scale_factor,
             is_dirty: true,
+            opaque: false,
+            fills_window: false,
             assigned_node,
+            min_repaint_interval: None,
+            last_repaint: None,
         }
     }
 
-    pub fn set_outer_position(&mut self, position: Point, scale_factor: ScaleFactor) {
+    pub fn set_outer_position(&mut self, position: Point, scale_factor: ScaleFactor2D) {
         self.outer_position = position;
-        self.physical_outer_position = position.to_physical(scale_factor);
+        self.physical_outer_position = position.to_physical_2d(scale_factor);
     }
 
     pub fn set_explicit_visibility(&mut self, explicit_visibility: bool) {
@@ -65,23 +83,150 @@ impl BackgroundLayer {
         }
     }
 
-    pub fn set_size(&mut self, size: Size, scale_factor: ScaleFactor) {
+    pub fn set_size(&mut self, size: Size, scale_factor: ScaleFactor2D) {
         if self.size != size || self.scale_factor != scale_factor {
             self.size = size;
             self.scale_factor = scale_factor;
 
-            self.physical_size = self.size.to_physical(scale_factor);
-            self.physical_outer_position = self.outer_position.to_physical(scale_factor);
+            self.physical_size = self.size.to_physical_2d(scale_factor);
+            self.physical_outer_position = self.outer_position.to_physical_2d(scale_factor);
 
             self.is_dirty = self.is_visible();
         }
     }
 
+    pub fn set_opaque(&mut self, opaque: bool) {
+        if self.opaque != opaque {
+            self.opaque = opaque;
+            // The texture is reallocated, so it needs to be repainted.
+            self.is_dirty = self.is_visible();
+        }
+    }
+
     pub fn mark_dirty(&mut self) {
         self.is_dirty = self.is_visible();
     }
 
+    /// Limit how often this layer is repainted. `None` means the layer is
+    /// repainted every time it is rendered while dirty.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.min_repaint_interval = max_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_nanos((1e9 / f64::from(fps)).round() as u64));
+    }
+
+    /// Whether this layer should be repainted at the given time. A dirty
+    /// layer whose repaint is throttled stays dirty until it is due.
+    pub fn should_repaint(&self, now: Instant) -> bool {
+        if !self.is_dirty {
+            return false;
+        }
+
+        match (self.min_repaint_interval, self.last_repaint) {
+            (Some(min_interval), Some(last_repaint)) => {
+                now.saturating_duration_since(last_repaint) >= min_interval
+            }
+            _ => true,
+        }
+    }
+
+    pub fn repainted(&mut self, now: Instant) {
+        self.is_dirty = false;
+        self.last_repaint = Some(now);
+    }
+
     pub fn is_visible(&self) -> bool {
         self.explicit_visibility && self.window_visibility
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BackgroundNode;
+
+    struct EmptyBackgroundNode {}
+
+    impl BackgroundNode for EmptyBackgroundNode {}
+
+    fn new_test_layer() -> BackgroundLayer {
+        BackgroundLayer::new(
+            0,
+            0,
+            Size::new(100.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(1.0, 1.0),
+            StrongBackgroundNodeEntry::new(Box::new(EmptyBackgroundNode {}), 0),
+        )
+    }
+
+    /// Mark the layer dirty every millisecond for the given duration and
+    /// return the number of times it was repainted.
+    fn count_repaints(layer: &mut BackgroundLayer, start: Instant, millis: u64) -> usize {
+        let mut repaints = 0;
+        for i in 0..=millis {
+            let now = start + Duration::from_millis(i);
+
+            layer.mark_dirty();
+            if layer.should_repaint(now) {
+                layer.repainted(now);
+                repaints += 1;
+            }
+        }
+        repaints
+    }
+
+    #[test]
+    fn test_background_layer_max_fps() {
+        let start = Instant::now();
+
+        // Without a limit, the layer is repainted every time it is dirty.
+        let mut layer = new_test_layer();
+        assert_eq!(count_repaints(&mut layer, start, 100), 101);
+
+        // At 20 fps the layer is repainted at most once every 50ms.
+        let mut layer = new_test_layer();
+        layer.set_max_fps(Some(20.0));
+        assert_eq!(count_repaints(&mut layer, start, 120), 3);
+
+        // A throttled layer stays dirty until the repaint is due.
+        assert!(layer.is_dirty);
+        assert!(!layer.should_repaint(start + Duration::from_millis(149)));
+        assert!(layer.should_repaint(start + Duration::from_millis(150)));
+
+        // Removing the limit repaints right away.
+        layer.set_max_fps(None);
+        assert!(layer.should_repaint(start + Duration::from_millis(121)));
+    }
+
+    #[test]
+    fn test_background_layer_repainted_when_shown() {
+        let mut layer = new_test_layer();
+        layer.repainted(Instant::now());
+
+        // A hidden layer may have its texture freed, so it is repainted in
+        // full once it is shown again.
+        layer.set_explicit_visibility(false);
+        assert!(!layer.is_dirty);
+        layer.set_explicit_visibility(true);
+        assert!(layer.is_dirty);
+    }
+
+    #[test]
+    fn test_background_layer_opaque() {
+        let mut layer = new_test_layer();
+        assert!(!layer.opaque);
+        layer.repainted(Instant::now());
+
+        // Switching the texture format repaints the layer.
+        layer.set_opaque(true);
+        assert!(layer.opaque);
+        assert!(layer.is_dirty);
+
+        layer.repainted(Instant::now());
+        layer.set_opaque(true);
+        assert!(!layer.is_dirty);
+    }
+}
@@ -1,4 +1,5 @@
 use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
 use std::rc::{Rc, Weak};
 
 pub mod background_layer;
@@ -7,7 +8,10 @@ pub mod widget_layer;
 pub(crate) use background_layer::BackgroundLayer;
 pub(crate) use widget_layer::{WeakRegionTreeEntry, WidgetLayer};
 
-pub use widget_layer::{ContainerRegionRef, ParentAnchorType, RegionInfo};
+pub use widget_layer::{
+    BlendMode, Compositing, ContainerRegionRef, CoordinateOrigin, ParentAnchorType, RegionInfo,
+    ScrollBehavior,
+};
 
 pub(crate) struct StrongWidgetLayerEntry<A: Clone + Send + Sync + 'static> {
     shared: Rc<RefCell<WidgetLayer<A>>>,
@@ -133,7 +137,50 @@ pub struct WidgetLayerRef<A: Clone + Send + Sync + 'static> {
     pub(crate) shared: WeakWidgetLayerEntry<A>,
 }
 
+impl<A: Clone + Send + Sync + 'static> fmt::Debug for WidgetLayerRef<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("WidgetLayerRef");
+        if let Some(layer_entry) = self.shared.upgrade() {
+            let layer = layer_entry.borrow();
+            s.field("id", &layer.id);
+            #[cfg(feature = "debug-names")]
+            s.field("debug_name", &layer.debug_name);
+        } else {
+            s.field("removed", &true);
+        }
+        s.finish()
+    }
+}
+
+/// The default paint order of background layers, which is below the default
+/// paint order of widget layers so that a background is painted behind the
+/// widget layers with the same `z_order`.
+pub(crate) const DEFAULT_BACKGROUND_PAINT_ORDER: i32 = -1;
+/// The default paint order of widget layers.
+pub(crate) const DEFAULT_WIDGET_LAYER_PAINT_ORDER: i32 = 0;
+
 pub(crate) enum StrongLayerEntry<A: Clone + Send + Sync + 'static> {
     Widget(StrongWidgetLayerEntry<A>),
     Background(StrongBackgroundLayerEntry),
 }
+
+impl<A: Clone + Send + Sync + 'static> StrongLayerEntry<A> {
+    /// The order of this layer among the layers that share its `z_order`.
+    pub fn paint_order(&self) -> i32 {
+        match self {
+            StrongLayerEntry::Widget(layer_entry) => layer_entry.borrow().paint_order,
+            StrongLayerEntry::Background(layer_entry) => layer_entry.borrow().paint_order,
+        }
+    }
+
+    pub fn set_paint_order(&mut self, paint_order: i32) {
+        match self {
+            StrongLayerEntry::Widget(layer_entry) => {
+                layer_entry.borrow_mut().paint_order = paint_order;
+            }
+            StrongLayerEntry::Background(layer_entry) => {
+                layer_entry.borrow_mut().paint_order = paint_order;
+            }
+        }
+    }
+}
@@ -1,24 +1,166 @@
 use crossbeam_channel::Sender;
+use femtovg::{Color, TextContext};
 
-use crate::anchor::Anchor;
+use super::DEFAULT_WIDGET_LAYER_PAINT_ORDER;
+use crate::anchor::{Anchor, AnchorOffset};
 use crate::error::FirewheelError;
 use crate::event::PointerEvent;
-use crate::node::StrongWidgetNodeEntry;
+use crate::node::{DebugLabel, StrongWidgetNodeEntry, WeakWidgetNodeEntry};
 use crate::renderer::WidgetLayerRenderer;
-use crate::size::{PhysicalPoint, Point, Size};
+use crate::size::{PhysicalPoint, PhysicalRect, Point, Rect, Size, TextureRect};
+use crate::stack_layout::StackLayout;
 use crate::widget_node_set::WidgetNodeSet;
-use crate::{ScaleFactor, WidgetNodeRequests, WidgetNodeType};
+use crate::{HitShape, ScaleFactor2D, ScrollAlign, WidgetNodeRequests, WidgetNodeType};
 
 mod region_tree;
 
 use region_tree::RegionTree;
 pub(crate) use region_tree::WeakRegionTreeEntry;
-pub use region_tree::{ContainerRegionRef, ParentAnchorType, RegionInfo};
+pub use region_tree::{ContainerRegionRef, ParentAnchorType, RegionInfo, ScrollBehavior};
+
+/// How a widget layer is composited onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compositing {
+    /// The widgets are painted into a texture owned by the layer, and then
+    /// that texture is blitted to the screen. Only the dirty widgets are
+    /// repainted each frame.
+    Texture,
+    /// The widgets are painted straight onto the screen (clipped to the
+    /// bounds of the layer), skipping the layer's texture entirely.
+    ///
+    /// This avoids the overhead of the offscreen texture and the extra copy,
+    /// but every visible widget in the layer is repainted every frame. None of
+    /// the dirty region optimizations apply in this mode, so it is only
+    /// suitable for simple layers with few widgets.
+    ///
+    /// There is no texture to blend, so only `BlendMode::Normal` is
+    /// supported in this mode.
+    Direct,
+}
+
+impl Default for Compositing {
+    fn default() -> Self {
+        Compositing::Texture
+    }
+}
+
+/// How a widget layer's texture is blended with the pixels beneath it when
+/// it is composited onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Regular alpha blending.
+    Normal,
+    /// The layer's colors are added to the pixels beneath it (i.e. for
+    /// glow effects).
+    Add,
+    /// The pixels beneath the layer are multiplied by the layer's colors,
+    /// darkening them.
+    Multiply,
+    /// The inverse of the pixels beneath the layer is multiplied by the
+    /// inverse of the layer's colors, brightening them.
+    Screen,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Normal
+    }
+}
+
+/// Which corner of a widget layer the coordinates of its widgets start from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateOrigin {
+    /// The y axis points down.
+    TopLeft,
+    /// The y axis points up, which is often more natural for plots and
+    /// visualizations.
+    ///
+    /// Anchors are flipped vertically, so a region anchored to the top of
+    /// its parent is placed at the bottom of it, and a positive y offset
+    /// moves a region up. The rects given to the widgets (in
+    /// `WidgetNode::on_region_changed()` and `PaintRegionInfo::rect`) and
+    /// the positions of the pointer events sent to them are flipped the same
+    /// way within the layer, so a widget can compare the two directly.
+    ///
+    /// Painting is not flipped; `PaintRegionInfo::physical_rect` is always
+    /// where the widget is on the screen.
+    BottomLeft,
+}
+
+impl Default for CoordinateOrigin {
+    fn default() -> Self {
+        CoordinateOrigin::TopLeft
+    }
+}
+
+impl CoordinateOrigin {
+    /// Convert a y coordinate between the screen's top-left coordinates and
+    /// this coordinate system within the given layer rect. The conversion is
+    /// its own inverse.
+    fn convert_y(&self, y: f64, layer_rect: Rect) -> f64 {
+        match self {
+            CoordinateOrigin::TopLeft => y,
+            CoordinateOrigin::BottomLeft => layer_rect.y() + layer_rect.y2() - y,
+        }
+    }
+
+    pub(crate) fn convert_rect(&self, rect: Rect, layer_rect: Rect) -> Rect {
+        match self {
+            CoordinateOrigin::TopLeft => rect,
+            CoordinateOrigin::BottomLeft => Rect::new(
+                Point::new(rect.x(), self.convert_y(rect.y2(), layer_rect)),
+                rect.size(),
+            ),
+        }
+    }
+
+    pub(crate) fn convert_pointer_event(
+        &self,
+        mut event: PointerEvent,
+        layer_rect: Rect,
+    ) -> PointerEvent {
+        if *self == CoordinateOrigin::BottomLeft {
+            event.position.y = self.convert_y(event.position.y, layer_rect);
+            event.delta.y = -event.delta.y;
+            event.velocity.y = -event.velocity.y;
+        }
+        event
+    }
+}
 
 pub(crate) struct WidgetLayer<A: Clone + Send + Sync + 'static> {
     pub id: u64,
     pub z_order: i32,
+    /// The order of this layer among the layers that share its `z_order`.
+    pub paint_order: i32,
     pub renderer: Option<WidgetLayerRenderer>,
+    pub compositing: Compositing,
+    pub blend_mode: BlendMode,
+    pub clear_color: Color,
+    /// A version number for the content of this layer set by the user. While
+    /// it is unchanged (and no region has moved or been resized), the layer's
+    /// texture is reused without painting its dirty widgets.
+    pub content_version: Option<u64>,
+    /// Whether the dirty widgets were dropped without being painted because
+    /// the content version was unchanged, so the whole layer needs to be
+    /// repainted once it is painted again.
+    pub content_stale: bool,
+    /// The size to allocate the layer's texture at while the layer fits
+    /// inside of it, so that resizing the layer (i.e. in an animation)
+    /// doesn't reallocate the texture every frame.
+    pub reserved_texture_size: Option<Size>,
+    /// Whether the widgets in this layer receive pointer events. If this is
+    /// `false`, pointer events pass through to the layers below.
+    pub input_enabled: bool,
+    /// Whether this layer swallows every pointer event that none of its
+    /// widgets captured, anywhere in the window (i.e. the backdrop behind a
+    /// modal dialog).
+    pub is_backdrop: bool,
+    /// Whether this layer is resized to the size of the window by
+    /// `AppWindow::commit_resize()`.
+    pub fills_window: bool,
+    #[cfg(feature = "debug-names")]
+    pub debug_name: Option<String>,
 
     pub region_tree: RegionTree<A>,
     pub outer_position: Point,
@@ -34,12 +176,24 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         inner_position: Point,
         explicit_visibility: bool,
         window_visibility: bool,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
     ) -> Self {
         Self {
             id,
             z_order,
+            paint_order: DEFAULT_WIDGET_LAYER_PAINT_ORDER,
             renderer: Some(WidgetLayerRenderer::new()),
+            compositing: Compositing::default(),
+            blend_mode: BlendMode::default(),
+            clear_color: Color::rgba(0, 0, 0, 0),
+            content_version: None,
+            content_stale: false,
+            reserved_texture_size: None,
+            input_enabled: true,
+            is_backdrop: false,
+            fills_window: false,
+            #[cfg(feature = "debug-names")]
+            debug_name: None,
             region_tree: RegionTree::new(
                 size,
                 inner_position,
@@ -49,13 +203,106 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
                 id,
             ),
             outer_position,
-            physical_outer_position: outer_position.to_physical(scale_factor),
+            physical_outer_position: outer_position.to_physical_2d(scale_factor),
         }
     }
 
-    pub fn set_outer_position(&mut self, position: Point, scale_factor: ScaleFactor) {
+    #[cfg(feature = "debug-names")]
+    pub fn set_debug_name(&mut self, name: &str) {
+        self.debug_name = Some(name.into());
+    }
+
+    #[cfg(not(feature = "debug-names"))]
+    pub fn set_debug_name(&mut self, _name: &str) {}
+
+    pub fn debug_label(&self) -> DebugLabel {
+        DebugLabel {
+            id: self.id,
+            #[cfg(feature = "debug-names")]
+            name: self.debug_name.clone(),
+        }
+    }
+
+    pub fn set_outer_position(&mut self, position: Point, scale_factor: ScaleFactor2D) {
         self.outer_position = position;
-        self.physical_outer_position = position.to_physical(scale_factor);
+        self.physical_outer_position = position.to_physical_2d(scale_factor);
+    }
+
+    pub fn set_compositing(&mut self, compositing: Compositing) {
+        if self.compositing != compositing {
+            self.compositing = compositing;
+
+            // The layer's texture will need to be fully repainted.
+            self.region_tree.mark_all_dirty();
+        }
+    }
+
+    pub fn set_reserved_texture_size(&mut self, size: Option<Size>) {
+        if self.reserved_texture_size != size {
+            self.reserved_texture_size = size;
+
+            // The layer's texture may be reallocated, so it will need to be
+            // fully repainted.
+            self.region_tree.mark_all_dirty();
+        }
+    }
+
+    pub fn set_coordinate_origin(
+        &mut self,
+        coordinate_origin: CoordinateOrigin,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree.set_coordinate_origin(
+            coordinate_origin,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+    }
+
+    pub fn set_layer_bounds_epsilon(
+        &mut self,
+        epsilon: f64,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree
+            .set_layer_bounds_epsilon(epsilon, widgets_just_shown, widgets_just_hidden);
+    }
+
+    /// Set how much the content of this layer is zoomed in, independently of
+    /// the scale factor of the window.
+    pub fn set_content_zoom(
+        &mut self,
+        content_zoom: f32,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree
+            .set_content_zoom(content_zoom, widgets_just_shown, widgets_just_hidden);
+    }
+
+    pub fn content_zoom(&self) -> f32 {
+        self.region_tree.content_zoom()
+    }
+
+    /// Convert a pointer event into the coordinates of the widgets on this
+    /// layer.
+    pub fn widget_pointer_event(&self, event: PointerEvent) -> PointerEvent {
+        let event = unzoom_pointer_event(event, self.outer_position, self.content_zoom());
+
+        self.region_tree
+            .coordinate_origin()
+            .convert_pointer_event(event, self.region_tree.layer_rect())
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        if self.blend_mode != blend_mode {
+            self.blend_mode = blend_mode;
+
+            // Make sure the window gets redrawn with the new blend mode.
+            self.region_tree.mark_all_dirty();
+        }
     }
 
     pub fn set_inner_position(
@@ -97,7 +344,7 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
     pub fn set_size(
         &mut self,
         size: Size,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) {
@@ -124,6 +371,23 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         )
     }
 
+    pub fn add_stack_container(
+        &mut self,
+        region_info: RegionInfo<A>,
+        stack_layout: StackLayout,
+        explicit_visibility: bool,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<ContainerRegionRef<A>, FirewheelError> {
+        self.region_tree.add_stack_container(
+            region_info,
+            stack_layout,
+            explicit_visibility,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
     pub fn remove_container_region(
         &mut self,
         container_ref: ContainerRegionRef<A>,
@@ -131,13 +395,39 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         self.region_tree.remove_container_region(container_ref)
     }
 
+    /// Remove the given container region along with all of its descendant
+    /// container regions and widget regions.
+    ///
+    /// Returns the widgets whose regions were removed, in depth-first order.
+    pub fn remove_container_region_recursive(
+        &mut self,
+        container_ref: ContainerRegionRef<A>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<Vec<StrongWidgetNodeEntry<A>>, FirewheelError> {
+        let mut widgets = self.region_tree.container_region_widgets(&container_ref)?;
+
+        for widget_entry in widgets.iter_mut() {
+            self.region_tree.remove_widget_region(
+                widget_entry,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+        }
+
+        self.region_tree
+            .remove_container_region_recursive(container_ref)?;
+
+        Ok(widgets)
+    }
+
     pub fn modify_container_region(
         &mut self,
         container_ref: &mut ContainerRegionRef<A>,
         new_size: Option<Size>,
         new_internal_anchor: Option<Anchor>,
         new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        new_anchor_offset: Option<AnchorOffset>,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) -> Result<(), FirewheelError> {
@@ -167,6 +457,67 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         )
     }
 
+    pub fn set_container_region_scroll_offset(
+        &mut self,
+        container_ref: &mut ContainerRegionRef<A>,
+        scroll_offset: Option<Point>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        self.region_tree.set_container_region_scroll_offset(
+            container_ref,
+            scroll_offset,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
+    pub fn set_container_region_scroll_behavior(
+        &mut self,
+        container_ref: &mut ContainerRegionRef<A>,
+        behavior: Option<(ScrollBehavior, Point)>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        self.region_tree.set_container_region_scroll_behavior(
+            container_ref,
+            behavior,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
+    pub fn step_scroll_bounce(
+        &mut self,
+        time_delta: f32,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree
+            .step_scroll_bounce(time_delta, widgets_just_shown, widgets_just_hidden);
+    }
+
+    pub fn is_scroll_bouncing(&self) -> bool {
+        self.region_tree.is_scroll_bouncing()
+    }
+
+    pub fn set_container_region_bubble_handler(
+        &mut self,
+        container_ref: &ContainerRegionRef<A>,
+        handler: Option<WeakWidgetNodeEntry<A>>,
+    ) -> Result<(), FirewheelError> {
+        self.region_tree
+            .set_container_region_bubble_handler(container_ref, handler)
+    }
+
+    pub fn container_region_scroll_offset(
+        &self,
+        container_ref: &ContainerRegionRef<A>,
+    ) -> Result<Option<Point>, FirewheelError> {
+        self.region_tree
+            .container_region_scroll_offset(container_ref)
+    }
+
     pub fn mark_container_region_dirty(
         &mut self,
         container_ref: &mut ContainerRegionRef<A>,
@@ -193,6 +544,21 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         )
     }
 
+    pub fn add_overlay_widget_region(
+        &mut self,
+        assigned_widget: &mut StrongWidgetNodeEntry<A>,
+        node_type: WidgetNodeType,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        self.region_tree.add_overlay_widget_region(
+            assigned_widget,
+            node_type,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
     pub fn remove_widget_region(
         &mut self,
         widget: &mut StrongWidgetNodeEntry<A>,
@@ -209,7 +575,7 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         new_size: Option<Size>,
         new_internal_anchor: Option<Anchor>,
         new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        new_anchor_offset: Option<AnchorOffset>,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) {
@@ -224,6 +590,36 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         );
     }
 
+    pub fn reparent_widget_region(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        region_info: RegionInfo<A>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        self.region_tree.reparent_widget_region(
+            widget,
+            region_info,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
+    pub fn scroll_widget_into_view(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        align: ScrollAlign,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree.scroll_widget_into_view(
+            widget,
+            align,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+    }
+
     pub fn set_widget_explicit_visibility(
         &mut self,
         widget: &mut StrongWidgetNodeEntry<A>,
@@ -243,6 +639,20 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         self.region_tree.mark_widget_dirty(widget);
     }
 
+    pub fn mark_widget_region_layout_dirty(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region_tree
+            .mark_widget_layout_dirty(widget, widgets_just_shown, widgets_just_hidden);
+    }
+
+    pub fn mark_widget_region_rect_dirty(&mut self, widget: &StrongWidgetNodeEntry<A>, rect: Rect) {
+        self.region_tree.mark_widget_rect_dirty(widget, rect);
+    }
+
     pub fn set_widget_region_listens_to_pointer_events(
         &mut self,
         widget: &StrongWidgetNodeEntry<A>,
@@ -252,29 +662,108 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
             .set_widget_listens_to_pointer_events(widget, listens);
     }
 
+    pub fn set_widget_input_transparent(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        input_transparent: bool,
+    ) {
+        self.region_tree
+            .set_widget_input_transparent(widget, input_transparent);
+    }
+
+    pub fn set_widget_counts_toward_bounds(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        counts_toward_bounds: bool,
+    ) {
+        self.region_tree
+            .set_widget_counts_toward_bounds(widget, counts_toward_bounds);
+    }
+
+    pub fn content_bounds(&self) -> Option<Rect> {
+        self.region_tree.content_bounds()
+    }
+
+    pub fn widgets_intersecting_rect(
+        &self,
+        rect: Rect,
+        widgets: &mut Vec<StrongWidgetNodeEntry<A>>,
+    ) {
+        self.region_tree.widgets_intersecting_rect(rect, widgets);
+    }
+
+    pub fn is_widget_visible(&self, widget: &StrongWidgetNodeEntry<A>) -> bool {
+        self.region_tree.is_widget_visible(widget)
+    }
+
+    pub fn set_widget_hit_shape(&mut self, widget: &StrongWidgetNodeEntry<A>, hit_shape: HitShape) {
+        self.region_tree.set_widget_hit_shape(widget, hit_shape);
+    }
+
+    pub fn set_widget_render_scale(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        render_scale: f32,
+    ) {
+        self.region_tree
+            .set_widget_render_scale(widget, render_scale);
+    }
+
     pub fn handle_pointer_event(
         &mut self,
         mut event: PointerEvent,
+        text_context: &TextContext,
         action_tx: &mut Sender<A>,
     ) -> Option<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)> {
+        if !self.input_enabled {
+            return None;
+        }
+
+        // Remove this layer's offset from the position of the mouse event.
+        event.position = self.layer_local_point(event.position)?;
+        let event = unzoom_pointer_event(event, Point::default(), self.content_zoom());
+
+        self.region_tree
+            .handle_pointer_event(event, text_context, action_tx)
+    }
+
+    /// Find the widget that a pointer event at this position (in window
+    /// coordinates) would be sent to, without sending any events.
+    pub fn widget_at_point(&self, point: Point) -> Option<StrongWidgetNodeEntry<A>> {
+        if !self.input_enabled {
+            return None;
+        }
+
+        let point = self.layer_local_point(point)?;
+        let zoom = f64::from(self.content_zoom());
+
+        self.region_tree
+            .widget_at_point(Point::new(point.x / zoom, point.y / zoom))
+    }
+
+    /// Whether a pointer event that none of this layer's widgets captured
+    /// should be kept from reaching the layers below.
+    pub fn blocks_pointer_events(&self) -> bool {
+        self.is_backdrop && self.input_enabled && self.region_tree.layer_explicit_visibility()
+    }
+
+    /// Convert a point in window coordinates to a point relative to this
+    /// layer, or return `None` if this layer is hidden or the point lies
+    /// outside of it.
+    fn layer_local_point(&self, point: Point) -> Option<Point> {
         if !self.region_tree.layer_explicit_visibility() {
             return None;
         }
 
-        if event.position.x < self.outer_position.x
-            || event.position.y < self.outer_position.y
-            || event.position.x
-                > self.outer_position.x + f64::from(self.region_tree.layer_size().width())
-            || event.position.y
-                > self.outer_position.y + f64::from(self.region_tree.layer_size().height())
+        if point.x < self.outer_position.x
+            || point.y < self.outer_position.y
+            || point.x > self.outer_position.x + f64::from(self.region_tree.layer_size().width())
+            || point.y > self.outer_position.y + f64::from(self.region_tree.layer_size().height())
         {
             return None;
         }
 
-        // Remove this layer's offset from the position of the mouse event.
-        event.position -= self.outer_position;
-
-        self.region_tree.handle_pointer_event(event, action_tx)
+        Some(point - self.outer_position)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -285,6 +774,75 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         self.region_tree.is_dirty()
     }
 
+    pub fn collect_widgets(&self, widgets: &mut Vec<StrongWidgetNodeEntry<A>>) {
+        self.region_tree.collect_widgets(widgets);
+    }
+
+    pub fn validate_region_tree(&self) -> Result<(), String> {
+        self.region_tree.validate()
+    }
+
+    /// The rects that will be cleared or repainted on the next frame, in
+    /// physical screen coordinates.
+    pub fn damage(&self) -> Vec<PhysicalRect> {
+        let whole_layer = PhysicalRect::new(
+            self.physical_outer_position,
+            self.region_tree.layer_physical_size(),
+        );
+
+        if self.compositing == Compositing::Direct {
+            // Every visible widget is repainted every frame in this mode.
+            return if self.is_visible() {
+                vec![whole_layer]
+            } else {
+                Vec::new()
+            };
+        }
+
+        if self.region_tree.clear_whole_layer {
+            return vec![whole_layer];
+        }
+
+        let dirty_widget_rects = self.region_tree.dirty_widgets.iter().filter_map(|widget| {
+            widget.assigned_region().upgrade().map(|region| {
+                let region = &region.borrow().region;
+                region
+                    .repaint_texture_rect()
+                    .unwrap_or_else(|| TextureRect::from_physical_rect(region.physical_rect))
+            })
+        });
+
+        let mut damage: Vec<PhysicalRect> = Vec::new();
+        for rect in self
+            .region_tree
+            .texture_rects_to_clear
+            .iter()
+            .copied()
+            .chain(dirty_widget_rects)
+        {
+            if rect.size.width == 0 || rect.size.height == 0 {
+                continue;
+            }
+
+            // Move the rect from the layer's texture to the screen.
+            let rect = PhysicalRect::new(
+                PhysicalPoint::new(
+                    self.physical_outer_position.x + rect.x as i32,
+                    self.physical_outer_position.y + rect.y as i32,
+                ),
+                rect.size,
+            );
+
+            // A widget which is repainted in place clears the same rect that
+            // it paints into.
+            if !damage.contains(&rect) {
+                damage.push(rect);
+            }
+        }
+
+        damage
+    }
+
     pub fn is_visible(&self) -> bool {
         self.region_tree.is_visible()
     }
@@ -293,3 +851,19 @@ impl<A: Clone + Send + Sync + 'static> WidgetLayer<A> {
         self.region_tree.layer_size()
     }
 }
+
+/// Undo the content zoom of a layer on a pointer event, scaling its position
+/// about `origin` (the top-left corner of the layer in the coordinates of the
+/// event).
+fn unzoom_pointer_event(mut event: PointerEvent, origin: Point, content_zoom: f32) -> PointerEvent {
+    if content_zoom != 1.0 {
+        let zoom = f64::from(content_zoom);
+        event.position = Point::new(
+            origin.x + (event.position.x - origin.x) / zoom,
+            origin.y + (event.position.y - origin.y) / zoom,
+        );
+        event.delta = Point::new(event.delta.x / zoom, event.delta.y / zoom);
+        event.velocity = Point::new(event.velocity.x / zoom, event.velocity.y / zoom);
+    }
+    event
+}
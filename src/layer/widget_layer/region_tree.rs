@@ -1,15 +1,20 @@
 use crossbeam_channel::Sender;
-use std::cell::{RefCell, RefMut};
+use femtovg::TextContext;
+use fnv::FnvHashSet;
+use std::cell::{Ref, RefCell, RefMut};
 use std::rc::{Rc, Weak};
 
 use crate::error::FirewheelError;
 use crate::event::{InputEvent, PointerEvent};
 use crate::layer::WeakWidgetLayerEntry;
-use crate::node::StrongWidgetNodeEntry;
+#[cfg(feature = "alpha-hit-testing")]
+use crate::node::AlphaMask;
+use crate::node::{StrongWidgetNodeEntry, WeakWidgetNodeEntry};
 use crate::size::{PhysicalPoint, PhysicalRect, PhysicalSize, TextureRect};
 use crate::widget_node_set::WidgetNodeSet;
 use crate::{
-    Anchor, EventCapturedStatus, HAlign, Point, Rect, ScaleFactor, Size, VAlign,
+    Anchor, AnchorOffset, CoordinateOrigin, Edges, EventCapturedStatus, HAlign, HitShape,
+    NormalizedAnchor, Point, Rect, ScaleFactor2D, ScrollAlign, Size, StackLayout, VAlign,
     WidgetNodeRequests, WidgetNodeType,
 };
 
@@ -18,28 +23,97 @@ use crate::{
 // allow for further scrolling and pointer input optimizations for long lists of
 // items.
 
+/// The smallest resolution a widget can be painted at (see
+/// `WidgetNodeRequests::set_render_scale`).
+const MIN_RENDER_SCALE: f32 = 0.1;
+
+/// How far (in points) a region that is within the layer rect may move past
+/// the edges of the layer before it is hidden (see
+/// `RegionTree::set_layer_bounds_epsilon`).
+pub(crate) const DEFAULT_LAYER_BOUNDS_EPSILON: f64 = 0.01;
+
 #[derive(Clone)]
 pub struct RegionInfo<A: Clone + Send + Sync + 'static> {
     pub size: Size,
     pub internal_anchor: Anchor,
     pub parent_anchor: Anchor,
     pub parent_anchor_type: ParentAnchorType<A>,
-    pub anchor_offset: Point,
+    pub anchor_offset: AnchorOffset,
+    /// An arbitrary point on the parent region to anchor to, in place of
+    /// `parent_anchor`. Use this when the discrete alignments of `Anchor`
+    /// aren't enough (i.e. to anchor to a point a third of the way across
+    /// the parent).
+    pub normalized_parent_anchor: Option<NormalizedAnchor>,
+}
+
+impl<A: Clone + Send + Sync + 'static> RegionInfo<A> {
+    /// A region with the given rect in layer coordinates, for when a widget
+    /// is positioned absolutely instead of with anchors.
+    ///
+    /// The position is relative to the top-left corner of the layer's
+    /// contents (the layer's inner position is applied on top of this).
+    pub fn from_layer_rect(rect: Rect) -> Self {
+        Self {
+            size: rect.size(),
+            internal_anchor: Anchor::top_left(),
+            parent_anchor: Anchor::top_left(),
+            parent_anchor_type: ParentAnchorType::Layer,
+            anchor_offset: AnchorOffset::Points(rect.pos()),
+            normalized_parent_anchor: None,
+        }
+    }
+
+    /// A region that keeps the given margins to the pinned edges of its
+    /// parent.
+    ///
+    /// If both the left and right edges (or both the top and bottom edges)
+    /// are pinned, then the region stretches with its parent on that axis
+    /// and the corresponding dimension of `size` is ignored. If only one
+    /// edge of an axis is pinned, then the region is anchored to that edge.
+    /// If neither is pinned, then the region is centered on that axis.
+    pub fn pinned(edges: Edges, size: Size, parent_anchor_type: ParentAnchorType<A>) -> Self {
+        Self {
+            size,
+            internal_anchor: edges.anchor(),
+            parent_anchor: edges.anchor(),
+            parent_anchor_type,
+            anchor_offset: AnchorOffset::Pinned(edges),
+            normalized_parent_anchor: None,
+        }
+    }
 }
 
 pub(crate) struct RegionTree<A: Clone + Send + Sync + 'static> {
     pub dirty_widgets: WidgetNodeSet<A>,
     pub texture_rects_to_clear: Vec<TextureRect>,
     pub clear_whole_layer: bool,
+    /// Whether a region has been laid out again (i.e. moved, resized, added,
+    /// removed, shown or hidden) since the layer was last painted, as
+    /// opposed to only having its widget marked dirty.
+    pub geometry_changed: bool,
+    /// The unique IDs of the removed widgets that were painted with a render
+    /// scale below `1.0`, so that the renderer can free their images.
+    pub removed_scaled_widgets: Vec<u64>,
 
     next_region_id: u64,
     roots: Vec<StrongRegionTreeEntry<A>>,
+    /// The rect of the layer in the coordinates of its content (which is
+    /// smaller than the layer when the content is zoomed in).
     layer_rect: Rect,
     layer_physical_rect: PhysicalRect,
+    layer_size: Size,
     layer_explicit_visibility: bool,
     window_visibility: bool,
-    scale_factor: ScaleFactor,
+    /// The scale factor of the content, including the content zoom.
+    scale_factor: ScaleFactor2D,
+    window_scale_factor: ScaleFactor2D,
+    content_zoom: f32,
+    coordinate_origin: CoordinateOrigin,
+    layer_bounds_epsilon: f64,
     layer_id: u64,
+    /// The scrollable container regions in `ScrollBehavior::Bounce` mode that
+    /// are scrolled past their bounds and are springing back.
+    bouncing_regions: Vec<WeakRegionTreeEntry<A>>,
 }
 
 impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
@@ -48,7 +122,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         inner_position: Point,
         layer_explicit_visibility: bool,
         window_visibility: bool,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         layer_id: u64,
     ) -> Self {
         Self {
@@ -56,16 +130,24 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             roots: Vec::new(),
             dirty_widgets: WidgetNodeSet::new(),
             texture_rects_to_clear: Vec::new(),
+            removed_scaled_widgets: Vec::new(),
             layer_rect: Rect::new(Point::new(0.0, 0.0) - inner_position, layer_size),
             layer_physical_rect: PhysicalRect::new(
-                inner_position.to_physical(scale_factor),
-                layer_size.to_physical(scale_factor),
+                inner_position.to_physical_2d(scale_factor),
+                layer_size.to_physical_2d(scale_factor),
             ),
+            layer_size,
             layer_explicit_visibility,
             window_visibility,
             clear_whole_layer: true,
+            geometry_changed: false,
             scale_factor,
+            window_scale_factor: scale_factor,
+            content_zoom: 1.0,
+            coordinate_origin: CoordinateOrigin::default(),
+            layer_bounds_epsilon: DEFAULT_LAYER_BOUNDS_EPSILON,
             layer_id,
+            bouncing_regions: Vec::new(),
         }
     }
 
@@ -89,7 +171,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     rect: Rect::new(Point::default(), region_info.size), // The position will be overwritten
                     physical_rect: PhysicalRect::new(
                         PhysicalPoint::default(), // The position will be overwritten
-                        region_info.size.to_physical(self.scale_factor),
+                        region_info.size.to_physical_2d(self.scale_factor),
                     ),
                     parent_rect: Rect::default(), // This will be overwritten
                     last_rendered_texture_rect: None,
@@ -97,10 +179,19 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     parent_explicit_visibility: false, // This will be overwritten
                     is_within_layer_rect: false,       // This will be overwritten
                     is_visible: false,                 // This will be overwritten
+                    normalized_parent_anchor: region_info.normalized_parent_anchor,
+                    render_scale: 1.0,
+                    partial_repaint_rect: None,
+                    coordinate_origin: self.coordinate_origin,
+                    layer_bounds_epsilon: self.layer_bounds_epsilon,
                 },
                 parent: None,
                 children: Some(Vec::new()),
                 assigned_widget: None,
+                scroll_offset: None,
+                scroll_behavior: None,
+                bubble_handler: None,
+                stack_layout: None,
             })),
             region_id: new_id,
         };
@@ -129,7 +220,10 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                                 panic!("Parent region is not a container region");
                             }
                             (
-                                parent_entry_ref.region.rect,
+                                scrolled_rect(
+                                    parent_entry_ref.region.rect,
+                                    parent_entry_ref.scroll_offset,
+                                ),
                                 parent_entry_ref.region.explicit_visibility
                                     && parent_entry_ref.region.parent_explicit_visibility
                                     && self.window_visibility,
@@ -147,6 +241,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                 (parent_rect, parent_explicit_visibility)
             }
         };
+        self.geometry_changed = true;
         {
             new_entry.borrow_mut().parent_changed(
                 parent_rect,
@@ -160,11 +255,14 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             );
         }
 
+        let parent = new_entry.borrow().parent.clone();
+        self.update_stack_layouts(parent, widgets_just_shown, widgets_just_hidden);
+
         let container_ref = ContainerRegionRef {
             shared: new_entry.downgrade(),
             assigned_layer: WeakWidgetLayerEntry::new(), // This will be overwritten.
             assigned_layer_id: self.layer_id,
-            _unique_id: new_id,
+            unique_id: new_id,
         };
 
         Ok(container_ref)
@@ -233,13 +331,62 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         Ok(())
     }
 
+    /// The widgets assigned to the given container region and all of its
+    /// descendants, in depth-first order.
+    pub fn container_region_widgets(
+        &self,
+        container_ref: &ContainerRegionRef<A>,
+    ) -> Result<Vec<StrongWidgetNodeEntry<A>>, FirewheelError> {
+        if container_ref.assigned_layer_id != self.layer_id {
+            panic!("container region was not assigned to this layer");
+        }
+
+        let entry = container_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+
+        let mut widgets = Vec::new();
+        entry.borrow().collect_widgets(&mut widgets);
+
+        Ok(widgets)
+    }
+
+    /// Remove the given container region along with all of its descendant
+    /// container regions.
+    ///
+    /// The widgets assigned to the descendants of this container region must
+    /// be removed first (see `container_region_widgets()`).
+    pub fn remove_container_region_recursive(
+        &mut self,
+        container_ref: ContainerRegionRef<A>,
+    ) -> Result<(), FirewheelError> {
+        if let Some(entry) = container_ref.shared.upgrade() {
+            let mut entry_ref = entry.borrow_mut();
+
+            if let Some(children) = &mut entry_ref.children {
+                debug_assert!(children.iter().all(|child_entry| {
+                    let mut widgets = Vec::new();
+                    child_entry.borrow().collect_widgets(&mut widgets);
+                    widgets.is_empty()
+                }));
+
+                // Only empty container regions are left, which can simply be
+                // dropped.
+                children.clear();
+            }
+        }
+
+        self.remove_container_region(container_ref)
+    }
+
     pub fn modify_container_region(
         &mut self,
         container_ref: &mut ContainerRegionRef<A>,
         new_size: Option<Size>,
         new_internal_anchor: Option<Anchor>,
         new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        new_anchor_offset: Option<AnchorOffset>,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) -> Result<(), FirewheelError> {
@@ -248,7 +395,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             .upgrade()
             .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
 
-        entry.borrow_mut().modify(
+        self.geometry_changed |= entry.borrow_mut().modify(
             new_size,
             new_internal_anchor,
             new_parent_anchor,
@@ -262,9 +409,309 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             widgets_just_hidden,
         );
 
+        // A stack container is always sized to fit its children.
+        let stack_region = if entry.borrow().stack_layout.is_some() {
+            Some(container_ref.shared.clone())
+        } else {
+            entry.borrow().parent.clone()
+        };
+        self.update_stack_layouts(stack_region, widgets_just_shown, widgets_just_hidden);
+
+        Ok(())
+    }
+
+    /// Add a container region that lays out its direct children one after
+    /// the other along an axis, and is sized to fit them.
+    ///
+    /// The children are positioned from the top-left corner of the stack,
+    /// overriding their anchors and anchor offsets, and are laid out again
+    /// whenever a child is added, removed, or resized. The size in
+    /// `region_info` is ignored. The children of a stack container should
+    /// not be pinned to its edges.
+    ///
+    /// Removing a child container region (which is always empty) doesn't
+    /// lay out the stack again until the next change to its children.
+    pub fn add_stack_container(
+        &mut self,
+        region_info: RegionInfo<A>,
+        stack_layout: StackLayout,
+        explicit_visibility: bool,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<ContainerRegionRef<A>, FirewheelError> {
+        let container_ref = self.add_container_region(
+            region_info,
+            explicit_visibility,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )?;
+
+        container_ref
+            .shared
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .stack_layout = Some(stack_layout);
+        self.update_stack_layouts(
+            Some(container_ref.shared.clone()),
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+
+        Ok(container_ref)
+    }
+
+    /// Lay out the given stack container again, along with the stack
+    /// containers that it is directly nested in (since its size may have
+    /// changed). Does nothing if the region is not a stack container.
+    fn update_stack_layouts(
+        &mut self,
+        region: Option<WeakRegionTreeEntry<A>>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        let mut region = region;
+        while let Some(weak_entry) = region.take() {
+            let entry = if let Some(entry) = weak_entry.upgrade() {
+                entry
+            } else {
+                break;
+            };
+            let mut entry = entry.borrow_mut();
+            if entry.stack_layout.is_none() {
+                break;
+            }
+
+            self.geometry_changed |= entry.layout_stack(
+                self.layer_rect,
+                self.scale_factor,
+                &mut self.dirty_widgets,
+                &mut self.texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+
+            region = entry.parent.clone();
+        }
+    }
+
+    /// Make a container region scrollable with the given scroll offset, or
+    /// make it not scrollable with `None`.
+    pub fn set_container_region_scroll_offset(
+        &mut self,
+        container_ref: &mut ContainerRegionRef<A>,
+        scroll_offset: Option<Point>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        let entry = container_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+
+        let mut scroll_offset = scroll_offset;
+        if let (Some(offset), Some((behavior, max_offset))) =
+            (scroll_offset, entry.borrow().scroll_behavior)
+        {
+            let clamped = clamp_scroll_offset(offset, max_offset);
+            match behavior {
+                ScrollBehavior::Clamp => scroll_offset = Some(clamped),
+                ScrollBehavior::Bounce { .. } => {
+                    let region_id = container_ref.shared.region_id;
+                    if clamped != offset
+                        && !self
+                            .bouncing_regions
+                            .iter()
+                            .any(|weak_entry| weak_entry.region_id == region_id)
+                    {
+                        self.bouncing_regions.push(container_ref.shared.clone());
+                    }
+                }
+            }
+        }
+
+        self.geometry_changed |= entry.borrow_mut().set_scroll_offset(
+            scroll_offset,
+            self.layer_rect,
+            self.scale_factor,
+            &mut self.dirty_widgets,
+            &mut self.texture_rects_to_clear,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+
+        Ok(())
+    }
+
+    /// Set how a scrollable container region behaves when it is scrolled
+    /// past `max_scroll_offset` (or below zero), or `None` to not limit the
+    /// scroll offset (the default).
+    pub fn set_container_region_scroll_behavior(
+        &mut self,
+        container_ref: &mut ContainerRegionRef<A>,
+        behavior: Option<(ScrollBehavior, Point)>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        let entry = container_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+
+        entry.borrow_mut().scroll_behavior = behavior;
+
+        // Apply the new bounds to the current scroll offset.
+        let scroll_offset = entry.borrow().scroll_offset;
+        if scroll_offset.is_some() {
+            self.set_container_region_scroll_offset(
+                container_ref,
+                scroll_offset,
+                widgets_just_shown,
+                widgets_just_hidden,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the scroll offsets of the bouncing container regions back toward
+    /// their bounds by one animation frame.
+    pub fn step_scroll_bounce(
+        &mut self,
+        time_delta: f32,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        let mut bouncing_regions = std::mem::take(&mut self.bouncing_regions);
+        bouncing_regions.retain(|weak_entry| {
+            let entry = if let Some(entry) = weak_entry.upgrade() {
+                entry
+            } else {
+                return false;
+            };
+            let mut entry = entry.borrow_mut();
+
+            let (offset, stiffness, max_offset) = match (entry.scroll_offset, entry.scroll_behavior)
+            {
+                (Some(offset), Some((ScrollBehavior::Bounce { stiffness }, max_offset))) => {
+                    (offset, stiffness, max_offset)
+                }
+                // The container is no longer bouncing.
+                _ => return false,
+            };
+
+            let new_offset = Point::new(
+                bounce_step(offset.x, 0.0, max_offset.x, stiffness, time_delta),
+                bounce_step(offset.y, 0.0, max_offset.y, stiffness, time_delta),
+            );
+            self.geometry_changed |= entry.set_scroll_offset(
+                Some(new_offset),
+                self.layer_rect,
+                self.scale_factor,
+                &mut self.dirty_widgets,
+                &mut self.texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+
+            new_offset != clamp_scroll_offset(new_offset, max_offset)
+        });
+        self.bouncing_regions = bouncing_regions;
+    }
+
+    /// Whether any container regions are springing back from being scrolled
+    /// past their bounds.
+    pub fn is_scroll_bouncing(&self) -> bool {
+        !self.bouncing_regions.is_empty()
+    }
+
+    pub fn set_container_region_bubble_handler(
+        &mut self,
+        container_ref: &ContainerRegionRef<A>,
+        handler: Option<WeakWidgetNodeEntry<A>>,
+    ) -> Result<(), FirewheelError> {
+        let entry = container_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+
+        entry.borrow_mut().bubble_handler = handler;
+
         Ok(())
     }
 
+    pub fn container_region_scroll_offset(
+        &self,
+        container_ref: &ContainerRegionRef<A>,
+    ) -> Result<Option<Point>, FirewheelError> {
+        let entry = container_ref
+            .shared
+            .upgrade()
+            .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
+
+        let scroll_offset = entry.borrow().scroll_offset;
+        Ok(scroll_offset)
+    }
+
+    /// Adjust the scroll offsets of the scrollable container regions that
+    /// the widget is nested in so that the widget's region is visible.
+    pub fn scroll_widget_into_view(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        align: ScrollAlign,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        let widget_region = widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region");
+
+        // Start with the innermost scroll container so that the outer scroll
+        // containers reveal the widget at its newly scrolled position.
+        let mut parent = widget_region.borrow().parent.clone();
+        while let Some(weak_parent) = parent {
+            let parent_entry = weak_parent.upgrade().unwrap();
+            let mut parent_entry = parent_entry.borrow_mut();
+
+            if let Some(scroll_offset) = parent_entry.scroll_offset {
+                let target_rect = widget_region.borrow().region.rect;
+                let view_rect = parent_entry.region.rect;
+
+                let new_scroll_offset = Point::new(
+                    scroll_offset_to_reveal(
+                        scroll_offset.x,
+                        view_rect.x(),
+                        view_rect.x2(),
+                        target_rect.x(),
+                        target_rect.x2(),
+                        align,
+                    ),
+                    scroll_offset_to_reveal(
+                        scroll_offset.y,
+                        view_rect.y(),
+                        view_rect.y2(),
+                        target_rect.y(),
+                        target_rect.y2(),
+                        align,
+                    ),
+                );
+
+                self.geometry_changed |= parent_entry.set_scroll_offset(
+                    Some(new_scroll_offset),
+                    self.layer_rect,
+                    self.scale_factor,
+                    &mut self.dirty_widgets,
+                    &mut self.texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
+
+            parent = parent_entry.parent.clone();
+        }
+    }
+
     pub fn mark_container_region_dirty(
         &mut self,
         container_ref: &mut ContainerRegionRef<A>,
@@ -293,7 +740,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             .upgrade()
             .ok_or_else(|| FirewheelError::ContainerRegionRemoved)?;
 
-        entry.borrow_mut().modify(
+        self.geometry_changed |= entry.borrow_mut().modify(
             None,
             None,
             None,
@@ -336,7 +783,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     rect: Rect::new(Point::default(), region_info.size), // This will be overwritten
                     physical_rect: PhysicalRect::new(
                         PhysicalPoint::default(), // The position will be overwritten
-                        region_info.size.to_physical(self.scale_factor),
+                        region_info.size.to_physical_2d(self.scale_factor),
                     ),
                     parent_rect: Rect::default(), // This will be overwritten
                     last_rendered_texture_rect: None,
@@ -344,14 +791,29 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     parent_explicit_visibility: false, // This will be overwritten
                     is_within_layer_rect: false,       // This will be overwritten
                     is_visible: false,                 // This will be overwritten
+                    normalized_parent_anchor: region_info.normalized_parent_anchor,
+                    render_scale: 1.0,
+                    partial_repaint_rect: None,
+                    coordinate_origin: self.coordinate_origin,
+                    layer_bounds_epsilon: self.layer_bounds_epsilon,
                 },
                 parent: None,
                 children: None,
                 assigned_widget: Some(RegionAssignedWidget {
                     widget: assigned_widget.clone(),
                     listens_to_pointer_events: false,
+                    input_transparent: false,
+                    counts_toward_bounds: true,
+                    hit_shape: HitShape::Rect,
+                    focusable: false,
+                    #[cfg(feature = "alpha-hit-testing")]
+                    alpha_mask: None,
                     node_type,
                 }),
+                scroll_offset: None,
+                scroll_behavior: None,
+                bubble_handler: None,
+                stack_layout: None,
             })),
             region_id: new_id,
         };
@@ -382,7 +844,10 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                                 panic!("Parent region is not a container region");
                             }
                             (
-                                parent_entry_ref.region.rect,
+                                scrolled_rect(
+                                    parent_entry_ref.region.rect,
+                                    parent_entry_ref.scroll_offset,
+                                ),
                                 parent_entry_ref.region.explicit_visibility
                                     && parent_entry_ref.region.parent_explicit_visibility
                                     && self.window_visibility,
@@ -412,6 +877,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                 .widget
                 .set_assigned_region(weak_entry);
 
+            self.geometry_changed = true;
             entry_ref.parent_changed(
                 parent_rect,
                 self.layer_rect,
@@ -424,9 +890,37 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             );
         }
 
+        let parent = new_entry.borrow().parent.clone();
+        self.update_stack_layouts(parent, widgets_just_shown, widgets_just_hidden);
+
         Ok(())
     }
 
+    /// Add a widget region that always covers the whole layer.
+    pub fn add_overlay_widget_region(
+        &mut self,
+        assigned_widget: &mut StrongWidgetNodeEntry<A>,
+        node_type: WidgetNodeType,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> Result<(), FirewheelError> {
+        self.add_widget_region(
+            assigned_widget,
+            RegionInfo {
+                size: self.layer_rect.size(),
+                internal_anchor: Anchor::top_left(),
+                parent_anchor: Anchor::top_left(),
+                parent_anchor_type: ParentAnchorType::Layer,
+                anchor_offset: AnchorOffset::Pinned(Edges::all(0.0)),
+                normalized_parent_anchor: None,
+            },
+            node_type,
+            true,
+            widgets_just_shown,
+            widgets_just_hidden,
+        )
+    }
+
     pub fn remove_widget_region(
         &mut self,
         widget: &mut StrongWidgetNodeEntry<A>,
@@ -454,6 +948,10 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         if let Some(rect) = entry_ref.region.last_rendered_texture_rect.take() {
             self.texture_rects_to_clear.push(rect);
         }
+        self.geometry_changed = true;
+        if entry_ref.region.render_scale < 1.0 {
+            self.removed_scaled_widgets.push(widget.unique_id());
+        }
 
         widgets_just_shown.remove(widget);
         widgets_just_hidden.remove(widget);
@@ -494,68 +992,276 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                 panic!("widget region was not assigned to layer");
             }
         }
+
+        let parent = entry_ref.parent.clone();
+        self.update_stack_layouts(parent, widgets_just_shown, widgets_just_hidden);
     }
 
-    pub fn modify_widget_region(
+    /// Move a widget's region under a new parent within this layer while
+    /// keeping the same widget node.
+    pub fn reparent_widget_region(
         &mut self,
         widget: &StrongWidgetNodeEntry<A>,
-        new_size: Option<Size>,
-        new_internal_anchor: Option<Anchor>,
-        new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
+        region_info: RegionInfo<A>,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
-    ) {
-        widget
-            .assigned_region()
-            .upgrade()
-            .expect("Widget was not assigned a region")
-            .borrow_mut()
-            .modify(
-                new_size,
-                new_internal_anchor,
-                new_parent_anchor,
-                new_anchor_offset,
-                None,
-                self.layer_rect,
-                self.scale_factor,
-                &mut self.dirty_widgets,
-                &mut self.texture_rects_to_clear,
-                widgets_just_shown,
-                widgets_just_hidden,
-            );
-    }
+    ) -> Result<(), FirewheelError> {
+        let weak_entry = widget.assigned_region().clone();
+        let mut entry = StrongRegionTreeEntry {
+            shared: weak_entry
+                .upgrade()
+                .expect("Widget was not assigned a region"),
+            region_id: weak_entry.region_id,
+        };
 
-    pub fn mark_widget_dirty(&mut self, widget: &StrongWidgetNodeEntry<A>) {
-        widget
-            .assigned_region()
-            .upgrade()
-            .expect("Widget was not assigned a region")
-            .borrow_mut()
-            .mark_dirty(&mut self.dirty_widgets, &mut self.texture_rects_to_clear);
-    }
+        // Validate the new parent before detaching the region from its
+        // current parent.
+        let new_parent_entry = match &region_info.parent_anchor_type {
+            ParentAnchorType::Layer => None,
+            ParentAnchorType::ContainerRegion(container_ref) => {
+                if container_ref.assigned_layer_id != self.layer_id {
+                    return Err(FirewheelError::ParentAnchorRegionNotPartOfLayer);
+                }
 
-    pub fn set_widget_explicit_visibility(
-        &mut self,
-        widget: &StrongWidgetNodeEntry<A>,
-        explicit_visibility: bool,
-        widgets_just_shown: &mut WidgetNodeSet<A>,
-        widgets_just_hidden: &mut WidgetNodeSet<A>,
-    ) {
-        widget
-            .assigned_region()
-            .upgrade()
-            .expect("Widget was not assigned a region")
-            .borrow_mut()
-            .modify(
-                None,
-                None,
-                None,
-                None,
-                Some(explicit_visibility),
-                self.layer_rect,
-                self.scale_factor,
-                &mut self.dirty_widgets,
+                let parent_entry = container_ref
+                    .shared
+                    .upgrade()
+                    .ok_or_else(|| FirewheelError::ParentAnchorRegionRemoved)?;
+                if parent_entry.borrow().children.is_none() {
+                    panic!("Parent region is not a container region");
+                }
+
+                Some((parent_entry, container_ref.shared.clone()))
+            }
+        };
+
+        // Remove this entry from its old parent.
+        let old_parent = { entry.borrow_mut().parent.take() };
+        let old_stack_parent = old_parent.clone();
+        if let Some(old_parent) = old_parent {
+            let old_parent_entry = old_parent.upgrade().unwrap();
+            let mut old_parent = old_parent_entry.borrow_mut();
+
+            if let Some(children) = &mut old_parent.children {
+                let mut remove_i = None;
+                for (i, e) in children.iter().enumerate() {
+                    if e.region_id == entry.region_id {
+                        remove_i = Some(i);
+                        break;
+                    }
+                }
+                if let Some(i) = remove_i {
+                    children.remove(i);
+                } else {
+                    panic!("parent region did not contain child region");
+                }
+            } else {
+                panic!("parent region was not a container region");
+            }
+        } else {
+            let mut remove_i = None;
+            for (i, e) in self.roots.iter().enumerate() {
+                if e.region_id == entry.region_id {
+                    remove_i = Some(i);
+                    break;
+                }
+            }
+            if let Some(i) = remove_i {
+                self.roots.remove(i);
+            } else {
+                panic!("widget region was not assigned to layer");
+            }
+        }
+
+        // Add this entry to its new parent.
+        let (parent_rect, parent_explicit_visibility) =
+            if let Some((parent_entry, weak_parent_entry)) = new_parent_entry {
+                let (parent_rect, parent_explicit_visibility) = {
+                    let mut parent_entry_ref = parent_entry.borrow_mut();
+                    parent_entry_ref
+                        .children
+                        .as_mut()
+                        .unwrap()
+                        .push(entry.clone());
+                    (
+                        scrolled_rect(parent_entry_ref.region.rect, parent_entry_ref.scroll_offset),
+                        parent_entry_ref.region.explicit_visibility
+                            && parent_entry_ref.region.parent_explicit_visibility
+                            && self.window_visibility,
+                    )
+                };
+                {
+                    entry.borrow_mut().parent = Some(weak_parent_entry);
+                }
+
+                (parent_rect, parent_explicit_visibility)
+            } else {
+                self.roots.push(entry.clone());
+
+                (
+                    self.layer_rect,
+                    self.layer_explicit_visibility && self.window_visibility,
+                )
+            };
+
+        {
+            let mut entry_ref = entry.borrow_mut();
+
+            entry_ref.region.rect.set_size(region_info.size);
+            entry_ref.region.internal_anchor = region_info.internal_anchor;
+            entry_ref.region.parent_anchor = region_info.parent_anchor;
+            entry_ref.region.anchor_offset = region_info.anchor_offset;
+            entry_ref.region.normalized_parent_anchor = region_info.normalized_parent_anchor;
+
+            self.geometry_changed = true;
+            entry_ref.parent_changed(
+                parent_rect,
+                self.layer_rect,
+                self.scale_factor,
+                parent_explicit_visibility,
+                &mut self.dirty_widgets,
+                &mut self.texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+        }
+
+        self.update_stack_layouts(old_stack_parent, widgets_just_shown, widgets_just_hidden);
+        let parent = entry.borrow().parent.clone();
+        self.update_stack_layouts(parent, widgets_just_shown, widgets_just_hidden);
+
+        Ok(())
+    }
+
+    pub fn modify_widget_region(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        new_size: Option<Size>,
+        new_internal_anchor: Option<Anchor>,
+        new_parent_anchor: Option<Anchor>,
+        new_anchor_offset: Option<AnchorOffset>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        let entry = widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region");
+
+        self.geometry_changed |= entry.borrow_mut().modify(
+            new_size,
+            new_internal_anchor,
+            new_parent_anchor,
+            new_anchor_offset,
+            None,
+            self.layer_rect,
+            self.scale_factor,
+            &mut self.dirty_widgets,
+            &mut self.texture_rects_to_clear,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+
+        let parent = entry.borrow().parent.clone();
+        self.update_stack_layouts(parent, widgets_just_shown, widgets_just_hidden);
+    }
+
+    pub fn mark_widget_dirty(&mut self, widget: &StrongWidgetNodeEntry<A>) {
+        widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .mark_dirty(&mut self.dirty_widgets, &mut self.texture_rects_to_clear);
+    }
+
+    /// Re-resolve the rect and visibility of the widget's region, only marking
+    /// the widget dirty if either of them actually changed.
+    pub fn mark_widget_layout_dirty(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.geometry_changed |= widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .update_layout(
+                self.layer_rect,
+                self.scale_factor,
+                &mut self.dirty_widgets,
+                &mut self.texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+    }
+
+    /// Mark only part of a widget as dirty. The rect is relative to the
+    /// top-left corner of the widget's region.
+    ///
+    /// If the widget has not been rendered yet, or if it is painted at a
+    /// render scale below `1.0`, then the whole widget is marked dirty
+    /// instead.
+    pub fn mark_widget_rect_dirty(&mut self, widget: &StrongWidgetNodeEntry<A>, rect: Rect) {
+        let assigned_region = widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region");
+        let mut entry = assigned_region.borrow_mut();
+
+        if !entry.region.is_visible() {
+            return;
+        }
+
+        let texture_rect = match entry.region.last_rendered_texture_rect {
+            Some(last_rendered_texture_rect) if entry.region.render_scale >= 1.0 => {
+                partial_repaint_texture_rect(
+                    entry.region.physical_rect,
+                    rect,
+                    self.scale_factor,
+                    last_rendered_texture_rect,
+                )
+            }
+            _ => {
+                entry.mark_dirty(&mut self.dirty_widgets, &mut self.texture_rects_to_clear);
+                return;
+            }
+        };
+
+        // Nothing needs to be repainted if the rect lies outside of the
+        // widget.
+        if let Some(texture_rect) = texture_rect {
+            entry.region.partial_repaint_rect = Some(match entry.region.partial_repaint_rect {
+                Some(partial_repaint_rect) => partial_repaint_rect.bounding_union(&texture_rect),
+                None => texture_rect,
+            });
+            self.dirty_widgets.insert(widget);
+        }
+    }
+
+    pub fn set_widget_explicit_visibility(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        explicit_visibility: bool,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.geometry_changed |= widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .modify(
+                None,
+                None,
+                None,
+                None,
+                Some(explicit_visibility),
+                self.layer_rect,
+                self.scale_factor,
+                &mut self.dirty_widgets,
                 &mut self.texture_rects_to_clear,
                 widgets_just_shown,
                 widgets_just_hidden,
@@ -578,6 +1284,119 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
             .listens_to_pointer_events = listens;
     }
 
+    pub fn set_widget_input_transparent(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        input_transparent: bool,
+    ) {
+        widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .assigned_widget
+            .as_mut()
+            .unwrap()
+            .input_transparent = input_transparent;
+    }
+
+    pub fn set_widget_counts_toward_bounds(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        counts_toward_bounds: bool,
+    ) {
+        widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .assigned_widget
+            .as_mut()
+            .unwrap()
+            .counts_toward_bounds = counts_toward_bounds;
+    }
+
+    /// The smallest rect (in the coordinates of the widgets) that contains
+    /// every explicitly visible widget in this layer that counts toward the
+    /// content bounds, or `None` if there are no such widgets.
+    ///
+    /// Widgets that lie outside of the layer's rect are included.
+    pub fn content_bounds(&self) -> Option<Rect> {
+        let mut bounds = None;
+        for entry in self.roots.iter() {
+            entry.borrow().content_bounds(self.layer_rect, &mut bounds);
+        }
+        bounds
+    }
+
+    /// Collect every visible widget whose rect overlaps with the given rect
+    /// (in the coordinates of the layer's widgets).
+    pub fn widgets_intersecting_rect(
+        &self,
+        rect: Rect,
+        widgets: &mut Vec<StrongWidgetNodeEntry<A>>,
+    ) {
+        if !self.layer_explicit_visibility {
+            return;
+        }
+
+        for entry in self.roots.iter() {
+            entry
+                .borrow()
+                .widgets_intersecting_rect(rect, self.layer_rect, widgets);
+        }
+    }
+
+    /// Whether the widget's region is currently visible, taking into account
+    /// the visibility of its parent regions and the layer, and whether it
+    /// lies within the layer's rect.
+    pub fn is_widget_visible(&self, widget: &StrongWidgetNodeEntry<A>) -> bool {
+        widget
+            .assigned_region()
+            .upgrade()
+            .map(|region| region.borrow().region.is_visible())
+            .unwrap_or(false)
+    }
+
+    pub fn set_widget_hit_shape(&mut self, widget: &StrongWidgetNodeEntry<A>, hit_shape: HitShape) {
+        widget
+            .assigned_region()
+            .upgrade()
+            .expect("Widget was not assigned a region")
+            .borrow_mut()
+            .assigned_widget
+            .as_mut()
+            .unwrap()
+            .set_hit_shape(hit_shape);
+    }
+
+    pub fn set_widget_render_scale(
+        &mut self,
+        widget: &StrongWidgetNodeEntry<A>,
+        render_scale: f32,
+    ) {
+        let render_scale = render_scale.clamp(MIN_RENDER_SCALE, 1.0);
+
+        let changed = {
+            let region = widget
+                .assigned_region()
+                .upgrade()
+                .expect("Widget was not assigned a region");
+            let mut region = region.borrow_mut();
+
+            if region.region.render_scale != render_scale {
+                region.region.render_scale = render_scale;
+                true
+            } else {
+                false
+            }
+        };
+
+        if changed {
+            self.mark_widget_dirty(widget);
+        }
+    }
+
     pub fn set_layer_inner_position(
         &mut self,
         position: Point,
@@ -586,7 +1405,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
     ) {
         if self.layer_rect.pos() != position {
             self.layer_rect.set_pos(position);
-            self.layer_physical_rect.pos = self.layer_rect.pos().to_physical(self.scale_factor);
+            self.layer_physical_rect.pos = self.layer_rect.pos().to_physical_2d(self.scale_factor);
             self.clear_whole_layer = true;
 
             for entry in self.roots.iter_mut() {
@@ -594,7 +1413,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     self.layer_rect,
                     self.layer_rect,
                     self.scale_factor,
-                    self.layer_explicit_visibility,
+                    self.layer_explicit_visibility && self.window_visibility,
                     &mut self.dirty_widgets,
                     &mut self.texture_rects_to_clear,
                     widgets_just_shown,
@@ -607,14 +1426,15 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
     pub fn set_layer_size(
         &mut self,
         size: Size,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) {
-        if self.layer_rect.size() != size || self.scale_factor != scale_factor {
-            self.layer_rect.set_size(size);
-            self.layer_physical_rect.size = self.layer_rect.size().to_physical(scale_factor);
-            self.scale_factor = scale_factor;
+        if self.layer_size != size || self.window_scale_factor != scale_factor {
+            self.layer_size = size;
+            self.layer_physical_rect.size = size.to_physical_2d(scale_factor);
+            self.window_scale_factor = scale_factor;
+            self.update_content_scale();
             self.clear_whole_layer = true;
 
             for entry in self.roots.iter_mut() {
@@ -622,7 +1442,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     self.layer_rect,
                     self.layer_rect,
                     self.scale_factor,
-                    self.layer_explicit_visibility,
+                    self.layer_explicit_visibility && self.window_visibility,
                     &mut self.dirty_widgets,
                     &mut self.texture_rects_to_clear,
                     widgets_just_shown,
@@ -632,14 +1452,18 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         }
     }
 
-    pub fn set_layer_explicit_visibility(
+    /// Set how much the content of the layer is zoomed in, on top of the
+    /// scale factor of the window.
+    pub fn set_content_zoom(
         &mut self,
-        explicit_visibility: bool,
+        content_zoom: f32,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) {
-        if self.layer_explicit_visibility != explicit_visibility {
-            self.layer_explicit_visibility = explicit_visibility;
+        if self.content_zoom != content_zoom {
+            self.content_zoom = content_zoom;
+            self.update_content_scale();
+            self.layer_physical_rect.pos = self.layer_rect.pos().to_physical_2d(self.scale_factor);
             self.clear_whole_layer = true;
 
             for entry in self.roots.iter_mut() {
@@ -647,7 +1471,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
                     self.layer_rect,
                     self.layer_rect,
                     self.scale_factor,
-                    self.layer_explicit_visibility,
+                    self.layer_explicit_visibility && self.window_visibility,
                     &mut self.dirty_widgets,
                     &mut self.texture_rects_to_clear,
                     widgets_just_shown,
@@ -657,24 +1481,44 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         }
     }
 
-    pub fn set_window_visibility(
+    pub fn content_zoom(&self) -> f32 {
+        self.content_zoom
+    }
+
+    /// The scale factor the content of the layer is painted at.
+    pub fn content_scale_factor(&self) -> ScaleFactor2D {
+        self.scale_factor
+    }
+
+    fn update_content_scale(&mut self) {
+        self.scale_factor = ScaleFactor2D::new(
+            self.window_scale_factor.x * self.content_zoom,
+            self.window_scale_factor.y * self.content_zoom,
+        );
+        self.layer_rect.set_size(Size::new(
+            self.layer_size.width() / self.content_zoom,
+            self.layer_size.height() / self.content_zoom,
+        ));
+    }
+
+    pub fn set_coordinate_origin(
         &mut self,
-        visible: bool,
+        coordinate_origin: CoordinateOrigin,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
     ) {
-        self.window_visibility = visible;
-
-        if self.is_visible() {
-            let parent_explicit_visibility =
-                self.window_visibility && self.layer_explicit_visibility;
+        if self.coordinate_origin != coordinate_origin {
+            self.coordinate_origin = coordinate_origin;
+            self.clear_whole_layer = true;
 
             for entry in self.roots.iter_mut() {
-                entry.borrow_mut().parent_changed(
+                let mut entry = entry.borrow_mut();
+                entry.set_coordinate_origin(coordinate_origin);
+                entry.parent_changed(
                     self.layer_rect,
                     self.layer_rect,
                     self.scale_factor,
-                    parent_explicit_visibility,
+                    self.layer_explicit_visibility && self.window_visibility,
                     &mut self.dirty_widgets,
                     &mut self.texture_rects_to_clear,
                     widgets_just_shown,
@@ -684,24 +1528,142 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         }
     }
 
-    pub fn layer_explicit_visibility(&self) -> bool {
-        self.layer_explicit_visibility
-    }
-
-    pub fn layer_size(&self) -> Size {
-        self.layer_rect.size()
-    }
-
-    pub fn layer_physical_size(&self) -> PhysicalSize {
-        self.layer_physical_rect.size
+    pub fn coordinate_origin(&self) -> CoordinateOrigin {
+        self.coordinate_origin
     }
 
-    pub fn layer_rect(&self) -> Rect {
-        self.layer_rect
-    }
+    /// Set how far (in points) a region that is within the layer rect may
+    /// move past the edges of the layer before it is hidden. This keeps a
+    /// region straddling an edge from flickering in and out of visibility.
+    ///
+    /// By default this is `DEFAULT_LAYER_BOUNDS_EPSILON`.
+    pub fn set_layer_bounds_epsilon(
+        &mut self,
+        epsilon: f64,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        let epsilon = epsilon.max(0.0);
+        if self.layer_bounds_epsilon != epsilon {
+            self.layer_bounds_epsilon = epsilon;
+            self.geometry_changed = true;
 
-    pub fn is_dirty(&self) -> bool {
-        !self.dirty_widgets.is_empty()
+            for entry in self.roots.iter_mut() {
+                let mut entry = entry.borrow_mut();
+                entry.set_layer_bounds_epsilon(epsilon);
+                entry.parent_changed(
+                    self.layer_rect,
+                    self.layer_rect,
+                    self.scale_factor,
+                    self.layer_explicit_visibility && self.window_visibility,
+                    &mut self.dirty_widgets,
+                    &mut self.texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
+        }
+    }
+
+    pub fn set_layer_explicit_visibility(
+        &mut self,
+        explicit_visibility: bool,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        if self.layer_explicit_visibility != explicit_visibility {
+            self.layer_explicit_visibility = explicit_visibility;
+            self.clear_whole_layer = true;
+
+            for entry in self.roots.iter_mut() {
+                entry.borrow_mut().parent_changed(
+                    self.layer_rect,
+                    self.layer_rect,
+                    self.scale_factor,
+                    self.layer_explicit_visibility && self.window_visibility,
+                    &mut self.dirty_widgets,
+                    &mut self.texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
+        }
+    }
+
+    pub fn set_window_visibility(
+        &mut self,
+        visible: bool,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        if self.window_visibility != visible {
+            self.window_visibility = visible;
+
+            if visible {
+                // Repaint the whole layer so that the first visible frame is
+                // complete.
+                self.clear_whole_layer = true;
+            }
+
+            let parent_explicit_visibility =
+                self.window_visibility && self.layer_explicit_visibility;
+
+            for entry in self.roots.iter_mut() {
+                entry.borrow_mut().parent_changed(
+                    self.layer_rect,
+                    self.layer_rect,
+                    self.scale_factor,
+                    parent_explicit_visibility,
+                    &mut self.dirty_widgets,
+                    &mut self.texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.clear_whole_layer = true;
+
+        for entry in self.roots.iter_mut() {
+            entry
+                .borrow_mut()
+                .mark_dirty(&mut self.dirty_widgets, &mut self.texture_rects_to_clear);
+        }
+    }
+
+    /// Collect every visible widget that paints something into this layer.
+    pub fn collect_widgets(&self, widgets: &mut Vec<StrongWidgetNodeEntry<A>>) {
+        for entry in self.roots.iter() {
+            entry.borrow().collect_widgets(widgets);
+        }
+    }
+
+    pub fn collect_visible_painted_widgets(&mut self, widgets: &mut Vec<StrongWidgetNodeEntry<A>>) {
+        for entry in self.roots.iter_mut() {
+            entry.borrow_mut().collect_visible_painted_widgets(widgets);
+        }
+    }
+
+    pub fn layer_explicit_visibility(&self) -> bool {
+        self.layer_explicit_visibility
+    }
+
+    pub fn layer_size(&self) -> Size {
+        self.layer_size
+    }
+
+    pub fn layer_physical_size(&self) -> PhysicalSize {
+        self.layer_physical_rect.size
+    }
+
+    pub fn layer_rect(&self) -> Rect {
+        self.layer_rect
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_widgets.is_empty()
             || !self.texture_rects_to_clear.is_empty()
             || self.clear_whole_layer
     }
@@ -710,15 +1672,76 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         self.roots.is_empty()
     }
 
+    /// Check the invariants of the tree, returning a description of the
+    /// first one that is violated.
+    ///
+    /// The layout engine keeps these invariants on its own, so a violation
+    /// means there is a bug in it.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut region_ids = FnvHashSet::default();
+        for root in self.roots.iter() {
+            root.validate(
+                None,
+                self.layer_rect,
+                self.layer_explicit_visibility && self.window_visibility,
+                self.layer_rect,
+                &mut region_ids,
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn is_visible(&self) -> bool {
-        self.layer_explicit_visibility && !self.roots.is_empty()
+        self.layer_explicit_visibility && self.window_visibility && !self.roots.is_empty()
     }
 
     pub fn handle_pointer_event(
         &mut self,
         event: PointerEvent,
+        text_context: &TextContext,
         action_tx: &mut Sender<A>,
     ) -> Option<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)> {
+        // Widgets receive the event in the coordinates of the layer, but the
+        // hit testing is done in screen coordinates.
+        let raw_event = self
+            .coordinate_origin
+            .convert_pointer_event(event, self.layer_rect);
+        let raw_input_event = InputEvent::Pointer(raw_event);
+        let input_event = InputEvent::Pointer(raw_event.without_raw_scroll());
+
+        self.dispatch_pointer_event(event.position, &mut |widget, bubbled| {
+            let input_event = if widget.receives_raw_scroll() {
+                &raw_input_event
+            } else {
+                &input_event
+            };
+
+            if bubbled {
+                widget
+                    .borrow_mut()
+                    .on_child_event_bubbled(input_event, text_context, action_tx)
+            } else {
+                widget
+                    .borrow_mut()
+                    .on_input_event(input_event, text_context, action_tx)
+            }
+        })
+    }
+
+    /// Find the widget that captures a pointer event at the given position.
+    ///
+    /// `send` is called with each widget the event is sent to, in order,
+    /// along with whether the event was bubbled up to that widget from the
+    /// children of a container region.
+    fn dispatch_pointer_event<F>(
+        &mut self,
+        position: Point,
+        send: &mut F,
+    ) -> Option<(StrongWidgetNodeEntry<A>, WidgetNodeRequests)>
+    where
+        F: FnMut(&mut StrongWidgetNodeEntry<A>, bool) -> EventCapturedStatus,
+    {
         if !self.layer_explicit_visibility {
             return None;
         }
@@ -727,7 +1750,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
         //event.position += self.layer_rect.pos();
 
         for region in self.roots.iter_mut() {
-            match region.borrow_mut().handle_pointer_event(event, action_tx) {
+            match region.borrow_mut().handle_pointer_event(position, send) {
                 PointerCapturedStatus::Captured { widget, requests } => {
                     return Some((widget, requests));
                 }
@@ -740,6 +1763,24 @@ impl<A: Clone + Send + Sync + 'static> RegionTree<A> {
 
         None
     }
+
+    /// Find the widget that a pointer event at this position (relative to
+    /// the layer) would be sent to, without sending any events.
+    pub fn widget_at_point(&self, point: Point) -> Option<StrongWidgetNodeEntry<A>> {
+        if !self.layer_explicit_visibility {
+            return None;
+        }
+
+        for region in self.roots.iter() {
+            match region.borrow().widget_at_point(point) {
+                HitTestStatus::Hit(widget) => return Some(widget),
+                HitTestStatus::InRegionButNoHit => return None,
+                HitTestStatus::NotInRegion => {}
+            }
+        }
+
+        None
+    }
 }
 
 struct StrongRegionTreeEntry<A: Clone + Send + Sync + 'static> {
@@ -748,6 +1789,10 @@ struct StrongRegionTreeEntry<A: Clone + Send + Sync + 'static> {
 }
 
 impl<A: Clone + Send + Sync + 'static> StrongRegionTreeEntry<A> {
+    fn borrow(&self) -> Ref<'_, RegionTreeEntry<A>> {
+        RefCell::borrow(&self.shared)
+    }
+
     fn borrow_mut(&mut self) -> RefMut<'_, RegionTreeEntry<A>> {
         RefCell::borrow_mut(&self.shared)
     }
@@ -758,6 +1803,132 @@ impl<A: Clone + Send + Sync + 'static> StrongRegionTreeEntry<A> {
             region_id: self.region_id,
         }
     }
+
+    /// Check the invariants of this region and its children (see
+    /// `RegionTree::validate()`).
+    fn validate(
+        &self,
+        parent: Option<&StrongRegionTreeEntry<A>>,
+        parent_rect: Rect,
+        parent_explicit_visibility: bool,
+        layer_rect: Rect,
+        region_ids: &mut FnvHashSet<u64>,
+    ) -> Result<(), String> {
+        let entry = self.borrow();
+        let id = entry.region.id;
+
+        if !region_ids.insert(id) {
+            return Err(format!("region {} appears more than once in the tree", id));
+        }
+        if self.region_id != id {
+            return Err(format!(
+                "region {} is referenced with the ID of region {}",
+                id, self.region_id
+            ));
+        }
+
+        match (parent, &entry.parent) {
+            (None, None) => {}
+            (None, Some(_)) => {
+                return Err(format!("root region {} has a parent", id));
+            }
+            (Some(parent), None) => {
+                return Err(format!(
+                    "region {} is a child of region {} but has no parent",
+                    id, parent.region_id
+                ));
+            }
+            (Some(parent), Some(weak_parent)) => {
+                let is_parent = weak_parent
+                    .upgrade()
+                    .map(|p| Rc::ptr_eq(&p, &parent.shared))
+                    .unwrap_or(false);
+                if !is_parent {
+                    return Err(format!(
+                        "region {} does not reference its container region {} as its parent",
+                        id, parent.region_id
+                    ));
+                }
+            }
+        }
+
+        if entry.children.is_some() && entry.assigned_widget.is_some() {
+            return Err(format!(
+                "region {} is a container region but is also assigned a widget",
+                id
+            ));
+        }
+
+        if let Some(assigned_widget) = &entry.assigned_widget {
+            let is_assigned = assigned_widget
+                .widget
+                .assigned_region()
+                .upgrade()
+                .map(|r| Rc::ptr_eq(&r, &self.shared))
+                .unwrap_or(false);
+            if !is_assigned {
+                return Err(format!(
+                    "the widget assigned to region {} does not reference it",
+                    id
+                ));
+            }
+        }
+
+        if !entry
+            .region
+            .parent_rect
+            .partial_eq_with_epsilon(parent_rect)
+        {
+            return Err(format!(
+                "region {} has the parent rect {:?}, but its parent's rect is {:?}",
+                id, entry.region.parent_rect, parent_rect
+            ));
+        }
+        if entry.region.parent_explicit_visibility != parent_explicit_visibility {
+            return Err(format!(
+                "region {} has the parent explicit visibility {}, but its parent's is {}",
+                id, entry.region.parent_explicit_visibility, parent_explicit_visibility
+            ));
+        }
+        let is_within_layer_rect_consistent = if entry.region.is_within_layer_rect {
+            layer_rect.overlaps_with_rect_with_epsilon(
+                entry.region.rect,
+                entry.region.layer_bounds_epsilon,
+            )
+        } else {
+            !layer_rect.overlaps_with_rect(entry.region.rect)
+        };
+        if !is_within_layer_rect_consistent {
+            return Err(format!(
+                "region {} has is_within_layer_rect = {}, which does not match its rect {:?}",
+                id, entry.region.is_within_layer_rect, entry.region.rect
+            ));
+        }
+        let expected_visibility = entry.region.explicit_visibility
+            && entry.region.parent_explicit_visibility
+            && entry.region.is_within_layer_rect;
+        if entry.region.is_visible() != expected_visibility {
+            return Err(format!(
+                "region {} has is_visible = {}, which does not match its visibility flags",
+                id,
+                entry.region.is_visible()
+            ));
+        }
+
+        if let Some(children) = &entry.children {
+            for child in children.iter() {
+                child.validate(
+                    Some(self),
+                    scrolled_rect(entry.region.rect, entry.scroll_offset),
+                    entry.region.explicit_visibility && entry.region.parent_explicit_visibility,
+                    layer_rect,
+                    region_ids,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl<A: Clone + Send + Sync + 'static> Clone for StrongRegionTreeEntry<A> {
@@ -809,35 +1980,184 @@ enum PointerCapturedStatus<A: Clone + Send + Sync + 'static> {
     NotInRegion,
 }
 
+enum HitTestStatus<A: Clone + Send + Sync + 'static> {
+    Hit(StrongWidgetNodeEntry<A>),
+    InRegionButNoHit,
+    NotInRegion,
+}
+
 struct RegionAssignedWidget<A: Clone + Send + Sync + 'static> {
     widget: StrongWidgetNodeEntry<A>,
     listens_to_pointer_events: bool,
+    /// If true, then this widget is skipped when hit-testing regardless of
+    /// whether it listens to pointer events.
+    input_transparent: bool,
+    /// If false, then this widget is left out of the content bounds of the
+    /// layer (i.e. for a background or a shadow).
+    counts_toward_bounds: bool,
+    /// The part of the region that pointer events hit.
+    hit_shape: HitShape,
+    /// Whether this widget can receive the keyboard focus, as last set with
+    /// `WidgetNodeRequests::set_focusable`. This is kept while the widget is
+    /// hidden so that it becomes focusable again once it is shown.
+    focusable: bool,
+    /// The pixels the widget last painted, if its hit shape is
+    /// `HitShape::PaintedAlpha`.
+    #[cfg(feature = "alpha-hit-testing")]
+    alpha_mask: Option<AlphaMask>,
     node_type: WidgetNodeType,
 }
 
+impl<A: Clone + Send + Sync + 'static> RegionAssignedWidget<A> {
+    fn is_hit_testable(&self) -> bool {
+        self.listens_to_pointer_events && !self.input_transparent
+    }
+
+    fn set_hit_shape(&mut self, hit_shape: HitShape) {
+        self.hit_shape = hit_shape;
+        #[cfg(feature = "alpha-hit-testing")]
+        {
+            self.alpha_mask = None;
+        }
+    }
+
+    /// Whether a pointer at the given point hits this widget, where `rect` is
+    /// the rect of its region.
+    fn is_hit(&self, rect: Rect, point: Point) -> bool {
+        if !self.hit_shape.contains_point(rect, point) {
+            return false;
+        }
+
+        #[cfg(feature = "alpha-hit-testing")]
+        if let Some(alpha_mask) = &self.alpha_mask {
+            return alpha_mask.is_painted_at(rect, point);
+        }
+
+        true
+    }
+}
+
 pub(crate) struct RegionTreeEntry<A: Clone + Send + Sync + 'static> {
     pub region: Region,
     parent: Option<WeakRegionTreeEntry<A>>,
     children: Option<Vec<StrongRegionTreeEntry<A>>>,
     assigned_widget: Option<RegionAssignedWidget<A>>,
+    /// If this is a scrollable container region, the amount that its
+    /// children are scrolled by.
+    scroll_offset: Option<Point>,
+    /// How the scroll offset behaves at its bounds, and the maximum scroll
+    /// offset (the minimum is zero). `None` if the scroll offset is not
+    /// limited.
+    scroll_behavior: Option<(ScrollBehavior, Point)>,
+    /// If this is a container region, the widget which receives the pointer
+    /// events inside of this region that none of its children captured.
+    ///
+    /// This is a weak reference so that the handler is dropped as soon as
+    /// it is removed from the window.
+    bubble_handler: Option<WeakWidgetNodeEntry<A>>,
+    /// If this is a stack container, how its children are laid out.
+    stack_layout: Option<StackLayout>,
 }
 
 impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
-    fn handle_pointer_event(
-        &mut self,
-        event: PointerEvent,
-        action_tx: &mut Sender<A>,
-    ) -> PointerCapturedStatus<A> {
+    /// The container regions that this region is nested in, starting with
+    /// its direct parent and ending with the root container.
+    pub fn ancestors(
+        &self,
+        assigned_layer: &WeakWidgetLayerEntry<A>,
+        assigned_layer_id: u64,
+    ) -> Vec<ContainerRegionRef<A>> {
+        let mut ancestors = Vec::new();
+
+        let mut parent = self.parent.clone();
+        while let Some(parent_weak) = parent.take() {
+            if let Some(parent_entry) = parent_weak.upgrade() {
+                parent = parent_entry.borrow().parent.clone();
+                ancestors.push(ContainerRegionRef {
+                    unique_id: parent_weak.region_id,
+                    shared: parent_weak,
+                    assigned_layer: assigned_layer.clone(),
+                    assigned_layer_id,
+                });
+            }
+        }
+
+        ancestors
+    }
+
+    /// The part of the layer's texture that this region is clipped to by
+    /// the scrollable container regions it is nested in, or `None` if it
+    /// isn't nested in any.
+    ///
+    /// The rect has a size of zero if the region is scrolled out of view
+    /// entirely.
+    pub fn scroll_clip_rect(&self) -> Option<TextureRect> {
+        let mut clip_rect: Option<TextureRect> = None;
+
+        let mut parent = self.parent.clone();
+        while let Some(parent_weak) = parent.take() {
+            if let Some(parent_entry) = parent_weak.upgrade() {
+                let parent_entry = parent_entry.borrow();
+                if parent_entry.scroll_offset.is_some() {
+                    let view_rect =
+                        TextureRect::from_physical_rect(parent_entry.region.physical_rect);
+                    clip_rect = Some(match clip_rect {
+                        Some(clip_rect) => {
+                            clip_rect.intersection(&view_rect).unwrap_or(TextureRect {
+                                x: clip_rect.x,
+                                y: clip_rect.y,
+                                size: PhysicalSize::new(0, 0),
+                            })
+                        }
+                        None => view_rect,
+                    });
+                }
+                parent = parent_entry.parent.clone();
+            }
+        }
+
+        clip_rect
+    }
+
+    pub fn widget_is_focusable(&self) -> bool {
+        self.assigned_widget
+            .as_ref()
+            .map(|w| w.focusable)
+            .unwrap_or(false)
+    }
+
+    pub fn set_widget_focusable(&mut self, focusable: bool) {
+        if let Some(assigned_widget) = &mut self.assigned_widget {
+            assigned_widget.focusable = focusable;
+        }
+    }
+
+    /// Whether the pixels that the widget assigned to this region paints
+    /// should be read back for `HitShape::PaintedAlpha`.
+    #[cfg(feature = "alpha-hit-testing")]
+    pub fn uses_painted_alpha(&self) -> bool {
+        matches!(
+            self.assigned_widget.as_ref().map(|w| &w.hit_shape),
+            Some(HitShape::PaintedAlpha)
+        )
+    }
+
+    #[cfg(feature = "alpha-hit-testing")]
+    pub(crate) fn set_alpha_mask(&mut self, alpha_mask: AlphaMask) {
+        if let Some(assigned_widget) = &mut self.assigned_widget {
+            assigned_widget.alpha_mask = Some(alpha_mask);
+        }
+    }
+
+    fn handle_pointer_event<F>(&mut self, position: Point, send: &mut F) -> PointerCapturedStatus<A>
+    where
+        F: FnMut(&mut StrongWidgetNodeEntry<A>, bool) -> EventCapturedStatus,
+    {
         if self.region.is_visible() {
             if let Some(assigned_widget) = &mut self.assigned_widget {
-                if assigned_widget.listens_to_pointer_events {
-                    if self.region.rect.contains_point(event.position) {
-                        let status = {
-                            assigned_widget
-                                .widget
-                                .borrow_mut()
-                                .on_input_event(&InputEvent::Pointer(event), action_tx)
-                        };
+                if assigned_widget.is_hit_testable() {
+                    if assigned_widget.is_hit(self.region.rect, position) {
+                        let status = send(&mut assigned_widget.widget, false);
                         let status = if let EventCapturedStatus::Captured(requests) = status {
                             PointerCapturedStatus::Captured {
                                 widget: assigned_widget.widget.clone(),
@@ -850,24 +2170,36 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
                         return status;
                     }
                 }
-            } else if self.region.rect.contains_point(event.position) {
+            } else if self.region.rect.contains_point(position) {
                 if let Some(children) = &mut self.children {
                     for child_region in children.iter_mut() {
                         match child_region
                             .borrow_mut()
-                            .handle_pointer_event(event, action_tx)
+                            .handle_pointer_event(position, send)
                         {
                             PointerCapturedStatus::Captured { widget, requests } => {
                                 return PointerCapturedStatus::Captured { widget, requests };
                             }
-                            PointerCapturedStatus::InRegionButNotCaptured => {
-                                return PointerCapturedStatus::InRegionButNotCaptured;
-                            }
+                            PointerCapturedStatus::InRegionButNotCaptured => break,
                             PointerCapturedStatus::NotInRegion => {}
                         }
                     }
                 }
 
+                // None of the children captured the event, so bubble it up to
+                // this container region's handler (if it has one).
+                if let Some(mut handler) = self.bubble_handler.as_ref().and_then(|h| h.upgrade()) {
+                    // Skip handlers which have since been removed.
+                    if handler.assigned_region().upgrade().is_some() {
+                        if let EventCapturedStatus::Captured(requests) = send(&mut handler, true) {
+                            return PointerCapturedStatus::Captured {
+                                widget: handler,
+                                requests,
+                            };
+                        }
+                    }
+                }
+
                 return PointerCapturedStatus::InRegionButNotCaptured;
             }
         }
@@ -875,6 +2207,33 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
         PointerCapturedStatus::NotInRegion
     }
 
+    /// The same as `handle_pointer_event()`, except that the first widget
+    /// under the point is returned instead of being sent the event.
+    fn widget_at_point(&self, point: Point) -> HitTestStatus<A> {
+        if self.region.is_visible() {
+            if let Some(assigned_widget) = &self.assigned_widget {
+                if assigned_widget.is_hit_testable()
+                    && assigned_widget.is_hit(self.region.rect, point)
+                {
+                    return HitTestStatus::Hit(assigned_widget.widget.clone());
+                }
+            } else if self.region.rect.contains_point(point) {
+                if let Some(children) = &self.children {
+                    for child_region in children.iter() {
+                        match child_region.borrow().widget_at_point(point) {
+                            HitTestStatus::NotInRegion => {}
+                            status => return status,
+                        }
+                    }
+                }
+
+                return HitTestStatus::InRegionButNoHit;
+            }
+        }
+
+        HitTestStatus::NotInRegion
+    }
+
     fn mark_dirty(
         &mut self,
         dirty_widgets: &mut WidgetNodeSet<A>,
@@ -898,35 +2257,195 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
         }
     }
 
-    fn modify(
+    fn set_coordinate_origin(&mut self, coordinate_origin: CoordinateOrigin) {
+        self.region.coordinate_origin = coordinate_origin;
+
+        if let Some(children) = &mut self.children {
+            for child_entry in children.iter_mut() {
+                child_entry
+                    .borrow_mut()
+                    .set_coordinate_origin(coordinate_origin);
+            }
+        }
+    }
+
+    fn content_bounds(&self, layer_rect: Rect, bounds: &mut Option<Rect>) {
+        if !self.region.explicit_visibility {
+            return;
+        }
+
+        if let Some(assigned_widget_info) = &self.assigned_widget {
+            if assigned_widget_info.counts_toward_bounds {
+                let rect = self.region.widget_rect(layer_rect);
+                *bounds = Some(if let Some(bounds) = bounds {
+                    bounds.bounding_union(&rect)
+                } else {
+                    rect
+                });
+            }
+        } else if let Some(children) = &self.children {
+            for child_entry in children.iter() {
+                child_entry.borrow().content_bounds(layer_rect, bounds);
+            }
+        }
+    }
+
+    /// If this is a stack container, position its children one after the
+    /// other and size it to fit them.
+    ///
+    /// Returns whether any of the regions were moved or resized.
+    fn layout_stack(
         &mut self,
-        new_size: Option<Size>,
-        new_internal_anchor: Option<Anchor>,
-        new_parent_anchor: Option<Anchor>,
-        new_anchor_offset: Option<Point>,
-        new_explicit_visibility: Option<bool>,
         layer_rect: Rect,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         dirty_widgets: &mut WidgetNodeSet<A>,
         texture_rects_to_clear: &mut Vec<TextureRect>,
         widgets_just_shown: &mut WidgetNodeSet<A>,
         widgets_just_hidden: &mut WidgetNodeSet<A>,
-    ) {
-        let mut changed = false;
-        if let Some(new_size) = new_size {
-            if self.region.rect.size() != new_size {
-                self.region.rect.set_size(new_size);
-                changed = true;
-            }
-        }
-        if let Some(new_internal_anchor) = new_internal_anchor {
-            if self.region.internal_anchor != new_internal_anchor {
-                self.region.internal_anchor = new_internal_anchor;
-                changed = true;
-            }
-        }
-        if let Some(new_parent_anchor) = new_parent_anchor {
-            if self.region.parent_anchor != new_parent_anchor {
+    ) -> bool {
+        let stack_layout = if let Some(stack_layout) = self.stack_layout {
+            stack_layout
+        } else {
+            return false;
+        };
+
+        let child_sizes: Vec<Size> = self
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|child_entry| child_entry.borrow().region.rect.size())
+            .collect();
+        let (size, positions) = stack_layout.layout(&child_sizes);
+
+        let mut changed = self.modify(
+            Some(size),
+            None,
+            None,
+            None,
+            None,
+            layer_rect,
+            scale_factor,
+            dirty_widgets,
+            texture_rects_to_clear,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+
+        for (child_entry, position) in self.children.as_mut().unwrap().iter_mut().zip(positions) {
+            changed |= child_entry.borrow_mut().modify(
+                None,
+                Some(Anchor::top_left()),
+                Some(Anchor::top_left()),
+                Some(AnchorOffset::Points(position)),
+                None,
+                layer_rect,
+                scale_factor,
+                dirty_widgets,
+                texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+        }
+
+        changed
+    }
+
+    fn widgets_intersecting_rect(
+        &self,
+        rect: Rect,
+        layer_rect: Rect,
+        widgets: &mut Vec<StrongWidgetNodeEntry<A>>,
+    ) {
+        if !self.region.is_visible() {
+            return;
+        }
+
+        if let Some(assigned_widget_info) = &self.assigned_widget {
+            if self
+                .region
+                .widget_rect(layer_rect)
+                .intersection(&rect)
+                .is_some()
+            {
+                widgets.push(assigned_widget_info.widget.clone());
+            }
+        } else if let Some(children) = &self.children {
+            for child_entry in children.iter() {
+                child_entry
+                    .borrow()
+                    .widgets_intersecting_rect(rect, layer_rect, widgets);
+            }
+        }
+    }
+
+    fn set_layer_bounds_epsilon(&mut self, epsilon: f64) {
+        self.region.layer_bounds_epsilon = epsilon;
+
+        if let Some(children) = &mut self.children {
+            for child_entry in children.iter_mut() {
+                child_entry.borrow_mut().set_layer_bounds_epsilon(epsilon);
+            }
+        }
+    }
+
+    fn collect_widgets(&self, widgets: &mut Vec<StrongWidgetNodeEntry<A>>) {
+        if let Some(assigned_widget_info) = &self.assigned_widget {
+            widgets.push(assigned_widget_info.widget.clone());
+        } else if let Some(children) = &self.children {
+            for child_entry in children.iter() {
+                child_entry.borrow().collect_widgets(widgets);
+            }
+        }
+    }
+
+    fn collect_visible_painted_widgets(&mut self, widgets: &mut Vec<StrongWidgetNodeEntry<A>>) {
+        if self.region.is_visible() {
+            if let Some(assigned_widget_info) = &self.assigned_widget {
+                if let WidgetNodeType::Painted = assigned_widget_info.node_type {
+                    widgets.push(assigned_widget_info.widget.clone());
+                }
+            } else if let Some(children) = &mut self.children {
+                for child_entry in children.iter_mut() {
+                    child_entry
+                        .borrow_mut()
+                        .collect_visible_painted_widgets(widgets);
+                }
+            }
+        }
+    }
+
+    /// Returns whether anything about the region changed (in which case it
+    /// was laid out again).
+    fn modify(
+        &mut self,
+        new_size: Option<Size>,
+        new_internal_anchor: Option<Anchor>,
+        new_parent_anchor: Option<Anchor>,
+        new_anchor_offset: Option<AnchorOffset>,
+        new_explicit_visibility: Option<bool>,
+        layer_rect: Rect,
+        scale_factor: ScaleFactor2D,
+        dirty_widgets: &mut WidgetNodeSet<A>,
+        texture_rects_to_clear: &mut Vec<TextureRect>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> bool {
+        let mut changed = false;
+        if let Some(new_size) = new_size {
+            if self.region.rect.size() != new_size {
+                self.region.rect.set_size(new_size);
+                changed = true;
+            }
+        }
+        if let Some(new_internal_anchor) = new_internal_anchor {
+            if self.region.internal_anchor != new_internal_anchor {
+                self.region.internal_anchor = new_internal_anchor;
+                changed = true;
+            }
+        }
+        if let Some(new_parent_anchor) = new_parent_anchor {
+            if self.region.parent_anchor != new_parent_anchor {
                 self.region.parent_anchor = new_parent_anchor;
                 changed = true;
             }
@@ -945,69 +2464,164 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
         }
 
         if changed {
-            self.region.update_rect(scale_factor);
-            self.region.is_within_layer_rect = layer_rect.overlaps_with_rect(self.region.rect);
-            let visibility_changed_to = self.region.sync_visibility();
-
-            if let Some(assigned_widget_info) = &mut self.assigned_widget {
-                if let Some(new_visibility) = visibility_changed_to {
-                    if new_visibility {
-                        widgets_just_shown.insert(&assigned_widget_info.widget);
-                        widgets_just_hidden.remove(&assigned_widget_info.widget);
-
-                        if let WidgetNodeType::Painted = assigned_widget_info.node_type {
-                            dirty_widgets.insert(&assigned_widget_info.widget);
-                            if let Some(rect) = self.region.last_rendered_texture_rect.take() {
-                                texture_rects_to_clear.push(rect);
-                            }
-                        }
-                    } else {
-                        widgets_just_hidden.insert(&assigned_widget_info.widget);
-                        widgets_just_shown.remove(&assigned_widget_info.widget);
+            self.apply_layout(
+                layer_rect,
+                scale_factor,
+                dirty_widgets,
+                texture_rects_to_clear,
+                widgets_just_shown,
+                widgets_just_hidden,
+            );
+        }
 
-                        if let WidgetNodeType::Painted = assigned_widget_info.node_type {
-                            dirty_widgets.remove(&assigned_widget_info.widget);
-                            if let Some(rect) = self.region.last_rendered_texture_rect.take() {
-                                texture_rects_to_clear.push(rect);
-                            }
+        changed
+    }
+
+    /// Re-resolve the rect of this region, but only repaint and notify the
+    /// assigned widget if the rect or the visibility of the region actually
+    /// changed.
+    ///
+    /// Returns whether either of them changed.
+    fn update_layout(
+        &mut self,
+        layer_rect: Rect,
+        scale_factor: ScaleFactor2D,
+        dirty_widgets: &mut WidgetNodeSet<A>,
+        texture_rects_to_clear: &mut Vec<TextureRect>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> bool {
+        let mut resolved = self.region;
+        resolved.update_rect(scale_factor);
+        resolved.update_is_within_layer_rect(layer_rect);
+
+        if resolved.rect == self.region.rect
+            && resolved.physical_rect == self.region.physical_rect
+            && resolved.sync_visibility().is_none()
+        {
+            return false;
+        }
+
+        self.apply_layout(
+            layer_rect,
+            scale_factor,
+            dirty_widgets,
+            texture_rects_to_clear,
+            widgets_just_shown,
+            widgets_just_hidden,
+        );
+
+        true
+    }
+
+    /// Re-resolve the rect and visibility of this region after its layout
+    /// has changed, and update the assigned widget or the child regions.
+    fn apply_layout(
+        &mut self,
+        layer_rect: Rect,
+        scale_factor: ScaleFactor2D,
+        dirty_widgets: &mut WidgetNodeSet<A>,
+        texture_rects_to_clear: &mut Vec<TextureRect>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) {
+        self.region.update_rect(scale_factor);
+        self.region.update_is_within_layer_rect(layer_rect);
+        let visibility_changed_to = self.region.sync_visibility();
+
+        if let Some(assigned_widget_info) = &mut self.assigned_widget {
+            if let Some(new_visibility) = visibility_changed_to {
+                if new_visibility {
+                    widgets_just_shown.insert(&assigned_widget_info.widget);
+                    widgets_just_hidden.remove(&assigned_widget_info.widget);
+
+                    if let WidgetNodeType::Painted = assigned_widget_info.node_type {
+                        dirty_widgets.insert(&assigned_widget_info.widget);
+                        if let Some(rect) = self.region.last_rendered_texture_rect.take() {
+                            texture_rects_to_clear.push(rect);
                         }
                     }
-                } else if self.region.is_visible() {
+                } else {
+                    widgets_just_hidden.insert(&assigned_widget_info.widget);
+                    widgets_just_shown.remove(&assigned_widget_info.widget);
+
                     if let WidgetNodeType::Painted = assigned_widget_info.node_type {
-                        // Mark the region as dirty since it has changed.
-                        dirty_widgets.insert(&assigned_widget_info.widget);
+                        dirty_widgets.remove(&assigned_widget_info.widget);
                         if let Some(rect) = self.region.last_rendered_texture_rect.take() {
                             texture_rects_to_clear.push(rect);
                         }
                     }
                 }
-
-                assigned_widget_info
-                    .widget
-                    .borrow_mut()
-                    .on_region_changed(self.region.rect);
-            } else if let Some(children) = &mut self.children {
-                for child_entry in children.iter_mut() {
-                    child_entry.borrow_mut().parent_changed(
-                        self.region.rect,
-                        layer_rect,
-                        scale_factor,
-                        self.region.explicit_visibility && self.region.parent_explicit_visibility,
-                        dirty_widgets,
-                        texture_rects_to_clear,
-                        widgets_just_shown,
-                        widgets_just_hidden,
-                    );
+            } else if self.region.is_visible() {
+                if let WidgetNodeType::Painted = assigned_widget_info.node_type {
+                    // Mark the region as dirty since it has changed.
+                    dirty_widgets.insert(&assigned_widget_info.widget);
+                    if let Some(rect) = self.region.last_rendered_texture_rect.take() {
+                        texture_rects_to_clear.push(rect);
+                    }
                 }
             }
+
+            assigned_widget_info
+                .widget
+                .borrow_mut()
+                .on_region_changed(self.region.widget_rect(layer_rect));
+        } else if let Some(children) = &mut self.children {
+            for child_entry in children.iter_mut() {
+                child_entry.borrow_mut().parent_changed(
+                    scrolled_rect(self.region.rect, self.scroll_offset),
+                    layer_rect,
+                    scale_factor,
+                    self.region.explicit_visibility && self.region.parent_explicit_visibility,
+                    dirty_widgets,
+                    texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
+        }
+    }
+
+    /// Returns whether the scroll offset changed (in which case the children
+    /// were laid out again).
+    fn set_scroll_offset(
+        &mut self,
+        scroll_offset: Option<Point>,
+        layer_rect: Rect,
+        scale_factor: ScaleFactor2D,
+        dirty_widgets: &mut WidgetNodeSet<A>,
+        texture_rects_to_clear: &mut Vec<TextureRect>,
+        widgets_just_shown: &mut WidgetNodeSet<A>,
+        widgets_just_hidden: &mut WidgetNodeSet<A>,
+    ) -> bool {
+        if self.scroll_offset == scroll_offset {
+            return false;
+        }
+        self.scroll_offset = scroll_offset;
+
+        if let Some(children) = &mut self.children {
+            for child in children.iter_mut() {
+                child.borrow_mut().parent_changed(
+                    scrolled_rect(self.region.rect, self.scroll_offset),
+                    layer_rect,
+                    scale_factor,
+                    self.region.explicit_visibility && self.region.parent_explicit_visibility,
+                    dirty_widgets,
+                    texture_rects_to_clear,
+                    widgets_just_shown,
+                    widgets_just_hidden,
+                );
+            }
         }
+
+        true
     }
 
     fn parent_changed(
         &mut self,
         parent_rect: Rect,
         layer_rect: Rect,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         parent_explicit_visibility: bool,
         dirty_widgets: &mut WidgetNodeSet<A>,
         texture_rects_to_clear: &mut Vec<TextureRect>,
@@ -1016,7 +2630,7 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
     ) {
         self.region.update_parent_rect(parent_rect, scale_factor);
         self.region.parent_explicit_visibility = parent_explicit_visibility;
-        self.region.is_within_layer_rect = layer_rect.overlaps_with_rect(self.region.rect);
+        self.region.update_is_within_layer_rect(layer_rect);
         let visibility_changed_to = self.region.sync_visibility();
 
         if let Some(assigned_widget_info) = &mut self.assigned_widget {
@@ -1056,11 +2670,11 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
             assigned_widget_info
                 .widget
                 .borrow_mut()
-                .on_region_changed(self.region.rect);
+                .on_region_changed(self.region.widget_rect(layer_rect));
         } else if let Some(children) = &mut self.children {
             for child in children.iter_mut() {
                 child.borrow_mut().parent_changed(
-                    self.region.rect,
+                    scrolled_rect(self.region.rect, self.scroll_offset),
                     layer_rect,
                     scale_factor,
                     self.region.explicit_visibility && self.region.parent_explicit_visibility,
@@ -1074,12 +2688,75 @@ impl<A: Clone + Send + Sync + 'static> RegionTreeEntry<A> {
     }
 }
 
+/// The rect that the children of a container region are positioned
+/// relative to.
+fn scrolled_rect(rect: Rect, scroll_offset: Option<Point>) -> Rect {
+    if let Some(scroll_offset) = scroll_offset {
+        Rect::new(rect.pos() - scroll_offset, rect.size())
+    } else {
+        rect
+    }
+}
+
+fn clamp_scroll_offset(offset: Point, max_offset: Point) -> Point {
+    Point::new(
+        offset.x.min(max_offset.x).max(0.0),
+        offset.y.min(max_offset.y).max(0.0),
+    )
+}
+
+/// The scroll offset along one axis after one animation frame of springing
+/// back from past the bounds `min..max`, where `stiffness` is the rate (per
+/// second) at which the distance past the bound decays.
+///
+/// The offset snaps to the bound once it is within a tenth of a point of it.
+fn bounce_step(offset: f64, min: f64, max: f64, stiffness: f32, time_delta: f32) -> f64 {
+    let bound = offset.min(max).max(min);
+    let overshoot = (offset - bound) * f64::from((-stiffness * time_delta).exp());
+
+    if overshoot.abs() < 0.1 {
+        bound
+    } else {
+        bound + overshoot
+    }
+}
+
+/// Returns the new scroll offset along one axis needed to reveal the span
+/// `target_start..target_end` within the span `view_start..view_end`.
+fn scroll_offset_to_reveal(
+    offset: f64,
+    view_start: f64,
+    view_end: f64,
+    target_start: f64,
+    target_end: f64,
+    align: ScrollAlign,
+) -> f64 {
+    if target_start >= view_start && target_end <= view_end {
+        // The target is already fully visible.
+        return offset;
+    }
+
+    let delta = match align {
+        ScrollAlign::Start => target_start - view_start,
+        ScrollAlign::Center => ((target_start + target_end) - (view_start + view_end)) / 2.0,
+        ScrollAlign::End => target_end - view_end,
+    };
+
+    offset + delta
+}
+
 #[derive(Clone)]
 pub struct ContainerRegionRef<A: Clone + Send + Sync + 'static> {
     pub(crate) shared: WeakRegionTreeEntry<A>,
     pub(crate) assigned_layer: WeakWidgetLayerEntry<A>,
     assigned_layer_id: u64,
-    _unique_id: u64,
+    unique_id: u64,
+}
+
+impl<A: Clone + Send + Sync + 'static> ContainerRegionRef<A> {
+    pub fn unique_id(&self) -> u64 {
+        self.unique_id
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1089,50 +2766,113 @@ pub(crate) struct Region {
     pub physical_rect: PhysicalRect,
     pub internal_anchor: Anchor,
     pub parent_anchor: Anchor,
-    pub anchor_offset: Point,
+    pub anchor_offset: AnchorOffset,
     pub last_rendered_texture_rect: Option<TextureRect>,
     pub parent_rect: Rect,
     pub explicit_visibility: bool,
     pub parent_explicit_visibility: bool,
     pub is_within_layer_rect: bool,
     is_visible: bool,
+    /// Overrides `parent_anchor` if set.
+    pub normalized_parent_anchor: Option<NormalizedAnchor>,
+    /// The resolution that the assigned widget is painted at relative to
+    /// the physical size of this region (see
+    /// `WidgetNodeRequests::set_render_scale`).
+    pub render_scale: f32,
+    /// The part of the layer's texture to repaint if only part of the
+    /// assigned widget is dirty (see `WidgetNodeRequests::repaint_rect`).
+    ///
+    /// This is ignored if the whole widget has been marked dirty since it
+    /// was last rendered.
+    pub partial_repaint_rect: Option<TextureRect>,
+    /// The coordinate origin of the layer this region is in.
+    pub coordinate_origin: CoordinateOrigin,
+    /// The layer bounds epsilon of the layer this region is in.
+    pub layer_bounds_epsilon: f64,
 }
 
 impl Region {
-    fn update_rect(&mut self, scale_factor: ScaleFactor) {
+    /// Update whether this region overlaps with the layer rect.
+    ///
+    /// A region that is already within the layer rect stays within it until
+    /// it has moved more than `layer_bounds_epsilon` past the edges of the
+    /// layer, so that a region straddling an edge during a smooth animation
+    /// does not rapidly toggle its visibility at sub-pixel boundaries.
+    fn update_is_within_layer_rect(&mut self, layer_rect: Rect) {
+        self.is_within_layer_rect = if self.is_within_layer_rect {
+            layer_rect.overlaps_with_rect_with_epsilon(self.rect, self.layer_bounds_epsilon)
+        } else {
+            layer_rect.overlaps_with_rect(self.rect)
+        };
+    }
+
+    fn update_rect(&mut self, scale_factor: ScaleFactor2D) {
         self.update_parent_rect(self.parent_rect, scale_factor);
     }
 
-    fn update_parent_rect(&mut self, parent_rect: Rect, scale_factor: ScaleFactor) {
-        let parent_anchor_pos_x = match self.parent_anchor.h_align {
-            HAlign::Left => parent_rect.x(),
-            HAlign::Center => parent_rect.center_x(),
-            HAlign::Right => parent_rect.x2(),
-        };
-        let parent_anchor_pos_y = match self.parent_anchor.v_align {
-            VAlign::Top => parent_rect.y(),
-            VAlign::Center => parent_rect.center_y(),
-            VAlign::Bottom => parent_rect.y2(),
+    fn update_parent_rect(&mut self, parent_rect: Rect, scale_factor: ScaleFactor2D) {
+        if let AnchorOffset::Pinned(edges) = self.anchor_offset {
+            self.rect
+                .set_size(edges.pinned_size(self.rect.size(), parent_rect.size()));
+        }
+
+        // Layers with a bottom-left origin are laid out upside down.
+        let flip_y = self.coordinate_origin == CoordinateOrigin::BottomLeft;
+        let (parent_v_align, internal_v_align) = if flip_y {
+            (
+                self.parent_anchor.v_align.flipped(),
+                self.internal_anchor.v_align.flipped(),
+            )
+        } else {
+            (self.parent_anchor.v_align, self.internal_anchor.v_align)
         };
 
+        let (parent_anchor_pos_x, parent_anchor_pos_y) =
+            if let Some(normalized_anchor) = self.normalized_parent_anchor {
+                let normalized_anchor = if flip_y {
+                    normalized_anchor.flipped_y()
+                } else {
+                    normalized_anchor
+                };
+                let pos = normalized_anchor.resolve(parent_rect);
+                (pos.x, pos.y)
+            } else {
+                let x = match self.parent_anchor.h_align {
+                    HAlign::Left => parent_rect.x(),
+                    HAlign::Center => parent_rect.center_x(),
+                    HAlign::Right => parent_rect.x2(),
+                };
+                let y = match parent_v_align {
+                    VAlign::Top => parent_rect.y(),
+                    VAlign::Center => parent_rect.center_y(),
+                    VAlign::Bottom => parent_rect.y2(),
+                };
+                (x, y)
+            };
+
         self.parent_rect = parent_rect;
 
-        let internal_anchor_pos_x = parent_anchor_pos_x + self.anchor_offset.x;
-        let internal_anchor_pos_y = parent_anchor_pos_y + self.anchor_offset.y;
+        let mut anchor_offset = self.anchor_offset.resolve(parent_rect.size());
+        if flip_y {
+            anchor_offset.y = -anchor_offset.y;
+        }
+
+        let internal_anchor_pos_x = parent_anchor_pos_x + anchor_offset.x;
+        let internal_anchor_pos_y = parent_anchor_pos_y + anchor_offset.y;
 
         let new_x = match self.internal_anchor.h_align {
             HAlign::Left => internal_anchor_pos_x,
             HAlign::Center => internal_anchor_pos_x - (f64::from(self.rect.width()) / 2.0),
             HAlign::Right => internal_anchor_pos_x - f64::from(self.rect.width()),
         };
-        let new_y = match self.internal_anchor.v_align {
+        let new_y = match internal_v_align {
             VAlign::Top => internal_anchor_pos_y,
             VAlign::Center => internal_anchor_pos_y - (f64::from(self.rect.height()) / 2.0),
             VAlign::Bottom => internal_anchor_pos_y - f64::from(self.rect.height()),
         };
 
         self.rect.set_pos(Point::new(new_x, new_y));
-        self.physical_rect = self.rect.to_physical(scale_factor);
+        self.physical_rect = self.rect.to_physical_2d(scale_factor);
     }
 
     pub fn sync_visibility(&mut self) -> Option<bool> {
@@ -1152,6 +2892,63 @@ impl Region {
     pub fn is_visible(&self) -> bool {
         self.is_visible
     }
+
+    /// The rect of this region in the coordinates of its layer (see
+    /// `CoordinateOrigin`).
+    pub fn widget_rect(&self, layer_rect: Rect) -> Rect {
+        self.coordinate_origin.convert_rect(self.rect, layer_rect)
+    }
+
+    /// The part of the layer's texture to repaint, or `None` if the whole
+    /// region should be repainted.
+    pub fn repaint_texture_rect(&self) -> Option<TextureRect> {
+        // Marking the whole widget dirty takes the last rendered rect.
+        if self.last_rendered_texture_rect.is_some() {
+            self.partial_repaint_rect
+        } else {
+            None
+        }
+    }
+}
+
+/// The part of the layer's texture covered by the given rect within a
+/// widget's region, clipped to the part of the texture that the widget was
+/// last rendered to.
+///
+/// The rect is rounded outwards to whole physical pixels.
+fn partial_repaint_texture_rect(
+    physical_rect: PhysicalRect,
+    rect: Rect,
+    scale_factor: ScaleFactor2D,
+    last_rendered_texture_rect: TextureRect,
+) -> Option<TextureRect> {
+    let x = (rect.x() * f64::from(scale_factor.x)).floor() as i32;
+    let y = (rect.y() * f64::from(scale_factor.y)).floor() as i32;
+    let x2 = (rect.x2() * f64::from(scale_factor.x)).ceil() as i32;
+    let y2 = (rect.y2() * f64::from(scale_factor.y)).ceil() as i32;
+
+    if x2 <= x || y2 <= y {
+        return None;
+    }
+
+    let texture_rect = TextureRect::from_physical_rect(PhysicalRect::new(
+        PhysicalPoint::new(physical_rect.pos.x + x, physical_rect.pos.y + y),
+        PhysicalSize::new((x2 - x) as u32, (y2 - y) as u32),
+    ));
+
+    texture_rect.intersection(&last_rendered_texture_rect)
+}
+
+/// How a scrollable container region behaves when it is scrolled past its
+/// bounds (see `AppWindow::set_container_region_scroll_behavior()`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollBehavior {
+    /// The scroll offset is clamped to the bounds.
+    Clamp,
+    /// The scroll offset can temporarily go past the bounds (i.e. while the
+    /// user is dragging the content), and then springs back on each
+    /// `InputEvent::Animation`. A higher `stiffness` springs back faster.
+    Bounce { stiffness: f32 },
 }
 
 #[derive(Clone)]
@@ -1163,8 +2960,7 @@ pub enum ParentAnchorType<A: Clone + Send + Sync + 'static> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{WidgetNode, WidgetNodeType};
-    use std::cell::Ref;
+    use crate::{Axis, StackAlign, WidgetNode, WidgetNodeType};
 
     impl Region {
         fn new_test_region(
@@ -1191,13 +2987,22 @@ mod tests {
                 parent_explicit_visibility,
                 is_within_layer_rect,
                 is_visible: explicit_visibility & parent_explicit_visibility & is_within_layer_rect,
+                normalized_parent_anchor: region_info.normalized_parent_anchor,
+                render_scale: 1.0,
+                partial_repaint_rect: None,
+                coordinate_origin: CoordinateOrigin::TopLeft,
+                layer_bounds_epsilon: DEFAULT_LAYER_BOUNDS_EPSILON,
             }
         }
     }
 
-    impl<A: Clone + Send + Sync + 'static> StrongRegionTreeEntry<A> {
-        fn borrow(&self) -> Ref<'_, RegionTreeEntry<A>> {
-            RefCell::borrow(&self.shared)
+    impl AnchorOffset {
+        fn as_points(&self) -> Point {
+            if let AnchorOffset::Points(offset) = self {
+                *offset
+            } else {
+                panic!("anchor offset is not in points");
+            }
         }
     }
 
@@ -1217,6 +3022,7 @@ mod tests {
         fn on_input_event(
             &mut self,
             event: &InputEvent,
+            _text_context: &TextContext,
             _action_tx: &mut Sender<()>,
         ) -> EventCapturedStatus {
             println!(
@@ -1243,6 +3049,7 @@ mod tests {
         fn on_input_event(
             &mut self,
             event: &InputEvent,
+            _text_context: &TextContext,
             _action_tx: &mut Sender<()>,
         ) -> EventCapturedStatus {
             println!(
@@ -1257,7 +3064,7 @@ mod tests {
     fn test_region_tree() {
         let layer_rect = Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 100.0));
         let layer_explicit_visibility = true;
-        let scale_factor = ScaleFactor(1.0);
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
 
         let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
         let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
@@ -1287,7 +3094,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(20.0, 10.0),
+            anchor_offset: AnchorOffset::Points(Point::new(20.0, 10.0)),
+            normalized_parent_anchor: None,
         };
         let container_root0_explicit_visibility = true;
         let container_root0_ref = region_tree
@@ -1299,15 +3107,15 @@ mod tests {
             )
             .unwrap();
         let container_root0_expected_rect = Rect::new(
-            container_root0_region_info.anchor_offset,
+            container_root0_region_info.anchor_offset.as_points(),
             container_root0_region_info.size,
         );
         assert_region(
             &region_tree.roots[0].borrow().region,
             &Region::new_test_region(
-                container_root0_ref._unique_id,
+                container_root0_ref.unique_id,
                 container_root0_expected_rect,
-                container_root0_expected_rect.to_physical(scale_factor),
+                container_root0_expected_rect.to_physical_2d(scale_factor),
                 container_root0_region_info,
                 None,
                 layer_rect,
@@ -1330,7 +3138,8 @@ mod tests {
                 v_align: VAlign::Bottom,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(-20.0, -10.0),
+            anchor_offset: AnchorOffset::Points(Point::new(-20.0, -10.0)),
+            normalized_parent_anchor: None,
         };
         let container_root1_explicit_visibility = false;
         let container_root1_ref = region_tree
@@ -1343,9 +3152,9 @@ mod tests {
             .unwrap();
         let container_root1_expected_rect = Rect::new(
             Point {
-                x: layer_rect.x2() + container_root1_region_info.anchor_offset.x
+                x: layer_rect.x2() + container_root1_region_info.anchor_offset.as_points().x
                     - f64::from(container_root1_region_info.size.width()),
-                y: layer_rect.y2() + container_root1_region_info.anchor_offset.y
+                y: layer_rect.y2() + container_root1_region_info.anchor_offset.as_points().y
                     - f64::from(container_root1_region_info.size.height()),
             },
             container_root1_region_info.size,
@@ -1353,9 +3162,9 @@ mod tests {
         assert_region(
             &region_tree.roots[1].borrow().region,
             &Region::new_test_region(
-                container_root1_ref._unique_id,
+                container_root1_ref.unique_id,
                 container_root1_expected_rect,
-                container_root1_expected_rect.to_physical(scale_factor),
+                container_root1_expected_rect.to_physical_2d(scale_factor),
                 container_root1_region_info,
                 None,
                 layer_rect,
@@ -1378,7 +3187,8 @@ mod tests {
                 v_align: VAlign::Bottom,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(100.0, 100.0),
+            anchor_offset: AnchorOffset::Points(Point::new(100.0, 100.0)),
+            normalized_parent_anchor: None,
         };
         let container_root2_explicit_visibility = true;
         let container_root2_ref = region_tree
@@ -1391,17 +3201,17 @@ mod tests {
             .unwrap();
         let container_root2_expected_rect = Rect::new(
             Point {
-                x: layer_rect.x2() + container_root2_region_info.anchor_offset.x,
-                y: layer_rect.y2() + container_root2_region_info.anchor_offset.y,
+                x: layer_rect.x2() + container_root2_region_info.anchor_offset.as_points().x,
+                y: layer_rect.y2() + container_root2_region_info.anchor_offset.as_points().y,
             },
             container_root2_region_info.size,
         );
         assert_region(
             &region_tree.roots[2].borrow().region,
             &Region::new_test_region(
-                container_root2_ref._unique_id,
+                container_root2_ref.unique_id,
                 container_root2_expected_rect,
-                container_root2_expected_rect.to_physical(scale_factor),
+                container_root2_expected_rect.to_physical_2d(scale_factor),
                 container_root2_region_info,
                 None,
                 layer_rect,
@@ -1424,7 +3234,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(300.0, 100.0),
+            anchor_offset: AnchorOffset::Points(Point::new(300.0, 100.0)),
+            normalized_parent_anchor: None,
         };
         let container_root3_explicit_visibility = false;
         let container_root3_ref = region_tree
@@ -1437,17 +3248,17 @@ mod tests {
             .unwrap();
         let container_root3_expected_rect = Rect::new(
             Point {
-                x: layer_rect.x2() + container_root3_region_info.anchor_offset.x,
-                y: layer_rect.y() + container_root3_region_info.anchor_offset.y,
+                x: layer_rect.x2() + container_root3_region_info.anchor_offset.as_points().x,
+                y: layer_rect.y() + container_root3_region_info.anchor_offset.as_points().y,
             },
             container_root3_region_info.size,
         );
         assert_region(
             &region_tree.roots[3].borrow().region,
             &Region::new_test_region(
-                container_root3_ref._unique_id,
+                container_root3_ref.unique_id,
                 container_root3_expected_rect,
-                container_root3_expected_rect.to_physical(scale_factor),
+                container_root3_expected_rect.to_physical_2d(scale_factor),
                 container_root3_region_info,
                 None,
                 layer_rect,
@@ -1470,7 +3281,8 @@ mod tests {
                 v_align: VAlign::Center,
             },
             parent_anchor_type: ParentAnchorType::ContainerRegion(container_root0_ref.clone()),
-            anchor_offset: Point::new(-10.0, 4.0),
+            anchor_offset: AnchorOffset::Points(Point::new(-10.0, 4.0)),
+            normalized_parent_anchor: None,
         };
         let container_root0_0_explicit_visibility = true;
         let container_root0_0_ref = region_tree
@@ -1485,10 +3297,10 @@ mod tests {
             Point {
                 x: container_root0_expected_rect.center_x()
                     - (f64::from(container_root0_0_region_info.size.width()) / 2.0)
-                    + container_root0_0_region_info.anchor_offset.x,
+                    + container_root0_0_region_info.anchor_offset.as_points().x,
                 y: container_root0_expected_rect.center_y()
                     - (f64::from(container_root0_0_region_info.size.height()) / 2.0)
-                    + container_root0_0_region_info.anchor_offset.y,
+                    + container_root0_0_region_info.anchor_offset.as_points().y,
             },
             container_root0_0_region_info.size,
         );
@@ -1497,9 +3309,9 @@ mod tests {
                 .borrow()
                 .region,
             &Region::new_test_region(
-                container_root0_0_ref._unique_id,
+                container_root0_0_ref.unique_id,
                 container_root0_0_expected_rect,
-                container_root0_0_expected_rect.to_physical(scale_factor),
+                container_root0_0_expected_rect.to_physical_2d(scale_factor),
                 container_root0_0_region_info,
                 None,
                 container_root0_expected_rect,
@@ -1538,7 +3350,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(20.0, 40.0),
+            anchor_offset: AnchorOffset::Points(Point::new(20.0, 40.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root4_explicit_visibility = true;
         region_tree
@@ -1552,7 +3365,7 @@ mod tests {
             )
             .unwrap();
         let widget_root4_expected_rect = Rect::new(
-            widget_root4_region_info.anchor_offset,
+            widget_root4_region_info.anchor_offset.as_points(),
             widget_root4_region_info.size,
         );
         assert_region(
@@ -1566,7 +3379,7 @@ mod tests {
                     .region
                     .id,
                 widget_root4_expected_rect,
-                widget_root4_expected_rect.to_physical(scale_factor),
+                widget_root4_expected_rect.to_physical_2d(scale_factor),
                 widget_root4_region_info,
                 None,
                 layer_rect,
@@ -1597,7 +3410,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(80.0, 40.0),
+            anchor_offset: AnchorOffset::Points(Point::new(80.0, 40.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root5_explicit_visibility = false;
         region_tree
@@ -1611,7 +3425,7 @@ mod tests {
             )
             .unwrap();
         let widget_root5_expected_rect = Rect::new(
-            widget_root5_region_info.anchor_offset,
+            widget_root5_region_info.anchor_offset.as_points(),
             widget_root5_region_info.size,
         );
         assert_region(
@@ -1625,7 +3439,7 @@ mod tests {
                     .region
                     .id,
                 widget_root5_expected_rect,
-                widget_root5_expected_rect.to_physical(scale_factor),
+                widget_root5_expected_rect.to_physical_2d(scale_factor),
                 widget_root5_region_info,
                 None,
                 layer_rect,
@@ -1658,7 +3472,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::Layer,
-            anchor_offset: Point::new(300.0, 40.0),
+            anchor_offset: AnchorOffset::Points(Point::new(300.0, 40.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root6_explicit_visibility = true;
         region_tree
@@ -1672,7 +3487,7 @@ mod tests {
             )
             .unwrap();
         let widget_root6_expected_rect = Rect::new(
-            widget_root6_region_info.anchor_offset,
+            widget_root6_region_info.anchor_offset.as_points(),
             widget_root6_region_info.size,
         );
         assert_region(
@@ -1686,7 +3501,7 @@ mod tests {
                     .region
                     .id,
                 widget_root6_expected_rect,
-                widget_root6_expected_rect.to_physical(scale_factor),
+                widget_root6_expected_rect.to_physical_2d(scale_factor),
                 widget_root6_region_info,
                 None,
                 layer_rect,
@@ -1720,7 +3535,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::ContainerRegion(container_root0_0_ref.clone()),
-            anchor_offset: Point::new(2.0, 2.0),
+            anchor_offset: AnchorOffset::Points(Point::new(2.0, 2.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root0_0_0_explicit_visibility = true;
         region_tree
@@ -1734,7 +3550,8 @@ mod tests {
             )
             .unwrap();
         let widget_root0_0_0_expected_rect = Rect::new(
-            container_root0_0_expected_rect.pos() + widget_root0_0_0_region_info.anchor_offset,
+            container_root0_0_expected_rect.pos()
+                + widget_root0_0_0_region_info.anchor_offset.as_points(),
             widget_root0_0_0_region_info.size,
         );
         assert_region(
@@ -1754,7 +3571,7 @@ mod tests {
                     .region
                     .id,
                 widget_root0_0_0_expected_rect,
-                widget_root0_0_0_expected_rect.to_physical(scale_factor),
+                widget_root0_0_0_expected_rect.to_physical_2d(scale_factor),
                 widget_root0_0_0_region_info,
                 None,
                 container_root0_0_expected_rect,
@@ -1788,7 +3605,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::ContainerRegion(container_root1_ref.clone()),
-            anchor_offset: Point::new(2.0, 2.0),
+            anchor_offset: AnchorOffset::Points(Point::new(2.0, 2.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root1_0_explicit_visibility = true;
         region_tree
@@ -1802,7 +3620,8 @@ mod tests {
             )
             .unwrap();
         let widget_root1_0_expected_rect = Rect::new(
-            container_root1_expected_rect.pos() + widget_root1_0_region_info.anchor_offset,
+            container_root1_expected_rect.pos()
+                + widget_root1_0_region_info.anchor_offset.as_points(),
             widget_root1_0_region_info.size,
         );
         assert_region(
@@ -1818,7 +3637,7 @@ mod tests {
                     .region
                     .id,
                 widget_root1_0_expected_rect,
-                widget_root1_0_expected_rect.to_physical(scale_factor),
+                widget_root1_0_expected_rect.to_physical_2d(scale_factor),
                 widget_root1_0_region_info,
                 None,
                 container_root1_expected_rect,
@@ -1852,7 +3671,8 @@ mod tests {
                 v_align: VAlign::Top,
             },
             parent_anchor_type: ParentAnchorType::ContainerRegion(container_root2_ref.clone()),
-            anchor_offset: Point::new(2.0, 2.0),
+            anchor_offset: AnchorOffset::Points(Point::new(2.0, 2.0)),
+            normalized_parent_anchor: None,
         };
         let widget_root2_0_explicit_visibility = true;
         region_tree
@@ -1866,7 +3686,8 @@ mod tests {
             )
             .unwrap();
         let widget_root2_0_expected_rect = Rect::new(
-            container_root2_expected_rect.pos() + widget_root2_0_region_info.anchor_offset,
+            container_root2_expected_rect.pos()
+                + widget_root2_0_region_info.anchor_offset.as_points(),
             widget_root2_0_region_info.size,
         );
         assert_region(
@@ -1882,7 +3703,7 @@ mod tests {
                     .region
                     .id,
                 widget_root2_0_expected_rect,
-                widget_root2_0_expected_rect.to_physical(scale_factor),
+                widget_root2_0_expected_rect.to_physical_2d(scale_factor),
                 widget_root2_0_region_info,
                 None,
                 container_root2_expected_rect,
@@ -1902,35 +3723,1664 @@ mod tests {
         // TODO: more tests
     }
 
-    fn assert_region(region: &Region, expected_region: &Region) {
-        assert_eq!(region.id, expected_region.id);
-        if !region.rect.partial_eq_with_epsilon(expected_region.rect) {
-            panic!(
-                "region.rect: {:?}, expected_region.rect: {:?}",
-                &region.rect, &expected_region.rect
-            );
-        }
-        assert_eq!(region.internal_anchor, expected_region.internal_anchor);
-        assert_eq!(region.parent_anchor, expected_region.parent_anchor);
-        if !region
-            .anchor_offset
-            .partial_eq_with_epsilon(expected_region.anchor_offset)
-        {
-            panic!(
-                "region.anchor_offset: {:?}, expected_region.anchor_offset: {:?}",
-                &region.anchor_offset, &expected_region.anchor_offset
-            );
-        }
-        assert_eq!(
-            region.last_rendered_texture_rect,
-            expected_region.last_rendered_texture_rect
-        );
-        if !region
-            .parent_rect
-            .partial_eq_with_epsilon(expected_region.parent_rect)
-        {
-            panic!(
-                "region.parent_rect: {:?}, expected_region.parent_rect: {:?}",
+    #[test]
+    fn test_percent_anchor_offset() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let mut container_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(100.0, 50.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(10.0, 20.0)),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo {
+                    size: Size::new(10.0, 10.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::ContainerRegion(container_ref.clone()),
+                    anchor_offset: AnchorOffset::Percent(Point::new(25.0, 50.0)),
+                    normalized_parent_anchor: None,
+                },
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let widget_rect = || {
+            widget_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .rect
+        };
+
+        assert!(widget_rect()
+            .pos()
+            .partial_eq_with_epsilon(Point::new(10.0 + 25.0, 20.0 + 25.0)));
+
+        // The offset should follow the size of the parent.
+        region_tree
+            .modify_container_region(
+                &mut container_ref,
+                Some(Size::new(200.0, 100.0)),
+                None,
+                None,
+                None,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        assert!(widget_rect()
+            .pos()
+            .partial_eq_with_epsilon(Point::new(10.0 + 50.0, 20.0 + 50.0)));
+    }
+
+    #[test]
+    fn test_normalized_parent_anchor() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let container_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(100.0, 80.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(40.0, 20.0)),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo {
+                    size: Size::new(10.0, 10.0),
+                    internal_anchor: Anchor::center(),
+                    // Ignored in favor of the normalized anchor.
+                    parent_anchor: Anchor::bottom_right(),
+                    parent_anchor_type: ParentAnchorType::ContainerRegion(container_ref),
+                    anchor_offset: AnchorOffset::Points(Point::new(1.0, 2.0)),
+                    normalized_parent_anchor: Some(NormalizedAnchor::new(0.25, 0.75)),
+                },
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        // The anchor point is a quarter of the way across the container and
+        // three quarters of the way down, plus the offset.
+        let anchor_point = Point::new(40.0 + 25.0 + 1.0, 20.0 + 60.0 + 2.0);
+        let rect = widget_entry
+            .assigned_region()
+            .upgrade()
+            .unwrap()
+            .borrow()
+            .region
+            .rect;
+        assert!(rect
+            .pos()
+            .partial_eq_with_epsilon(Point::new(anchor_point.x - 5.0, anchor_point.y - 5.0)));
+
+        // Discrete anchors convert to and from normalized anchors.
+        assert_eq!(
+            NormalizedAnchor::from(Anchor::bottom_center()),
+            NormalizedAnchor::new(0.5, 1.0)
+        );
+        assert_eq!(
+            NormalizedAnchor::new(1.0, 0.5).to_anchor(),
+            Some(Anchor::center_right())
+        );
+        assert_eq!(NormalizedAnchor::new(0.25, 0.75).to_anchor(), None);
+    }
+
+    #[test]
+    fn test_reparent_widget_region() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let container_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(100.0, 100.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(50.0, 60.0)),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo {
+                    size: Size::new(10.0, 10.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(5.0, 5.0)),
+                    normalized_parent_anchor: None,
+                },
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        assert_eq!(region_tree.roots.len(), 2);
+
+        region_tree
+            .reparent_widget_region(
+                &widget_entry,
+                RegionInfo {
+                    size: Size::new(20.0, 10.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::ContainerRegion(container_ref.clone()),
+                    anchor_offset: AnchorOffset::Points(Point::new(5.0, 5.0)),
+                    normalized_parent_anchor: None,
+                },
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        // The widget region should now be a child of the container region.
+        assert_eq!(region_tree.roots.len(), 1);
+        let container_entry = container_ref.shared.upgrade().unwrap();
+        let container_entry = container_entry.borrow();
+        let children = container_entry.children.as_ref().unwrap();
+        assert_eq!(children.len(), 1);
+
+        // The same widget node and region should still be assigned to each other.
+        assert!(Rc::ptr_eq(
+            &children[0].shared,
+            &widget_entry.assigned_region().upgrade().unwrap()
+        ));
+        let child_entry = children[0].borrow();
+        let assigned_widget = &child_entry.assigned_widget.as_ref().unwrap().widget;
+        assert_eq!(assigned_widget.unique_id(), widget_entry.unique_id());
+
+        assert!(child_entry
+            .region
+            .rect
+            .partial_eq_with_epsilon(Rect::new(Point::new(55.0, 65.0), Size::new(20.0, 10.0))));
+        assert!(region_tree.dirty_widgets.contains(&widget_entry));
+    }
+
+    #[test]
+    fn test_stack_container() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let stack_ref = region_tree
+            .add_stack_container(
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 20.0),
+                    Size::new(100.0, 100.0),
+                )),
+                StackLayout::new(Axis::Horizontal, 8.0, StackAlign::Start),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        let stack_rect = || stack_ref.shared.upgrade().unwrap().borrow().region.rect;
+
+        // An empty stack has no size.
+        assert!(stack_rect()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 20.0), Size::new(0.0, 0.0))));
+
+        let mut widget_entries: Vec<StrongWidgetNodeEntry<()>> = Vec::new();
+        for (i, size) in [
+            Size::new(20.0, 10.0),
+            Size::new(30.0, 20.0),
+            Size::new(40.0, 16.0),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget {
+                    id: i as u64,
+                }))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                i as u64,
+            );
+            region_tree
+                .add_widget_region(
+                    &mut widget_entry,
+                    RegionInfo::pinned(
+                        Edges::default(),
+                        *size,
+                        ParentAnchorType::ContainerRegion(stack_ref.clone()),
+                    ),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            widget_entries.push(widget_entry);
+        }
+
+        let widget_pos = |widget_entry: &StrongWidgetNodeEntry<()>| {
+            widget_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .rect
+                .pos()
+        };
+
+        assert_eq!(widget_pos(&widget_entries[0]), Point::new(10.0, 20.0));
+        assert_eq!(widget_pos(&widget_entries[1]), Point::new(38.0, 20.0));
+        assert_eq!(widget_pos(&widget_entries[2]), Point::new(76.0, 20.0));
+        assert!(stack_rect()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 20.0), Size::new(106.0, 20.0))));
+        assert!(region_tree.validate().is_ok());
+
+        // Resizing a child moves the children after it.
+        region_tree.modify_widget_region(
+            &widget_entries[1],
+            Some(Size::new(50.0, 20.0)),
+            None,
+            None,
+            None,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(widget_pos(&widget_entries[2]), Point::new(96.0, 20.0));
+        assert!(region_tree.dirty_widgets.contains(&widget_entries[2]));
+
+        // Removing a child closes the gap.
+        region_tree.remove_widget_region(
+            &mut widget_entries[0],
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(widget_pos(&widget_entries[1]), Point::new(10.0, 20.0));
+        assert_eq!(widget_pos(&widget_entries[2]), Point::new(68.0, 20.0));
+        assert!(stack_rect()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 20.0), Size::new(98.0, 20.0))));
+    }
+
+    #[test]
+    fn test_remove_container_region_recursive() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let region_info = |parent_anchor_type: ParentAnchorType<()>| RegionInfo {
+            size: Size::new(50.0, 50.0),
+            internal_anchor: Anchor::top_left(),
+            parent_anchor: Anchor::top_left(),
+            parent_anchor_type,
+            anchor_offset: AnchorOffset::default(),
+            normalized_parent_anchor: None,
+        };
+        let new_widget_entry = |id: u64| {
+            StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id }))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                id,
+            )
+        };
+
+        // outer_container
+        // |- widget 0
+        // |- inner_container
+        //    |- widget 1
+        //    |- widget 2
+        // widget 3
+        let outer_container_ref = region_tree
+            .add_container_region(
+                region_info(ParentAnchorType::Layer),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        let inner_container_ref = region_tree
+            .add_container_region(
+                region_info(ParentAnchorType::ContainerRegion(
+                    outer_container_ref.clone(),
+                )),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut widget_entries: Vec<StrongWidgetNodeEntry<()>> =
+            (0..4).map(new_widget_entry).collect();
+        let parents = [
+            ParentAnchorType::ContainerRegion(outer_container_ref.clone()),
+            ParentAnchorType::ContainerRegion(inner_container_ref.clone()),
+            ParentAnchorType::ContainerRegion(inner_container_ref.clone()),
+            ParentAnchorType::Layer,
+        ];
+        for (widget_entry, parent) in widget_entries.iter_mut().zip(parents) {
+            region_tree
+                .add_widget_region(
+                    widget_entry,
+                    region_info(parent),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+        }
+        assert_eq!(region_tree.roots.len(), 2);
+
+        // The widgets are collected depth-first in the order their regions
+        // were added, so the widgets of the inner container come first.
+        let mut widgets = region_tree
+            .container_region_widgets(&outer_container_ref)
+            .unwrap();
+        assert_eq!(
+            widgets.iter().map(|w| w.unique_id()).collect::<Vec<_>>(),
+            vec![1, 2, 0]
+        );
+
+        for widget_entry in widgets.iter_mut() {
+            region_tree.remove_widget_region(
+                widget_entry,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            );
+        }
+        region_tree
+            .remove_container_region_recursive(outer_container_ref.clone())
+            .unwrap();
+
+        // Only the widget outside of the container is left.
+        assert_eq!(region_tree.roots.len(), 1);
+        assert!(outer_container_ref.shared.upgrade().is_none());
+        assert!(inner_container_ref.shared.upgrade().is_none());
+        for widget_entry in widget_entries[0..3].iter() {
+            assert!(widget_entry.assigned_region().upgrade().is_none());
+        }
+        assert!(widget_entries[3].assigned_region().upgrade().is_some());
+        assert!(matches!(
+            region_tree.container_region_widgets(&outer_container_ref),
+            Err(FirewheelError::ContainerRegionRemoved)
+        ));
+    }
+
+    #[test]
+    fn test_region_info_from_layer_rect() {
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(1.0, 1.0),
+            0,
+        );
+
+        let rect = Rect::new(Point::new(120.0, 35.5), Size::new(64.0, 20.0));
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(rect),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let region = widget_entry.assigned_region().upgrade().unwrap();
+        assert!(region.borrow().region.rect.partial_eq_with_epsilon(rect));
+    }
+
+    #[test]
+    fn test_anisotropic_scale_factor() {
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(2.0, 1.5),
+            0,
+        );
+        assert_eq!(
+            region_tree.layer_physical_size(),
+            PhysicalSize::new(800, 450)
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 20.0),
+                    Size::new(30.0, 40.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let physical_rect = |widget_entry: &StrongWidgetNodeEntry<()>| {
+            widget_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .physical_rect
+        };
+
+        // The x axis is scaled by 2.0 and the y axis by 1.5.
+        assert_eq!(
+            physical_rect(&widget_entry),
+            PhysicalRect::new(PhysicalPoint::new(20, 30), PhysicalSize::new(60, 60))
+        );
+
+        // Changing only one of the factors rescales only that axis.
+        region_tree.set_layer_size(
+            Size::new(400.0, 300.0),
+            ScaleFactor2D::new(1.0, 1.5),
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(
+            physical_rect(&widget_entry),
+            PhysicalRect::new(PhysicalPoint::new(10, 30), PhysicalSize::new(30, 60))
+        );
+    }
+
+    #[test]
+    fn test_layer_bounds_epsilon() {
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(1.0, 1.0),
+            0,
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(399.996, 20.0),
+                    Size::new(30.0, 40.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        assert!(region_tree.is_widget_visible(&widget_entry));
+
+        // The region oscillates by a tiny amount around the right edge of
+        // the layer, i.e. while it is slowly animated out of the layer.
+        let move_to = |region_tree: &mut RegionTree<()>, x: f64| {
+            region_tree.modify_widget_region(
+                &widget_entry,
+                None,
+                None,
+                None,
+                Some(AnchorOffset::Points(Point::new(x, 20.0))),
+                &mut WidgetNodeSet::new(),
+                &mut WidgetNodeSet::new(),
+            );
+        };
+        for _ in 0..4 {
+            move_to(&mut region_tree, 400.004);
+            assert!(region_tree.is_widget_visible(&widget_entry));
+            move_to(&mut region_tree, 399.996);
+            assert!(region_tree.is_widget_visible(&widget_entry));
+        }
+        region_tree.validate().unwrap();
+
+        // Moving further past the edge hides the region.
+        move_to(&mut region_tree, 400.5);
+        assert!(!region_tree.is_widget_visible(&widget_entry));
+        region_tree.validate().unwrap();
+
+        // A hidden region is only shown again once it overlaps the layer.
+        move_to(&mut region_tree, 400.004);
+        assert!(!region_tree.is_widget_visible(&widget_entry));
+        move_to(&mut region_tree, 399.996);
+        assert!(region_tree.is_widget_visible(&widget_entry));
+
+        // Without an epsilon, the region toggles at the exact edge.
+        region_tree.set_layer_bounds_epsilon(
+            0.0,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        move_to(&mut region_tree, 400.004);
+        assert!(!region_tree.is_widget_visible(&widget_entry));
+    }
+
+    #[test]
+    fn test_content_zoom() {
+        use crate::layer::WidgetLayer;
+
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let new_layer = |id: u64| -> WidgetLayer<()> {
+            WidgetLayer::new(
+                id,
+                0,
+                Size::new(400.0, 300.0),
+                Point::new(0.0, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+                true,
+                scale_factor,
+            )
+        };
+        let mut zoomed_layer = new_layer(0);
+        let mut other_layer = new_layer(1);
+
+        let mut widget_entries: Vec<StrongWidgetNodeEntry<()>> = (0..2)
+            .map(|id| {
+                StrongWidgetNodeEntry::new(
+                    Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id }))),
+                    WeakWidgetLayerEntry::new(),
+                    WeakRegionTreeEntry::new(),
+                    id,
+                )
+            })
+            .collect();
+        for (layer, widget_entry) in [&mut zoomed_layer, &mut other_layer]
+            .into_iter()
+            .zip(widget_entries.iter_mut())
+        {
+            layer
+                .add_widget_region(
+                    widget_entry,
+                    RegionInfo::from_layer_rect(Rect::new(
+                        Point::new(10.0, 20.0),
+                        Size::new(30.0, 40.0),
+                    )),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+        }
+
+        zoomed_layer.set_content_zoom(2.0, &mut widgets_just_shown, &mut widgets_just_hidden);
+
+        let region = |widget_entry: &StrongWidgetNodeEntry<()>| {
+            let region = widget_entry.assigned_region().upgrade().unwrap();
+            let region = region.borrow();
+            (region.region.rect, region.region.physical_rect)
+        };
+
+        // The zoomed widget keeps its logical rect but covers twice as many
+        // pixels, while the widget on the other layer is unaffected.
+        let (zoomed_rect, zoomed_physical_rect) = region(&widget_entries[0]);
+        let (other_rect, other_physical_rect) = region(&widget_entries[1]);
+        assert!(zoomed_rect.partial_eq_with_epsilon(other_rect));
+        assert_eq!(
+            zoomed_physical_rect,
+            PhysicalRect::new(PhysicalPoint::new(20, 40), PhysicalSize::new(60, 80))
+        );
+        assert_eq!(
+            other_physical_rect,
+            PhysicalRect::new(PhysicalPoint::new(10, 20), PhysicalSize::new(30, 40))
+        );
+
+        // The layer itself keeps its size.
+        assert_eq!(
+            zoomed_layer.region_tree.layer_physical_size(),
+            PhysicalSize::new(400, 300)
+        );
+        assert_eq!(
+            zoomed_layer.region_tree.content_scale_factor(),
+            ScaleFactor2D::new(2.0, 2.0)
+        );
+        assert_eq!(other_layer.region_tree.content_scale_factor(), scale_factor);
+
+        // Pointer positions are unprojected by the zoom before hit testing.
+        let hit = |layer: &WidgetLayer<()>, point: Point| {
+            layer.widget_at_point(point).map(|w| w.unique_id())
+        };
+        assert_eq!(hit(&zoomed_layer, Point::new(50.0, 70.0)), Some(0));
+        assert_eq!(hit(&other_layer, Point::new(50.0, 70.0)), None);
+        assert_eq!(hit(&zoomed_layer, Point::new(30.0, 30.0)), None);
+        assert_eq!(hit(&other_layer, Point::new(30.0, 30.0)), Some(1));
+
+        let event = zoomed_layer.widget_pointer_event(PointerEvent {
+            position: Point::new(50.0, 70.0),
+            delta: Point::new(4.0, -2.0),
+            ..PointerEvent::default()
+        });
+        assert!(event
+            .position
+            .partial_eq_with_epsilon(Point::new(25.0, 35.0)));
+        assert!(event.delta.partial_eq_with_epsilon(Point::new(2.0, -1.0)));
+
+        // Zooming back out restores the original physical rect.
+        zoomed_layer.set_content_zoom(1.0, &mut widgets_just_shown, &mut widgets_just_hidden);
+        assert_eq!(region(&widget_entries[0]).1, other_physical_rect);
+    }
+
+    #[test]
+    fn test_pointer_event_bubbling() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let panel_ref = region_tree
+            .add_container_region(
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(0.0, 0.0),
+                    Size::new(200.0, 200.0),
+                )),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut add_widget = |region_info: RegionInfo<()>, id: u64| {
+            let mut widget_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id }))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                id,
+            );
+            region_tree
+                .add_widget_region(
+                    &mut widget_entry,
+                    region_info,
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            widget_entry
+        };
+
+        // A child of the panel which does not capture pointer events, and
+        // the widget which handles the panel's bubbled events.
+        let child = add_widget(
+            RegionInfo {
+                size: Size::new(50.0, 50.0),
+                internal_anchor: Anchor::top_left(),
+                parent_anchor: Anchor::top_left(),
+                parent_anchor_type: ParentAnchorType::ContainerRegion(panel_ref.clone()),
+                anchor_offset: AnchorOffset::Points(Point::new(10.0, 10.0)),
+                normalized_parent_anchor: None,
+            },
+            0,
+        );
+        let handler = add_widget(
+            RegionInfo::from_layer_rect(Rect::new(Point::new(300.0, 300.0), Size::new(10.0, 10.0))),
+            1,
+        );
+        region_tree.set_widget_listens_to_pointer_events(&child, true);
+
+        // Returns the widget which captured the event, along with every
+        // widget the event was sent to and whether it was bubbled.
+        let click = |region_tree: &mut RegionTree<()>, position: Point| {
+            let mut sent: Vec<(u64, bool)> = Vec::new();
+            let captured = region_tree
+                .dispatch_pointer_event(position, &mut |widget, bubbled| {
+                    sent.push((widget.unique_id(), bubbled));
+                    if bubbled {
+                        EventCapturedStatus::Captured(WidgetNodeRequests::default())
+                    } else {
+                        EventCapturedStatus::NotCaptured
+                    }
+                })
+                .map(|(widget, _)| widget.unique_id());
+            (captured, sent)
+        };
+
+        // Without a handler, the event stops at the panel.
+        assert_eq!(
+            click(&mut region_tree, Point::new(20.0, 20.0)),
+            (None, vec![(0, false)])
+        );
+
+        region_tree
+            .set_container_region_bubble_handler(&panel_ref, Some(handler.downgrade()))
+            .unwrap();
+
+        // A click on the non-capturing child bubbles up to the handler.
+        assert_eq!(
+            click(&mut region_tree, Point::new(20.0, 20.0)),
+            (Some(1), vec![(0, false), (1, true)])
+        );
+
+        // So does a click in the empty area of the panel.
+        assert_eq!(
+            click(&mut region_tree, Point::new(150.0, 150.0)),
+            (Some(1), vec![(1, true)])
+        );
+
+        // Clicks outside of the panel are not bubbled.
+        assert_eq!(
+            click(&mut region_tree, Point::new(250.0, 250.0)),
+            (None, vec![])
+        );
+
+        // The panel doesn't keep its handler alive once it is removed.
+        let weak_handler = handler.downgrade();
+        let mut handler = handler;
+        region_tree.remove_widget_region(
+            &mut handler,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        drop(handler);
+        assert!(weak_handler.upgrade().is_none());
+        assert_eq!(
+            click(&mut region_tree, Point::new(150.0, 150.0)),
+            (None, vec![])
+        );
+    }
+
+    #[test]
+    fn test_overlay_widget_region() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let mut overlay_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_overlay_widget_region(
+                &mut overlay_entry,
+                WidgetNodeType::Painted,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let overlay_rect = |overlay_entry: &StrongWidgetNodeEntry<()>| {
+            overlay_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .rect
+        };
+
+        // The overlay region covers the whole layer.
+        assert!(overlay_rect(&overlay_entry).partial_eq_with_epsilon(region_tree.layer_rect()));
+        assert!(region_tree.dirty_widgets.contains(&overlay_entry));
+        region_tree.dirty_widgets.clear();
+
+        // The overlay region tracks the size of the layer, and it is repainted
+        // when the layer changes.
+        region_tree.set_layer_size(
+            Size::new(640.0, 480.0),
+            scale_factor,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(overlay_rect(&overlay_entry)
+            .partial_eq_with_epsilon(Rect::new(Point::new(0.0, 0.0), Size::new(640.0, 480.0))));
+        assert!(region_tree.dirty_widgets.contains(&overlay_entry));
+    }
+
+    #[test]
+    fn test_validate_region_tree() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let region_info = |parent_anchor_type: ParentAnchorType<()>| RegionInfo {
+            size: Size::new(50.0, 50.0),
+            internal_anchor: Anchor::top_left(),
+            parent_anchor: Anchor::top_left(),
+            parent_anchor_type,
+            anchor_offset: AnchorOffset::Points(Point::new(10.0, 10.0)),
+            normalized_parent_anchor: None,
+        };
+
+        // container
+        // |- widget 0
+        // widget 1
+        let container_ref = region_tree
+            .add_container_region(
+                region_info(ParentAnchorType::Layer),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        let mut widget_entries: Vec<StrongWidgetNodeEntry<()>> = (0..2)
+            .map(|id| {
+                StrongWidgetNodeEntry::new(
+                    Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id }))),
+                    WeakWidgetLayerEntry::new(),
+                    WeakRegionTreeEntry::new(),
+                    id,
+                )
+            })
+            .collect();
+        let parents = [
+            ParentAnchorType::ContainerRegion(container_ref.clone()),
+            ParentAnchorType::Layer,
+        ];
+        for (widget_entry, parent) in widget_entries.iter_mut().zip(parents) {
+            region_tree
+                .add_widget_region(
+                    widget_entry,
+                    region_info(parent),
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(region_tree.validate(), Ok(()));
+
+        // The tree stays valid when it is changed through the layout engine.
+        region_tree.set_layer_size(
+            Size::new(30.0, 30.0),
+            scale_factor,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(region_tree.validate(), Ok(()));
+
+        // Detach the child of the container from it.
+        let child_region = widget_entries[0].assigned_region().upgrade().unwrap();
+        let parent = child_region.borrow_mut().parent.take();
+        let err = region_tree.validate().unwrap_err();
+        assert!(err.contains("has no parent"), "{}", err);
+        child_region.borrow_mut().parent = parent;
+        assert_eq!(region_tree.validate(), Ok(()));
+
+        // Make a region's visibility inconsistent with its rect.
+        let root_region = widget_entries[1].assigned_region().upgrade().unwrap();
+        root_region.borrow_mut().region.is_within_layer_rect = false;
+        let err = region_tree.validate().unwrap_err();
+        assert!(err.contains("is_within_layer_rect"), "{}", err);
+    }
+
+    #[test]
+    fn test_mark_widget_layout_dirty() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(10.0, 10.0),
+                    Size::new(50.0, 50.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        region_tree.dirty_widgets.clear();
+        widgets_just_shown.clear();
+
+        // Nothing affecting the layout has changed, so the widget is not
+        // repainted.
+        region_tree.mark_widget_layout_dirty(
+            &widget_entry,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(region_tree.dirty_widgets.is_empty());
+        assert!(widgets_just_shown.is_empty());
+        assert!(widgets_just_hidden.is_empty());
+
+        // Change the layout state without resolving the rect.
+        let assigned_region = widget_entry.assigned_region().upgrade().unwrap();
+        assigned_region.borrow_mut().region.anchor_offset =
+            AnchorOffset::Points(Point::new(30.0, 10.0));
+
+        region_tree.mark_widget_layout_dirty(
+            &widget_entry,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(region_tree.dirty_widgets.contains(&widget_entry));
+        assert!(assigned_region
+            .borrow()
+            .region
+            .rect
+            .partial_eq_with_epsilon(Rect::new(Point::new(30.0, 10.0), Size::new(50.0, 50.0))));
+    }
+
+    #[test]
+    fn test_pinned_region() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 300.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        // Pinned to the left and right edges, and to the bottom edge.
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo::pinned(
+                    Edges {
+                        left: Some(10.0),
+                        right: Some(20.0),
+                        top: None,
+                        bottom: Some(5.0),
+                    },
+                    Size::new(0.0, 30.0),
+                    ParentAnchorType::Layer,
+                ),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let widget_rect = || {
+            widget_entry
+                .assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .rect
+        };
+
+        assert!(widget_rect()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 265.0), Size::new(370.0, 30.0))));
+
+        // The width follows the width of the parent, and the height is kept.
+        region_tree.set_layer_size(
+            Size::new(600.0, 200.0),
+            scale_factor,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(widget_rect()
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 165.0), Size::new(570.0, 30.0))));
+
+        // The width never goes below zero.
+        region_tree.set_layer_size(
+            Size::new(20.0, 200.0),
+            scale_factor,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(widget_rect().width(), 0.0);
+    }
+
+    #[test]
+    fn test_scroll_bounce() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(200.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let mut list_ref = region_tree
+            .add_container_region(
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(0.0, 0.0),
+                    Size::new(200.0, 100.0),
+                )),
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut scroll_to = |region_tree: &mut RegionTree<()>,
+                             list_ref: &mut ContainerRegionRef<()>,
+                             offset: Point| {
+            region_tree
+                .set_container_region_scroll_offset(
+                    list_ref,
+                    Some(offset),
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            region_tree
+                .container_region_scroll_offset(list_ref)
+                .unwrap()
+                .unwrap()
+        };
+        scroll_to(&mut region_tree, &mut list_ref, Point::new(0.0, 0.0));
+
+        // Without a scroll behavior, the offset isn't limited.
+        assert_eq!(
+            scroll_to(&mut region_tree, &mut list_ref, Point::new(0.0, 500.0)),
+            Point::new(0.0, 500.0)
+        );
+
+        // In clamp mode, the current offset is clamped to the new bounds.
+        region_tree
+            .set_container_region_scroll_behavior(
+                &mut list_ref,
+                Some((ScrollBehavior::Clamp, Point::new(0.0, 300.0))),
+                &mut WidgetNodeSet::new(),
+                &mut WidgetNodeSet::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            region_tree
+                .container_region_scroll_offset(&list_ref)
+                .unwrap(),
+            Some(Point::new(0.0, 300.0))
+        );
+        assert_eq!(
+            scroll_to(&mut region_tree, &mut list_ref, Point::new(-20.0, -50.0)),
+            Point::new(0.0, 0.0)
+        );
+        assert!(!region_tree.is_scroll_bouncing());
+
+        // In bounce mode, the content can be dragged past the top...
+        region_tree
+            .set_container_region_scroll_behavior(
+                &mut list_ref,
+                Some((
+                    ScrollBehavior::Bounce { stiffness: 10.0 },
+                    Point::new(0.0, 300.0),
+                )),
+                &mut WidgetNodeSet::new(),
+                &mut WidgetNodeSet::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            scroll_to(&mut region_tree, &mut list_ref, Point::new(0.0, -60.0)),
+            Point::new(0.0, -60.0)
+        );
+        assert!(region_tree.is_scroll_bouncing());
+
+        // ...and then springs back to it over successive animation frames.
+        let mut last_y = -60.0;
+        let mut frames = 0;
+        while region_tree.is_scroll_bouncing() {
+            region_tree.step_scroll_bounce(
+                1.0 / 60.0,
+                &mut WidgetNodeSet::new(),
+                &mut WidgetNodeSet::new(),
+            );
+            let y = region_tree
+                .container_region_scroll_offset(&list_ref)
+                .unwrap()
+                .unwrap()
+                .y;
+            assert!(y > last_y && y <= 0.0);
+            last_y = y;
+
+            frames += 1;
+            assert!(frames < 120);
+        }
+        assert!(frames > 1);
+        assert_eq!(last_y, 0.0);
+    }
+
+    #[test]
+    fn test_widget_scrolled_out_of_layer_is_not_visible() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(200.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        let mut list_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(200.0, 100.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::default(),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        region_tree
+            .set_container_region_scroll_offset(
+                &mut list_ref,
+                Some(Point::new(0.0, 0.0)),
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut items = Vec::new();
+        for i in 0..10 {
+            let mut item_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: i }))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                i,
+            );
+            region_tree
+                .add_widget_region(
+                    &mut item_entry,
+                    RegionInfo {
+                        size: Size::new(200.0, 20.0),
+                        internal_anchor: Anchor::top_left(),
+                        parent_anchor: Anchor::top_left(),
+                        parent_anchor_type: ParentAnchorType::ContainerRegion(list_ref.clone()),
+                        anchor_offset: AnchorOffset::Points(Point::new(0.0, i as f64 * 20.0)),
+                        normalized_parent_anchor: None,
+                    },
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            items.push(item_entry);
+        }
+
+        assert!(region_tree.is_widget_visible(&items[0]));
+        assert!(!region_tree.is_widget_visible(&items[9]));
+
+        // Scroll the first two items out of the top of the layer.
+        region_tree
+            .set_container_region_scroll_offset(
+                &mut list_ref,
+                Some(Point::new(0.0, 40.0)),
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        assert!(!region_tree.is_widget_visible(&items[0]));
+        assert!(region_tree.is_widget_visible(&items[2]));
+        assert!(!region_tree.is_widget_visible(&items[9]));
+
+        // Hiding the layer hides every widget.
+        region_tree.set_layer_explicit_visibility(
+            false,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(!region_tree.is_widget_visible(&items[2]));
+    }
+
+    #[test]
+    fn test_scroll_widget_into_view() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(400.0, 400.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            scale_factor,
+            0,
+        );
+
+        // An outer scroll container that is half as tall as the inner scroll
+        // container, which holds a list of 10 items.
+        let mut outer_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(100.0, 50.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::default(),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        region_tree
+            .set_container_region_scroll_offset(
+                &mut outer_ref,
+                Some(Point::new(0.0, 0.0)),
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut inner_ref = region_tree
+            .add_container_region(
+                RegionInfo {
+                    size: Size::new(100.0, 100.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::ContainerRegion(outer_ref.clone()),
+                    anchor_offset: AnchorOffset::default(),
+                    normalized_parent_anchor: None,
+                },
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        region_tree
+            .set_container_region_scroll_offset(
+                &mut inner_ref,
+                Some(Point::new(0.0, 0.0)),
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let mut items = Vec::new();
+        for i in 0..10 {
+            let mut item_entry = StrongWidgetNodeEntry::new(
+                Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: i }))),
+                WeakWidgetLayerEntry::new(),
+                WeakRegionTreeEntry::new(),
+                i,
+            );
+            region_tree
+                .add_widget_region(
+                    &mut item_entry,
+                    RegionInfo {
+                        size: Size::new(100.0, 20.0),
+                        internal_anchor: Anchor::top_left(),
+                        parent_anchor: Anchor::top_left(),
+                        parent_anchor_type: ParentAnchorType::ContainerRegion(inner_ref.clone()),
+                        anchor_offset: AnchorOffset::Points(Point::new(0.0, i as f64 * 20.0)),
+                        normalized_parent_anchor: None,
+                    },
+                    WidgetNodeType::Painted,
+                    true,
+                    &mut widgets_just_shown,
+                    &mut widgets_just_hidden,
+                )
+                .unwrap();
+            items.push(item_entry);
+        }
+
+        let item_rect = |item: &StrongWidgetNodeEntry<()>| -> Rect {
+            item.assigned_region()
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .region
+                .rect
+        };
+
+        // An item that is already visible should not cause any scrolling.
+        region_tree.scroll_widget_into_view(
+            &items[1],
+            ScrollAlign::End,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert_eq!(
+            region_tree
+                .container_region_scroll_offset(&inner_ref)
+                .unwrap(),
+            Some(Point::new(0.0, 0.0))
+        );
+
+        // Item 7 spans 140..160 in the inner container, so the inner container
+        // scrolls by 60 to align its bottom edge, which puts the item at
+        // 80..100. The outer container then scrolls by 50 to reveal that.
+        region_tree.scroll_widget_into_view(
+            &items[7],
+            ScrollAlign::End,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(region_tree
+            .container_region_scroll_offset(&inner_ref)
+            .unwrap()
+            .unwrap()
+            .partial_eq_with_epsilon(Point::new(0.0, 60.0)));
+        assert!(region_tree
+            .container_region_scroll_offset(&outer_ref)
+            .unwrap()
+            .unwrap()
+            .partial_eq_with_epsilon(Point::new(0.0, 50.0)));
+        assert!(item_rect(&items[7])
+            .partial_eq_with_epsilon(Rect::new(Point::new(0.0, 30.0), Size::new(100.0, 20.0))));
+
+        // Align the first item with the top of the scroll containers.
+        region_tree.scroll_widget_into_view(
+            &items[0],
+            ScrollAlign::Start,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(item_rect(&items[0])
+            .partial_eq_with_epsilon(Rect::new(Point::new(0.0, 0.0), Size::new(100.0, 20.0))));
+    }
+
+    #[test]
+    fn test_window_visibility() {
+        let scale_factor = ScaleFactor2D::new(1.0, 1.0);
+
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        // The window starts out hidden.
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(200.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+            false,
+            scale_factor,
+            0,
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo {
+                    size: Size::new(10.0, 10.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(10.0, 10.0)),
+                    normalized_parent_anchor: None,
+                },
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        // Nothing should be rendered while the window is hidden.
+        assert!(!region_tree.is_visible());
+        assert!(!region_tree.dirty_widgets.contains(&widget_entry));
+        assert!(!widgets_just_shown.contains(&widget_entry));
+
+        region_tree.clear_whole_layer = false;
+
+        region_tree.set_window_visibility(true, &mut widgets_just_shown, &mut widgets_just_hidden);
+
+        // Becoming visible should mark everything dirty.
+        assert!(region_tree.is_visible());
+        assert!(region_tree.clear_whole_layer);
+        assert!(region_tree.dirty_widgets.contains(&widget_entry));
+        assert!(widgets_just_shown.contains(&widget_entry));
+
+        widgets_just_shown.clear();
+        region_tree.dirty_widgets.clear();
+        region_tree.clear_whole_layer = false;
+
+        region_tree.set_window_visibility(false, &mut widgets_just_shown, &mut widgets_just_hidden);
+
+        assert!(!region_tree.is_visible());
+        assert!(!region_tree.is_dirty());
+        assert!(widgets_just_hidden.contains(&widget_entry));
+    }
+
+    fn assert_region(region: &Region, expected_region: &Region) {
+        assert_eq!(region.id, expected_region.id);
+        if !region.rect.partial_eq_with_epsilon(expected_region.rect) {
+            panic!(
+                "region.rect: {:?}, expected_region.rect: {:?}",
+                &region.rect, &expected_region.rect
+            );
+        }
+        assert_eq!(region.internal_anchor, expected_region.internal_anchor);
+        assert_eq!(region.parent_anchor, expected_region.parent_anchor);
+        if !region
+            .anchor_offset
+            .partial_eq_with_epsilon(expected_region.anchor_offset)
+        {
+            panic!(
+                "region.anchor_offset: {:?}, expected_region.anchor_offset: {:?}",
+                &region.anchor_offset, &expected_region.anchor_offset
+            );
+        }
+        assert_eq!(
+            region.last_rendered_texture_rect,
+            expected_region.last_rendered_texture_rect
+        );
+        if !region
+            .parent_rect
+            .partial_eq_with_epsilon(expected_region.parent_rect)
+        {
+            panic!(
+                "region.parent_rect: {:?}, expected_region.parent_rect: {:?}",
                 &region.parent_rect, &expected_region.parent_rect
             );
         }
@@ -1947,4 +5397,228 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_partial_repaint_rect() {
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(200.0, 200.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(2.0, 2.0),
+            0,
+        );
+
+        // A meter with a 10x100 region.
+        let mut meter_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut meter_entry,
+                RegionInfo::from_layer_rect(Rect::new(
+                    Point::new(20.0, 30.0),
+                    Size::new(10.0, 100.0),
+                )),
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+
+        let region = meter_entry.assigned_region().upgrade().unwrap();
+
+        // The meter has not been rendered yet, so it is repainted in full.
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 40.0), Size::new(10.0, 5.0)),
+        );
+        assert!(region_tree.dirty_widgets.contains(&meter_entry));
+        assert_eq!(region.borrow().region.repaint_texture_rect(), None);
+
+        // Render the meter the same way the renderer does.
+        let render = |region_tree: &mut RegionTree<()>| {
+            let mut region = region.borrow_mut();
+            let repaint_texture_rect = region.region.repaint_texture_rect();
+            region.region.partial_repaint_rect = None;
+            region.region.last_rendered_texture_rect =
+                Some(TextureRect::from_physical_rect(region.region.physical_rect));
+            region_tree.dirty_widgets.clear();
+            region_tree.texture_rects_to_clear.clear();
+            repaint_texture_rect
+        };
+        assert_eq!(render(&mut region_tree), None);
+
+        // Only the top of the bar changes. The rect is rounded outwards to
+        // whole physical pixels.
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 40.0), Size::new(10.0, 5.25)),
+        );
+        assert!(region_tree.dirty_widgets.contains(&meter_entry));
+        assert!(region_tree.texture_rects_to_clear.is_empty());
+        assert_eq!(
+            render(&mut region_tree),
+            Some(TextureRect {
+                x: 40,
+                y: 140,
+                size: PhysicalSize::new(20, 11),
+            })
+        );
+
+        // Multiple rects in the same frame are merged, and clipped to the
+        // meter's region.
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 40.0), Size::new(10.0, 5.0)),
+        );
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 90.0), Size::new(20.0, 20.0)),
+        );
+        assert_eq!(
+            render(&mut region_tree),
+            Some(TextureRect {
+                x: 40,
+                y: 140,
+                size: PhysicalSize::new(20, 120),
+            })
+        );
+
+        // A rect outside of the meter doesn't repaint anything.
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 200.0), Size::new(10.0, 5.0)),
+        );
+        assert!(!region_tree.dirty_widgets.contains(&meter_entry));
+
+        // Marking the whole meter dirty overrides the partial repaint.
+        region_tree.mark_widget_rect_dirty(
+            &meter_entry,
+            Rect::new(Point::new(0.0, 40.0), Size::new(10.0, 5.0)),
+        );
+        region_tree.mark_widget_dirty(&meter_entry);
+        assert_eq!(
+            region_tree.texture_rects_to_clear,
+            vec![TextureRect {
+                x: 40,
+                y: 60,
+                size: PhysicalSize::new(20, 200),
+            }]
+        );
+        assert_eq!(render(&mut region_tree), None);
+    }
+
+    #[test]
+    fn test_bottom_left_coordinate_origin() {
+        let mut widgets_just_shown: WidgetNodeSet<()> = WidgetNodeSet::new();
+        let mut widgets_just_hidden: WidgetNodeSet<()> = WidgetNodeSet::new();
+
+        let mut region_tree: RegionTree<()> = RegionTree::new(
+            Size::new(200.0, 100.0),
+            Point::new(0.0, 0.0),
+            true,
+            true,
+            ScaleFactor2D::new(1.0, 1.0),
+            0,
+        );
+        region_tree.set_coordinate_origin(
+            CoordinateOrigin::BottomLeft,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+
+        let mut widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyPaintedTestWidget { id: 0 }))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            0,
+        );
+        region_tree
+            .add_widget_region(
+                &mut widget_entry,
+                RegionInfo {
+                    size: Size::new(20.0, 10.0),
+                    internal_anchor: Anchor::top_left(),
+                    parent_anchor: Anchor::top_left(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::Points(Point::new(10.0, 10.0)),
+                    normalized_parent_anchor: None,
+                },
+                WidgetNodeType::Painted,
+                true,
+                &mut widgets_just_shown,
+                &mut widgets_just_hidden,
+            )
+            .unwrap();
+        region_tree.set_widget_listens_to_pointer_events(&widget_entry, true);
+
+        let region = widget_entry.assigned_region().upgrade().unwrap();
+        let layer_rect = region_tree.layer_rect();
+
+        // The top-anchored widget appears at the bottom of the layer on the
+        // screen, 10 points above the bottom edge.
+        let screen_rect = Rect::new(Point::new(10.0, 80.0), Size::new(20.0, 10.0));
+        assert!(region
+            .borrow()
+            .region
+            .rect
+            .partial_eq_with_epsilon(screen_rect));
+        assert_eq!(
+            region.borrow().region.physical_rect,
+            PhysicalRect::new(PhysicalPoint::new(10, 80), PhysicalSize::new(20, 10))
+        );
+
+        // The widget sees its rect in y-up coordinates.
+        let widget_rect = Rect::new(Point::new(10.0, 10.0), Size::new(20.0, 10.0));
+        assert!(region
+            .borrow()
+            .region
+            .widget_rect(layer_rect)
+            .partial_eq_with_epsilon(widget_rect));
+
+        // Pointer positions are flipped the same way, so a pointer over the
+        // widget on the screen is inside of the widget's rect.
+        let event = PointerEvent {
+            position: Point::new(15.0, 85.0),
+            delta: Point::new(0.0, 3.0),
+            ..PointerEvent::default()
+        };
+        let widget_event = CoordinateOrigin::BottomLeft.convert_pointer_event(event, layer_rect);
+        assert!(widget_event
+            .position
+            .partial_eq_with_epsilon(Point::new(15.0, 15.0)));
+        assert!(widget_event
+            .delta
+            .partial_eq_with_epsilon(Point::new(0.0, -3.0)));
+        assert!(widget_rect.contains_point(widget_event.position));
+        assert!(region_tree
+            .widget_at_point(event.position)
+            .map(|w| w.unique_id() == widget_entry.unique_id())
+            .unwrap_or(false));
+
+        // Switching back to a top-left origin moves the widget to the top.
+        region_tree.set_coordinate_origin(
+            CoordinateOrigin::TopLeft,
+            &mut widgets_just_shown,
+            &mut widgets_just_hidden,
+        );
+        assert!(region
+            .borrow()
+            .region
+            .rect
+            .partial_eq_with_epsilon(Rect::new(Point::new(10.0, 10.0), Size::new(20.0, 10.0))));
+        assert!(region
+            .borrow()
+            .region
+            .widget_rect(layer_rect)
+            .partial_eq_with_epsilon(region.borrow().region.rect));
+    }
 }
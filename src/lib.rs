@@ -1,28 +1,77 @@
+/// The log target of the trace-level messages that follow the event and
+/// render lifecycle of an `AppWindow` (i.e. which widgets an event was sent
+/// to, which requests they made, and which layers were rendered).
+///
+/// These are useful for finding out why a widget didn't update. Enable them
+/// by allowing the trace level for this target in your logger (i.e. with
+/// `RUST_LOG=firewheel::lifecycle=trace`).
+pub const LIFECYCLE_LOG_TARGET: &str = "firewheel::lifecycle";
+
+macro_rules! trace_lifecycle {
+    ($($arg:tt)+) => {
+        log::trace!(target: $crate::LIFECYCLE_LOG_TARGET, $($arg)+)
+    };
+}
+
 mod anchor;
+mod animator;
 mod app_window;
 mod bg_color;
+#[cfg(feature = "input-recording")]
+mod input_recording;
 mod layer;
+mod list_manager;
+mod loaded_assets;
 mod node;
+mod pointer_capture;
+mod pointer_velocity;
+mod popup;
 mod renderer;
+mod shared_resources;
+#[cfg(feature = "software-text")]
+mod software_text;
+mod spatial_navigation;
+mod stack_layout;
+#[cfg(test)]
+mod test_utils;
+mod text_layout_cache;
 
 pub(crate) mod widget_node_set;
 
 pub mod error;
 pub mod event;
 pub mod size;
+#[cfg(feature = "image")]
+pub mod testing;
+pub mod text;
 pub mod widgets;
 
-pub use anchor::{Anchor, HAlign, VAlign};
-pub use app_window::AppWindow;
+pub use anchor::{Anchor, AnchorOffset, Edges, HAlign, NormalizedAnchor, VAlign};
+pub use animator::{Animator, AnimatorId, AnimatorUpdate, Easing};
+pub use app_window::{ActionQueueOverflow, AppWindow, AppWindowSettings, ModalHandle};
 pub use bg_color::{BgColor, GradientDirection};
 pub use error::FirewheelError;
-pub use layer::{ContainerRegionRef, ParentAnchorType, RegionInfo};
+#[cfg(feature = "input-recording")]
+pub use input_recording::{InputLog, RecordedInputEvent};
+pub use layer::{
+    BlendMode, Compositing, ContainerRegionRef, CoordinateOrigin, ParentAnchorType, RegionInfo,
+    ScrollBehavior,
+};
+pub use list_manager::{ListManager, ListUpdateResult};
 pub use node::{
-    BackgroundNode, EventCapturedStatus, PaintRegionInfo, SetPointerLockType, WidgetNode,
-    WidgetNodeRef, WidgetNodeRequests, WidgetNodeType,
+    BackgroundNode, EventCapturedStatus, HitShape, PaintRegionInfo, ScrollAlign,
+    SetPointerLockType, WidgetNode, WidgetNodeRef, WidgetNodeRequests, WidgetNodeType,
 };
+pub use popup::{Edge, PopupPlacement};
+pub use renderer::ColorSpace;
+pub use shared_resources::{SharedImageRef, SharedResources};
 pub use size::*;
 pub use size::{Point, Rect, ScaleFactor, Size};
+#[cfg(feature = "software-text")]
+pub use software_text::{SoftwareTextRenderer, TextImage, TextPaint};
+pub use spatial_navigation::Direction;
+pub use stack_layout::{Axis, StackAlign, StackLayout};
+pub use text_layout_cache::{TextLayoutCache, DEFAULT_TEXT_LAYOUT_CACHE_CAPACITY};
 
 pub use femtovg as vg;
 pub type VG = femtovg::Canvas<femtovg::renderer::OpenGl>;
@@ -34,10 +83,7 @@ pub fn compute_font_bounds(
     scale_factor: ScaleFactor,
     vg: &VG,
 ) -> Size {
-    let mut font_paint = femtovg::Paint::color(femtovg::Color::black());
-    font_paint.set_font(&[font_id]);
-    font_paint.set_font_size(font_size_pts * scale_factor.0);
-    font_paint.set_text_baseline(femtovg::Baseline::Middle);
+    let font_paint = font_paint(font_id, font_size_pts, scale_factor);
 
     let font_metrics = vg.measure_text(0.0, 0.0, label, &font_paint).unwrap();
 
@@ -46,3 +92,114 @@ pub fn compute_font_bounds(
         font_metrics.height() / scale_factor.0,
     )
 }
+
+/// Compute the tight bounding box of the ink of the given text, including
+/// the full extents of ascenders, descenders, and accents.
+///
+/// `compute_font_bounds()` measures the advance width of the text, which is
+/// what should be used to lay out text next to other text. The ink of some
+/// glyphs can extend past that (i.e. the tail of a "g" or the overhang of an
+/// italic "f"), so size a widget with these bounds instead to make sure
+/// nothing gets clipped.
+///
+/// The returned rect is relative to the point the text is drawn at, with
+/// the text's baseline set to `femtovg::Baseline::Middle` (the same as
+/// `compute_font_bounds()`). An empty rect is returned if the text has no
+/// visible glyphs.
+pub fn compute_font_visual_bounds(
+    label: &str,
+    font_id: femtovg::FontId,
+    font_size_pts: f32,
+    scale_factor: ScaleFactor,
+    vg: &VG,
+) -> Rect {
+    let font_paint = font_paint(font_id, font_size_pts, scale_factor);
+
+    let font_metrics = vg.measure_text(0.0, 0.0, label, &font_paint).unwrap();
+
+    ink_bounds(&font_metrics, scale_factor)
+}
+
+/// The union of the bitmap rects of all glyphs in the given metrics, in
+/// logical coordinates (points).
+pub(crate) fn ink_bounds(font_metrics: &femtovg::TextMetrics, scale_factor: ScaleFactor) -> Rect {
+    let mut bounds: Option<(f32, f32, f32, f32)> = None;
+
+    for glyph in font_metrics.glyphs.iter() {
+        if glyph.width <= 0.0 || glyph.height <= 0.0 {
+            // This glyph has no ink (i.e. a space).
+            continue;
+        }
+
+        let (x1, y1, x2, y2) = (
+            glyph.x,
+            glyph.y,
+            glyph.x + glyph.width,
+            glyph.y + glyph.height,
+        );
+
+        bounds = Some(if let Some((bx1, by1, bx2, by2)) = bounds {
+            (bx1.min(x1), by1.min(y1), bx2.max(x2), by2.max(y2))
+        } else {
+            (x1, y1, x2, y2)
+        });
+    }
+
+    if let Some((x1, y1, x2, y2)) = bounds {
+        Rect::new(
+            Point::new(
+                f64::from(x1 / scale_factor.0),
+                f64::from(y1 / scale_factor.0),
+            ),
+            Size::new((x2 - x1) / scale_factor.0, (y2 - y1) / scale_factor.0),
+        )
+    } else {
+        Rect::default()
+    }
+}
+
+/// The paint used to measure text in `compute_font_bounds()`.
+pub(crate) fn font_paint(
+    font_id: femtovg::FontId,
+    font_size_pts: f32,
+    scale_factor: ScaleFactor,
+) -> femtovg::Paint {
+    let mut font_paint = femtovg::Paint::color(femtovg::Color::black());
+    font_paint.set_font(&[font_id]);
+    font_paint.set_font_size(font_size_pts * scale_factor.0);
+    font_paint.set_text_baseline(femtovg::Baseline::Middle);
+    font_paint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use femtovg::TextContext;
+
+    #[test]
+    fn test_ink_bounds_include_descenders() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let scale_factor = ScaleFactor(2.0);
+        let paint = font_paint(font_id, 14.0, scale_factor);
+        let bounds = |text: &str| {
+            let metrics = text_context.measure_text(0.0, 0.0, text, &paint).unwrap();
+            ink_bounds(&metrics, scale_factor)
+        };
+
+        let caps = bounds("HE");
+        let descenders = bounds("HEgy");
+        assert!(caps.height() > 0.0);
+
+        // The descenders extend below the caps, while the tops line up.
+        assert!(descenders.height() > caps.height());
+        assert!(descenders.y2() > caps.y2());
+        assert!((descenders.y() - caps.y()).abs() < 1.0);
+
+        // Text without any ink has empty bounds.
+        assert_eq!(bounds("   ").size(), Size::default());
+    }
+}
@@ -0,0 +1,432 @@
+use std::hash::Hash;
+
+use fnv::FnvHashMap;
+
+use crate::error::FirewheelError;
+use crate::layer::WidgetLayerRef;
+use crate::{AppWindow, RegionInfo, WidgetNode, WidgetNodeRef};
+
+/// The number of widgets that were touched by a call to
+/// `ListManager::update()`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ListUpdateResult {
+    pub added: usize,
+    pub removed: usize,
+    pub moved: usize,
+}
+
+/// A helper for keeping a dynamic list of widgets in sync with a list of
+/// keys (i.e. the IDs of the items in a data model).
+///
+/// Instead of removing and re-adding every widget whenever the data changes,
+/// only the widgets whose keys were added, removed, or moved to a different
+/// index are touched. Widgets are reused by key, so they keep their internal
+/// state.
+pub struct ListManager<K: Eq + Hash + Clone, A: Clone + Send + Sync + 'static> {
+    entries: Vec<(K, WidgetNodeRef<A>)>,
+}
+
+impl<K: Eq + Hash + Clone, A: Clone + Send + Sync + 'static> ListManager<K, A> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Sync the widgets in this list with the given keys.
+    ///
+    /// * `create` - Called to create the widget for a newly added key.
+    /// * `region_info` - Called with the new index of a key to get the region
+    /// of a widget that was added or moved.
+    ///
+    /// Returns `FirewheelError::DuplicateListKey` without touching any
+    /// widgets if the same key appears more than once in `keys`. If adding
+    /// or moving a widget fails, the widgets added by this call are removed
+    /// again, the widgets moved by it are moved back to the regions returned
+    /// by `region_info` for their old indices, and the list is left as it
+    /// was.
+    pub fn update<C, R>(
+        &mut self,
+        app_window: &mut AppWindow<A>,
+        layer: &WidgetLayerRef<A>,
+        keys: &[K],
+        mut create: C,
+        mut region_info: R,
+    ) -> Result<ListUpdateResult, FirewheelError>
+    where
+        C: FnMut(&K) -> Box<dyn WidgetNode<A>>,
+        R: FnMut(usize, &K) -> RegionInfo<A>,
+    {
+        let old_keys: Vec<K> = self.entries.iter().map(|(key, _)| key.clone()).collect();
+        let diff = diff_keys(&old_keys, keys)?;
+
+        let mut added_widgets: Vec<(usize, WidgetNodeRef<A>)> =
+            Vec::with_capacity(diff.added.len());
+        let mut res = Ok(());
+
+        for &new_i in diff.added.iter() {
+            let key = &keys[new_i];
+            match app_window.add_widget_node(create(key), layer, region_info(new_i, key), true) {
+                Ok(widget) => added_widgets.push((new_i, widget)),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let mut moved_widgets: Vec<usize> = Vec::new();
+        if res.is_ok() {
+            for &(old_i, new_i) in diff.kept.iter() {
+                if old_i != new_i {
+                    res = app_window.reparent_widget(
+                        &mut self.entries[old_i].1,
+                        region_info(new_i, &keys[new_i]),
+                    );
+                    if res.is_err() {
+                        break;
+                    }
+                    moved_widgets.push(old_i);
+                }
+            }
+        }
+
+        if let Err(e) = res {
+            for old_i in moved_widgets.drain(..).rev() {
+                // The widget was just moved to a valid region, so this only
+                // fails if `region_info` returns a different region for the
+                // old index than it did before.
+                let _ = app_window.reparent_widget(
+                    &mut self.entries[old_i].1,
+                    region_info(old_i, &old_keys[old_i]),
+                );
+            }
+            for (_, mut widget) in added_widgets.drain(..) {
+                let _ = app_window.remove_widget(&mut widget);
+            }
+            return Err(e);
+        }
+
+        // Nothing below can fail, so the old entries can be taken now.
+        let mut old_widgets: Vec<Option<WidgetNodeRef<A>>> = self
+            .entries
+            .drain(..)
+            .map(|(_, widget)| Some(widget))
+            .collect();
+
+        for &old_i in diff.removed.iter() {
+            let mut widget = old_widgets[old_i].take().unwrap();
+            // This only fails if the widget was already removed, in which
+            // case there is nothing left to do.
+            let _ = app_window.remove_widget(&mut widget);
+        }
+
+        let mut new_widgets: Vec<Option<WidgetNodeRef<A>>> = Vec::with_capacity(keys.len());
+        new_widgets.resize_with(keys.len(), || None);
+
+        for &(old_i, new_i) in diff.kept.iter() {
+            new_widgets[new_i] = old_widgets[old_i].take();
+        }
+        for (new_i, widget) in added_widgets.drain(..) {
+            new_widgets[new_i] = Some(widget);
+        }
+
+        self.entries = keys
+            .iter()
+            .cloned()
+            .zip(new_widgets.into_iter().map(|w| w.unwrap()))
+            .collect();
+
+        Ok(ListUpdateResult {
+            added: diff.added.len(),
+            removed: diff.removed.len(),
+            moved: diff.moved(),
+        })
+    }
+
+    /// Remove all widgets in this list.
+    pub fn clear(&mut self, app_window: &mut AppWindow<A>) -> Result<(), FirewheelError> {
+        for (_, mut widget) in self.entries.drain(..) {
+            app_window.remove_widget(&mut widget)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &K) -> Option<&WidgetNodeRef<A>> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, widget)| widget)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut WidgetNodeRef<A>> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, widget)| widget)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &WidgetNodeRef<A>)> {
+        self.entries.iter().map(|(key, widget)| (key, widget))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: Eq + Hash + Clone, A: Clone + Send + Sync + 'static> Default for ListManager<K, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct KeyDiff {
+    /// The indices of the old keys which are no longer present.
+    removed: Vec<usize>,
+    /// The indices of the new keys which were not present before.
+    added: Vec<usize>,
+    /// The `(old index, new index)` of every key present in both lists.
+    kept: Vec<(usize, usize)>,
+}
+
+impl KeyDiff {
+    fn moved(&self) -> usize {
+        self.kept
+            .iter()
+            .filter(|(old_i, new_i)| old_i != new_i)
+            .count()
+    }
+}
+
+fn diff_keys<K: Eq + Hash + Clone>(
+    old_keys: &[K],
+    new_keys: &[K],
+) -> Result<KeyDiff, FirewheelError> {
+    let old_indices: FnvHashMap<K, usize> = old_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.clone(), i))
+        .collect();
+    let new_indices: FnvHashMap<K, usize> = new_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.clone(), i))
+        .collect();
+
+    if new_indices.len() != new_keys.len() {
+        return Err(FirewheelError::DuplicateListKey);
+    }
+
+    let mut diff = KeyDiff::default();
+
+    for (old_i, key) in old_keys.iter().enumerate() {
+        if !new_indices.contains_key(key) {
+            diff.removed.push(old_i);
+        }
+    }
+
+    for (new_i, key) in new_keys.iter().enumerate() {
+        if let Some(old_i) = old_indices.get(key) {
+            diff.kept.push((*old_i, new_i));
+        } else {
+            diff.added.push(new_i);
+        }
+    }
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestWidget;
+    use crate::{ParentAnchorType, Point, Rect, ScaleFactor, Size};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_diff_keys() {
+        // No changes.
+        let diff = diff_keys(&[1, 2, 3], &[1, 2, 3]).unwrap();
+        assert!(diff.removed.is_empty());
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.moved(), 0);
+
+        // Appending an item only touches the new item.
+        let diff = diff_keys(&[1, 2, 3], &[1, 2, 3, 4]).unwrap();
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.added, vec![3]);
+        assert_eq!(diff.moved(), 0);
+
+        // Removing an item from the middle shifts the items after it.
+        let diff = diff_keys(&[1, 2, 3, 4], &[1, 3, 4]).unwrap();
+        assert_eq!(diff.removed, vec![1]);
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.kept, vec![(0, 0), (2, 1), (3, 2)]);
+        assert_eq!(diff.moved(), 2);
+
+        // Swapping two items only moves those two items.
+        let diff = diff_keys(&[1, 2, 3, 4], &[1, 4, 3, 2, 5]).unwrap();
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.added, vec![4]);
+        assert_eq!(diff.kept, vec![(0, 0), (3, 1), (2, 2), (1, 3)]);
+        assert_eq!(diff.moved(), 2);
+
+        assert_eq!(
+            diff_keys(&[1, 2], &[1, 2, 1]),
+            Err(FirewheelError::DuplicateListKey)
+        );
+    }
+
+    #[test]
+    fn test_list_manager_errors_leave_list_unchanged() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let layer = app_window.add_widget_layer(
+            Size::new(100.0, 100.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        let region_info = |i: usize, _: &u32| {
+            RegionInfo::from_layer_rect(Rect::new(
+                Point::new(0.0, i as f64 * 10.0),
+                Size::new(100.0, 10.0),
+            ))
+        };
+
+        let mut list = ListManager::new();
+        list.update(
+            &mut app_window,
+            &layer,
+            &[1, 2, 3],
+            |_| Box::new(TestWidget::new()),
+            region_info,
+        )
+        .unwrap();
+
+        // Duplicate keys are rejected before any widget is touched.
+        let mut num_created = 0;
+        let res = list.update(
+            &mut app_window,
+            &layer,
+            &[3, 4, 3],
+            |_| {
+                num_created += 1;
+                Box::new(TestWidget::new())
+            },
+            region_info,
+        );
+        assert_eq!(res, Err(FirewheelError::DuplicateListKey));
+        assert_eq!(num_created, 0);
+        assert_eq!(
+            list.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(list.iter().all(|(_, w)| w.is_alive()));
+
+        // Moving a widget into a removed container region fails. The widget
+        // that was added before the failure is removed again, and the list
+        // still holds the widgets it had before.
+        let container = app_window
+            .add_container_region(
+                &layer,
+                RegionInfo::from_layer_rect(Rect::new(Point::new(0.0, 0.0), Size::new(1.0, 1.0))),
+                true,
+            )
+            .unwrap();
+        let removed_container = container.clone();
+        app_window.remove_container_region(container).unwrap();
+
+        let mut added_widget_events = None;
+        let res = list.update(
+            &mut app_window,
+            &layer,
+            &[4, 3, 2, 1],
+            |_| {
+                let widget = TestWidget::new();
+                added_widget_events = Some(widget.events());
+                Box::new(widget)
+            },
+            |i, key| {
+                if *key == 4 {
+                    region_info(i, key)
+                } else {
+                    RegionInfo {
+                        parent_anchor_type: ParentAnchorType::ContainerRegion(
+                            removed_container.clone(),
+                        ),
+                        ..region_info(i, key)
+                    }
+                }
+            },
+        );
+        assert_eq!(res, Err(FirewheelError::ParentAnchorRegionRemoved));
+        assert_eq!(
+            list.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(list.iter().all(|(_, w)| w.is_alive()));
+        // The added widget was dropped when it was removed.
+        assert_eq!(Rc::strong_count(&added_widget_events.unwrap()), 1);
+
+        // A failure partway through moving the widgets moves the widgets that
+        // were already moved back to where they were.
+        let widget_y = |app_window: &AppWindow<()>, list: &ListManager<u32, ()>, key| {
+            app_window
+                .widget_region_rect(list.get(&key).unwrap())
+                .unwrap()
+                .y()
+        };
+        let res = list.update(
+            &mut app_window,
+            &layer,
+            &[3, 2, 1],
+            |_| Box::new(TestWidget::new()),
+            |i, key| {
+                if *key == 1 {
+                    RegionInfo {
+                        parent_anchor_type: ParentAnchorType::ContainerRegion(
+                            removed_container.clone(),
+                        ),
+                        ..region_info(i, key)
+                    }
+                } else {
+                    region_info(i, key)
+                }
+            },
+        );
+        assert_eq!(res, Err(FirewheelError::ParentAnchorRegionRemoved));
+        assert_eq!(
+            list.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(widget_y(&app_window, &list, 1), 0.0);
+        assert_eq!(widget_y(&app_window, &list, 2), 10.0);
+        assert_eq!(widget_y(&app_window, &list, 3), 20.0);
+
+        // The list can still be updated afterwards.
+        let res = list.update(
+            &mut app_window,
+            &layer,
+            &[4, 3, 2, 1],
+            |_| Box::new(TestWidget::new()),
+            region_info,
+        );
+        assert_eq!(
+            res,
+            Ok(ListUpdateResult {
+                added: 1,
+                removed: 0,
+                moved: 3,
+            })
+        );
+    }
+}
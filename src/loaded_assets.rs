@@ -0,0 +1,111 @@
+use femtovg::{FontId, ImageId};
+use fnv::FnvHashMap;
+
+/// The fonts and images that have been loaded into a window's canvas with
+/// the helpers on `AppWindow`, in the order they were loaded.
+pub(crate) struct LoadedAssets {
+    fonts: Vec<FontId>,
+    font_names: FnvHashMap<String, FontId>,
+    images: Vec<ImageId>,
+}
+
+impl LoadedAssets {
+    pub fn new() -> Self {
+        Self {
+            fonts: Vec::new(),
+            font_names: FnvHashMap::default(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn add_font(&mut self, font_id: FontId) {
+        if !self.fonts.contains(&font_id) {
+            self.fonts.push(font_id);
+        }
+    }
+
+    /// Add a font that can be looked up by the given name. A font that was
+    /// previously added with the same name can no longer be looked up by it.
+    pub fn add_named_font(&mut self, name: &str, font_id: FontId) {
+        self.add_font(font_id);
+        self.font_names.insert(name.to_string(), font_id);
+    }
+
+    pub fn font_id_by_name(&self, name: &str) -> Option<FontId> {
+        self.font_names.get(name).copied()
+    }
+
+    #[cfg_attr(not(feature = "image-loading"), allow(unused))]
+    pub fn add_image(&mut self, image_id: ImageId) {
+        if !self.images.contains(&image_id) {
+            self.images.push(image_id);
+        }
+    }
+
+    pub fn fonts(&self) -> &[FontId] {
+        &self.fonts
+    }
+
+    pub fn images(&self) -> &[ImageId] {
+        &self.images
+    }
+
+    pub fn remove_image(&mut self, image_id: ImageId) {
+        self.images.retain(|id| *id != image_id);
+    }
+
+    /// Forget all images (i.e. when the canvas they were loaded into has been
+    /// destroyed).
+    pub fn clear_images(&mut self) {
+        self.images.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use femtovg::TextContext;
+
+    #[test]
+    fn test_loaded_fonts() {
+        let text_context = TextContext::default();
+        let mut loaded_assets = LoadedAssets::new();
+        assert!(loaded_assets.fonts().is_empty());
+
+        // Each load of a font returns a new ID, even for the same data.
+        let font_data = include_bytes!("../examples/assets/Roboto-Regular.ttf");
+        let font_a = text_context.add_font_mem(font_data).unwrap();
+        loaded_assets.add_font(font_a);
+        let font_b = text_context.add_font_mem(font_data).unwrap();
+        loaded_assets.add_font(font_b);
+
+        assert_eq!(loaded_assets.fonts(), &[font_a, font_b]);
+
+        // Registering the same ID again doesn't duplicate it.
+        loaded_assets.add_font(font_a);
+        assert_eq!(loaded_assets.fonts(), &[font_a, font_b]);
+    }
+
+    #[test]
+    fn test_named_fonts() {
+        let text_context = TextContext::default();
+        let mut loaded_assets = LoadedAssets::new();
+
+        let font_data = include_bytes!("../examples/assets/Roboto-Regular.ttf");
+        let body = text_context.add_font_mem(font_data).unwrap();
+        loaded_assets.add_named_font("body", body);
+        let heading = text_context.add_font_mem(font_data).unwrap();
+        loaded_assets.add_named_font("heading", heading);
+
+        assert_eq!(loaded_assets.font_id_by_name("body"), Some(body));
+        assert_eq!(loaded_assets.font_id_by_name("heading"), Some(heading));
+        assert_eq!(loaded_assets.font_id_by_name("mono"), None);
+        assert_eq!(loaded_assets.fonts(), &[body, heading]);
+
+        // Adding a font with an existing name replaces it.
+        let new_body = text_context.add_font_mem(font_data).unwrap();
+        loaded_assets.add_named_font("body", new_body);
+        assert_eq!(loaded_assets.font_id_by_name("body"), Some(new_body));
+        assert_eq!(loaded_assets.fonts(), &[body, heading, new_body]);
+    }
+}
@@ -1,3 +1,4 @@
+use femtovg::ImageId;
 use std::any::Any;
 
 use crate::VG;
@@ -12,4 +13,11 @@ pub trait BackgroundNode {
 
     #[allow(unused)]
     fn paint(&mut self, vg: &mut VG, region: &PaintRegionInfo) {}
+
+    /// Whether this node paints the given image (see
+    /// `WidgetNode::uses_image()`).
+    #[allow(unused)]
+    fn uses_image(&self, image_id: ImageId) -> bool {
+        false
+    }
 }
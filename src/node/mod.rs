@@ -1,16 +1,20 @@
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
+use std::fmt;
 use std::hash::Hash;
 use std::rc::{Rc, Weak};
 
 use crate::layer::{WeakBackgroundLayerEntry, WeakRegionTreeEntry, WeakWidgetLayerEntry};
-use crate::size::{PhysicalRect, Rect, ScaleFactor};
+use crate::size::{PhysicalPoint, PhysicalRect, PhysicalSize, Rect, ScaleFactor, ScaleFactor2D};
 
 mod background_node;
 mod widget_node;
 pub use background_node::BackgroundNode;
 use femtovg::Path;
+#[cfg(feature = "alpha-hit-testing")]
+pub(crate) use widget_node::AlphaMask;
 pub use widget_node::{
-    EventCapturedStatus, SetPointerLockType, WidgetNode, WidgetNodeRequests, WidgetNodeType,
+    EventCapturedStatus, HitShape, ScrollAlign, SetPointerLockType, WidgetNode, WidgetNodeRequests,
+    WidgetNodeType,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -29,8 +33,12 @@ pub struct PaintRegionInfo {
     /// (the physical coordinates in the layer's texture, not the screen).
     pub layer_physical_rect: PhysicalRect,
 
-    /// The dpi scaling factor.
+    /// The dpi scaling factor. If the horizontal and vertical scaling
+    /// factors differ, then this is the larger of the two.
     pub scale_factor: ScaleFactor,
+
+    /// The dpi scaling factor of each axis.
+    pub scale_factor_2d: ScaleFactor2D,
 }
 
 impl PaintRegionInfo {
@@ -99,6 +107,54 @@ impl PaintRegionInfo {
 
         path
     }
+
+    /// The pixel-snapped rectangle of a focus ring that is inset from the
+    /// edges of this widget's region by `inset_pts`.
+    ///
+    /// A negative inset places the ring outside of the region. Note that
+    /// anything painted outside of the region is not cleared when the widget
+    /// repaints, so prefer a positive inset unless the widget is painted
+    /// with a margin that leaves room for the ring.
+    pub fn focus_ring_rect(&self, inset_pts: f32) -> PhysicalRect {
+        let inset_px = (inset_pts * self.scale_factor.0).round() as i32;
+
+        let width_px = (self.physical_rect.size.width as i32 - (inset_px * 2)).max(0);
+        let height_px = (self.physical_rect.size.height as i32 - (inset_px * 2)).max(0);
+
+        PhysicalRect::new(
+            PhysicalPoint::new(
+                self.physical_rect.pos.x + inset_px,
+                self.physical_rect.pos.y + inset_px,
+            ),
+            PhysicalSize::new(width_px as u32, height_px as u32),
+        )
+    }
+
+    /// A rounded-rect path for drawing a focus ring that is inset from the
+    /// edges of this widget's region by `inset_pts` (see `focus_ring_rect`).
+    pub fn focus_ring_path(&self, inset_pts: f32, corner_radius_pts: f32) -> Path {
+        let rect = self.focus_ring_rect(inset_pts);
+
+        let mut path = Path::new();
+        if corner_radius_pts == 0.0 {
+            path.rect(
+                rect.pos.x as f32,
+                rect.pos.y as f32,
+                rect.size.width as f32,
+                rect.size.height as f32,
+            );
+        } else {
+            path.rounded_rect(
+                rect.pos.x as f32,
+                rect.pos.y as f32,
+                rect.size.width as f32,
+                rect.size.height as f32,
+                corner_radius_pts * self.scale_factor.0,
+            );
+        }
+
+        path
+    }
 }
 
 pub(crate) struct StrongWidgetNodeEntry<A: Clone + Send + Sync + 'static> {
@@ -106,6 +162,10 @@ pub(crate) struct StrongWidgetNodeEntry<A: Clone + Send + Sync + 'static> {
     assigned_layer: WeakWidgetLayerEntry<A>,
     assigned_region: WeakRegionTreeEntry<A>,
     unique_id: u64,
+    /// Whether the widget receives `PointerEvent::raw_scroll`.
+    raw_scroll: Rc<Cell<bool>>,
+    #[cfg(feature = "debug-names")]
+    debug_name: Rc<RefCell<Option<String>>>,
 }
 
 impl<A: Clone + Send + Sync + 'static> StrongWidgetNodeEntry<A> {
@@ -120,6 +180,9 @@ impl<A: Clone + Send + Sync + 'static> StrongWidgetNodeEntry<A> {
             assigned_layer,
             assigned_region,
             unique_id,
+            raw_scroll: Rc::new(Cell::new(false)),
+            #[cfg(feature = "debug-names")]
+            debug_name: Rc::new(RefCell::new(None)),
         }
     }
 
@@ -147,12 +210,39 @@ impl<A: Clone + Send + Sync + 'static> StrongWidgetNodeEntry<A> {
         &mut self.assigned_region
     }
 
+    #[cfg(feature = "debug-names")]
+    pub fn set_debug_name(&self, name: &str) {
+        *RefCell::borrow_mut(&self.debug_name) = Some(name.into());
+    }
+
+    #[cfg(not(feature = "debug-names"))]
+    pub fn set_debug_name(&self, _name: &str) {}
+
+    pub fn debug_label(&self) -> DebugLabel {
+        DebugLabel {
+            id: self.unique_id,
+            #[cfg(feature = "debug-names")]
+            name: RefCell::borrow(&self.debug_name).clone(),
+        }
+    }
+
+    pub fn receives_raw_scroll(&self) -> bool {
+        self.raw_scroll.get()
+    }
+
+    pub fn set_receives_raw_scroll(&self, receives_raw_scroll: bool) {
+        self.raw_scroll.set(receives_raw_scroll);
+    }
+
     pub fn downgrade(&self) -> WeakWidgetNodeEntry<A> {
         WeakWidgetNodeEntry {
             shared: Rc::downgrade(&self.shared),
             assigned_layer: self.assigned_layer.clone(),
             assigned_region: self.assigned_region.clone(),
             unique_id: self.unique_id,
+            raw_scroll: Rc::clone(&self.raw_scroll),
+            #[cfg(feature = "debug-names")]
+            debug_name: Rc::clone(&self.debug_name),
         }
     }
 }
@@ -164,6 +254,9 @@ impl<A: Clone + Send + Sync + 'static> Clone for StrongWidgetNodeEntry<A> {
             assigned_layer: self.assigned_layer.clone(),
             assigned_region: self.assigned_region.clone(),
             unique_id: self.unique_id,
+            raw_scroll: Rc::clone(&self.raw_scroll),
+            #[cfg(feature = "debug-names")]
+            debug_name: Rc::clone(&self.debug_name),
         }
     }
 }
@@ -182,11 +275,36 @@ impl<A: Clone + Send + Sync + 'static> Hash for StrongWidgetNodeEntry<A> {
     }
 }
 
+/// Identifies a widget or a layer in log messages by its ID, followed by the
+/// name assigned to it when the `debug-names` feature is enabled.
+pub(crate) struct DebugLabel {
+    pub id: u64,
+    #[cfg(feature = "debug-names")]
+    pub name: Option<String>,
+}
+
+impl fmt::Display for DebugLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        #[cfg(feature = "debug-names")]
+        {
+            if let Some(name) = &self.name {
+                write!(f, " ({:?})", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct WeakWidgetNodeEntry<A: Clone + Send + Sync + 'static> {
     shared: Weak<RefCell<Box<dyn WidgetNode<A>>>>,
     assigned_layer: WeakWidgetLayerEntry<A>,
     assigned_region: WeakRegionTreeEntry<A>,
     unique_id: u64,
+    /// Whether the widget receives `PointerEvent::raw_scroll`.
+    raw_scroll: Rc<Cell<bool>>,
+    #[cfg(feature = "debug-names")]
+    debug_name: Rc<RefCell<Option<String>>>,
 }
 
 impl<A: Clone + Send + Sync + 'static> WeakWidgetNodeEntry<A> {
@@ -196,6 +314,9 @@ impl<A: Clone + Send + Sync + 'static> WeakWidgetNodeEntry<A> {
             assigned_layer: self.assigned_layer.clone(),
             assigned_region: self.assigned_region.clone(),
             unique_id: self.unique_id,
+            raw_scroll: Rc::clone(&self.raw_scroll),
+            #[cfg(feature = "debug-names")]
+            debug_name: Rc::clone(&self.debug_name),
         })
     }
 }
@@ -266,6 +387,13 @@ impl WeakBackgroundNodeEntry {
     }
 }
 
+/// A handle to a widget that has been added to an `AppWindow`.
+///
+/// This only holds a weak reference to the widget. Once the widget is
+/// removed (including when the container region or modal it lives in is
+/// removed), the reference becomes stale: methods on `AppWindow` that take
+/// it will return `FirewheelError::WidgetNodeRemoved` or do nothing. Use
+/// `is_alive()` to check for this.
 pub struct WidgetNodeRef<A: Clone + Send + Sync + 'static> {
     pub(crate) shared: WeakWidgetNodeEntry<A>,
 }
@@ -274,6 +402,21 @@ impl<A: Clone + Send + Sync + 'static> WidgetNodeRef<A> {
     pub fn unique_id(&self) -> u64 {
         self.shared.unique_id
     }
+
+    /// Whether the widget this refers to still exists in the window.
+    pub fn is_alive(&self) -> bool {
+        self.shared.upgrade().is_some()
+    }
+}
+
+impl<A: Clone + Send + Sync + 'static> fmt::Debug for WidgetNodeRef<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("WidgetNodeRef");
+        s.field("unique_id", &self.shared.unique_id);
+        #[cfg(feature = "debug-names")]
+        s.field("debug_name", &*RefCell::borrow(&self.shared.debug_name));
+        s.finish()
+    }
 }
 
 pub struct BackgroundNodeRef {
@@ -285,3 +428,93 @@ impl BackgroundNodeRef {
         self.shared.unique_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::InputEvent;
+    use crossbeam_channel::Sender;
+    use femtovg::TextContext;
+
+    #[test]
+    fn test_focus_ring_rect() {
+        let region = PaintRegionInfo {
+            rect: Rect::default(),
+            layer_rect: Rect::default(),
+            physical_rect: PhysicalRect::new(
+                PhysicalPoint::new(20, 30),
+                PhysicalSize::new(100, 40),
+            ),
+            layer_physical_rect: PhysicalRect::default(),
+            scale_factor: ScaleFactor(1.5),
+            scale_factor_2d: ScaleFactor(1.5).into(),
+        };
+
+        // 2 points at a scale factor of 1.5 is 3 pixels.
+        assert_eq!(
+            region.focus_ring_rect(2.0),
+            PhysicalRect::new(PhysicalPoint::new(23, 33), PhysicalSize::new(94, 34))
+        );
+        assert_eq!(
+            region.focus_ring_rect(-2.0),
+            PhysicalRect::new(PhysicalPoint::new(17, 27), PhysicalSize::new(106, 46))
+        );
+
+        // 1.5 points at a scale factor of 1.5 is snapped to 2 pixels.
+        assert_eq!(
+            region.focus_ring_rect(1.5),
+            PhysicalRect::new(PhysicalPoint::new(22, 32), PhysicalSize::new(96, 36))
+        );
+
+        // An inset larger than the region collapses to an empty rect.
+        assert_eq!(region.focus_ring_rect(100.0).size, PhysicalSize::new(0, 0));
+    }
+
+    struct EmptyWidget;
+
+    impl WidgetNode<()> for EmptyWidget {
+        fn on_added(
+            &mut self,
+            _action_tx: &mut Sender<()>,
+        ) -> (WidgetNodeType, WidgetNodeRequests) {
+            (WidgetNodeType::Painted, WidgetNodeRequests::default())
+        }
+
+        fn on_input_event(
+            &mut self,
+            _event: &InputEvent,
+            _text_context: &TextContext,
+            _action_tx: &mut Sender<()>,
+        ) -> EventCapturedStatus {
+            EventCapturedStatus::NotCaptured
+        }
+    }
+
+    #[test]
+    fn test_widget_node_ref_debug_output() {
+        let widget_entry = StrongWidgetNodeEntry::new(
+            Rc::new(RefCell::new(Box::new(EmptyWidget))),
+            WeakWidgetLayerEntry::new(),
+            WeakRegionTreeEntry::new(),
+            7,
+        );
+        let widget_node_ref = WidgetNodeRef {
+            shared: widget_entry.downgrade(),
+        };
+
+        assert!(format!("{:?}", widget_node_ref).contains("unique_id: 7"));
+        assert_eq!(widget_entry.debug_label().to_string(), "7");
+
+        widget_entry.set_debug_name("play button");
+        #[cfg(feature = "debug-names")]
+        {
+            assert!(format!("{:?}", widget_node_ref).contains("\"play button\""));
+            assert_eq!(
+                widget_entry.debug_label().to_string(),
+                "7 (\"play button\")"
+            );
+        }
+        #[cfg(not(feature = "debug-names"))]
+        assert_eq!(widget_entry.debug_label().to_string(), "7");
+    }
+}
@@ -1,9 +1,12 @@
 use crossbeam_channel::Sender;
+use femtovg::{ImageId, TextContext};
 use std::any::Any;
 
+#[cfg(feature = "alpha-hit-testing")]
+use crate::PhysicalSize;
 use crate::{
     event::{InputEvent, KeyboardEventsListen},
-    Rect, VG,
+    Point, Rect, Size, VG,
 };
 
 use super::PaintRegionInfo;
@@ -23,6 +26,10 @@ pub trait WidgetNode<A: Clone + Send + Sync + 'static> {
     #[allow(unused)]
     fn on_visibility_hidden(&mut self, action_tx: &mut Sender<A>) {}
 
+    /// Called after this widget has been removed from the app window.
+    #[allow(unused)]
+    fn on_removed(&mut self, action_tx: &mut Sender<A>) {}
+
     #[allow(unused)]
     fn on_region_changed(&mut self, assigned_rect: Rect) {}
 
@@ -35,34 +42,150 @@ pub trait WidgetNode<A: Clone + Send + Sync + 'static> {
         None
     }
 
+    /// Called when this widget receives an input event.
+    ///
+    /// The `text_context` is provided so the widget can measure text (i.e.
+    /// to find the position of a text caret from a pointer click). It is
+    /// available even while the window has no renderer (see
+    /// `AppWindow::new_headless()` and `AppWindow::suspend_gpu()`).
     fn on_input_event(
         &mut self,
         event: &InputEvent,
+        text_context: &TextContext,
         action_tx: &mut Sender<A>,
     ) -> EventCapturedStatus;
 
+    /// Called when a pointer event inside of a container region was not
+    /// captured by any of the container's children, and this widget was set
+    /// as the handler for that container region (see
+    /// `AppWindow::set_container_region_bubble_handler()`).
+    ///
+    /// This lets a widget such as a panel handle clicks in the empty areas
+    /// between its children.
+    #[allow(unused)]
+    fn on_child_event_bubbled(
+        &mut self,
+        event: &InputEvent,
+        text_context: &TextContext,
+        action_tx: &mut Sender<A>,
+    ) -> EventCapturedStatus {
+        EventCapturedStatus::NotCaptured
+    }
+
+    /// Called once per frame at the start of `AppWindow::render()`, before
+    /// anything is painted.
+    ///
+    /// This is only called on visible widgets that have opted in with
+    /// `WidgetNodeRequests::set_pre_frame_listen`. It is a lighter-weight
+    /// alternative to animation events for widgets that poll shared state
+    /// (i.e. an audio meter reading an atomic value) and only need to decide
+    /// whether to repaint.
+    #[allow(unused)]
+    fn pre_frame(&mut self, action_tx: &mut Sender<A>) -> Option<WidgetNodeRequests> {
+        None
+    }
+
+    /// Paint this widget.
+    ///
+    /// The state of the canvas is saved before this is called and restored
+    /// afterwards, so the widget is free to call `vg.translate()`,
+    /// `vg.rotate()`, `vg.scale()`, `vg.scissor()`, and so on (i.e. to draw
+    /// in a rotated local space) without affecting the widgets painted after
+    /// it.
     #[allow(unused)]
     fn paint(&mut self, vg: &mut VG, region: &PaintRegionInfo) {}
+
+    /// Whether this widget paints the given image. Widgets that paint images
+    /// should implement this so that `AppWindow::unload_image()` doesn't
+    /// unload an image while they are visible.
+    #[allow(unused)]
+    fn uses_image(&self, image_id: ImageId) -> bool {
+        false
+    }
 }
 
+#[derive(Debug)]
 pub struct WidgetNodeRequests {
     pub repaint: bool,
+    /// Repaint only this part of the widget (relative to the top-left corner
+    /// of the widget's region). This is ignored if `repaint` is `true`.
+    ///
+    /// Only this part of the layer's texture is cleared before `paint()` is
+    /// called, and painting is clipped to it, so the widget can skip drawing
+    /// anything outside of it (i.e. a meter that only needs to update the
+    /// top of its bar on each animation frame). The widget must not change
+    /// how it looks outside of this rect.
+    ///
+    /// This has no effect on layers using `Compositing::Direct`, which are
+    /// repainted every frame.
+    pub repaint_rect: Option<Rect>,
     pub set_receive_next_animation_event: Option<bool>,
     pub set_pointer_events_listen: Option<bool>,
     pub set_keyboard_events_listen: Option<KeyboardEventsListen>,
     pub set_pointer_lock: Option<SetPointerLockType>,
     pub set_pointer_leave_listen: Option<bool>,
+    /// Whether or not this widget receives the unprocessed scroll deltas and
+    /// the phase of scroll gestures in `PointerEvent::raw_scroll` (i.e. a map
+    /// or a canvas that handles trackpad momentum itself).
+    pub set_raw_scroll: Option<bool>,
+    /// Capture (`Some(true)`) or release (`Some(false)`) the pointer. While
+    /// a widget has captured the pointer, it receives every pointer event
+    /// regardless of where the pointer is.
+    ///
+    /// Captures are kept in a stack, with the most recent capture on top.
+    /// Pointer events are sent down the stack from the top until a widget
+    /// captures them, so a widget lower in the stack can watch a gesture
+    /// that the widgets above it don't capture.
+    pub set_pointer_capture: Option<bool>,
+    /// Take the pointer capture from every widget above this one in the
+    /// capture stack (or from every widget in the stack if this widget is
+    /// not in it). Those widgets receive `InputEvent::PointerCaptureLost`.
+    ///
+    /// This lets a container (i.e. a scroll container) take over a drag that
+    /// started on one of its children once the drag moves past a threshold.
+    pub steal_pointer_capture: bool,
+    /// Whether or not `WidgetNode::pre_frame()` should be called on this
+    /// widget at the start of every frame while it is visible.
+    pub set_pre_frame_listen: Option<bool>,
+    /// Whether or not this widget can receive the keyboard focus while it
+    /// is visible.
+    pub set_focusable: Option<bool>,
+    /// Scroll the scrollable container regions that this widget is nested in
+    /// so that this widget is visible.
+    pub scroll_into_view: Option<ScrollAlign>,
+    /// Paint this widget at a fraction of its physical resolution (i.e.
+    /// `0.5` for half the width and height), and then stretch it over its
+    /// region with linear filtering. This trades sharpness for fill-rate in
+    /// widgets that are expensive to paint. Use `1.0` to paint at the full
+    /// resolution again.
+    ///
+    /// This only has an effect on layers using `Compositing::Texture`.
+    /// Pointer events still use the full region of the widget.
+    pub set_render_scale: Option<f32>,
+    /// The shape of the area within this widget's region that pointer events
+    /// hit. Pointer events outside of the shape fall through to the widgets
+    /// beneath it (i.e. the corners of a round knob).
+    pub set_hit_shape: Option<HitShape>,
 }
 
 impl Default for WidgetNodeRequests {
     fn default() -> Self {
         Self {
             repaint: false,
+            repaint_rect: None,
             set_receive_next_animation_event: None,
             set_pointer_events_listen: None,
             set_keyboard_events_listen: None,
             set_pointer_lock: None,
             set_pointer_leave_listen: None,
+            set_raw_scroll: None,
+            set_pointer_capture: None,
+            steal_pointer_capture: false,
+            set_pre_frame_listen: None,
+            set_focusable: None,
+            scroll_into_view: None,
+            set_render_scale: None,
+            set_hit_shape: None,
         }
     }
 }
@@ -84,3 +207,155 @@ pub enum SetPointerLockType {
     LockToWidget,
     LockInPlaceAndHideCursor,
 }
+
+/// Where a widget should end up within its scroll container when it is
+/// scrolled into view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAlign {
+    /// Align the widget with the top/left edge of the scroll container.
+    Start,
+    /// Center the widget in the scroll container.
+    Center,
+    /// Align the widget with the bottom/right edge of the scroll container.
+    End,
+}
+
+/// The shape of the area within a widget's region that is hit by pointer
+/// events (see `WidgetNodeRequests::set_hit_shape`).
+pub enum HitShape {
+    /// The whole region.
+    Rect,
+    /// The largest circle that fits in the center of the region.
+    Circle,
+    /// The region with its corners rounded by the given radius in points.
+    RoundedRect { corner_radius: f32 },
+    /// A custom shape. The closure is given the position of the pointer
+    /// relative to the top-left corner of the region and the size of the
+    /// region, and returns whether the point is inside the shape.
+    ///
+    /// This is only called with points that lie within the region.
+    Custom(Box<dyn Fn(Point, Size) -> bool>),
+    /// The pixels that the widget painted the last time it was repainted,
+    /// so that pointer events pass through its fully transparent pixels
+    /// (i.e. the corners of an irregularly shaped sprite). The whole region
+    /// is hit until the widget has been painted.
+    ///
+    /// The pixels are read back from the layer's texture after every
+    /// repaint of the widget, so this only works on layers using
+    /// `Compositing::Texture`.
+    #[cfg(feature = "alpha-hit-testing")]
+    PaintedAlpha,
+}
+
+impl std::fmt::Debug for HitShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HitShape::Rect => write!(f, "Rect"),
+            HitShape::Circle => write!(f, "Circle"),
+            HitShape::RoundedRect { corner_radius } => f
+                .debug_struct("RoundedRect")
+                .field("corner_radius", corner_radius)
+                .finish(),
+            HitShape::Custom(_) => write!(f, "Custom(..)"),
+            #[cfg(feature = "alpha-hit-testing")]
+            HitShape::PaintedAlpha => write!(f, "PaintedAlpha"),
+        }
+    }
+}
+
+impl HitShape {
+    /// Whether the given point is inside of this shape when it is placed in
+    /// the given rect.
+    pub fn contains_point(&self, rect: Rect, point: Point) -> bool {
+        if !rect.contains_point(point) {
+            return false;
+        }
+
+        match self {
+            HitShape::Rect => true,
+            HitShape::Circle => {
+                let radius = f64::from(rect.width().min(rect.height())) / 2.0;
+                let dx = point.x - rect.center_x();
+                let dy = point.y - rect.center_y();
+
+                (dx * dx) + (dy * dy) <= radius * radius
+            }
+            HitShape::RoundedRect { corner_radius } => {
+                let radius = f64::from(
+                    corner_radius
+                        .min(rect.width() / 2.0)
+                        .min(rect.height() / 2.0)
+                        .max(0.0),
+                );
+
+                // The distance from the point to the rect inset by the
+                // radius.
+                let dx = (point.x - (rect.x() + radius)).min(0.0)
+                    + (point.x - (rect.x2() - radius)).max(0.0);
+                let dy = (point.y - (rect.y() + radius)).min(0.0)
+                    + (point.y - (rect.y2() - radius)).max(0.0);
+
+                (dx * dx) + (dy * dy) <= radius * radius
+            }
+            HitShape::Custom(f) => f(
+                Point::new(point.x - rect.x(), point.y - rect.y()),
+                rect.size(),
+            ),
+            // The painted pixels are checked against the widget's alpha mask
+            // by the region tree.
+            #[cfg(feature = "alpha-hit-testing")]
+            HitShape::PaintedAlpha => true,
+        }
+    }
+}
+
+/// The alpha channel of the pixels that a widget painted into its region,
+/// used by `HitShape::PaintedAlpha`.
+#[cfg(feature = "alpha-hit-testing")]
+#[derive(Debug, Clone)]
+pub(crate) struct AlphaMask {
+    size: PhysicalSize,
+    alpha: Vec<u8>,
+}
+
+#[cfg(feature = "alpha-hit-testing")]
+impl AlphaMask {
+    /// Create a mask from RGBA pixels in row-major order, starting from the
+    /// top-left corner.
+    pub fn from_rgba(size: PhysicalSize, rgba: &[u8]) -> Self {
+        Self {
+            size,
+            alpha: rgba.chunks_exact(4).map(|pixel| pixel[3]).collect(),
+        }
+    }
+
+    /// Whether the pixel under the given point is not fully transparent,
+    /// when the mask is stretched over the given rect.
+    pub fn is_painted_at(&self, rect: Rect, point: Point) -> bool {
+        if self.size.width == 0
+            || self.size.height == 0
+            || rect.width() <= 0.0
+            || rect.height() <= 0.0
+        {
+            return false;
+        }
+
+        let pixel = |pos: f64, start: f64, length: f32, num_pixels: u32| {
+            let i = ((pos - start) / f64::from(length) * f64::from(num_pixels)).floor();
+            (i.max(0.0) as usize).min(num_pixels as usize - 1)
+        };
+        let x = pixel(point.x, rect.x(), rect.width(), self.size.width);
+        let y = pixel(point.y, rect.y(), rect.height(), self.size.height);
+
+        self.alpha
+            .get(y * self.size.width as usize + x)
+            .map(|alpha| *alpha > 0)
+            .unwrap_or(true)
+    }
+}
+
+impl Default for HitShape {
+    fn default() -> Self {
+        HitShape::Rect
+    }
+}
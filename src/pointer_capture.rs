@@ -0,0 +1,125 @@
+/// The widgets that have captured the pointer, with the most recent capture
+/// on top.
+///
+/// The rules are:
+///
+/// * A widget requests capture with `WidgetNodeRequests::set_pointer_capture`
+///   set to `Some(true)`. It is pushed on top of the stack (if it is not
+///   already in it).
+/// * While the stack is not empty, pointer events are not hit tested. They
+///   are sent to the widget on top of the stack first, and then down the
+///   stack until a widget returns `EventCapturedStatus::Captured`. A widget
+///   that does not capture an event (i.e. a button that only cares about
+///   the press and the release) lets a widget lower in the stack (i.e. a
+///   scroll container) watch the gesture, while a widget that captures it
+///   (i.e. a slider being dragged) keeps it to itself.
+/// * A widget steals capture with `WidgetNodeRequests::steal_pointer_capture`.
+///   Every widget above it in the stack (or every widget in the stack if it
+///   was not in it) loses capture and receives
+///   `InputEvent::PointerCaptureLost`, and it becomes the top of the stack.
+/// * A widget releases capture with `set_pointer_capture` set to
+///   `Some(false)`, or when it is hidden or removed.
+pub(crate) struct PointerCaptureStack<T> {
+    entries: Vec<(u64, T)>,
+}
+
+impl<T> PointerCaptureStack<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.entries.iter().any(|(entry_id, _)| *entry_id == id)
+    }
+
+    /// The widget that currently has the pointer captured.
+    #[cfg(test)]
+    pub fn top(&self) -> Option<&T> {
+        self.entries.last().map(|(_, entry)| entry)
+    }
+
+    /// Iterate over the stack from the top down.
+    pub fn iter_top_down(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().rev().map(|(_, entry)| entry)
+    }
+
+    /// Push a widget on top of the stack if it is not already in it.
+    pub fn capture(&mut self, id: u64, entry: T) {
+        if !self.contains(id) {
+            self.entries.push((id, entry));
+        }
+    }
+
+    /// Remove a widget from the stack. Returns `true` if it was in the stack.
+    pub fn release(&mut self, id: u64) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(entry_id, _)| *entry_id != id);
+        self.entries.len() != len
+    }
+
+    /// Make a widget the top of the stack, and return the widgets that lost
+    /// capture to it (from the top down).
+    pub fn steal(&mut self, id: u64, entry: T) -> Vec<T> {
+        let keep = self
+            .entries
+            .iter()
+            .position(|(entry_id, _)| *entry_id == id)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        let lost: Vec<T> = self.entries.drain(keep..).rev().map(|(_, e)| e).collect();
+
+        if keep == 0 {
+            self.entries.push((id, entry));
+        }
+
+        lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_capture_stack() {
+        const SCROLL_CONTAINER: u64 = 0;
+        const CHILD: u64 = 1;
+
+        let mut stack = PointerCaptureStack::new();
+
+        // Capturing twice does not move a widget to the top.
+        stack.capture(SCROLL_CONTAINER, SCROLL_CONTAINER);
+        stack.capture(CHILD, CHILD);
+        stack.capture(SCROLL_CONTAINER, SCROLL_CONTAINER);
+        assert_eq!(stack.top(), Some(&CHILD));
+        assert_eq!(
+            stack.iter_top_down().copied().collect::<Vec<_>>(),
+            vec![CHILD, SCROLL_CONTAINER]
+        );
+
+        // Stealing takes the capture from every widget above the thief.
+        assert_eq!(stack.steal(SCROLL_CONTAINER, SCROLL_CONTAINER), vec![CHILD]);
+        assert_eq!(stack.top(), Some(&SCROLL_CONTAINER));
+        assert!(!stack.contains(CHILD));
+
+        // Stealing again is a no-op.
+        assert!(stack.steal(SCROLL_CONTAINER, SCROLL_CONTAINER).is_empty());
+
+        assert!(stack.release(SCROLL_CONTAINER));
+        assert!(!stack.release(SCROLL_CONTAINER));
+        assert!(stack.is_empty());
+
+        // A widget that was not in the stack steals from everyone.
+        stack.capture(SCROLL_CONTAINER, SCROLL_CONTAINER);
+        stack.capture(CHILD, CHILD);
+        assert_eq!(stack.steal(2, 2), vec![CHILD, SCROLL_CONTAINER]);
+        assert_eq!(stack.iter_top_down().copied().collect::<Vec<_>>(), vec![2]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::event::PointerEvent;
+use crate::Point;
+
+/// Only pointer positions within this window are used to compute the
+/// velocity. This smooths out jitter from individual events while still
+/// reacting quickly when the pointer changes speed or stops.
+const SMOOTHING_WINDOW: Duration = Duration::from_millis(100);
+
+/// Keeps track of recent pointer positions in order to compute the pointer's
+/// velocity.
+pub(crate) struct PointerVelocityTracker {
+    samples: VecDeque<(Point, Instant)>,
+}
+
+impl PointerVelocityTracker {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the given pointer event and return the pointer's velocity in
+    /// logical points per second.
+    pub fn update(&mut self, event: &PointerEvent, time: Instant) -> Point {
+        if event.any_button_just_pressed() {
+            self.samples.clear();
+        }
+
+        if self
+            .samples
+            .back()
+            .map(|(position, _)| *position != event.position)
+            .unwrap_or(true)
+        {
+            self.samples.push_back((event.position, time));
+        }
+
+        while let Some((_, sample_time)) = self.samples.front() {
+            if time.saturating_duration_since(*sample_time) > SMOOTHING_WINDOW {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.velocity()
+    }
+
+    /// Forget all recorded positions (i.e. when the pointer leaves the
+    /// window).
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    fn velocity(&self) -> Point {
+        if let (Some((first_pos, first_time)), Some((last_pos, last_time))) =
+            (self.samples.front(), self.samples.back())
+        {
+            let dt = last_time
+                .saturating_duration_since(*first_time)
+                .as_secs_f64();
+            if dt > 0.0 {
+                return Point::new(
+                    (last_pos.x - first_pos.x) / dt,
+                    (last_pos.y - first_pos.y) / dt,
+                );
+            }
+        }
+
+        Point::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PointerButton, PointerButtonState};
+
+    #[test]
+    fn test_constant_speed_velocity() {
+        let mut tracker = PointerVelocityTracker::new();
+        let start = Instant::now();
+
+        let mut event = PointerEvent::default();
+        let mut velocity = Point::default();
+
+        // Move 2 points right and 1 point down every 10 milliseconds, which
+        // is 200 points per second right and 100 points per second down.
+        for i in 0..30 {
+            event.update_position(Point::new(i as f64 * 2.0, i as f64));
+            velocity = tracker.update(&event, start + Duration::from_millis(i * 10));
+        }
+
+        assert!((velocity.x - 200.0).abs() < 0.01);
+        assert!((velocity.y - 100.0).abs() < 0.01);
+
+        // Pressing a button resets the velocity.
+        event.update_button(PointerButton::Left, true);
+        assert_eq!(event.left_button, PointerButtonState::JustPressed);
+        let velocity = tracker.update(&event, start + Duration::from_millis(300));
+        assert_eq!(velocity, Point::default());
+
+        // The pointer stopping moving for longer than the smoothing window
+        // brings the velocity back to zero.
+        let mut tracker = PointerVelocityTracker::new();
+        let mut event = PointerEvent::default();
+        for i in 0..10 {
+            event.update_position(Point::new(i as f64 * 2.0, 0.0));
+            tracker.update(&event, start + Duration::from_millis(i * 10));
+        }
+        event.update_button(PointerButton::Left, false);
+        let velocity = tracker.update(&event, start + Duration::from_millis(500));
+        assert_eq!(velocity, Point::default());
+    }
+}
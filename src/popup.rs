@@ -0,0 +1,154 @@
+use crate::size::{Point, Rect, Size};
+
+/// An edge of a rect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Edge {
+    pub fn opposite(&self) -> Edge {
+        match self {
+            Edge::Top => Edge::Bottom,
+            Edge::Bottom => Edge::Top,
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+        }
+    }
+}
+
+/// Where to open a popup (i.e. a dropdown menu or a tooltip) relative to the
+/// rect of the widget that it is opened from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PopupPlacement {
+    /// The edge of the target rect that the popup opens from. The popup is
+    /// aligned with the start of this edge (the left side of the top and
+    /// bottom edges, and the top side of the left and right edges).
+    pub preferred: Edge,
+    /// Whether to open the popup from the opposite edge instead if it
+    /// doesn't fit in the window on the preferred side, but does fit (or
+    /// fits better) on the opposite side.
+    pub flip: bool,
+}
+
+impl PopupPlacement {
+    pub fn new(preferred: Edge, flip: bool) -> Self {
+        Self { preferred, flip }
+    }
+
+    /// Compute the rect of a popup with the given size in window
+    /// coordinates.
+    ///
+    /// The popup is first placed against the preferred edge of `target_rect`
+    /// (flipping to the opposite edge if allowed), and then shifted along
+    /// both axes as needed to stay within the window.
+    pub fn place(&self, target_rect: Rect, popup_size: Size, window_size: Size) -> Rect {
+        let mut edge = self.preferred;
+        if self.flip {
+            let preferred_overflow = overflow(edge, target_rect, popup_size, window_size);
+            let flipped_overflow = overflow(edge.opposite(), target_rect, popup_size, window_size);
+            if preferred_overflow > 0.0 && flipped_overflow < preferred_overflow {
+                edge = edge.opposite();
+            }
+        }
+
+        let pos = position_against(edge, target_rect, popup_size);
+
+        Rect::new(
+            Point::new(
+                clamp_to_window(pos.x, popup_size.width().into(), window_size.width().into()),
+                clamp_to_window(
+                    pos.y,
+                    popup_size.height().into(),
+                    window_size.height().into(),
+                ),
+            ),
+            popup_size,
+        )
+    }
+}
+
+impl Default for PopupPlacement {
+    fn default() -> Self {
+        Self {
+            preferred: Edge::Bottom,
+            flip: true,
+        }
+    }
+}
+
+fn position_against(edge: Edge, target_rect: Rect, popup_size: Size) -> Point {
+    match edge {
+        Edge::Top => Point::new(
+            target_rect.x(),
+            target_rect.y() - f64::from(popup_size.height()),
+        ),
+        Edge::Bottom => Point::new(target_rect.x(), target_rect.y2()),
+        Edge::Left => Point::new(
+            target_rect.x() - f64::from(popup_size.width()),
+            target_rect.y(),
+        ),
+        Edge::Right => Point::new(target_rect.x2(), target_rect.y()),
+    }
+}
+
+/// How far (in points) a popup placed against the given edge would extend
+/// past the window along the axis that it opens in.
+fn overflow(edge: Edge, target_rect: Rect, popup_size: Size, window_size: Size) -> f64 {
+    let pos = position_against(edge, target_rect, popup_size);
+    match edge {
+        Edge::Top => (-pos.y).max(0.0),
+        Edge::Left => (-pos.x).max(0.0),
+        Edge::Bottom => {
+            (pos.y + f64::from(popup_size.height()) - f64::from(window_size.height())).max(0.0)
+        }
+        Edge::Right => {
+            (pos.x + f64::from(popup_size.width()) - f64::from(window_size.width())).max(0.0)
+        }
+    }
+}
+
+/// Shift a popup along one axis so that it stays within the window. A popup
+/// that is larger than the window is aligned with the start of the window.
+fn clamp_to_window(pos: f64, popup_length: f64, window_length: f64) -> f64 {
+    pos.min(window_length - popup_length).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_popup_flips_near_window_edge() {
+        let window_size = Size::new(400.0, 300.0);
+        let menu_size = Size::new(100.0, 120.0);
+        let placement = PopupPlacement::new(Edge::Bottom, true);
+
+        // A dropdown in the middle of the window opens downward.
+        let button = Rect::new(Point::new(50.0, 100.0), Size::new(80.0, 20.0));
+        assert!(placement
+            .place(button, menu_size, window_size)
+            .partial_eq_with_epsilon(Rect::new(Point::new(50.0, 120.0), menu_size)));
+
+        // A dropdown near the bottom edge flips to open upward.
+        let button = Rect::new(Point::new(50.0, 270.0), Size::new(80.0, 20.0));
+        assert!(placement
+            .place(button, menu_size, window_size)
+            .partial_eq_with_epsilon(Rect::new(Point::new(50.0, 150.0), menu_size)));
+
+        // Without flipping, the popup is shifted up just enough to fit.
+        assert!(PopupPlacement::new(Edge::Bottom, false)
+            .place(button, menu_size, window_size)
+            .partial_eq_with_epsilon(Rect::new(Point::new(50.0, 180.0), menu_size)));
+
+        // A submenu near the right edge flips to open to the left, and is
+        // shifted up to stay within the window.
+        let item = Rect::new(Point::new(320.0, 250.0), Size::new(80.0, 20.0));
+        assert!(PopupPlacement::new(Edge::Right, true)
+            .place(item, menu_size, window_size)
+            .partial_eq_with_epsilon(Rect::new(Point::new(220.0, 180.0), menu_size)));
+    }
+}
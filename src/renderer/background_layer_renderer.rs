@@ -1,12 +1,13 @@
 use femtovg::{Color, RenderTarget};
+use std::time::Instant;
 
 use crate::{
     layer::BackgroundLayer,
     size::{PhysicalPoint, PhysicalRect, Point},
-    PaintRegionInfo, Rect, ScaleFactor,
+    PaintRegionInfo, Rect, ScaleFactor2D,
 };
 
-use super::TextureState;
+use super::{blit_blend_factors, TextureState};
 
 pub(crate) struct BackgroundLayerRenderer {
     texture_state: Option<TextureState>,
@@ -23,23 +24,40 @@ impl BackgroundLayerRenderer {
         &mut self,
         layer: &mut BackgroundLayer,
         vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
     ) {
         if layer.physical_size.width == 0 || layer.physical_size.height == 0 {
             return;
         }
 
+        // The texture has to be reallocated when the layer switches between
+        // being opaque and transparent.
+        if self
+            .texture_state
+            .as_ref()
+            .map(|t| t.opaque != layer.opaque)
+            .unwrap_or(false)
+        {
+            self.clean_up(vg);
+        }
+
+        let mut texture_allocated = false;
         if self.texture_state.is_none() {
-            self.texture_state = Some(TextureState::new(layer.physical_size, vg));
+            self.texture_state = Some(TextureState::new(layer.physical_size, layer.opaque, vg));
+            texture_allocated = true;
         }
         let texture_state = self.texture_state.as_mut().unwrap();
 
         if texture_state.physical_size != layer.physical_size {
             texture_state.resize(layer.physical_size, vg);
+            texture_allocated = true;
         }
 
-        if layer.is_dirty {
-            layer.is_dirty = false;
+        // The contents of a newly allocated texture are undefined, so it is
+        // painted right away even if the repaint is throttled.
+        let now = Instant::now();
+        if texture_allocated || layer.should_repaint(now) {
+            layer.repainted(now);
 
             vg.set_render_target(RenderTarget::Image(texture_state.texture_id));
 
@@ -48,7 +66,11 @@ impl BackgroundLayerRenderer {
                 0,
                 layer.physical_size.width,
                 layer.physical_size.height,
-                Color::rgbaf(0.0, 0.0, 0.0, 0.0),
+                if layer.opaque {
+                    Color::rgbf(0.0, 0.0, 0.0)
+                } else {
+                    Color::rgbaf(0.0, 0.0, 0.0, 0.0)
+                },
             );
 
             let assigned_region_info = PaintRegionInfo {
@@ -62,7 +84,8 @@ impl BackgroundLayerRenderer {
                     pos: PhysicalPoint::new(0, 0),
                     size: layer.physical_size,
                 },
-                scale_factor,
+                scale_factor: scale_factor.scalar(),
+                scale_factor_2d: scale_factor,
             };
 
             vg.save();
@@ -84,6 +107,9 @@ impl BackgroundLayerRenderer {
             layer.physical_outer_position.x as f32,
             layer.physical_outer_position.y as f32,
         );
+        if let Some((src_factor, dst_factor)) = blit_blend_factors(layer.opaque) {
+            vg.global_composite_blend_func(src_factor, dst_factor);
+        }
 
         let mut path = femtovg::Path::new();
         path.rect(
@@ -107,6 +133,14 @@ impl BackgroundLayerRenderer {
         vg.restore();
     }
 
+    /// The amount of GPU memory used by this layer's texture in bytes.
+    pub fn texture_memory_bytes(&self) -> usize {
+        self.texture_state
+            .as_ref()
+            .map(|t| t.memory_bytes())
+            .unwrap_or(0)
+    }
+
     pub fn clean_up(&mut self, vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
         if let Some(mut texture_state) = self.texture_state.take() {
             texture_state.free(vg);
@@ -1,67 +1,222 @@
-use femtovg::{Color, ImageFlags, ImageId, PixelFormat};
+use femtovg::{BlendFactor, Color, ImageFlags, ImageId, PixelFormat, TextContext};
 //use glow::{HasContext, NativeFramebuffer, NativeTexture};
+use fnv::FnvHashMap;
 use std::ffi::c_void;
+use std::time::{Duration, Instant};
 
-use crate::{layer::StrongLayerEntry, size::PhysicalSize, AppWindow, ScaleFactor};
+use crate::{
+    layer::StrongLayerEntry,
+    size::{PhysicalPoint, PhysicalSize},
+    AppWindow, ScaleFactor2D,
+};
 
 mod background_layer_renderer;
 mod widget_layer_renderer;
 pub(crate) use background_layer_renderer::BackgroundLayerRenderer;
-pub(crate) use widget_layer_renderer::WidgetLayerRenderer;
+pub(crate) use widget_layer_renderer::{capture_widget_image, WidgetLayerRenderer};
 
 // TODO: Pack multiple layers into a single texture instead of having one
 // texture per layer.
 
+/// How the colors that are painted are interpreted when they are written to
+/// the window.
+///
+/// Note that femtovg always blends colors in the space they are given in,
+/// and it has no sRGB texture formats. This means the layer textures always
+/// store colors as-is, and this setting only affects how the final image is
+/// written to the window's framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colors are treated as sRGB-encoded values and are written to the
+    /// window as-is.
+    Srgb,
+    /// Colors are treated as linear values and are encoded to sRGB by the
+    /// GPU when they are written to the window (`GL_FRAMEBUFFER_SRGB`).
+    ///
+    /// This requires the window's surface to be sRGB-capable. It is not
+    /// supported on OpenGL ES, in which case colors are written as-is.
+    Linear,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Srgb
+    }
+}
+
+impl ColorSpace {
+    /// Whether `GL_FRAMEBUFFER_SRGB` should be enabled for this color space.
+    fn enables_framebuffer_srgb(&self, is_gles: bool) -> bool {
+        *self == ColorSpace::Linear && !is_gles
+    }
+}
+
 pub(crate) struct Renderer {
     pub vg: femtovg::Canvas<femtovg::renderer::OpenGl>,
     //glow_context: glow::Context,
     window_size: PhysicalSize,
-    scale_factor: ScaleFactor,
+    scale_factor: ScaleFactor2D,
+    is_gles: bool,
+    gl_version: Option<String>,
+    color_space: ColorSpace,
+    /// Used to enable `GL_FRAMEBUFFER_SRGB` while the frame is drawn, if the
+    /// color space needs it.
+    framebuffer_srgb: Option<FramebufferSrgb>,
+    read_pixels: Option<ReadPixelsFn>,
+    /// The image that the window mask is rasterized into, and its size.
+    window_mask_image: Option<(ImageId, PhysicalSize)>,
+    /// When each hidden layer was first seen hidden, keyed by the ID of the
+    /// layer. Only tracked while a hidden layer texture timeout is set.
+    hidden_layers_since: FnvHashMap<u64, Instant>,
 }
 
+/// The signature of `glReadPixels`. Use `read_rgba_pixels()` to call it.
+pub(crate) type ReadPixelsFn = unsafe extern "system" fn(i32, i32, i32, i32, u32, u32, *mut c_void);
+
 impl Renderer {
     #[cfg(all(feature = "glutin", not(target_arch = "wasm32")))]
-    pub fn new_from_glutin_display(display: &glutin::display::Display) -> Self {
+    pub fn new_from_glutin_display(
+        display: &glutin::display::Display,
+        text_context: Option<TextContext>,
+        color_space: ColorSpace,
+    ) -> Self {
         use glutin::display::GlDisplay;
 
         unsafe {
-            Self::new_from_function(|symbol| {
-                let symbol = std::ffi::CString::new(symbol).unwrap();
-                display.get_proc_address(symbol.as_c_str()).cast()
-            })
+            Self::new_from_function(
+                |symbol| {
+                    let symbol = std::ffi::CString::new(symbol).unwrap();
+                    display.get_proc_address(symbol.as_c_str()).cast()
+                },
+                text_context,
+                color_space,
+            )
         }
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    pub unsafe fn new_from_function<F>(mut load_fn: F) -> Self
+    pub unsafe fn new_from_function<F>(
+        mut load_fn: F,
+        text_context: Option<TextContext>,
+        color_space: ColorSpace,
+    ) -> Self
     where
         F: FnMut(&str) -> *const c_void,
     {
         let vg_renderer = femtovg::renderer::OpenGl::new_from_function(&mut load_fn).unwrap();
 
-        //log::info!("OpenGL renderer is ES: {}", vg_renderer.is_opengles());
-        println!("OpenGL renderer is ES: {}", vg_renderer.is_opengles());
+        let is_gles = vg_renderer.is_opengles();
+        let gl_version = query_gl_version(&mut load_fn);
+        let read_pixels = load_read_pixels(&mut load_fn);
 
-        let vg = femtovg::Canvas::new(vg_renderer).unwrap();
+        log::info!("OpenGL renderer is ES: {}", is_gles);
+        if let Some(gl_version) = &gl_version {
+            log::info!("OpenGL version: {}", gl_version);
+        }
 
-        //let glow_context = glow::Context::from_loader_function(load_fn);
+        let framebuffer_srgb = if color_space.enables_framebuffer_srgb(is_gles) {
+            load_framebuffer_srgb(&mut load_fn)
+        } else {
+            None
+        };
+        if color_space == ColorSpace::Linear && is_gles {
+            log::warn!(
+                "The linear color space is not supported on OpenGL ES, falling back to sRGB"
+            );
+        }
+
+        // Canvases created with the same text context share all of their fonts.
+        let vg = if let Some(text_context) = text_context {
+            femtovg::Canvas::new_with_text_context(vg_renderer, text_context).unwrap()
+        } else {
+            femtovg::Canvas::new(vg_renderer).unwrap()
+        };
 
-        //println!("{:?}", glow_context.version());
+        //let glow_context = glow::Context::from_loader_function(load_fn);
 
         Self {
             vg,
             //glow_context,
             window_size: PhysicalSize::default(),
-            scale_factor: ScaleFactor(0.0),
+            scale_factor: ScaleFactor2D::new(0.0, 0.0),
+            is_gles,
+            gl_version,
+            color_space,
+            framebuffer_srgb,
+            read_pixels,
+            window_mask_image: None,
+            hidden_layers_since: FnvHashMap::default(),
         }
     }
 
+    pub fn is_gles(&self) -> bool {
+        self.is_gles
+    }
+
+    pub fn gl_version(&self) -> Option<&str> {
+        self.gl_version.as_deref()
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Read back the RGBA color of a single pixel in the window's
+    /// framebuffer, where `point` is relative to the top-left corner of the
+    /// window.
+    ///
+    /// Returns `None` if the point lies outside of the window or if
+    /// `glReadPixels` could not be loaded.
+    pub fn read_pixel(&self, point: PhysicalPoint) -> Option<[u8; 4]> {
+        let read_pixels = self.read_pixels?;
+        let (x, y) = framebuffer_coords(point, self.window_size)?;
+
+        let mut pixel = [0u8; 4];
+        // SAFETY: `read_pixels` was loaded from the context that this
+        // renderer was created with, which the caller keeps current (see
+        // `AppWindow::read_pixel()`), and `framebuffer_coords()` only
+        // returns points inside the window.
+        unsafe {
+            read_rgba_pixels(read_pixels, x, y, PhysicalSize::new(1, 1), &mut pixel);
+        }
+
+        Some(pixel)
+    }
+
+    /// Read back the contents of the whole window's framebuffer as RGBA
+    /// pixels in row-major order, starting from the top-left corner.
+    ///
+    /// Returns `None` if nothing has been rendered yet or if `glReadPixels`
+    /// could not be loaded.
+    pub fn read_frame(&self) -> Option<Vec<u8>> {
+        let read_pixels = self.read_pixels?;
+        let (width, height) = (self.window_size.width, self.window_size.height);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        // SAFETY: `read_pixels` was loaded from the context that this
+        // renderer was created with, which the caller keeps current (see
+        // `AppWindow::render_to_rgba()`), and the whole window is read into
+        // a buffer of the same size.
+        unsafe {
+            read_rgba_pixels(read_pixels, 0, 0, self.window_size, &mut pixels);
+        }
+
+        // OpenGL's origin is the bottom-left corner.
+        flip_rows(&mut pixels, width as usize * 4);
+
+        Some(pixels)
+    }
+
     pub fn render<A: Clone + Send + Sync + 'static>(
         &mut self,
         app_window: &mut AppWindow<A>,
         window_size: PhysicalSize,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
         clear_color: Color,
+        isolated_layer_id: Option<u64>,
     ) {
         for mut layer_renderer in app_window.widget_layer_renderers_to_clean_up.drain(..) {
             layer_renderer.clean_up(&mut self.vg);
@@ -70,6 +225,27 @@ impl Renderer {
             layer_renderer.clean_up(&mut self.vg);
         }
 
+        if let Some(timeout) = app_window.hidden_layer_texture_timeout() {
+            self.free_timed_out_layer_textures(app_window, timeout);
+        } else {
+            self.hidden_layers_since.clear();
+        }
+        if let Some(budget) = app_window.texture_memory_budget() {
+            self.enforce_texture_memory_budget(app_window, budget);
+        }
+
+        if !app_window.window_visibility() {
+            trace_lifecycle!("render skipped: window hidden");
+            return;
+        }
+
+        trace_lifecycle!(
+            "render: size={}x{} isolated_layer={:?}",
+            window_size.width,
+            window_size.height,
+            isolated_layer_id
+        );
+
         /*
         unsafe {
             self.glow_context.bind_framebuffer(glow::FRAMEBUFFER, None);
@@ -95,34 +271,122 @@ impl Renderer {
         self.vg
             .clear_rect(0, 0, window_size.width, window_size.height, clear_color);
 
-        for (_z_order, layer_entries) in app_window.layers_ordered.iter_mut() {
-            for layer_entry in layer_entries.iter_mut() {
-                match layer_entry {
-                    StrongLayerEntry::Widget(layer_entry) => {
-                        let mut layer = layer_entry.borrow_mut();
-                        if layer.is_visible() {
-                            let mut layer_renderer = layer.renderer.take().unwrap();
+        // The post-composite pass is skipped when rendering a single layer in
+        // isolation.
+        let mut post_composite = if isolated_layer_id.is_none() {
+            app_window.post_composite.take()
+        } else {
+            None
+        };
 
-                            layer_renderer.render(&mut *layer, &mut self.vg, scale_factor);
+        // The window mask is not applied when rendering a single layer in
+        // isolation either.
+        let window_mask = if isolated_layer_id.is_none() {
+            app_window.window_mask.as_ref()
+        } else {
+            None
+        };
+        if window_mask.is_none() {
+            if let Some((image_id, _)) = self.window_mask_image.take() {
+                self.vg.delete_image(image_id);
+            }
+        }
+        let window_mask_image = &mut self.window_mask_image;
 
-                            layer.renderer = Some(layer_renderer);
-                        }
-                    }
-                    StrongLayerEntry::Background(layer_entry) => {
-                        let mut layer = layer_entry.borrow_mut();
-                        if layer.is_visible() {
-                            let mut layer_renderer = layer.renderer.take().unwrap();
+        let report_overlapping_dirty_widgets = app_window.report_overlapping_dirty_widgets();
+        #[cfg(feature = "alpha-hit-testing")]
+        let read_pixels = self.read_pixels;
+        let layers_ordered = &mut app_window.layers_ordered;
+        composite_frame(
+            &mut self.vg,
+            window_size,
+            |vg| {
+                for (_z_order, layer_entries) in layers_ordered.iter_mut() {
+                    for layer_entry in layer_entries.iter_mut() {
+                        match layer_entry {
+                            StrongLayerEntry::Widget(layer_entry) => {
+                                let mut layer = layer_entry.borrow_mut();
+
+                                // A layer that is hidden (or empty) isn't
+                                // rendered, so free these here.
+                                let layer_ref = &mut *layer;
+                                if let Some(layer_renderer) = &mut layer_ref.renderer {
+                                    layer_renderer.free_removed_widget_images(
+                                        &mut layer_ref.region_tree.removed_scaled_widgets,
+                                        vg,
+                                    );
+                                }
+
+                                if layer.is_visible()
+                                    && is_layer_rendered(layer.id, isolated_layer_id)
+                                {
+                                    trace_lifecycle!(
+                                        "render widget layer: layer={} dirty_widgets={}",
+                                        layer.debug_label(),
+                                        layer.region_tree.dirty_widgets.len()
+                                    );
+
+                                    let mut layer_renderer = layer.renderer.take().unwrap();
+                                    #[cfg(feature = "alpha-hit-testing")]
+                                    {
+                                        layer_renderer.read_pixels = read_pixels;
+                                    }
 
-                            layer_renderer.render(&mut *layer, &mut self.vg, scale_factor);
+                                    layer_renderer.render(
+                                        &mut *layer,
+                                        vg,
+                                        scale_factor,
+                                        report_overlapping_dirty_widgets,
+                                    );
 
-                            layer.renderer = Some(layer_renderer);
+                                    layer.renderer = Some(layer_renderer);
+                                }
+                            }
+                            StrongLayerEntry::Background(layer_entry) => {
+                                let mut layer = layer_entry.borrow_mut();
+                                if layer.is_visible()
+                                    && is_layer_rendered(layer.id, isolated_layer_id)
+                                {
+                                    trace_lifecycle!("render background layer: layer={}", layer.id);
+
+                                    let mut layer_renderer = layer.renderer.take().unwrap();
+
+                                    layer_renderer.render(&mut *layer, vg, scale_factor);
+
+                                    layer.renderer = Some(layer_renderer);
+                                }
+                            }
                         }
                     }
                 }
-            }
+
+                // The layer renderers leave the canvas targeting the screen,
+                // but make sure of it for the post-composite pass.
+                vg.set_render_target(femtovg::RenderTarget::Screen);
+            },
+            &mut post_composite,
+            window_mask.map(|mask| {
+                move |vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>| {
+                    apply_window_mask(vg, window_mask_image, mask, window_size, scale_factor);
+                }
+            }),
+        );
+
+        if post_composite.is_some() {
+            app_window.post_composite = post_composite;
         }
 
+        // femtovg only issues the GL calls for the frame when the canvas is
+        // flushed. `GL_FRAMEBUFFER_SRGB` is only enabled while that happens so
+        // that it doesn't leak into whatever else the host draws with the
+        // same context.
+        if let Some(framebuffer_srgb) = &self.framebuffer_srgb {
+            framebuffer_srgb.enable();
+        }
         self.vg.flush();
+        if let Some(framebuffer_srgb) = &self.framebuffer_srgb {
+            framebuffer_srgb.disable();
+        }
 
         /*
         unsafe {
@@ -135,6 +399,72 @@ impl Renderer {
         */
     }
 
+    /// Free the textures of layers that have been hidden for longer than
+    /// the timeout. A freed texture is recreated (and the layer is
+    /// repainted) once the layer is shown again.
+    fn free_timed_out_layer_textures<A: Clone + Send + Sync + 'static>(
+        &mut self,
+        app_window: &mut AppWindow<A>,
+        timeout: Duration,
+    ) {
+        let layers = layer_texture_memory(app_window);
+
+        let timed_out = layers_past_hidden_timeout(
+            &layers,
+            &mut self.hidden_layers_since,
+            Instant::now(),
+            timeout,
+        );
+        self.free_layer_textures(app_window, &timed_out);
+    }
+
+    /// Free the textures of hidden layers until the total texture memory is
+    /// within the budget. A freed texture is recreated (and the layer is
+    /// repainted) once the layer is shown again.
+    fn enforce_texture_memory_budget<A: Clone + Send + Sync + 'static>(
+        &mut self,
+        app_window: &mut AppWindow<A>,
+        budget: usize,
+    ) {
+        let layers = layer_texture_memory(app_window);
+
+        let evicted = layers_to_evict(&layers, budget);
+        self.free_layer_textures(app_window, &evicted);
+    }
+
+    fn free_layer_textures<A: Clone + Send + Sync + 'static>(
+        &mut self,
+        app_window: &mut AppWindow<A>,
+        layer_ids: &[u64],
+    ) {
+        if layer_ids.is_empty() {
+            return;
+        }
+
+        for (_z_order, layer_entries) in app_window.layers_ordered.iter_mut() {
+            for layer_entry in layer_entries.iter_mut() {
+                match layer_entry {
+                    StrongLayerEntry::Widget(layer_entry) => {
+                        let mut layer = layer_entry.borrow_mut();
+                        if layer_ids.contains(&layer.id) {
+                            if let Some(layer_renderer) = &mut layer.renderer {
+                                layer_renderer.clean_up(&mut self.vg);
+                            }
+                        }
+                    }
+                    StrongLayerEntry::Background(layer_entry) => {
+                        let mut layer = layer_entry.borrow_mut();
+                        if layer_ids.contains(&layer.id) {
+                            if let Some(layer_renderer) = &mut layer.renderer {
+                                layer_renderer.clean_up(&mut self.vg);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     pub fn free<A: Clone + Send + Sync + 'static>(&mut self, app_window: &mut AppWindow<A>) {
         for mut layer_renderer in app_window.widget_layer_renderers_to_clean_up.drain(..) {
             layer_renderer.clean_up(&mut self.vg);
@@ -142,25 +472,309 @@ impl Renderer {
         for mut layer_renderer in app_window.background_layer_renderers_to_clean_up.drain(..) {
             layer_renderer.clean_up(&mut self.vg);
         }
+        if let Some((image_id, _)) = self.window_mask_image.take() {
+            self.vg.delete_image(image_id);
+        }
+    }
+}
+
+/// Composite the layers onto the canvas, run the post-composite callback
+/// (if there is one) over the result, and then mask the whole frame (if
+/// there is a window mask).
+fn composite_frame<C, F: FnOnce(&mut C), M: FnOnce(&mut C)>(
+    canvas: &mut C,
+    window_size: PhysicalSize,
+    composite_layers: F,
+    post_composite: &mut Option<Box<dyn FnMut(&mut C, PhysicalSize)>>,
+    apply_window_mask: Option<M>,
+) {
+    composite_layers(canvas);
+
+    if let Some(post_composite) = post_composite {
+        post_composite(canvas, window_size);
+    }
+
+    if let Some(apply_window_mask) = apply_window_mask {
+        apply_window_mask(canvas);
+    }
+}
+
+/// Multiply the alpha of every pixel on the screen by the coverage of the
+/// mask path, so that everything outside of the path is cleared to fully
+/// transparent.
+///
+/// The path is rasterized into `mask_image` first, since pixels that a path
+/// doesn't cover can't be blended with directly.
+fn apply_window_mask(
+    vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    mask_image: &mut Option<(ImageId, PhysicalSize)>,
+    mask: &femtovg::Path,
+    window_size: PhysicalSize,
+    scale_factor: ScaleFactor2D,
+) {
+    if window_size.width == 0 || window_size.height == 0 {
+        return;
+    }
+
+    let image_id = match *mask_image {
+        Some((image_id, size)) if size == window_size => image_id,
+        _ => {
+            if let Some((old_image_id, _)) = mask_image.take() {
+                vg.delete_image(old_image_id);
+            }
+
+            match vg.create_image_empty(
+                window_size.width as usize,
+                window_size.height as usize,
+                PixelFormat::Rgba8,
+                ImageFlags::empty(),
+            ) {
+                Ok(image_id) => {
+                    *mask_image = Some((image_id, window_size));
+                    image_id
+                }
+                Err(e) => {
+                    log::error!("Failed to create image for the window mask: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+
+    vg.set_render_target(femtovg::RenderTarget::Image(image_id));
+    vg.clear_rect(
+        0,
+        0,
+        window_size.width,
+        window_size.height,
+        Color::rgba(0, 0, 0, 0),
+    );
+    vg.save();
+    vg.reset_transform();
+    vg.scale(scale_factor.x, scale_factor.y);
+    vg.fill_path(&mut mask.clone(), &femtovg::Paint::color(Color::white()));
+    vg.restore();
+
+    vg.set_render_target(femtovg::RenderTarget::Screen);
+    vg.save();
+    vg.reset_transform();
+    // "Destination in": keep the destination scaled by the mask's alpha.
+    vg.global_composite_blend_func(femtovg::BlendFactor::Zero, femtovg::BlendFactor::SrcAlpha);
+
+    let mut path = femtovg::Path::new();
+    path.rect(
+        0.0,
+        0.0,
+        window_size.width as f32,
+        window_size.height as f32,
+    );
+    let paint = femtovg::Paint::image(
+        image_id,
+        0.0,
+        window_size.height as f32,
+        window_size.width as f32,
+        -(window_size.height as f32),
+        0.0,
+        1.0,
+    );
+    vg.fill_path(&mut path, &paint);
+    vg.restore();
+}
+
+/// Whether the layer with the given ID should be rendered when rendering
+/// only the layer with `isolated_layer_id` (or all layers if it is `None`).
+fn is_layer_rendered(layer_id: u64, isolated_layer_id: Option<u64>) -> bool {
+    isolated_layer_id
+        .map(|isolated_layer_id| isolated_layer_id == layer_id)
+        .unwrap_or(true)
+}
+
+/// Convert a point relative to the top-left corner of the window into
+/// OpenGL framebuffer coordinates, which are relative to the bottom-left
+/// corner.
+///
+/// Returns `None` if the point lies outside of the window.
+fn framebuffer_coords(point: PhysicalPoint, window_size: PhysicalSize) -> Option<(i32, i32)> {
+    if point.x < 0
+        || point.y < 0
+        || point.x as u32 >= window_size.width
+        || point.y as u32 >= window_size.height
+    {
+        return None;
+    }
+
+    Some((point.x, window_size.height as i32 - 1 - point.y))
+}
+
+/// Reverse the order of the rows in the given image.
+fn flip_rows(pixels: &mut [u8], row_len: usize) {
+    let height = pixels.len() / row_len;
+    for y in 0..(height / 2) {
+        let (top, bottom) = pixels.split_at_mut((height - 1 - y) * row_len);
+        top[(y * row_len)..((y + 1) * row_len)].swap_with_slice(&mut bottom[..row_len]);
     }
 }
 
+/// Convert RGBA pixels with premultiplied alpha into RGBA pixels with
+/// straight alpha (the format that image files expect).
+pub(crate) fn unpremultiply_rgba(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let a = u16::from(pixel[3]);
+        if a == 0 {
+            pixel[0] = 0;
+            pixel[1] = 0;
+            pixel[2] = 0;
+        } else if a < 255 {
+            for c in pixel[0..3].iter_mut() {
+                *c = ((u16::from(*c) * 255 + (a / 2)) / a).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Read the RGBA pixels of the given rect of the currently bound read
+/// framebuffer into `pixels`, where `x` and `y` are OpenGL framebuffer
+/// coordinates (relative to the bottom-left corner, see
+/// `framebuffer_coords()`). The rows are stored from the bottom up.
+///
+/// # Panics
+///
+/// Panics if `pixels` is smaller than `size.width * size.height * 4` bytes.
+///
+/// # Safety
+///
+/// * `read_pixels` must have been loaded (see `load_read_pixels()`) from the
+///   GL context that is current on the calling thread.
+/// * `GL_PACK_ALIGNMENT` must be `1`, `2` or `4` (the default is `4`), and
+///   no pixel pack buffer may be bound, so that the pixels are written
+///   tightly packed into `pixels`.
+///
+/// A rect that reaches outside of the framebuffer is not undefined behavior,
+/// but the values of those pixels are undefined.
+pub(crate) unsafe fn read_rgba_pixels(
+    read_pixels: ReadPixelsFn,
+    x: i32,
+    y: i32,
+    size: PhysicalSize,
+    pixels: &mut [u8],
+) {
+    const GL_RGBA: u32 = 0x1908;
+    const GL_UNSIGNED_BYTE: u32 = 0x1401;
+
+    assert!(pixels.len() >= size.width as usize * size.height as usize * 4);
+
+    read_pixels(
+        x,
+        y,
+        size.width as i32,
+        size.height as i32,
+        GL_RGBA,
+        GL_UNSIGNED_BYTE,
+        pixels.as_mut_ptr().cast(),
+    );
+}
+
+/// Load `glReadPixels` from the current context.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn load_read_pixels<F>(load_fn: &mut F) -> Option<ReadPixelsFn>
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    let read_pixels_ptr = load_fn("glReadPixels");
+    if read_pixels_ptr.is_null() {
+        log::warn!("Could not load glReadPixels, reading back pixels is disabled");
+        return None;
+    }
+
+    Some(std::mem::transmute(read_pixels_ptr))
+}
+
+/// Query the `GL_VERSION` string of the current context.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn query_gl_version<F>(load_fn: &mut F) -> Option<String>
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    const GL_VERSION: u32 = 0x1F02;
+
+    let get_string_ptr = load_fn("glGetString");
+    if get_string_ptr.is_null() {
+        return None;
+    }
+
+    let get_string: extern "system" fn(u32) -> *const std::os::raw::c_char =
+        std::mem::transmute(get_string_ptr);
+
+    let version_ptr = get_string(GL_VERSION);
+    if version_ptr.is_null() {
+        return None;
+    }
+
+    Some(
+        std::ffi::CStr::from_ptr(version_ptr)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
+
+/// `glEnable` and `glDisable`, used to toggle `GL_FRAMEBUFFER_SRGB`.
+struct FramebufferSrgb {
+    enable: extern "system" fn(u32),
+    disable: extern "system" fn(u32),
+}
+
+impl FramebufferSrgb {
+    fn enable(&self) {
+        (self.enable)(GL_FRAMEBUFFER_SRGB);
+    }
+
+    fn disable(&self) {
+        (self.disable)(GL_FRAMEBUFFER_SRGB);
+    }
+}
+
+/// Load the functions used to toggle `GL_FRAMEBUFFER_SRGB` from the current
+/// context.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn load_framebuffer_srgb<F>(load_fn: &mut F) -> Option<FramebufferSrgb>
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    let enable_ptr = load_fn("glEnable");
+    let disable_ptr = load_fn("glDisable");
+    if enable_ptr.is_null() || disable_ptr.is_null() {
+        log::warn!("Could not load glEnable, falling back to the sRGB color space");
+        return None;
+    }
+
+    Some(FramebufferSrgb {
+        enable: std::mem::transmute(enable_ptr),
+        disable: std::mem::transmute(disable_ptr),
+    })
+}
+
 struct TextureState {
     texture_id: ImageId,
     physical_size: PhysicalSize,
+    /// Whether the texture was allocated without an alpha channel (see
+    /// `texture_pixel_format()`).
+    opaque: bool,
     freed: bool,
 }
 
 impl TextureState {
     fn new(
         physical_size: PhysicalSize,
+        opaque: bool,
         vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
     ) -> Self {
         let texture_id = vg
             .create_image_empty(
                 physical_size.width as usize,
                 physical_size.height as usize,
-                PixelFormat::Rgba8,
+                texture_pixel_format(opaque),
                 ImageFlags::NEAREST,
             )
             .unwrap();
@@ -168,10 +782,12 @@ impl TextureState {
         Self {
             texture_id,
             physical_size,
+            opaque,
             freed: false,
         }
     }
 
+    /// Recreate the texture with the given size.
     fn resize(
         &mut self,
         physical_size: PhysicalSize,
@@ -184,7 +800,7 @@ impl TextureState {
                 .create_image_empty(
                     physical_size.width as usize,
                     physical_size.height as usize,
-                    PixelFormat::Rgba8,
+                    texture_pixel_format(self.opaque),
                     ImageFlags::NEAREST,
                 )
                 .unwrap();
@@ -193,6 +809,15 @@ impl TextureState {
         }
     }
 
+    /// The amount of GPU memory used by this texture in bytes.
+    fn memory_bytes(&self) -> usize {
+        if self.freed {
+            return 0;
+        }
+
+        texture_memory_bytes_with_format(self.physical_size, texture_pixel_format(self.opaque))
+    }
+
     fn free(&mut self, vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
         if !self.freed {
             vg.delete_image(self.texture_id);
@@ -202,6 +827,151 @@ impl TextureState {
     }
 }
 
+/// The amount of GPU memory used by an RGBA8 texture of the given size in
+/// bytes.
+fn texture_memory_bytes(size: PhysicalSize) -> usize {
+    texture_memory_bytes_with_format(size, PixelFormat::Rgba8)
+}
+
+/// The amount of GPU memory used by a texture of the given size and pixel
+/// format in bytes.
+fn texture_memory_bytes_with_format(size: PhysicalSize, format: PixelFormat) -> usize {
+    let bytes_per_pixel = match format {
+        PixelFormat::Gray8 => 1,
+        PixelFormat::Rgb8 => 3,
+        _ => 4,
+    };
+
+    size.width as usize * size.height as usize * bytes_per_pixel
+}
+
+/// The pixel format of a layer texture. A layer that is marked as opaque
+/// has no use for an alpha channel, so its texture is allocated without one.
+fn texture_pixel_format(opaque: bool) -> PixelFormat {
+    if opaque {
+        PixelFormat::Rgb8
+    } else {
+        PixelFormat::Rgba8
+    }
+}
+
+/// The blend function used to blit a layer texture onto the screen, or
+/// `None` to use femtovg's regular alpha blending.
+///
+/// An opaque layer simply replaces the pixels beneath it.
+fn blit_blend_factors(opaque: bool) -> Option<(BlendFactor, BlendFactor)> {
+    if opaque {
+        Some((BlendFactor::One, BlendFactor::Zero))
+    } else {
+        None
+    }
+}
+
+/// The texture memory used by a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LayerTextureMemory {
+    layer_id: u64,
+    is_visible: bool,
+    bytes: usize,
+}
+
+/// The texture memory used by every layer in the window.
+fn layer_texture_memory<A: Clone + Send + Sync + 'static>(
+    app_window: &AppWindow<A>,
+) -> Vec<LayerTextureMemory> {
+    app_window
+        .layers_ordered
+        .iter()
+        .flat_map(|(_z_order, layer_entries)| layer_entries.iter())
+        .map(|layer_entry| match layer_entry {
+            StrongLayerEntry::Widget(layer_entry) => {
+                let layer = layer_entry.borrow();
+                LayerTextureMemory {
+                    layer_id: layer.id,
+                    is_visible: layer.is_visible(),
+                    bytes: layer
+                        .renderer
+                        .as_ref()
+                        .map(|r| r.texture_memory_bytes())
+                        .unwrap_or(0),
+                }
+            }
+            StrongLayerEntry::Background(layer_entry) => {
+                let layer = layer_entry.borrow();
+                LayerTextureMemory {
+                    layer_id: layer.id,
+                    is_visible: layer.is_visible(),
+                    bytes: layer
+                        .renderer
+                        .as_ref()
+                        .map(|r| r.texture_memory_bytes())
+                        .unwrap_or(0),
+                }
+            }
+        })
+        .collect()
+}
+
+/// The layers whose textures should be freed to bring the total texture
+/// memory within the budget.
+///
+/// Only the textures of hidden layers are freed (the largest first), since
+/// a visible layer would just recreate its texture on the next frame. This
+/// means the budget can still be exceeded by the visible layers alone.
+fn layers_to_evict(layers: &[LayerTextureMemory], budget: usize) -> Vec<u64> {
+    let mut total: usize = layers.iter().map(|layer| layer.bytes).sum();
+    if total <= budget {
+        return Vec::new();
+    }
+
+    let mut hidden_layers: Vec<&LayerTextureMemory> = layers
+        .iter()
+        .filter(|layer| !layer.is_visible && layer.bytes > 0)
+        .collect();
+    hidden_layers.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+    let mut evicted = Vec::new();
+    for layer in hidden_layers {
+        if total <= budget {
+            break;
+        }
+
+        total -= layer.bytes;
+        evicted.push(layer.layer_id);
+    }
+
+    evicted
+}
+
+/// The hidden layers that have kept their textures for longer than the
+/// timeout.
+///
+/// `hidden_since` records when each layer was first seen hidden, and is
+/// updated with the given layers: a layer that is visible (or was removed)
+/// is forgotten, so hiding it again restarts its timeout.
+fn layers_past_hidden_timeout(
+    layers: &[LayerTextureMemory],
+    hidden_since: &mut FnvHashMap<u64, Instant>,
+    now: Instant,
+    timeout: Duration,
+) -> Vec<u64> {
+    hidden_since.retain(|layer_id, _| {
+        layers
+            .iter()
+            .any(|layer| layer.layer_id == *layer_id && !layer.is_visible)
+    });
+
+    let mut timed_out = Vec::new();
+    for layer in layers.iter().filter(|layer| !layer.is_visible) {
+        let since = *hidden_since.entry(layer.layer_id).or_insert(now);
+        if layer.bytes > 0 && now.saturating_duration_since(since) >= timeout {
+            timed_out.push(layer.layer_id);
+        }
+    }
+
+    timed_out
+}
+
 /*
 struct TextureState {
     native_framebuffer: NativeFramebuffer,
@@ -335,3 +1105,356 @@ unsafe fn create_native_texture(
     native_texture
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{GlTestWindow, TestWidget};
+    use crate::{Point, Rect, ScaleFactor, Size};
+
+    #[test]
+    fn test_post_composite_draws_over_layers() {
+        // A fake canvas that records the order that things are drawn in.
+        let mut canvas: Vec<String> = Vec::new();
+        let window_size = PhysicalSize::new(800, 600);
+
+        let mut post_composite: Option<Box<dyn FnMut(&mut Vec<String>, PhysicalSize)>> =
+            Some(Box::new(|canvas, size| {
+                canvas.push(format!("vignette {}x{}", size.width, size.height));
+            }));
+
+        composite_frame(
+            &mut canvas,
+            window_size,
+            |canvas| {
+                canvas.push("background layer".into());
+                canvas.push("widget layer".into());
+            },
+            &mut post_composite,
+            None::<fn(&mut Vec<String>)>,
+        );
+
+        assert_eq!(
+            canvas,
+            vec![
+                "background layer".to_string(),
+                "widget layer".to_string(),
+                "vignette 800x600".to_string(),
+            ]
+        );
+
+        // Without a callback, only the layers are drawn.
+        canvas.clear();
+        composite_frame(
+            &mut canvas,
+            window_size,
+            |canvas| canvas.push("widget layer".into()),
+            &mut None,
+            None::<fn(&mut Vec<String>)>,
+        );
+        assert_eq!(canvas, vec!["widget layer".to_string()]);
+    }
+
+    #[test]
+    fn test_window_mask_applied_last() {
+        let mut canvas: Vec<String> = Vec::new();
+        let window_size = PhysicalSize::new(400, 400);
+
+        let mut post_composite: Option<Box<dyn FnMut(&mut Vec<String>, PhysicalSize)>> =
+            Some(Box::new(|canvas, _size| {
+                canvas.push("vignette".into());
+            }));
+
+        // The mask is applied over everything, including what the
+        // post-composite callback drew, so that nothing is left outside of
+        // the shape of the window.
+        composite_frame(
+            &mut canvas,
+            window_size,
+            |canvas| canvas.push("widget layer".into()),
+            &mut post_composite,
+            Some(|canvas: &mut Vec<String>| canvas.push("circle mask".into())),
+        );
+
+        assert_eq!(
+            canvas,
+            vec![
+                "widget layer".to_string(),
+                "vignette".to_string(),
+                "circle mask".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_texture_memory_budget_evicts_hidden_layers() {
+        let layer = |layer_id, is_visible, width, height| LayerTextureMemory {
+            layer_id,
+            is_visible,
+            bytes: texture_memory_bytes(PhysicalSize::new(width, height)),
+        };
+
+        let layers = [
+            layer(0, true, 1000, 1000),
+            layer(1, false, 200, 200),
+            layer(2, false, 500, 500),
+            layer(3, false, 100, 100),
+        ];
+        let total: usize = layers.iter().map(|l| l.bytes).sum();
+        assert_eq!(layers[0].bytes, 4_000_000);
+
+        // Within budget.
+        assert!(layers_to_evict(&layers, total).is_empty());
+
+        // The largest hidden layer is freed first, and only as many layers as
+        // needed are freed.
+        assert_eq!(layers_to_evict(&layers, total - 1), vec![2]);
+        assert_eq!(
+            layers_to_evict(&layers, total - layers[2].bytes - 1),
+            vec![2, 1]
+        );
+
+        // Visible layers are never freed, even if the budget can't be met.
+        assert_eq!(layers_to_evict(&layers, 0), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_opaque_layer_texture_format() {
+        assert!(matches!(texture_pixel_format(false), PixelFormat::Rgba8));
+        assert!(matches!(texture_pixel_format(true), PixelFormat::Rgb8));
+
+        // Dropping the alpha channel saves a quarter of the memory.
+        let size = PhysicalSize::new(100, 50);
+        assert_eq!(texture_memory_bytes(size), 20_000);
+        assert_eq!(
+            texture_memory_bytes_with_format(size, texture_pixel_format(true)),
+            15_000
+        );
+
+        // Transparent layers keep femtovg's regular alpha blending.
+        assert!(blit_blend_factors(false).is_none());
+
+        // An opaque layer replaces the pixels beneath it, no matter what
+        // alpha value is sampled from the texture.
+        let (src_factor, dst_factor) = blit_blend_factors(true).unwrap();
+        let factor = |factor: BlendFactor, src_alpha: f32| match factor {
+            BlendFactor::One => 1.0,
+            BlendFactor::Zero => 0.0,
+            BlendFactor::OneMinusSrcAlpha => 1.0 - src_alpha,
+            _ => unreachable!(),
+        };
+        let (src, dst) = (0.25, 0.5);
+        for src_alpha in [0.0, 0.5, 1.0] {
+            assert_eq!(
+                src * factor(src_factor, src_alpha) + dst * factor(dst_factor, src_alpha),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_hidden_layer_texture_timeout() {
+        let layer = |layer_id, is_visible, bytes| LayerTextureMemory {
+            layer_id,
+            is_visible,
+            bytes,
+        };
+        let timeout = Duration::from_secs(10);
+        let start = Instant::now();
+        let after = |secs| start + Duration::from_secs(secs);
+        let mut hidden_since = FnvHashMap::default();
+
+        let layers = [layer(0, true, 100), layer(1, false, 100)];
+        assert!(layers_past_hidden_timeout(&layers, &mut hidden_since, start, timeout).is_empty());
+        assert!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(9), timeout).is_empty()
+        );
+
+        // Only the hidden layer's texture is freed once the timeout passes.
+        assert_eq!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(10), timeout),
+            vec![1]
+        );
+
+        // A freed texture isn't freed again.
+        let layers = [layer(0, true, 100), layer(1, false, 0)];
+        assert!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(11), timeout).is_empty()
+        );
+
+        // Showing the layer recreates its texture, and hiding it again
+        // restarts the timeout.
+        let layers = [layer(0, true, 100), layer(1, true, 100)];
+        assert!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(12), timeout).is_empty()
+        );
+        assert!(hidden_since.is_empty());
+
+        let layers = [layer(0, true, 100), layer(1, false, 100)];
+        assert!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(13), timeout).is_empty()
+        );
+        assert!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(22), timeout).is_empty()
+        );
+        assert_eq!(
+            layers_past_hidden_timeout(&layers, &mut hidden_since, after(23), timeout),
+            vec![1]
+        );
+
+        // Removed layers are forgotten.
+        layers_past_hidden_timeout(
+            &[layer(0, true, 100)],
+            &mut hidden_since,
+            after(24),
+            timeout,
+        );
+        assert!(hidden_since.is_empty());
+    }
+
+    #[test]
+    fn test_srgb_color_space_is_default() {
+        assert_eq!(ColorSpace::default(), ColorSpace::Srgb);
+
+        // The default path must leave the framebuffer untouched so that
+        // colors are written exactly as they were before.
+        assert!(!ColorSpace::Srgb.enables_framebuffer_srgb(false));
+        assert!(!ColorSpace::Srgb.enables_framebuffer_srgb(true));
+
+        assert!(ColorSpace::Linear.enables_framebuffer_srgb(false));
+        assert!(!ColorSpace::Linear.enables_framebuffer_srgb(true));
+    }
+
+    #[test]
+    fn test_isolated_layer_rendering() {
+        // All layers are rendered by default.
+        assert!(is_layer_rendered(0, None));
+        assert!(is_layer_rendered(1, None));
+
+        // Isolating a layer excludes every other layer.
+        assert!(!is_layer_rendered(0, Some(1)));
+        assert!(is_layer_rendered(1, Some(1)));
+        assert!(!is_layer_rendered(2, Some(1)));
+    }
+
+    #[test]
+    fn test_render_single_layer_excludes_other_layers() {
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+
+        // Two layers side by side, each with a widget filling it.
+        let add_layer = |app_window: &mut AppWindow<()>, x: f64, z_order, color| {
+            let layer = app_window.add_widget_layer(
+                Size::new(50.0, 50.0),
+                z_order,
+                Point::new(x, 0.0),
+                Point::new(0.0, 0.0),
+                true,
+            );
+            app_window
+                .add_widget_node_at_rect(
+                    Box::new(TestWidget::new().with_color(color)),
+                    &layer,
+                    Rect::new(Point::new(0.0, 0.0), Size::new(50.0, 50.0)),
+                    true,
+                )
+                .unwrap();
+            layer
+        };
+        let left_layer = add_layer(app_window, 0.0, 0, Color::rgb(255, 0, 0));
+        let right_layer = add_layer(app_window, 50.0, 1, Color::rgb(0, 0, 255));
+
+        let clear_color = Color::rgb(0, 0, 0);
+        let left = PhysicalPoint::new(25, 25);
+        let right = PhysicalPoint::new(75, 25);
+
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(app_window.read_pixel(left), Some([255, 0, 0, 255]));
+        assert_eq!(app_window.read_pixel(right), Some([0, 0, 255, 255]));
+
+        // Only the isolated layer's widgets make it into the frame.
+        app_window
+            .render_single_layer(&left_layer, window_size, clear_color)
+            .unwrap();
+        assert_eq!(app_window.read_pixel(left), Some([255, 0, 0, 255]));
+        assert_eq!(app_window.read_pixel(right), Some([0, 0, 0, 255]));
+
+        app_window
+            .render_single_layer(&right_layer, window_size, clear_color)
+            .unwrap();
+        assert_eq!(app_window.read_pixel(left), Some([0, 0, 0, 255]));
+        assert_eq!(app_window.read_pixel(right), Some([0, 0, 255, 255]));
+
+        // The other layers are drawn again on the next full render.
+        app_window.render_with_clear_color(window_size, clear_color);
+        assert_eq!(app_window.read_pixel(left), Some([255, 0, 0, 255]));
+        assert_eq!(app_window.read_pixel(right), Some([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_framebuffer_coords() {
+        let window_size = PhysicalSize::new(200, 100);
+
+        // The y axis is flipped, since OpenGL's origin is the bottom-left
+        // corner.
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(0, 0), window_size),
+            Some((0, 99))
+        );
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(199, 99), window_size),
+            Some((199, 0))
+        );
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(50, 25), window_size),
+            Some((50, 74))
+        );
+
+        // Points outside of the window cannot be read.
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(-1, 0), window_size),
+            None
+        );
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(200, 0), window_size),
+            None
+        );
+        assert_eq!(
+            framebuffer_coords(PhysicalPoint::new(0, 100), window_size),
+            None
+        );
+    }
+
+    #[test]
+    fn test_flip_rows() {
+        // A 2x3 image with one byte per pixel.
+        let mut pixels = vec![0, 1, 2, 3, 4, 5];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, vec![4, 5, 2, 3, 0, 1]);
+
+        let mut pixels = vec![0, 1, 2, 3];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, vec![2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_unpremultiply_rgba() {
+        let mut pixels = vec![
+            // Opaque pixels are unchanged.
+            200, 100, 50, 255, //
+            // Half-transparent white.
+            128, 128, 128, 128, //
+            // Fully transparent pixels have no color.
+            10, 20, 30, 0,
+        ];
+        unpremultiply_rgba(&mut pixels);
+        assert_eq!(
+            pixels,
+            vec![200, 100, 50, 255, 255, 255, 255, 128, 0, 0, 0, 0]
+        );
+    }
+}
@@ -1,24 +1,45 @@
-use femtovg::{Color, RenderTarget};
+use femtovg::{BlendFactor, Color, ImageFlags, ImageId, PixelFormat, RenderTarget};
+use fnv::FnvHashMap;
 
 use crate::{
-    layer::WidgetLayer,
-    size::{PhysicalPoint, PhysicalRect, TextureRect},
-    PaintRegionInfo, Rect, ScaleFactor,
+    layer::{BlendMode, Compositing, WidgetLayer},
+    node::StrongWidgetNodeEntry,
+    size::{PhysicalPoint, PhysicalRect, PhysicalSize, TextureRect},
+    PaintRegionInfo, Rect, ScaleFactor2D, WidgetNodeRef,
 };
 
-use super::TextureState;
+#[cfg(feature = "alpha-hit-testing")]
+use super::{read_rgba_pixels, ReadPixelsFn};
+use super::{texture_memory_bytes, TextureState};
+#[cfg(feature = "alpha-hit-testing")]
+use crate::node::AlphaMask;
 
 // TODO: Pack multiple layers into a single texture instead of having one
 // texture per layer.
 
 pub(crate) struct WidgetLayerRenderer {
     texture_state: Option<TextureState>,
+    /// The images that widgets with a render scale below `1.0` are painted
+    /// into, keyed by the unique ID of the widget.
+    scaled_images: FnvHashMap<u64, (ImageId, PhysicalSize)>,
+    /// The content version of the layer when its texture was last painted
+    /// (see `WidgetLayer::content_version`).
+    last_rendered_content_version: Option<u64>,
+    /// Used to read back the pixels of the widgets that use
+    /// `HitShape::PaintedAlpha`. This is set by the `Renderer` before every
+    /// render.
+    #[cfg(feature = "alpha-hit-testing")]
+    pub read_pixels: Option<ReadPixelsFn>,
 }
 
 impl WidgetLayerRenderer {
     pub fn new() -> Self {
         Self {
             texture_state: None,
+            scaled_images: FnvHashMap::default(),
+            last_rendered_content_version: None,
+            #[cfg(feature = "alpha-hit-testing")]
+            read_pixels: None,
         }
     }
 
@@ -26,56 +47,79 @@ impl WidgetLayerRenderer {
         &mut self,
         layer: &mut WidgetLayer<A>,
         vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
-        scale_factor: ScaleFactor,
+        scale_factor: ScaleFactor2D,
+        report_overlapping_dirty_widgets: bool,
     ) {
         let physical_size = layer.region_tree.layer_physical_size();
         if physical_size.width == 0 || physical_size.height == 0 {
             return;
         }
 
+        if layer.compositing == Compositing::Direct {
+            self.render_direct(layer, vg);
+            return;
+        }
+
+        let texture_size = texture_alloc_size(
+            physical_size,
+            layer
+                .reserved_texture_size
+                .map(|size| size.to_physical_2d(scale_factor)),
+        );
+
+        let mut texture_changed = false;
         if self.texture_state.is_none() {
-            self.texture_state = Some(TextureState::new(physical_size, vg));
+            self.texture_state = Some(TextureState::new(texture_size, false, vg));
+            texture_changed = true;
         }
         let texture_state = self.texture_state.as_mut().unwrap();
 
-        if texture_state.physical_size != physical_size {
-            texture_state.resize(physical_size, vg);
+        if texture_state.physical_size != texture_size {
+            texture_state.resize(texture_size, vg);
+            texture_changed = true;
         }
 
-        if layer.is_dirty() {
+        let geometry_changed = texture_changed
+            || layer.region_tree.clear_whole_layer
+            || layer.region_tree.geometry_changed;
+        let skip_paint = content_unchanged(
+            layer.content_version,
+            self.last_rendered_content_version,
+            geometry_changed,
+        );
+
+        if layer.is_dirty() && skip_paint {
+            // Keep the texture as it is, and repaint the whole layer once the
+            // content version changes (or a region moves).
+            layer.region_tree.dirty_widgets.clear();
+            layer.region_tree.texture_rects_to_clear.clear();
+            layer.content_stale = true;
+        } else if layer.is_dirty() {
+            if layer.content_stale {
+                layer.region_tree.mark_all_dirty();
+                layer.content_stale = false;
+            }
+
             vg.set_render_target(RenderTarget::Image(texture_state.texture_id));
 
             // -- Clear the regions marked to be cleared -------------------------------------------
 
-            if layer.region_tree.clear_whole_layer {
-                layer.region_tree.clear_whole_layer = false;
-                layer.region_tree.texture_rects_to_clear.clear();
-
-                vg.clear_rect(
-                    0,
-                    0,
-                    physical_size.width,
-                    physical_size.height,
-                    Color::rgba(0, 0, 0, 0),
-                );
-            } else {
-                for clear_rect in layer.region_tree.texture_rects_to_clear.drain(..) {
-                    if clear_rect.size.width == 0 || clear_rect.size.height == 0 {
-                        continue;
-                    }
-
-                    vg.clear_rect(
-                        clear_rect.x,
-                        clear_rect.y,
-                        clear_rect.size.width,
-                        clear_rect.size.height,
-                        Color::rgba(0, 0, 0, 0),
-                    );
-                }
-            }
+            let region_tree = &mut layer.region_tree;
+            clear_texture_rects(
+                &mut region_tree.clear_whole_layer,
+                &mut region_tree.texture_rects_to_clear,
+                physical_size,
+                layer.clear_color,
+                |rect, color| {
+                    vg.clear_rect(rect.x, rect.y, rect.size.width, rect.size.height, color);
+                },
+            );
 
             // -- Paint the dirty widgets ----------------------------------------------------------
 
+            // Widgets are painted at the scale of the layer's content, which
+            // includes its content zoom.
+            let content_scale_factor = layer.region_tree.content_scale_factor();
             let mut assigned_region_info = PaintRegionInfo {
                 rect: Rect::default(),
                 layer_rect: layer.region_tree.layer_rect(),
@@ -86,31 +130,122 @@ impl WidgetLayerRenderer {
                     pos: PhysicalPoint::new(0, 0),
                     size: physical_size,
                 },
-                scale_factor,
+                scale_factor: content_scale_factor.scalar(),
+                scale_factor_2d: content_scale_factor,
             };
 
+            let clear_color = layer.clear_color;
+            let layer_rect = layer.region_tree.layer_rect();
+            let mut painted_widgets: Vec<(StrongWidgetNodeEntry<A>, TextureRect)> = Vec::new();
+            #[cfg(feature = "alpha-hit-testing")]
+            let mut alpha_widgets: Vec<(StrongWidgetNodeEntry<A>, TextureRect)> = Vec::new();
             for widget_entry in layer.region_tree.dirty_widgets.iter_mut() {
                 vg.save();
 
                 if let Some(assigned_region) = widget_entry.assigned_region().upgrade() {
-                    let (assigned_rect, physical_rect) = {
+                    let (
+                        assigned_rect,
+                        physical_rect,
+                        render_scale,
+                        repaint_texture_rect,
+                        visible_texture_rect,
+                        clipped,
+                    ) = {
                         let mut assigned_region = assigned_region.borrow_mut();
 
                         let physical_rect = assigned_region.region.physical_rect;
 
+                        let repaint_texture_rect = assigned_region.region.repaint_texture_rect();
+                        assigned_region.region.partial_repaint_rect = None;
+
                         // The `clear_rect` method in femtovg wants coordinates in `u32`, not
                         // `i32`, so we use this type to correctly clear the region the next
                         // time the widget needs to repaint.
                         let texture_rect = TextureRect::from_physical_rect(physical_rect);
-                        assigned_region.region.last_rendered_texture_rect = Some(texture_rect);
 
-                        (assigned_region.region.rect, physical_rect)
+                        // Widgets in a scrollable container region are clipped
+                        // to the container, so only the visible part of them is
+                        // painted (and later cleared).
+                        let clip_rect = assigned_region.scroll_clip_rect();
+                        let visible_texture_rect = match clip_rect {
+                            Some(clip_rect) => texture_rect.intersection(&clip_rect),
+                            None => Some(texture_rect),
+                        };
+                        assigned_region.region.last_rendered_texture_rect = visible_texture_rect;
+                        if report_overlapping_dirty_widgets {
+                            painted_widgets.push((widget_entry.clone(), texture_rect));
+                        }
+                        #[cfg(feature = "alpha-hit-testing")]
+                        if assigned_region.uses_painted_alpha() {
+                            alpha_widgets.push((widget_entry.clone(), texture_rect));
+                        }
+
+                        (
+                            assigned_region.region.widget_rect(layer_rect),
+                            physical_rect,
+                            assigned_region.region.render_scale,
+                            repaint_texture_rect,
+                            visible_texture_rect,
+                            clip_rect.is_some(),
+                        )
                     };
 
                     assigned_region_info.rect = assigned_rect;
                     assigned_region_info.physical_rect = physical_rect;
 
-                    widget_entry.borrow_mut().paint(vg, &assigned_region_info);
+                    // Only clear and paint the part of the widget that has
+                    // changed and that isn't scrolled out of view.
+                    let paint_rect = match (repaint_texture_rect, visible_texture_rect) {
+                        (Some(rect), Some(visible_rect)) => rect.intersection(&visible_rect),
+                        (None, visible_rect) => visible_rect,
+                        (Some(_), None) => None,
+                    };
+                    let paint_rect = match paint_rect {
+                        Some(rect) => rect,
+                        None => {
+                            vg.restore();
+                            continue;
+                        }
+                    };
+
+                    if repaint_texture_rect.is_some() {
+                        vg.clear_rect(
+                            paint_rect.x,
+                            paint_rect.y,
+                            paint_rect.size.width,
+                            paint_rect.size.height,
+                            clear_color,
+                        );
+                    }
+                    if repaint_texture_rect.is_some() || clipped {
+                        vg.scissor(
+                            paint_rect.x as f32,
+                            paint_rect.y as f32,
+                            paint_rect.size.width as f32,
+                            paint_rect.size.height as f32,
+                        );
+                    }
+
+                    if render_scale < 1.0 {
+                        paint_scaled(
+                            &mut self.scaled_images,
+                            widget_entry,
+                            &assigned_region_info,
+                            render_scale,
+                            texture_state.texture_id,
+                            vg,
+                        );
+                    } else {
+                        if let Some((image_id, _)) =
+                            self.scaled_images.remove(&widget_entry.unique_id())
+                        {
+                            vg.delete_image(image_id);
+                        }
+
+                        paint_isolated(vg, |vg| {
+                            widget_entry.borrow_mut().paint(vg, &assigned_region_info);
+                        });
+                    }
                 } else {
                     log::error!("Someting went wrong: widget was not assigned a region");
                 }
@@ -118,8 +253,46 @@ impl WidgetLayerRenderer {
                 vg.restore();
             }
             layer.region_tree.dirty_widgets.clear();
+            layer.region_tree.geometry_changed = false;
+
+            #[cfg(feature = "alpha-hit-testing")]
+            if let Some(read_pixels) = self.read_pixels {
+                if !alpha_widgets.is_empty() {
+                    // Draw the widgets into the texture before reading it
+                    // back. femtovg leaves the texture's framebuffer bound
+                    // afterwards.
+                    vg.flush();
+                }
+
+                for (widget_entry, rect) in alpha_widgets.drain(..) {
+                    if let Some(assigned_region) = widget_entry.assigned_region().upgrade() {
+                        assigned_region
+                            .borrow_mut()
+                            .set_alpha_mask(read_alpha_mask(read_pixels, rect));
+                    }
+                }
+            }
+
+            if report_overlapping_dirty_widgets {
+                let rects: Vec<TextureRect> =
+                    painted_widgets.iter().map(|(_, rect)| *rect).collect();
+                for (i, j) in overlapping_rects(&rects) {
+                    log::warn!(
+                        "Overlapping dirty widgets were painted in widget layer {}: {:?} and {:?}",
+                        layer.debug_label(),
+                        WidgetNodeRef {
+                            shared: painted_widgets[i].0.downgrade()
+                        },
+                        WidgetNodeRef {
+                            shared: painted_widgets[j].0.downgrade()
+                        },
+                    );
+                }
+            }
 
             vg.set_render_target(RenderTarget::Screen);
+
+            self.last_rendered_content_version = layer.content_version;
         }
 
         // -- Blit the layer to the screen ---------------------------------------------------------
@@ -129,6 +302,9 @@ impl WidgetLayerRenderer {
             layer.physical_outer_position.x as f32,
             layer.physical_outer_position.y as f32,
         );
+        if let Some((src_factor, dst_factor)) = blend_factors(layer.blend_mode) {
+            vg.global_composite_blend_func(src_factor, dst_factor);
+        }
 
         let mut path = femtovg::Path::new();
         path.rect(
@@ -138,12 +314,15 @@ impl WidgetLayerRenderer {
             physical_size.height as f32,
         );
 
+        // Only the top-left part of a reserved texture that is larger than the
+        // layer is blitted.
+        let blit_size = texture_state.physical_size;
         let paint = femtovg::Paint::image(
             texture_state.texture_id,
             0.0,
-            physical_size.height as f32,
-            physical_size.width as f32,
-            -(physical_size.height as f32),
+            blit_size.height as f32,
+            blit_size.width as f32,
+            -(blit_size.height as f32),
             0.0,
             1.0,
         );
@@ -152,9 +331,822 @@ impl WidgetLayerRenderer {
         vg.restore();
     }
 
+    /// Paint every visible widget straight onto the screen, without using
+    /// the layer's texture.
+    fn render_direct<A: Clone + Send + Sync + 'static>(
+        &mut self,
+        layer: &mut WidgetLayer<A>,
+        vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    ) {
+        // The texture isn't needed in this mode.
+        self.clean_up(vg);
+
+        // The screen is cleared every frame, so there is nothing to gain from
+        // keeping track of the dirty regions in this mode.
+        layer.region_tree.clear_whole_layer = false;
+        layer.region_tree.texture_rects_to_clear.clear();
+        layer.region_tree.dirty_widgets.clear();
+
+        let physical_size = layer.region_tree.layer_physical_size();
+        let layer_rect = layer.region_tree.layer_rect();
+        let content_scale_factor = layer.region_tree.content_scale_factor();
+
+        let mut assigned_region_info = PaintRegionInfo {
+            rect: Rect::default(),
+            layer_rect,
+            physical_rect: PhysicalRect::default(),
+            layer_physical_rect: PhysicalRect {
+                pos: PhysicalPoint::new(0, 0),
+                size: physical_size,
+            },
+            scale_factor: content_scale_factor.scalar(),
+            scale_factor_2d: content_scale_factor,
+        };
+
+        let mut widgets = Vec::new();
+        layer
+            .region_tree
+            .collect_visible_painted_widgets(&mut widgets);
+
+        vg.save();
+        vg.translate(
+            layer.physical_outer_position.x as f32,
+            layer.physical_outer_position.y as f32,
+        );
+
+        for widget_entry in widgets.iter_mut() {
+            if let Some(assigned_region) = widget_entry.assigned_region().upgrade() {
+                let (assigned_rect, physical_rect, clip_rect) = {
+                    let assigned_region = assigned_region.borrow();
+                    (
+                        assigned_region.region.widget_rect(layer_rect),
+                        assigned_region.region.physical_rect,
+                        assigned_region.scroll_clip_rect(),
+                    )
+                };
+
+                assigned_region_info.rect = assigned_rect;
+                assigned_region_info.physical_rect = physical_rect;
+
+                paint_isolated(vg, |vg| {
+                    vg.scissor(
+                        0.0,
+                        0.0,
+                        physical_size.width as f32,
+                        physical_size.height as f32,
+                    );
+                    if let Some(clip_rect) = clip_rect {
+                        vg.intersect_scissor(
+                            clip_rect.x as f32,
+                            clip_rect.y as f32,
+                            clip_rect.size.width as f32,
+                            clip_rect.size.height as f32,
+                        );
+                    }
+
+                    widget_entry.borrow_mut().paint(vg, &assigned_region_info);
+                });
+            } else {
+                log::error!("Someting went wrong: widget was not assigned a region");
+            }
+        }
+
+        vg.restore();
+    }
+
+    /// The amount of GPU memory used by this layer's textures in bytes.
+    pub fn texture_memory_bytes(&self) -> usize {
+        let texture_bytes = self
+            .texture_state
+            .as_ref()
+            .map(|t| t.memory_bytes())
+            .unwrap_or(0);
+        let scaled_image_bytes: usize = self
+            .scaled_images
+            .values()
+            .map(|(_, size)| texture_memory_bytes(*size))
+            .sum();
+
+        texture_bytes + scaled_image_bytes
+    }
+
+    /// Free the images of the scaled widgets that have been removed from the
+    /// layer (see `RegionTree::removed_scaled_widgets`).
+    pub fn free_removed_widget_images(
+        &mut self,
+        removed_scaled_widgets: &mut Vec<u64>,
+        vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+    ) {
+        for unique_id in removed_scaled_widgets.drain(..) {
+            if let Some((image_id, _)) = self.scaled_images.remove(&unique_id) {
+                vg.delete_image(image_id);
+            }
+        }
+    }
+
     pub fn clean_up(&mut self, vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>) {
         if let Some(mut texture_state) = self.texture_state.take() {
             texture_state.free(vg)
         }
+        for (_, (image_id, _)) in self.scaled_images.drain() {
+            vg.delete_image(image_id);
+        }
+        self.last_rendered_content_version = None;
+    }
+}
+
+/// Paint a widget into an image that is smaller than its physical region,
+/// and then stretch that image over its region in the layer's texture.
+fn paint_scaled<A: Clone + Send + Sync + 'static>(
+    scaled_images: &mut FnvHashMap<u64, (ImageId, PhysicalSize)>,
+    widget_entry: &mut StrongWidgetNodeEntry<A>,
+    region_info: &PaintRegionInfo,
+    render_scale: f32,
+    layer_texture_id: ImageId,
+    vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+) {
+    let scaled_region_info = scaled_paint_region_info(region_info, render_scale);
+    let image_size = scaled_region_info.physical_rect.size;
+    if image_size.width == 0 || image_size.height == 0 {
+        return;
+    }
+
+    let cached_image = scaled_images.get(&widget_entry.unique_id()).copied();
+    let image_id = match cached_image {
+        Some((image_id, size)) if size == image_size => image_id,
+        _ => {
+            if let Some((old_image_id, _)) = cached_image {
+                vg.delete_image(old_image_id);
+            }
+
+            match vg.create_image_empty(
+                image_size.width as usize,
+                image_size.height as usize,
+                PixelFormat::Rgba8,
+                ImageFlags::empty(),
+            ) {
+                Ok(image_id) => {
+                    scaled_images.insert(widget_entry.unique_id(), (image_id, image_size));
+                    image_id
+                }
+                Err(e) => {
+                    log::error!("Failed to create image for scaled widget: {}", e);
+                    scaled_images.remove(&widget_entry.unique_id());
+                    return;
+                }
+            }
+        }
+    };
+
+    vg.set_render_target(RenderTarget::Image(image_id));
+    vg.clear_rect(
+        0,
+        0,
+        image_size.width,
+        image_size.height,
+        Color::rgba(0, 0, 0, 0),
+    );
+
+    // The widget paints itself at its scaled-down position in the layer, so
+    // move that position to the origin of the image.
+    let image_pos = scaled_region_info.physical_rect.pos;
+    paint_isolated(vg, |vg| {
+        // The scissor set for the layer's texture doesn't apply to the
+        // image. It is applied again when the image is drawn below.
+        vg.reset_scissor();
+        vg.translate(-image_pos.x as f32, -image_pos.y as f32);
+        widget_entry.borrow_mut().paint(vg, &scaled_region_info);
+    });
+
+    vg.set_render_target(RenderTarget::Image(layer_texture_id));
+
+    let rect = region_info.physical_rect;
+    let mut path = femtovg::Path::new();
+    path.rect(
+        rect.pos.x as f32,
+        rect.pos.y as f32,
+        rect.size.width as f32,
+        rect.size.height as f32,
+    );
+
+    // The image is sampled with linear filtering when it is stretched.
+    let paint = femtovg::Paint::image(
+        image_id,
+        rect.pos.x as f32,
+        rect.pos.y as f32 + rect.size.height as f32,
+        rect.size.width as f32,
+        -(rect.size.height as f32),
+        0.0,
+        1.0,
+    );
+
+    vg.fill_path(&mut path, &paint);
+}
+
+/// Read back the alpha of the pixels in the given rect of the bound texture.
+#[cfg(feature = "alpha-hit-testing")]
+fn read_alpha_mask(read_pixels: ReadPixelsFn, rect: TextureRect) -> AlphaMask {
+    let mut pixels = vec![0u8; rect.size.width as usize * rect.size.height as usize * 4];
+    if !pixels.is_empty() {
+        // femtovg flips image render targets, so the rows of the texture
+        // start from the top like the rows of the layer.
+        //
+        // SAFETY: `read_pixels` was loaded from the renderer's context, which
+        // is current while rendering, and femtovg leaves the framebuffer of
+        // the layer's texture bound after `flush()`. The rect is within the
+        // texture since it was just painted.
+        unsafe {
+            read_rgba_pixels(
+                read_pixels,
+                rect.x as i32,
+                rect.y as i32,
+                rect.size,
+                &mut pixels,
+            );
+        }
+    }
+
+    AlphaMask::from_rgba(rect.size, &pixels)
+}
+
+/// The region info used to paint a widget into its scaled-down image. The
+/// logical rects are left as is, and the physical rects are scaled down
+/// along with the scale factor, so the widget paints at its scaled-down
+/// position in the layer (which is moved to the origin of the image).
+fn scaled_paint_region_info(region_info: &PaintRegionInfo, render_scale: f32) -> PaintRegionInfo {
+    let size = region_info.physical_rect.size;
+    let image_size = scaled_render_size(size, render_scale);
+
+    // The size of the image is rounded, so use the scale that it actually
+    // ended up with.
+    let actual_scale = |image_length: u32, length: u32| {
+        if length == 0 {
+            render_scale
+        } else {
+            image_length as f32 / length as f32
+        }
+    };
+    let scale_x = actual_scale(image_size.width, size.width);
+    let scale_y = actual_scale(image_size.height, size.height);
+
+    let scale_point = |point: PhysicalPoint| {
+        PhysicalPoint::new(
+            (point.x as f32 * scale_x).round() as i32,
+            (point.y as f32 * scale_y).round() as i32,
+        )
+    };
+    let layer_physical_rect = region_info.layer_physical_rect;
+
+    let scale_factor_2d = ScaleFactor2D::new(
+        region_info.scale_factor_2d.x * scale_x,
+        region_info.scale_factor_2d.y * scale_y,
+    );
+
+    PaintRegionInfo {
+        physical_rect: PhysicalRect {
+            pos: scale_point(region_info.physical_rect.pos),
+            size: image_size,
+        },
+        layer_physical_rect: PhysicalRect {
+            pos: scale_point(layer_physical_rect.pos),
+            size: PhysicalSize::new(
+                (layer_physical_rect.size.width as f32 * scale_x).round() as u32,
+                (layer_physical_rect.size.height as f32 * scale_y).round() as u32,
+            ),
+        },
+        scale_factor: scale_factor_2d.scalar(),
+        scale_factor_2d,
+        ..*region_info
+    }
+}
+
+/// The physical size of the image that a widget with the given render scale
+/// is painted into.
+fn scaled_render_size(size: PhysicalSize, render_scale: f32) -> PhysicalSize {
+    let scale = |length: u32| {
+        if length == 0 {
+            0
+        } else {
+            ((length as f32 * render_scale).round() as u32).max(1)
+        }
+    };
+
+    PhysicalSize::new(scale(size.width), scale(size.height))
+}
+
+/// Paint a single widget into a new image the size of its physical region.
+///
+/// Returns `None` if the widget has no assigned region or if its region is
+/// empty.
+pub(crate) fn capture_widget_image<A: Clone + Send + Sync + 'static>(
+    layer: &WidgetLayer<A>,
+    widget_entry: &mut StrongWidgetNodeEntry<A>,
+    vg: &mut femtovg::Canvas<femtovg::renderer::OpenGl>,
+) -> Option<ImageId> {
+    let (rect, physical_rect) = {
+        let assigned_region = widget_entry.assigned_region().upgrade()?;
+        let assigned_region = assigned_region.borrow();
+        (
+            assigned_region
+                .region
+                .widget_rect(layer.region_tree.layer_rect()),
+            assigned_region.region.physical_rect,
+        )
+    };
+
+    let image_size = capture_image_size(physical_rect)?;
+
+    let image_id = vg
+        .create_image_empty(
+            image_size.width as usize,
+            image_size.height as usize,
+            PixelFormat::Rgba8,
+            ImageFlags::empty(),
+        )
+        .ok()?;
+
+    vg.set_render_target(RenderTarget::Image(image_id));
+    vg.clear_rect(
+        0,
+        0,
+        image_size.width,
+        image_size.height,
+        Color::rgba(0, 0, 0, 0),
+    );
+
+    let content_scale_factor = layer.region_tree.content_scale_factor();
+    let region_info = PaintRegionInfo {
+        rect,
+        layer_rect: layer.region_tree.layer_rect(),
+        physical_rect,
+        layer_physical_rect: PhysicalRect {
+            pos: PhysicalPoint::new(0, 0),
+            size: layer.region_tree.layer_physical_size(),
+        },
+        scale_factor: content_scale_factor.scalar(),
+        scale_factor_2d: content_scale_factor,
+    };
+
+    // The widget paints itself at its position in the layer's texture, so
+    // move that position to the origin of the image.
+    paint_isolated(vg, |vg| {
+        vg.translate(-physical_rect.pos.x as f32, -physical_rect.pos.y as f32);
+
+        widget_entry.borrow_mut().paint(vg, &region_info);
+    });
+    vg.set_render_target(RenderTarget::Screen);
+
+    Some(image_id)
+}
+
+/// The size to allocate a layer's texture at: the reserved size while the
+/// layer fits inside of it, or else the size of the layer.
+fn texture_alloc_size(
+    layer_physical_size: PhysicalSize,
+    reserved_physical_size: Option<PhysicalSize>,
+) -> PhysicalSize {
+    match reserved_physical_size {
+        Some(reserved)
+            if layer_physical_size.width <= reserved.width
+                && layer_physical_size.height <= reserved.height =>
+        {
+            reserved
+        }
+        _ => layer_physical_size,
+    }
+}
+
+/// The state of the canvas that is saved before and restored after each
+/// widget is painted.
+trait CanvasState {
+    fn save(&mut self);
+    fn restore(&mut self);
+}
+
+impl CanvasState for femtovg::Canvas<femtovg::renderer::OpenGl> {
+    fn save(&mut self) {
+        femtovg::Canvas::save(self);
+    }
+
+    fn restore(&mut self) {
+        femtovg::Canvas::restore(self);
+    }
+}
+
+/// Run `paint` (which calls `WidgetNode::paint()`) between a save and a
+/// restore of the canvas state. Any transform, scissor, alpha, or composite
+/// operation that the widget sets is undone afterwards, so it doesn't leak
+/// into the widgets that are painted after it.
+///
+/// Every call to `WidgetNode::paint()` must go through this.
+fn paint_isolated<C: CanvasState>(vg: &mut C, paint: impl FnOnce(&mut C)) {
+    vg.save();
+    paint(vg);
+    vg.restore();
+}
+
+/// The size of the image needed to capture a widget with the given physical
+/// region, or `None` if the region is empty.
+fn capture_image_size(physical_rect: PhysicalRect) -> Option<PhysicalSize> {
+    if physical_rect.size.width == 0 || physical_rect.size.height == 0 {
+        None
+    } else {
+        Some(physical_rect.size)
+    }
+}
+
+/// Whether the layer's texture can be reused as-is without painting its
+/// dirty widgets, because the layer's content version is the same as when
+/// the texture was last painted and no region has moved or been resized.
+fn content_unchanged(
+    content_version: Option<u64>,
+    last_rendered_content_version: Option<u64>,
+    geometry_changed: bool,
+) -> bool {
+    !geometry_changed
+        && content_version.is_some()
+        && content_version == last_rendered_content_version
+}
+
+/// Clear the parts of a layer's texture that were marked to be cleared (the
+/// whole layer, or the rects vacated by widgets) to the given color.
+fn clear_texture_rects<F: FnMut(TextureRect, Color)>(
+    clear_whole_layer: &mut bool,
+    texture_rects_to_clear: &mut Vec<TextureRect>,
+    physical_size: PhysicalSize,
+    clear_color: Color,
+    mut clear: F,
+) {
+    if *clear_whole_layer {
+        *clear_whole_layer = false;
+        texture_rects_to_clear.clear();
+
+        clear(
+            TextureRect {
+                x: 0,
+                y: 0,
+                size: physical_size,
+            },
+            clear_color,
+        );
+    } else {
+        coalesce_clear_rects(texture_rects_to_clear);
+
+        for clear_rect in texture_rects_to_clear.drain(..) {
+            if clear_rect.size.width == 0 || clear_rect.size.height == 0 {
+                continue;
+            }
+
+            clear(clear_rect, clear_color);
+        }
+    }
+}
+
+/// Merge the given rects into as few rects as possible in order to reduce the
+/// number of calls to `clear_rect`.
+///
+/// Only rects whose union covers exactly the same pixels as the original rects
+/// are merged. Clearing any additional pixels would erase the contents of
+/// widgets which are not dirty and therefore won't be repainted.
+fn coalesce_clear_rects(rects: &mut Vec<TextureRect>) {
+    rects.retain(|rect| rect.size.width != 0 && rect.size.height != 0);
+
+    let mut merged_any = true;
+    while merged_any {
+        merged_any = false;
+
+        let mut i = 0;
+        while i < rects.len() {
+            let mut j = i + 1;
+            while j < rects.len() {
+                if let Some(union) = rects[i].exact_union(&rects[j]) {
+                    rects[i] = union;
+                    rects.swap_remove(j);
+                    merged_any = true;
+                } else {
+                    j += 1;
+                }
+            }
+
+            i += 1;
+        }
+    }
+}
+
+/// The pairs of indexes of the rects that overlap each other (ignoring rects
+/// which only touch at their edges).
+fn overlapping_rects(rects: &[TextureRect]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..rects.len() {
+        for j in (i + 1)..rects.len() {
+            if rects[i].intersection(&rects[j]).is_some() {
+                pairs.push((i, j));
+            }
+        }
+    }
+
+    pairs
+}
+
+/// The blend function used to composite a layer with the given blend mode
+/// onto the screen, or `None` to use femtovg's regular alpha blending.
+///
+/// femtovg uses premultiplied alpha, so the source color has already been
+/// multiplied by its alpha.
+fn blend_factors(blend_mode: BlendMode) -> Option<(BlendFactor, BlendFactor)> {
+    match blend_mode {
+        BlendMode::Normal => None,
+        // src + dst
+        BlendMode::Add => Some((BlendFactor::One, BlendFactor::One)),
+        // src * dst + dst * (1 - src_alpha)
+        BlendMode::Multiply => Some((BlendFactor::DstColor, BlendFactor::OneMinusSrcAlpha)),
+        // src + dst * (1 - src)
+        BlendMode::Screen => Some((BlendFactor::One, BlendFactor::OneMinusSrcColor)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Point, ScaleFactor, Size};
+
+    #[test]
+    fn test_overlapping_dirty_widgets() {
+        let rect = |x, y, width, height| TextureRect {
+            x,
+            y,
+            size: PhysicalSize::new(width, height),
+        };
+
+        let rects = [
+            // Two widgets that overlap by mistake.
+            rect(0, 0, 100, 30),
+            rect(90, 10, 50, 30),
+            // A widget right next to the first one only touches it.
+            rect(0, 30, 100, 30),
+            // A widget far away from the others.
+            rect(300, 300, 20, 20),
+        ];
+
+        assert_eq!(overlapping_rects(&rects), vec![(0, 1)]);
+        assert!(overlapping_rects(&rects[2..]).is_empty());
+    }
+
+    /// A canvas that only keeps track of its rotation.
+    #[derive(Default)]
+    struct RotationCanvas {
+        rotation: f32,
+        saved: Vec<f32>,
+    }
+
+    impl CanvasState for RotationCanvas {
+        fn save(&mut self) {
+            self.saved.push(self.rotation);
+        }
+
+        fn restore(&mut self) {
+            self.rotation = self.saved.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_resize_within_reserved_texture() {
+        // Simulates the texture allocation of `render()` while a panel
+        // animates its width, and counts the calls to `create_image_empty()`.
+        let count_allocations = |reserved: Option<PhysicalSize>| {
+            let mut allocated_size = None;
+            let mut allocations = 0;
+
+            for width in (100..=300).step_by(10).chain((100..=300).rev().step_by(10)) {
+                let texture_size = texture_alloc_size(PhysicalSize::new(width, 400), reserved);
+                if allocated_size != Some(texture_size) {
+                    allocated_size = Some(texture_size);
+                    allocations += 1;
+                }
+            }
+
+            allocations
+        };
+
+        // Without a reservation, the texture is reallocated on every frame
+        // of the animation (except for when it changes direction).
+        assert_eq!(count_allocations(None), 41);
+
+        // With a reservation, the texture is allocated once.
+        assert_eq!(count_allocations(Some(PhysicalSize::new(300, 400))), 1);
+
+        // A layer that outgrows the reservation falls back to its own size.
+        assert_eq!(
+            texture_alloc_size(
+                PhysicalSize::new(320, 400),
+                Some(PhysicalSize::new(300, 400))
+            ),
+            PhysicalSize::new(320, 400)
+        );
+    }
+
+    #[test]
+    fn test_widget_rotation_does_not_leak() {
+        let mut vg = RotationCanvas::default();
+        let mut painted_rotations = Vec::new();
+
+        // The first widget draws a rotated needle without undoing the
+        // rotation, and the second widget paints as usual.
+        paint_isolated(&mut vg, |vg| {
+            vg.rotation += std::f32::consts::FRAC_PI_4;
+            painted_rotations.push(vg.rotation);
+        });
+        paint_isolated(&mut vg, |vg| {
+            painted_rotations.push(vg.rotation);
+        });
+
+        assert_eq!(painted_rotations, vec![std::f32::consts::FRAC_PI_4, 0.0]);
+        assert_eq!(vg.rotation, 0.0);
+        assert!(vg.saved.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_clear_rects() {
+        // A column of list items which are touching each other, with one
+        // overlapping rect and one rect nested inside another.
+        let originals = vec![
+            TextureRect {
+                x: 10,
+                y: 0,
+                size: PhysicalSize::new(100, 20),
+            },
+            TextureRect {
+                x: 10,
+                y: 40,
+                size: PhysicalSize::new(100, 20),
+            },
+            TextureRect {
+                x: 10,
+                y: 20,
+                size: PhysicalSize::new(100, 20),
+            },
+            TextureRect {
+                x: 10,
+                y: 55,
+                size: PhysicalSize::new(100, 25),
+            },
+            TextureRect {
+                x: 20,
+                y: 30,
+                size: PhysicalSize::new(10, 10),
+            },
+        ];
+
+        let mut rects = originals.clone();
+        coalesce_clear_rects(&mut rects);
+
+        assert_eq!(
+            &rects,
+            &[TextureRect {
+                x: 10,
+                y: 0,
+                size: PhysicalSize::new(100, 80),
+            }]
+        );
+        for original in originals.iter() {
+            assert!(rects.iter().any(|rect| rect.contains_rect(original)));
+        }
+
+        // Rects that are not aligned must not be merged, since their union
+        // would clear pixels outside of the original rects.
+        let originals = vec![
+            TextureRect {
+                x: 0,
+                y: 0,
+                size: PhysicalSize::new(10, 10),
+            },
+            TextureRect {
+                x: 5,
+                y: 10,
+                size: PhysicalSize::new(10, 10),
+            },
+        ];
+
+        let mut rects = originals.clone();
+        coalesce_clear_rects(&mut rects);
+
+        assert_eq!(rects, originals);
+    }
+
+    #[test]
+    fn test_clear_texture_rects_with_clear_color() {
+        let clear_color = Color::rgb(30, 30, 30);
+        let vacated_rect = TextureRect {
+            x: 10,
+            y: 20,
+            size: PhysicalSize::new(30, 40),
+        };
+
+        let mut clear_whole_layer = false;
+        let mut texture_rects_to_clear = vec![vacated_rect];
+        let mut cleared = Vec::new();
+
+        clear_texture_rects(
+            &mut clear_whole_layer,
+            &mut texture_rects_to_clear,
+            PhysicalSize::new(400, 300),
+            clear_color,
+            |rect, color| cleared.push((rect, color)),
+        );
+
+        // The vacated rect is cleared to the layer's clear color instead of
+        // transparent.
+        assert_eq!(cleared, vec![(vacated_rect, clear_color)]);
+        assert!(texture_rects_to_clear.is_empty());
+    }
+
+    #[test]
+    fn test_render_scale_region_info() {
+        let region_info = PaintRegionInfo {
+            rect: Rect::new(Point::new(10.0, 20.0), Size::new(100.0, 40.0)),
+            layer_rect: Rect::new(Point::new(0.0, 0.0), Size::new(400.0, 300.0)),
+            physical_rect: PhysicalRect::new(
+                PhysicalPoint::new(15, 30),
+                PhysicalSize::new(150, 60),
+            ),
+            layer_physical_rect: PhysicalRect::new(
+                PhysicalPoint::new(0, 0),
+                PhysicalSize::new(600, 450),
+            ),
+            scale_factor: ScaleFactor(1.5),
+            scale_factor_2d: ScaleFactor(1.5).into(),
+        };
+
+        let scaled = scaled_paint_region_info(&region_info, 0.5);
+
+        // The widget paints into a half-size buffer at half the scale factor,
+        // and every physical rect is scaled the same way.
+        assert_eq!(
+            scaled.physical_rect,
+            PhysicalRect::new(PhysicalPoint::new(8, 15), PhysicalSize::new(75, 30))
+        );
+        assert_eq!(
+            scaled.layer_physical_rect,
+            PhysicalRect::new(PhysicalPoint::new(0, 0), PhysicalSize::new(300, 225))
+        );
+        assert_eq!(scaled.scale_factor.0, 0.75);
+        assert_eq!(scaled.scale_factor_2d, ScaleFactor2D::new(0.75, 0.75));
+
+        // The widget still occupies its full region.
+        assert!(scaled.rect.partial_eq_with_epsilon(region_info.rect));
+        assert!(scaled
+            .layer_rect
+            .partial_eq_with_epsilon(region_info.layer_rect));
+
+        // The scale factor matches the size of the image when it is rounded.
+        let region_info = PaintRegionInfo {
+            physical_rect: PhysicalRect::new(PhysicalPoint::new(0, 0), PhysicalSize::new(3, 4)),
+            ..region_info
+        };
+        let scaled = scaled_paint_region_info(&region_info, 0.25);
+        assert_eq!(scaled.physical_rect.size, PhysicalSize::new(1, 1));
+        assert_eq!(scaled.scale_factor_2d, ScaleFactor2D::new(0.5, 0.375));
+        assert_eq!(scaled.scale_factor.0, 0.5);
+
+        // Tiny regions are never scaled down to nothing.
+        assert_eq!(
+            scaled_render_size(PhysicalSize::new(1, 0), 0.1),
+            PhysicalSize::new(1, 0)
+        );
+    }
+
+    #[test]
+    fn test_blend_mode_factors() {
+        // Blend a single premultiplied color channel the way the GPU would.
+        fn blend(blend_mode: BlendMode, src: f32, src_alpha: f32, dst: f32) -> f32 {
+            let factor = |factor: BlendFactor| match factor {
+                BlendFactor::One => 1.0,
+                BlendFactor::DstColor => dst,
+                BlendFactor::OneMinusSrcAlpha => 1.0 - src_alpha,
+                BlendFactor::OneMinusSrcColor => 1.0 - src,
+                _ => unreachable!(),
+            };
+
+            let (src_factor, dst_factor) = blend_factors(blend_mode).unwrap();
+            (src * factor(src_factor) + dst * factor(dst_factor)).min(1.0)
+        }
+
+        // The normal mode keeps femtovg's default blending.
+        assert_eq!(blend_factors(BlendMode::default()), None);
+
+        // An opaque gray layer over a gray background.
+        let (src, src_alpha, dst) = (0.25, 1.0, 0.5);
+
+        // Add and screen brighten the pixels beneath the layer.
+        assert_eq!(blend(BlendMode::Add, src, src_alpha, dst), 0.75);
+        assert_eq!(blend(BlendMode::Screen, src, src_alpha, dst), 0.625);
+
+        // Multiply darkens them.
+        assert_eq!(blend(BlendMode::Multiply, src, src_alpha, dst), 0.125);
+
+        // A fully transparent layer leaves the pixels beneath it untouched.
+        for blend_mode in [BlendMode::Add, BlendMode::Multiply, BlendMode::Screen] {
+            assert_eq!(blend(blend_mode, 0.0, 0.0, dst), dst);
+        }
     }
 }
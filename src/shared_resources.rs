@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use femtovg::{FontId, ImageFlags, TextContext};
+
+use crate::error::FirewheelError;
+
+/// A handle to fonts and images that can be shared between multiple
+/// `AppWindow`s (i.e. a main window and a detachable meter window).
+///
+/// Fonts added to this handle can be used by every `AppWindow` that was
+/// created with it. Images are uploaded to each window's canvas the first
+/// time that window requests them with `AppWindow::shared_image_id()`.
+///
+/// Cloning this handle is cheap, and all clones refer to the same resources.
+#[derive(Clone)]
+pub struct SharedResources {
+    pub(crate) text_context: TextContext,
+    pub(crate) images: Rc<RefCell<SharedImages>>,
+}
+
+impl SharedResources {
+    pub fn new() -> Self {
+        Self {
+            text_context: TextContext::default(),
+            images: Rc::new(RefCell::new(SharedImages {
+                entries: Vec::new(),
+                next_image_id: 0,
+            })),
+        }
+    }
+
+    pub fn add_font<P: AsRef<Path>>(&self, file_path: P) -> Result<FontId, FirewheelError> {
+        let file_path = file_path.as_ref();
+        self.text_context
+            .add_font_file(file_path)
+            .map_err(|e| FirewheelError::FontLoad(format!("{}: {:?}", file_path.display(), e)))
+    }
+
+    pub fn add_font_from_bytes(&self, data: &[u8]) -> Result<FontId, FirewheelError> {
+        self.text_context
+            .add_font_mem(data)
+            .map_err(|e| FirewheelError::FontLoad(format!("{:?}", e)))
+    }
+
+    /// Register an encoded image (i.e. PNG or JPEG data).
+    ///
+    /// The image is not decoded until a window first requests it, so any
+    /// decoding errors are returned from `AppWindow::shared_image_id()`.
+    pub fn load_image_from_bytes(
+        &self,
+        data: &[u8],
+        flags: ImageFlags,
+    ) -> Result<SharedImageRef, FirewheelError> {
+        if data.is_empty() {
+            return Err(FirewheelError::ImageLoad("image data is empty".into()));
+        }
+
+        let mut images = self.images.borrow_mut();
+
+        let id = images.next_image_id;
+        images.next_image_id += 1;
+
+        images.entries.push(SharedImageEntry {
+            id,
+            data: Rc::new(data.to_vec()),
+            flags,
+        });
+
+        Ok(SharedImageRef { id })
+    }
+
+    pub fn remove_image(&self, image: SharedImageRef) {
+        self.images
+            .borrow_mut()
+            .entries
+            .retain(|entry| entry.id != image.id);
+    }
+}
+
+impl Default for SharedResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reference to an image registered in `SharedResources`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SharedImageRef {
+    pub(crate) id: u64,
+}
+
+impl SharedImageRef {
+    pub fn unique_id(&self) -> u64 {
+        self.id
+    }
+}
+
+pub(crate) struct SharedImages {
+    entries: Vec<SharedImageEntry>,
+    next_image_id: u64,
+}
+
+impl SharedImages {
+    #[cfg_attr(not(feature = "image-loading"), allow(unused))]
+    pub fn get(&self, id: u64) -> Option<(Rc<Vec<u8>>, ImageFlags)> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| (Rc::clone(&entry.data), entry.flags))
+    }
+}
+
+struct SharedImageEntry {
+    id: u64,
+    data: Rc<Vec<u8>>,
+    flags: ImageFlags,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_font_returns_error() {
+        let resources = SharedResources::new();
+
+        let res = resources.add_font_from_bytes(&[0, 1, 2, 3]);
+        assert!(matches!(res, Err(FirewheelError::FontLoad(_))));
+
+        let res = resources.add_font("this/font/does/not/exist.ttf");
+        assert!(matches!(res, Err(FirewheelError::FontLoad(_))));
+
+        let res = resources.load_image_from_bytes(&[], ImageFlags::empty());
+        assert!(matches!(res, Err(FirewheelError::ImageLoad(_))));
+    }
+}
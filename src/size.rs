@@ -25,6 +25,41 @@ impl ScaleFactor {
     }
 }
 
+/// A scale factor with separate horizontal and vertical factors, for
+/// displays with a different DPI on each axis.
+///
+/// Most displays scale both axes equally, in which case a `ScaleFactor` can
+/// be used instead. The coordinate types have a `_2d` variant of each
+/// conversion that takes this type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleFactor2D {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl ScaleFactor2D {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn is_uniform(&self) -> bool {
+        self.x == self.y
+    }
+
+    /// The single scale factor used for things which can't be scaled per
+    /// axis, such as font sizes and line widths. This is the larger of the
+    /// two factors so that these stay crisp.
+    pub fn scalar(&self) -> ScaleFactor {
+        ScaleFactor(self.x.max(self.y))
+    }
+}
+
+impl From<ScaleFactor> for ScaleFactor2D {
+    fn from(s: ScaleFactor) -> Self {
+        Self { x: s.0, y: s.0 }
+    }
+}
+
 /// A size in logical coordinates (points)
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct Size {
@@ -48,9 +83,15 @@ impl Size {
     /// Convert to physical size (pixels)
     #[inline]
     pub fn to_physical(&self, scale_factor: ScaleFactor) -> PhysicalSize {
+        self.to_physical_2d(scale_factor.into())
+    }
+
+    /// Convert to physical size (pixels), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_physical_2d(&self, scale_factor: ScaleFactor2D) -> PhysicalSize {
         PhysicalSize {
-            width: (self.width * scale_factor.as_f32()).round() as u32,
-            height: (self.height * scale_factor.as_f32()).round() as u32,
+            width: (self.width * scale_factor.x).round() as u32,
+            height: (self.height * scale_factor.y).round() as u32,
         }
     }
 
@@ -119,9 +160,15 @@ impl PhysicalSize {
     /// Convert to logical size (points)
     #[inline]
     pub fn to_logical(&self, scale_factor: ScaleFactor) -> Size {
+        self.to_logical_2d(scale_factor.into())
+    }
+
+    /// Convert to logical size (points), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_logical_2d(&self, scale_factor: ScaleFactor2D) -> Size {
         Size {
-            width: self.width as f32 / scale_factor.as_f32(),
-            height: self.height as f32 / scale_factor.as_f32(),
+            width: self.width as f32 / scale_factor.x,
+            height: self.height as f32 / scale_factor.y,
         }
     }
 
@@ -137,6 +184,10 @@ impl PhysicalSize {
 
 /// A point in logical coordinates (points)
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "input-recording",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -151,9 +202,15 @@ impl Point {
     /// Convert to physical coordinates (pixels)
     #[inline]
     pub fn to_physical(&self, scale_factor: ScaleFactor) -> PhysicalPoint {
+        self.to_physical_2d(scale_factor.into())
+    }
+
+    /// Convert to physical coordinates (pixels), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_physical_2d(&self, scale_factor: ScaleFactor2D) -> PhysicalPoint {
         PhysicalPoint {
-            x: (self.x * scale_factor.as_f64()).round() as i32,
-            y: (self.y * scale_factor.as_f64()).round() as i32,
+            x: (self.x * f64::from(scale_factor.x)).round() as i32,
+            y: (self.y * f64::from(scale_factor.y)).round() as i32,
         }
     }
 
@@ -211,9 +268,15 @@ impl PhysicalPoint {
     /// Convert to logical coordinates (points)
     #[inline]
     pub fn to_logical(&self, scale_factor: ScaleFactor) -> Point {
+        self.to_logical_2d(scale_factor.into())
+    }
+
+    /// Convert to logical coordinates (points), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_logical_2d(&self, scale_factor: ScaleFactor2D) -> Point {
         Point {
-            x: f64::from(self.x) / scale_factor.as_f64(),
-            y: f64::from(self.y) / scale_factor.as_f64(),
+            x: f64::from(self.x) / f64::from(scale_factor.x),
+            y: f64::from(self.y) / f64::from(scale_factor.y),
         }
     }
 
@@ -332,6 +395,49 @@ impl Rect {
             && other.pos_br.y >= self.pos_tl.y
     }
 
+    /// The area shared by both rects, or `None` if they don't overlap.
+    /// Rects that only touch along an edge don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.pos_tl.x.max(other.pos_tl.x);
+        let y = self.pos_tl.y.max(other.pos_tl.y);
+        let x2 = self.pos_br.x.min(other.pos_br.x);
+        let y2 = self.pos_br.y.min(other.pos_br.y);
+
+        if x2 > x && y2 > y {
+            Some(Rect::new(
+                Point::new(x, y),
+                Size::new((x2 - x) as f32, (y2 - y) as f32),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// The smallest rect that contains both rects.
+    pub fn bounding_union(&self, other: &Rect) -> Rect {
+        let pos = Point::new(
+            self.pos_tl.x.min(other.pos_tl.x),
+            self.pos_tl.y.min(other.pos_tl.y),
+        );
+        Rect::new(
+            pos,
+            Size::new(
+                (self.pos_br.x.max(other.pos_br.x) - pos.x) as f32,
+                (self.pos_br.y.max(other.pos_br.y) - pos.y) as f32,
+            ),
+        )
+    }
+
+    /// Whether this rect overlaps with the other rect when this rect is
+    /// grown by `epsilon` on every side.
+    #[inline]
+    pub fn overlaps_with_rect_with_epsilon(&self, other: Rect, epsilon: f64) -> bool {
+        self.pos_br.x + epsilon >= other.pos_tl.x
+            && other.pos_br.x >= self.pos_tl.x - epsilon
+            && self.pos_br.y + epsilon >= other.pos_tl.y
+            && other.pos_br.y >= self.pos_tl.y - epsilon
+    }
+
     #[inline]
     pub fn partial_eq_with_epsilon(&self, other: Rect) -> bool {
         self.pos_tl.partial_eq_with_epsilon(other.pos_tl)
@@ -341,9 +447,15 @@ impl Rect {
     /// Convert to physical coordinates (pixels)
     #[inline]
     pub fn to_physical(&self, scale_factor: ScaleFactor) -> PhysicalRect {
+        self.to_physical_2d(scale_factor.into())
+    }
+
+    /// Convert to physical coordinates (pixels), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_physical_2d(&self, scale_factor: ScaleFactor2D) -> PhysicalRect {
         PhysicalRect {
-            pos: self.pos_tl.to_physical(scale_factor),
-            size: self.size.to_physical(scale_factor),
+            pos: self.pos_tl.to_physical_2d(scale_factor),
+            size: self.size.to_physical_2d(scale_factor),
         }
     }
 }
@@ -381,9 +493,15 @@ impl PhysicalRect {
     /// Convert to logical coordinates (points)
     #[inline]
     pub fn to_logical(&self, scale_factor: ScaleFactor) -> Rect {
+        self.to_logical_2d(scale_factor.into())
+    }
+
+    /// Convert to logical coordinates (points), with a separate scale factor for each axis
+    #[inline]
+    pub fn to_logical_2d(&self, scale_factor: ScaleFactor2D) -> Rect {
         Rect::new(
-            self.pos.to_logical(scale_factor),
-            self.size.to_logical(scale_factor),
+            self.pos.to_logical_2d(scale_factor),
+            self.size.to_logical_2d(scale_factor),
         )
     }
 
@@ -436,4 +554,92 @@ impl TextureRect {
 
         TextureRect { x, y, size }
     }
+
+    #[inline]
+    pub fn x2(&self) -> u32 {
+        self.x + self.size.width
+    }
+
+    #[inline]
+    pub fn y2(&self) -> u32 {
+        self.y + self.size.height
+    }
+
+    #[inline]
+    pub fn contains_rect(&self, other: &TextureRect) -> bool {
+        other.x >= self.x && other.y >= self.y && other.x2() <= self.x2() && other.y2() <= self.y2()
+    }
+
+    /// The smallest rect that contains both rects.
+    pub fn bounding_union(&self, other: &TextureRect) -> TextureRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        TextureRect {
+            x,
+            y,
+            size: PhysicalSize::new(self.x2().max(other.x2()) - x, self.y2().max(other.y2()) - y),
+        }
+    }
+
+    /// The part of this rect that is also inside the other rect, or `None`
+    /// if they don't overlap.
+    pub fn intersection(&self, other: &TextureRect) -> Option<TextureRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let x2 = self.x2().min(other.x2());
+        let y2 = self.y2().min(other.y2());
+
+        if x2 > x && y2 > y {
+            Some(TextureRect {
+                x,
+                y,
+                size: PhysicalSize::new(x2 - x, y2 - y),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the union of the two rects if that union covers exactly the
+    /// same pixels as the two rects combined, or `None` if it would cover
+    /// any additional pixels.
+    ///
+    /// This is the case when one rect contains the other, or when both rects
+    /// share the same span on one axis and touch or overlap on the other.
+    pub fn exact_union(&self, other: &TextureRect) -> Option<TextureRect> {
+        if self.contains_rect(other) {
+            return Some(*self);
+        }
+        if other.contains_rect(self) {
+            return Some(*other);
+        }
+
+        if self.x == other.x
+            && self.size.width == other.size.width
+            && self.y <= other.y2()
+            && other.y <= self.y2()
+        {
+            let y = self.y.min(other.y);
+            return Some(TextureRect {
+                x: self.x,
+                y,
+                size: PhysicalSize::new(self.size.width, self.y2().max(other.y2()) - y),
+            });
+        }
+
+        if self.y == other.y
+            && self.size.height == other.size.height
+            && self.x <= other.x2()
+            && other.x <= self.x2()
+        {
+            let x = self.x.min(other.x);
+            return Some(TextureRect {
+                x,
+                y: self.y,
+                size: PhysicalSize::new(self.x2().max(other.x2()) - x, self.size.height),
+            });
+        }
+
+        None
+    }
 }
@@ -0,0 +1,564 @@
+use femtovg::{Baseline, Color, FontId, ImageFlags, ImageId, PixelFormat, RenderTarget};
+use fnv::FnvHashMap;
+use imgref::ImgRef;
+use rgb::RGBA8;
+use std::cell::RefCell;
+
+use crate::error::FirewheelError;
+use crate::VG;
+
+/// Text that has been rasterized on the CPU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextImage {
+    pub width: usize,
+    pub height: usize,
+    /// The coverage of each pixel in the range `[0, 255]`, in row-major
+    /// order.
+    pub coverage: Vec<u8>,
+    /// The distance in pixels from the top of the image to the baseline of
+    /// the text.
+    pub baseline: f32,
+}
+
+impl TextImage {
+    pub fn is_empty(&self) -> bool {
+        self.coverage.iter().all(|c| *c == 0)
+    }
+}
+
+/// The font, size, color, and baseline of a line of text drawn with
+/// `SoftwareTextRenderer::fill_text()`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextPaint {
+    pub font_id: FontId,
+    pub font_size_px: f32,
+    pub color: Color,
+    /// Where the `y` coordinate given to `fill_text()` lies on the text.
+    /// The text always starts at the given `x` coordinate.
+    pub baseline: Baseline,
+}
+
+impl TextPaint {
+    /// The femtovg paint that draws the same text.
+    pub fn to_paint(&self) -> femtovg::Paint {
+        let mut paint = femtovg::Paint::color(self.color);
+        paint.set_font(&[self.font_id]);
+        paint.set_font_size(self.font_size_px);
+        paint.set_text_baseline(self.baseline);
+        paint
+    }
+}
+
+/// A fallback for drawing text on GL contexts where femtovg's glyph
+/// rendering is unavailable (i.e. on some minimal OpenGL ES contexts on
+/// embedded targets).
+///
+/// Text is rasterized on the CPU with `fontdue`, and then drawn as an image.
+/// This is much slower than femtovg's glyph atlas, so it is only used when
+/// `detect()` has found that femtovg can't draw glyphs on the current
+/// context, or when femtovg fails to draw a piece of text.
+///
+/// Every `AppWindow` owns one of these (see `AppWindow::software_text_mut()`),
+/// which the built-in widgets draw their text with. The fallback can only
+/// draw with the fonts that were added to it with `add_font()`, under the
+/// same `FontId` as in femtovg.
+///
+/// The image of each piece of text is cached and reused for as long as that
+/// text is drawn. Call `free_unused_images()` once per frame (after
+/// rendering) to free the images of text that is no longer drawn.
+pub struct SoftwareTextRenderer {
+    fonts: Vec<(FontId, fontdue::Font)>,
+    always_fallback: bool,
+    images: FnvHashMap<TextImageKey, CachedTextImage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TextImageKey {
+    text: String,
+    /// The index of the font in `SoftwareTextRenderer::fonts`.
+    font_index: usize,
+    font_size_bits: u32,
+    color: [u8; 4],
+}
+
+struct CachedTextImage {
+    image_id: ImageId,
+    baseline: f32,
+    width: f32,
+    height: f32,
+    used: bool,
+}
+
+impl SoftwareTextRenderer {
+    pub fn new() -> Self {
+        Self {
+            fonts: Vec::new(),
+            always_fallback: false,
+            images: FnvHashMap::default(),
+        }
+    }
+
+    /// Add the data of a TrueType or OpenType font that was loaded into
+    /// femtovg with the given ID.
+    pub fn add_font(&mut self, font_id: FontId, font_data: &[u8]) -> Result<(), FirewheelError> {
+        let font = fontdue::Font::from_bytes(font_data, fontdue::FontSettings::default())
+            .map_err(|e| FirewheelError::FontLoad(e.into()))?;
+
+        match self.fonts.iter_mut().find(|(id, _)| *id == font_id) {
+            Some((_, existing)) => *existing = font,
+            None => self.fonts.push((font_id, font)),
+        }
+        Ok(())
+    }
+
+    /// The first font that was added, i.e. for passing to `detect()`.
+    pub fn first_font(&self) -> Option<FontId> {
+        self.fonts.first().map(|(font_id, _)| *font_id)
+    }
+
+    fn font_index(&self, font_id: FontId) -> Option<usize> {
+        self.fonts.iter().position(|(id, _)| *id == font_id)
+    }
+
+    /// Check whether femtovg can draw glyphs with the given font on the
+    /// current GL context, and always use the fallback if it can't.
+    ///
+    /// This draws a glyph into a small offscreen image and reads it back, so
+    /// it must not be called while painting. Returns `true` if the fallback
+    /// is used from now on.
+    pub fn detect(&mut self, vg: &mut VG, font_id: FontId) -> bool {
+        self.always_fallback = !glyph_rendering_works(vg, font_id);
+        if self.always_fallback {
+            log::warn!("femtovg can't draw glyphs on this context, text is rasterized on the CPU");
+        }
+        self.always_fallback
+    }
+
+    /// Always draw text with the fallback (i.e. when the host already knows
+    /// that femtovg's glyph rendering is broken on its target).
+    pub fn set_always_fallback(&mut self, always_fallback: bool) {
+        self.always_fallback = always_fallback;
+    }
+
+    pub fn always_fallback(&self) -> bool {
+        self.always_fallback
+    }
+
+    /// Rasterize a single line of text at the given font size in pixels, or
+    /// return `None` if the font was not added with `add_font()`.
+    pub fn rasterize(&self, font_id: FontId, text: &str, font_size_px: f32) -> Option<TextImage> {
+        let font = &self.fonts[self.font_index(font_id)?].1;
+        let (ascent, descent) = font
+            .horizontal_line_metrics(font_size_px)
+            .map(|m| (m.ascent, m.descent))
+            .unwrap_or((font_size_px, 0.0));
+
+        // Lay out the glyphs along the baseline.
+        let mut glyphs = Vec::new();
+        let mut pen_x: f32 = 0.0;
+        let mut max_x: f32 = 0.0;
+        for c in text.chars() {
+            let (metrics, bitmap) = font.rasterize(c, font_size_px);
+
+            let x = pen_x.round() as i32 + metrics.xmin;
+            let y = ascent.round() as i32 - (metrics.ymin + metrics.height as i32);
+
+            max_x = max_x.max(pen_x + metrics.advance_width);
+            max_x = max_x.max((x + metrics.width as i32) as f32);
+            pen_x += metrics.advance_width;
+
+            glyphs.push((x, y, metrics.width, metrics.height, bitmap));
+        }
+
+        let width = max_x.ceil().max(0.0) as usize;
+        let height = (ascent - descent).ceil().max(0.0) as usize;
+        let mut coverage = vec![0u8; width * height];
+
+        for (x, y, glyph_width, glyph_height, bitmap) in glyphs.iter() {
+            for gy in 0..*glyph_height {
+                for gx in 0..*glyph_width {
+                    let px = x + gx as i32;
+                    let py = y + gy as i32;
+                    if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                        continue;
+                    }
+
+                    let i = (py as usize * width) + px as usize;
+                    coverage[i] = coverage[i].max(bitmap[(gy * glyph_width) + gx]);
+                }
+            }
+        }
+
+        Some(TextImage {
+            width,
+            height,
+            coverage,
+            baseline: ascent.round(),
+        })
+    }
+
+    /// Draw the text with femtovg, falling back to rasterizing it on the CPU
+    /// if the fallback is always used (see `detect()`) or if femtovg fails to
+    /// draw it.
+    ///
+    /// The left edge of the text is placed at `x` (the same as
+    /// `Align::Left`).
+    ///
+    /// Returns `true` if the fallback was used.
+    pub fn fill_text(
+        &mut self,
+        vg: &mut VG,
+        x: f32,
+        y: f32,
+        text: &str,
+        paint: &TextPaint,
+    ) -> Result<bool, FirewheelError> {
+        if !self.always_fallback && vg.fill_text(x, y, text, &paint.to_paint()).is_ok() {
+            return Ok(false);
+        }
+
+        let font_index = self.font_index(paint.font_id).ok_or_else(|| {
+            log::error!("Failed to draw text: the font was not added to the software renderer");
+            FirewheelError::Text
+        })?;
+        let key = TextImageKey {
+            text: text.to_string(),
+            font_index,
+            font_size_bits: paint.font_size_px.to_bits(),
+            color: color_to_rgba8(paint.color),
+        };
+        let cached = match self.images.get_mut(&key) {
+            Some(cached) => cached,
+            None => {
+                let text_image = self
+                    .rasterize(paint.font_id, text, paint.font_size_px)
+                    .unwrap();
+                if text_image.width == 0 || text_image.height == 0 {
+                    return Ok(true);
+                }
+
+                let pixels = text_image_pixels(&text_image, paint.color);
+                let image_id = vg
+                    .create_image(
+                        ImgRef::new(&pixels, text_image.width, text_image.height),
+                        ImageFlags::empty(),
+                    )
+                    .map_err(|e| {
+                        log::error!("Failed to draw text: {:?}", e);
+                        FirewheelError::Text
+                    })?;
+
+                self.images.entry(key).or_insert(CachedTextImage {
+                    image_id,
+                    baseline: text_image.baseline,
+                    width: text_image.width as f32,
+                    height: text_image.height as f32,
+                    used: false,
+                })
+            }
+        };
+        cached.used = true;
+
+        let top = match paint.baseline {
+            Baseline::Top => y,
+            Baseline::Middle => y - (cached.height / 2.0),
+            Baseline::Alphabetic => y - cached.baseline,
+            Baseline::Bottom => y - cached.height,
+        };
+        let mut path = femtovg::Path::new();
+        path.rect(x, top, cached.width, cached.height);
+        vg.fill_path(
+            &mut path,
+            &femtovg::Paint::image(
+                cached.image_id,
+                x,
+                top,
+                cached.width,
+                cached.height,
+                0.0,
+                1.0,
+            ),
+        );
+
+        Ok(true)
+    }
+
+    /// Free the images of the text that hasn't been drawn since the last
+    /// call to this method.
+    ///
+    /// Call this once per frame after the frame has been rendered.
+    pub fn free_unused_images(&mut self, vg: &mut VG) {
+        self.images.retain(|_, cached| {
+            if !cached.used {
+                vg.delete_image(cached.image_id);
+            }
+            std::mem::take(&mut cached.used)
+        });
+    }
+
+    /// Free the images of all text (i.e. before the canvas is destroyed with
+    /// `AppWindow::suspend_gpu()`).
+    pub fn free_images(&mut self, vg: &mut VG) {
+        for (_, cached) in self.images.drain() {
+            vg.delete_image(cached.image_id);
+        }
+    }
+}
+
+impl Default for SoftwareTextRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    /// The renderer of the window that is painting its widgets on this
+    /// thread, if any (see `with_active_renderer()`).
+    static ACTIVE_RENDERER: RefCell<Option<SoftwareTextRenderer>> = RefCell::new(None);
+}
+
+/// Let `fill_text()` draw with the given renderer while `f` runs (i.e. while
+/// a window paints its widgets).
+pub(crate) fn with_active_renderer<R>(
+    renderer: &mut SoftwareTextRenderer,
+    f: impl FnOnce() -> R,
+) -> R {
+    let previous =
+        ACTIVE_RENDERER.with(|active| active.borrow_mut().replace(std::mem::take(renderer)));
+
+    let res = f();
+
+    *renderer = ACTIVE_RENDERER
+        .with(|active| std::mem::replace(&mut *active.borrow_mut(), previous))
+        .unwrap();
+    res
+}
+
+/// Draw a line of text for a widget with the software renderer of the
+/// window that is painting it (so that the text is still drawn on contexts
+/// where femtovg's glyph rendering is broken), or with femtovg if this is
+/// called outside of a window's `render()`.
+pub(crate) fn fill_text(
+    vg: &mut VG,
+    x: f32,
+    y: f32,
+    text: &str,
+    paint: &TextPaint,
+) -> Result<(), FirewheelError> {
+    ACTIVE_RENDERER.with(|active| match active.borrow_mut().as_mut() {
+        Some(renderer) => renderer.fill_text(vg, x, y, text, paint).map(|_| ()),
+        None => vg
+            .fill_text(x, y, text, &paint.to_paint())
+            .map(|_| ())
+            .map_err(|e| {
+                log::error!("Failed to draw text: {:?}", e);
+                FirewheelError::Text
+            }),
+    })
+}
+
+/// Draw a glyph with femtovg into an offscreen image and check that it left
+/// any ink behind.
+fn glyph_rendering_works(vg: &mut VG, font_id: FontId) -> bool {
+    const PROBE_SIZE: usize = 32;
+
+    let image_id = match vg.create_image_empty(
+        PROBE_SIZE,
+        PROBE_SIZE,
+        PixelFormat::Rgba8,
+        ImageFlags::empty(),
+    ) {
+        Ok(image_id) => image_id,
+        // There is no way to tell, so assume that it works.
+        Err(_) => return true,
+    };
+
+    vg.save();
+    vg.reset();
+    vg.set_render_target(RenderTarget::Image(image_id));
+    vg.clear_rect(
+        0,
+        0,
+        PROBE_SIZE as u32,
+        PROBE_SIZE as u32,
+        Color::rgbaf(0.0, 0.0, 0.0, 0.0),
+    );
+
+    let mut paint = femtovg::Paint::color(Color::white());
+    paint.set_font(&[font_id]);
+    paint.set_font_size(PROBE_SIZE as f32 * 0.75);
+    let drawn = vg
+        .fill_text(2.0, PROBE_SIZE as f32 - 4.0, "H", &paint)
+        .is_ok();
+
+    // `screenshot()` reads back the framebuffer of the current render
+    // target.
+    let works = match vg.screenshot() {
+        Ok(pixels) => drawn && pixels.pixels().any(|p| p.a > 0),
+        Err(_) => drawn,
+    };
+
+    vg.set_render_target(RenderTarget::Screen);
+    vg.restore();
+    vg.delete_image(image_id);
+
+    works
+}
+
+fn color_to_rgba8(color: Color) -> [u8; 4] {
+    [color.r, color.g, color.b, color.a].map(|c| (c * 255.0).round() as u8)
+}
+
+/// Convert the coverage of a text image into pixels of the given color.
+fn text_image_pixels(text_image: &TextImage, color: Color) -> Vec<RGBA8> {
+    let [r, g, b, a] = color_to_rgba8(color);
+
+    text_image
+        .coverage
+        .iter()
+        .map(|coverage| RGBA8::new(r, g, b, ((u16::from(a) * u16::from(*coverage)) / 255) as u8))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::GlTestWindow;
+    use crate::widgets::{LabelButton, LabelButtonStyle};
+    use crate::{AppWindow, PhysicalSize, Point, Rect, ScaleFactor, Size};
+    use std::rc::Rc;
+
+    const FONT_DATA: &[u8] = include_bytes!("../examples/assets/Roboto-Regular.ttf");
+
+    #[test]
+    fn test_software_text_rasterize() {
+        let (action_tx, _action_rx) = crossbeam_channel::unbounded::<()>();
+        let mut app_window: AppWindow<()> = AppWindow::new_headless(ScaleFactor(1.0), action_tx);
+        let font_id = app_window.add_font_from_bytes(FONT_DATA).unwrap();
+        let other_font_id = app_window.add_font_from_bytes(FONT_DATA).unwrap();
+
+        let mut renderer = SoftwareTextRenderer::new();
+        renderer.add_font(font_id, FONT_DATA).unwrap();
+        assert_eq!(renderer.first_font(), Some(font_id));
+
+        let text_image = renderer.rasterize(font_id, "Hello World", 16.0).unwrap();
+        assert!(text_image.width > 0);
+        assert!(text_image.height > 0);
+        assert_eq!(
+            text_image.coverage.len(),
+            text_image.width * text_image.height
+        );
+        assert!(!text_image.is_empty());
+        assert!(text_image.baseline > 0.0 && text_image.baseline < text_image.height as f32);
+
+        // A longer string makes a wider image.
+        assert!(
+            renderer
+                .rasterize(font_id, "Hello World!!!", 16.0)
+                .unwrap()
+                .width
+                > text_image.width
+        );
+
+        // Whitespace has no ink.
+        assert!(renderer.rasterize(font_id, "   ", 16.0).unwrap().is_empty());
+
+        // Fonts that weren't added can't be rasterized.
+        assert!(renderer.rasterize(other_font_id, "Hello", 16.0).is_none());
+
+        let pixels = text_image_pixels(&text_image, Color::rgb(255, 0, 0));
+        assert!(pixels.iter().any(|p| p.r == 255 && p.a > 0));
+    }
+
+    #[test]
+    fn test_software_text_images_are_cached() {
+        let window_size = PhysicalSize::new(100, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+        let font_id = app_window.add_font_from_bytes(FONT_DATA).unwrap();
+
+        let mut renderer = SoftwareTextRenderer::new();
+        renderer.add_font(font_id, FONT_DATA).unwrap();
+
+        // femtovg can draw glyphs on the test context.
+        assert!(!renderer.detect(app_window.vg(), font_id));
+        assert!(!renderer.always_fallback());
+
+        let paint = TextPaint {
+            font_id,
+            font_size_px: 16.0,
+            color: Color::rgb(255, 0, 0),
+            baseline: Baseline::Alphabetic,
+        };
+
+        // Drawing the same text in every frame reuses its image.
+        renderer.set_always_fallback(true);
+        for _ in 0..3 {
+            let vg = app_window.vg();
+            assert!(renderer.fill_text(vg, 0.0, 20.0, "Hello", &paint).unwrap());
+            renderer.free_unused_images(vg);
+        }
+        assert_eq!(renderer.images.len(), 1);
+        let hello_image = renderer.images.values().next().unwrap().image_id;
+
+        // The image of text that is no longer drawn is freed.
+        let vg = app_window.vg();
+        renderer.fill_text(vg, 0.0, 20.0, "World", &paint).unwrap();
+        renderer.free_unused_images(vg);
+        assert_eq!(renderer.images.len(), 1);
+        assert!(vg.image_info(hello_image).is_err());
+
+        renderer.free_images(vg);
+        assert!(renderer.images.is_empty());
+
+        // Without the fallback, femtovg draws the text itself.
+        renderer.set_always_fallback(false);
+        assert!(!renderer.fill_text(vg, 0.0, 20.0, "Hello", &paint).unwrap());
+        assert!(renderer.images.is_empty());
+    }
+
+    #[test]
+    fn test_label_button_uses_software_text() {
+        let window_size = PhysicalSize::new(200, 50);
+        let mut window = match GlTestWindow::new(window_size, ScaleFactor(1.0)) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+        let font_id = app_window.add_font_from_bytes(FONT_DATA).unwrap();
+        let layer = app_window.add_widget_layer(
+            Size::new(200.0, 50.0),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        // The first frame checks whether femtovg can draw glyphs, which it
+        // can on the test context.
+        app_window.render(window_size);
+        assert!(!app_window.software_text_mut().always_fallback());
+
+        // Once the fallback is forced, the button draws its label with it.
+        app_window.software_text_mut().set_always_fallback(true);
+        app_window
+            .add_widget_node_at_rect(
+                Box::new(LabelButton::new(
+                    "Hello".into(),
+                    font_id,
+                    Rc::new(LabelButtonStyle::default()),
+                    None,
+                    true,
+                )),
+                &layer,
+                Rect::new(Point::new(0.0, 0.0), Size::new(200.0, 50.0)),
+                true,
+            )
+            .unwrap();
+        app_window.render(window_size);
+        assert_eq!(app_window.software_text_mut().images.len(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Rect;
+
+/// A direction to move the keyboard focus in (i.e. from the arrow keys or a
+/// gamepad's d-pad).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// How much more a candidate's offset on the perpendicular axis counts
+/// against it than its offset in the direction of movement. This makes the
+/// focus prefer widgets that are in line with the focused widget over ones
+/// that are slightly closer but diagonal to it.
+const PERPENDICULAR_WEIGHT: f64 = 2.0;
+
+/// Find the candidate nearest to `from` in the given direction.
+///
+/// Only candidates whose center lies in that direction from the center of
+/// `from` are considered. When two candidates are equally near, the one
+/// with the lowest ID wins, so the result is always deterministic.
+pub(crate) fn nearest_in_direction<I: Iterator<Item = (u64, Rect)>>(
+    from: Rect,
+    direction: Direction,
+    candidates: I,
+) -> Option<u64> {
+    let mut nearest: Option<(u64, f64)> = None;
+
+    for (id, rect) in candidates {
+        let dx = rect.center_x() - from.center_x();
+        let dy = rect.center_y() - from.center_y();
+
+        let (primary, perpendicular) = match direction {
+            Direction::Left => (-dx, dy.abs()),
+            Direction::Right => (dx, dy.abs()),
+            Direction::Up => (-dy, dx.abs()),
+            Direction::Down => (dy, dx.abs()),
+        };
+
+        if primary <= f64::EPSILON {
+            continue;
+        }
+
+        let score = primary + (perpendicular * PERPENDICULAR_WEIGHT);
+
+        let is_nearer = match nearest {
+            Some((nearest_id, nearest_score)) => {
+                score < nearest_score || (score == nearest_score && id < nearest_id)
+            }
+            None => true,
+        };
+        if is_nearer {
+            nearest = Some((id, score));
+        }
+    }
+
+    nearest.map(|(id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Point, Size};
+
+    #[test]
+    fn test_nearest_in_direction() {
+        // A 2x2 grid of buttons with IDs:
+        //
+        // 0 1
+        // 2 3
+        let grid = [
+            (0, Rect::new(Point::new(0.0, 0.0), Size::new(50.0, 20.0))),
+            (1, Rect::new(Point::new(60.0, 0.0), Size::new(50.0, 20.0))),
+            (2, Rect::new(Point::new(0.0, 30.0), Size::new(50.0, 20.0))),
+            (3, Rect::new(Point::new(60.0, 30.0), Size::new(50.0, 20.0))),
+        ];
+
+        let nearest = |from: u64, direction: Direction| {
+            nearest_in_direction(
+                grid[from as usize].1,
+                direction,
+                grid.iter().copied().filter(|(id, _)| *id != from),
+            )
+        };
+
+        assert_eq!(nearest(0, Direction::Right), Some(1));
+        assert_eq!(nearest(0, Direction::Down), Some(2));
+        assert_eq!(nearest(1, Direction::Down), Some(3));
+        assert_eq!(nearest(2, Direction::Right), Some(3));
+        assert_eq!(nearest(3, Direction::Up), Some(1));
+        assert_eq!(nearest(3, Direction::Left), Some(2));
+
+        // There is nothing beyond the edges of the grid.
+        assert_eq!(nearest(0, Direction::Left), None);
+        assert_eq!(nearest(0, Direction::Up), None);
+        assert_eq!(nearest(3, Direction::Right), None);
+
+        // Ties go to the candidate with the lowest ID.
+        let from = Rect::new(Point::new(0.0, 15.0), Size::new(50.0, 20.0));
+        assert_eq!(
+            nearest_in_direction(from, Direction::Right, grid.iter().copied()),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::size::{Point, Size};
+
+/// The axis that the children of a stack container are laid out along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// How the children of a stack container are aligned on the axis that is
+/// perpendicular to the stack (i.e. vertically in a horizontal stack).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackAlign {
+    Start,
+    Center,
+    End,
+}
+
+impl Default for StackAlign {
+    fn default() -> Self {
+        StackAlign::Start
+    }
+}
+
+/// Lays out the children of a container region one after the other along an
+/// axis, i.e. for a toolbar. See `AppWindow::add_stack_container()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackLayout {
+    pub axis: Axis,
+    /// The space between each child in points.
+    pub spacing: f32,
+    pub item_align: StackAlign,
+}
+
+impl StackLayout {
+    pub fn new(axis: Axis, spacing: f32, item_align: StackAlign) -> Self {
+        Self {
+            axis,
+            spacing,
+            item_align,
+        }
+    }
+
+    /// Compute the size of the stack that fits children of the given sizes,
+    /// and the position of each child relative to the top-left corner of
+    /// the stack.
+    pub fn layout(&self, child_sizes: &[Size]) -> (Size, Vec<Point>) {
+        let main = |size: Size| match self.axis {
+            Axis::Horizontal => f64::from(size.width()),
+            Axis::Vertical => f64::from(size.height()),
+        };
+        let cross = |size: Size| match self.axis {
+            Axis::Horizontal => f64::from(size.height()),
+            Axis::Vertical => f64::from(size.width()),
+        };
+
+        let spacing = f64::from(self.spacing);
+        let cross_length = child_sizes.iter().map(|s| cross(*s)).fold(0.0, f64::max);
+
+        let mut positions = Vec::with_capacity(child_sizes.len());
+        let mut main_pos = 0.0;
+        for (i, size) in child_sizes.iter().enumerate() {
+            if i > 0 {
+                main_pos += spacing;
+            }
+
+            let cross_pos = match self.item_align {
+                StackAlign::Start => 0.0,
+                StackAlign::Center => (cross_length - cross(*size)) / 2.0,
+                StackAlign::End => cross_length - cross(*size),
+            };
+            positions.push(match self.axis {
+                Axis::Horizontal => Point::new(main_pos, cross_pos),
+                Axis::Vertical => Point::new(cross_pos, main_pos),
+            });
+
+            main_pos += main(*size);
+        }
+
+        let size = match self.axis {
+            Axis::Horizontal => Size::new(main_pos as f32, cross_length as f32),
+            Axis::Vertical => Size::new(cross_length as f32, main_pos as f32),
+        };
+
+        (size, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_layout() {
+        let sizes = [
+            Size::new(20.0, 10.0),
+            Size::new(30.0, 20.0),
+            Size::new(40.0, 16.0),
+        ];
+
+        let (size, positions) =
+            StackLayout::new(Axis::Horizontal, 8.0, StackAlign::Center).layout(&sizes);
+        assert_eq!(size, Size::new(106.0, 20.0));
+        assert_eq!(
+            positions,
+            vec![
+                Point::new(0.0, 5.0),
+                Point::new(28.0, 0.0),
+                Point::new(66.0, 2.0)
+            ]
+        );
+
+        let (size, positions) =
+            StackLayout::new(Axis::Vertical, 4.0, StackAlign::End).layout(&sizes);
+        assert_eq!(size, Size::new(40.0, 54.0));
+        assert_eq!(
+            positions,
+            vec![
+                Point::new(20.0, 0.0),
+                Point::new(10.0, 14.0),
+                Point::new(0.0, 38.0)
+            ]
+        );
+
+        // An empty stack has no size.
+        let (size, positions) =
+            StackLayout::new(Axis::Horizontal, 8.0, StackAlign::Start).layout(&[]);
+        assert_eq!(size, Size::new(0.0, 0.0));
+        assert!(positions.is_empty());
+    }
+}
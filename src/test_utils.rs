@@ -0,0 +1,399 @@
+//! Helpers shared by the unit tests of this crate.
+
+use crossbeam_channel::{Receiver, Sender};
+use femtovg::{Color, Paint, Path, TextContext};
+use khronos_egl as egl;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crate::animator::AnimatorUpdate;
+use crate::event::InputEvent;
+use crate::layer::WidgetLayerRef;
+use crate::node::{
+    EventCapturedStatus, PaintRegionInfo, WidgetNode, WidgetNodeRequests, WidgetNodeType,
+};
+use crate::{
+    AppWindow, AppWindowSettings, PhysicalSize, Point, Rect, ScaleFactor, Size, WidgetNodeRef, VG,
+};
+
+/// The widget used by tests that need a widget but don't care much about
+/// what it does.
+///
+/// It fills its region with a solid color, records every input event it
+/// receives, counts how many times it is painted, and asks to be repainted
+/// before a frame when the value it polls changes (like an audio meter
+/// reading an atomic value). The polled value can also be set with a `u32`
+/// user event or an animator.
+pub(crate) struct TestWidget {
+    value: Arc<AtomicU32>,
+    last_value: u32,
+    color: Color,
+    listen_to_pointer: bool,
+    events: Rc<RefCell<Vec<InputEvent>>>,
+    paint_count: Rc<Cell<u32>>,
+}
+
+impl TestWidget {
+    pub fn new() -> Self {
+        Self {
+            value: Arc::new(AtomicU32::new(0)),
+            last_value: 0,
+            color: Color::rgb(255, 0, 0),
+            listen_to_pointer: false,
+            events: Rc::new(RefCell::new(Vec::new())),
+            paint_count: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Poll the given value instead of a value of its own.
+    pub fn with_value(mut self, value: Arc<AtomicU32>) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Listen to pointer events and capture the ones that hit the widget.
+    pub fn with_pointer_listen(mut self) -> Self {
+        self.listen_to_pointer = true;
+        self
+    }
+
+    /// The input events this widget has received so far, in order.
+    pub fn events(&self) -> Rc<RefCell<Vec<InputEvent>>> {
+        Rc::clone(&self.events)
+    }
+
+    /// The number of times this widget has been painted so far.
+    pub fn paint_count(&self) -> Rc<Cell<u32>> {
+        Rc::clone(&self.paint_count)
+    }
+}
+
+impl WidgetNode<()> for TestWidget {
+    fn on_added(&mut self, _action_tx: &mut Sender<()>) -> (WidgetNodeType, WidgetNodeRequests) {
+        (
+            WidgetNodeType::Painted,
+            WidgetNodeRequests {
+                set_pre_frame_listen: Some(true),
+                set_pointer_events_listen: self.listen_to_pointer.then_some(true),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn on_input_event(
+        &mut self,
+        event: &InputEvent,
+        _text_context: &TextContext,
+        _action_tx: &mut Sender<()>,
+    ) -> EventCapturedStatus {
+        self.events.borrow_mut().push(event.clone());
+
+        if self.listen_to_pointer && matches!(event, InputEvent::Pointer(_)) {
+            EventCapturedStatus::Captured(WidgetNodeRequests::default())
+        } else {
+            EventCapturedStatus::NotCaptured
+        }
+    }
+
+    fn on_user_event(
+        &mut self,
+        event: Box<dyn Any>,
+        _action_tx: &mut Sender<()>,
+    ) -> Option<WidgetNodeRequests> {
+        // Overwrite the polled value with the value sent in the event.
+        match event.downcast::<u32>() {
+            Ok(value) => self.value.store(*value, Ordering::Relaxed),
+            Err(event) => {
+                if let Ok(update) = event.downcast::<AnimatorUpdate>() {
+                    self.value
+                        .store(update.value.round() as u32, Ordering::Relaxed);
+                }
+            }
+        }
+        None
+    }
+
+    fn pre_frame(&mut self, _action_tx: &mut Sender<()>) -> Option<WidgetNodeRequests> {
+        let value = self.value.load(Ordering::Relaxed);
+        if value != self.last_value {
+            self.last_value = value;
+            Some(WidgetNodeRequests {
+                repaint: true,
+                ..Default::default()
+            })
+        } else {
+            None
+        }
+    }
+
+    fn paint(&mut self, vg: &mut VG, region: &PaintRegionInfo) {
+        self.paint_count.set(self.paint_count.get() + 1);
+
+        let rect = region.physical_rect;
+
+        let mut path = Path::new();
+        path.rect(
+            rect.pos.x as f32,
+            rect.pos.y as f32,
+            rect.size.width as f32,
+            rect.size.height as f32,
+        );
+        vg.fill_path(&mut path, &Paint::color(self.color));
+    }
+}
+
+/// The environment variable that makes `TestGlContext::new()` panic instead
+/// of returning `None` when no GL context can be created, so that the tests
+/// that render can't be skipped silently (i.e. in CI).
+pub(crate) const REQUIRE_GL_ENV_VAR: &str = "FIREWHEEL_REQUIRE_GL";
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+type GetPlatformDisplayFn =
+    extern "system" fn(egl::Enum, *mut c_void, *const egl::Int) -> egl::EGLDisplay;
+
+/// An offscreen GL context for tests that render, created with Mesa's
+/// surfaceless EGL platform so that no display server is needed.
+///
+/// The default framebuffer is a pbuffer of the size given to `new()`, so
+/// `AppWindow::render()` and `AppWindow::render_to_rgba()` work as usual.
+pub(crate) struct TestGlContext {
+    egl: egl::DynamicInstance<egl::EGL1_4>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+}
+
+impl TestGlContext {
+    /// Create a context and make it current on this thread.
+    ///
+    /// Returns `None` if no EGL implementation with the surfaceless platform
+    /// is available, in which case the test should return early.
+    ///
+    /// # Panics
+    ///
+    /// Panics instead of returning `None` if the `FIREWHEEL_REQUIRE_GL`
+    /// environment variable is set.
+    pub fn new(size: PhysicalSize) -> Option<Self> {
+        let res = Self::try_new(size);
+        if res.is_none() {
+            if std::env::var_os(REQUIRE_GL_ENV_VAR).is_some() {
+                panic!(
+                    "no surfaceless EGL platform available, but {} is set",
+                    REQUIRE_GL_ENV_VAR
+                );
+            }
+            eprintln!(
+                "skipping test: no surfaceless EGL platform available (set {} to fail instead)",
+                REQUIRE_GL_ENV_VAR
+            );
+        }
+        res
+    }
+
+    /// Whether the given capability (i.e. `GL_FRAMEBUFFER_SRGB`) is enabled
+    /// in this context.
+    pub fn is_enabled(&self, cap: u32) -> bool {
+        let is_enabled: extern "system" fn(u32) -> u8 =
+            unsafe { std::mem::transmute(self.egl.get_proc_address("glIsEnabled").unwrap()) };
+        is_enabled(cap) != 0
+    }
+
+    fn try_new(size: PhysicalSize) -> Option<Self> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }.ok()?;
+
+        let get_platform_display: GetPlatformDisplayFn =
+            unsafe { std::mem::transmute(egl.get_proc_address("eglGetPlatformDisplayEXT")?) };
+        let display = get_platform_display(
+            PLATFORM_SURFACELESS_MESA,
+            std::ptr::null_mut(),
+            [egl::NONE].as_ptr(),
+        );
+        if display.is_null() {
+            return None;
+        }
+        let display = unsafe { egl::Display::from_ptr(display) };
+
+        egl.initialize(display).ok()?;
+        egl.bind_api(egl::OPENGL_API).ok()?;
+
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::SURFACE_TYPE,
+                    egl::PBUFFER_BIT,
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_BIT,
+                    egl::RED_SIZE,
+                    8,
+                    egl::GREEN_SIZE,
+                    8,
+                    egl::BLUE_SIZE,
+                    8,
+                    egl::ALPHA_SIZE,
+                    8,
+                    egl::STENCIL_SIZE,
+                    8,
+                    egl::NONE,
+                ],
+            )
+            .ok()??;
+        let surface = egl
+            .create_pbuffer_surface(
+                display,
+                config,
+                &[
+                    egl::WIDTH,
+                    size.width as egl::Int,
+                    egl::HEIGHT,
+                    size.height as egl::Int,
+                    egl::NONE,
+                ],
+            )
+            .ok()?;
+        let context = egl
+            .create_context(
+                display,
+                config,
+                None,
+                &[
+                    egl::CONTEXT_MAJOR_VERSION,
+                    3,
+                    egl::CONTEXT_MINOR_VERSION,
+                    3,
+                    egl::CONTEXT_OPENGL_PROFILE_MASK,
+                    egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    egl::NONE,
+                ],
+            )
+            .ok()?;
+        egl.make_current(display, Some(surface), Some(surface), Some(context))
+            .ok()?;
+
+        Some(Self {
+            egl,
+            display,
+            surface,
+            context,
+        })
+    }
+
+    /// Create an `AppWindow` that renders with this context.
+    ///
+    /// The window must be dropped before this context.
+    pub fn new_app_window<A: Clone + Send + Sync + 'static>(
+        &self,
+        scale_factor: ScaleFactor,
+        action_tx: Sender<A>,
+    ) -> AppWindow<A> {
+        self.new_app_window_with_settings(scale_factor, AppWindowSettings::default(), action_tx)
+    }
+
+    pub fn new_app_window_with_settings<A: Clone + Send + Sync + 'static>(
+        &self,
+        scale_factor: ScaleFactor,
+        settings: AppWindowSettings,
+        action_tx: Sender<A>,
+    ) -> AppWindow<A> {
+        unsafe {
+            AppWindow::new_from_function(
+                scale_factor,
+                |name| {
+                    self.egl
+                        .get_proc_address(name)
+                        .map_or(std::ptr::null(), |f| f as *const c_void)
+                },
+                None,
+                settings,
+                action_tx,
+            )
+        }
+    }
+
+    /// Resume the GPU resources of a window that was created with this
+    /// context and then suspended with `AppWindow::suspend_gpu()`.
+    pub fn resume_gpu<A: Clone + Send + Sync + 'static>(&self, app_window: &mut AppWindow<A>) {
+        unsafe {
+            app_window.resume_gpu(|name| {
+                self.egl
+                    .get_proc_address(name)
+                    .map_or(std::ptr::null(), |f| f as *const c_void)
+            })
+        }
+    }
+}
+
+impl Drop for TestGlContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_surface(self.display, self.surface);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+/// The setup shared by the tests that render: a `TestGlContext` and a window
+/// that renders with it.
+pub(crate) struct GlTestWindow {
+    // Fields are dropped in order, so the window is dropped before the
+    // context.
+    pub app_window: AppWindow<()>,
+    pub gl: TestGlContext,
+    _action_rx: Receiver<()>,
+}
+
+impl GlTestWindow {
+    /// Returns `None` if the test should return early (see
+    /// `TestGlContext::new()`).
+    pub fn new(window_size: PhysicalSize, scale_factor: ScaleFactor) -> Option<Self> {
+        Self::with_settings(window_size, scale_factor, AppWindowSettings::default())
+    }
+
+    pub fn with_settings(
+        window_size: PhysicalSize,
+        scale_factor: ScaleFactor,
+        settings: AppWindowSettings,
+    ) -> Option<Self> {
+        let gl = TestGlContext::new(window_size)?;
+        let (action_tx, action_rx) = crossbeam_channel::unbounded();
+        let app_window = gl.new_app_window_with_settings(scale_factor, settings, action_tx);
+
+        Some(Self {
+            app_window,
+            gl,
+            _action_rx: action_rx,
+        })
+    }
+}
+
+/// Add a layer of the given size at the top left corner of the window, with
+/// `widget` at `rect` inside of it.
+pub(crate) fn add_layer_with_widget(
+    app_window: &mut AppWindow<()>,
+    layer_size: Size,
+    widget: TestWidget,
+    rect: Rect,
+) -> (WidgetLayerRef<()>, WidgetNodeRef<()>) {
+    let layer = app_window.add_widget_layer(
+        layer_size,
+        0,
+        Point::new(0.0, 0.0),
+        Point::new(0.0, 0.0),
+        true,
+    );
+    let widget = app_window
+        .add_widget_node_at_rect(Box::new(widget), &layer, rect, true)
+        .unwrap();
+
+    (layer, widget)
+}
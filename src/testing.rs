@@ -0,0 +1,238 @@
+//! Helpers for visual regression tests.
+//!
+//! Render a frame with `AppWindow::render_to_rgba()` and compare it against
+//! a reference ("golden") image with `assert_image_matches()`:
+//!
+//! ```ignore
+//! let pixels = app_window.render_to_rgba(window_size, clear_color)?;
+//! firewheel::testing::assert_image_matches(&pixels, window_size, "golden/button.png", 2);
+//! ```
+//!
+//! If the golden image doesn't exist yet, set the `FIREWHEEL_UPDATE_GOLDEN`
+//! environment variable to write it (this also overwrites existing golden
+//! images after an intended change to the rendered output).
+
+use std::path::{Path, PathBuf};
+
+use crate::app_window::write_png;
+use crate::size::PhysicalSize;
+
+/// The environment variable that makes `assert_image_matches()` write the
+/// actual image to the golden path instead of comparing against it.
+pub const UPDATE_GOLDEN_ENV_VAR: &str = "FIREWHEEL_UPDATE_GOLDEN";
+
+/// Compare RGBA pixels with straight alpha (as returned by
+/// `AppWindow::render_to_rgba()`) against the PNG at `golden_path`.
+///
+/// A pixel matches if none of its channels differ by more than `tolerance`
+/// from the golden image, which allows for small differences between GPU
+/// drivers.
+///
+/// # Panics
+///
+/// Panics if the golden image doesn't exist, if its size differs from
+/// `size`, or if any pixel doesn't match. When pixels don't match, the
+/// actual image and a diff image (with the mismatched pixels in red) are
+/// written next to the golden image as `<name>.actual.png` and
+/// `<name>.diff.png`.
+pub fn assert_image_matches<P: AsRef<Path>>(
+    actual: &[u8],
+    size: PhysicalSize,
+    golden_path: P,
+    tolerance: u8,
+) {
+    let golden_path = golden_path.as_ref();
+    assert_eq!(
+        actual.len(),
+        size.width as usize * size.height as usize * 4,
+        "the actual image does not have the given size"
+    );
+
+    if std::env::var_os(UPDATE_GOLDEN_ENV_VAR).is_some() {
+        if let Some(dir) = golden_path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        write_png(golden_path, actual, size).unwrap();
+        return;
+    }
+
+    let golden = match image::open(golden_path) {
+        Ok(golden) => golden.into_rgba8(),
+        Err(e) => panic!(
+            "could not open the golden image {}: {} (set {} to create it)",
+            golden_path.display(),
+            e,
+            UPDATE_GOLDEN_ENV_VAR
+        ),
+    };
+    assert_eq!(
+        golden.dimensions(),
+        (size.width, size.height),
+        "the size of the golden image {} does not match",
+        golden_path.display()
+    );
+
+    let (mismatched, diff) = image_diff(actual, golden.as_raw(), tolerance);
+    if mismatched > 0 {
+        let actual_path = sibling_path(golden_path, "actual");
+        let diff_path = sibling_path(golden_path, "diff");
+        write_png(&actual_path, actual, size).unwrap();
+        write_png(&diff_path, &diff, size).unwrap();
+
+        panic!(
+            "{} pixels differ from the golden image {} by more than {} (see {} and {})",
+            mismatched,
+            golden_path.display(),
+            tolerance,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+/// Compare two RGBA images of the same size and return the number of pixels
+/// with a channel that differs by more than `tolerance`, along with a diff
+/// image. The diff image shows the mismatched pixels in opaque red over a
+/// faded grayscale copy of the actual image.
+fn image_diff(actual: &[u8], expected: &[u8], tolerance: u8) -> (usize, Vec<u8>) {
+    let mut mismatched = 0;
+    let mut diff = Vec::with_capacity(actual.len());
+
+    for (a, e) in actual.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let matches = a
+            .iter()
+            .zip(e.iter())
+            .all(|(a, e)| a.abs_diff(*e) <= tolerance);
+        if matches {
+            let luma = (u16::from(a[0]) + u16::from(a[1]) + u16::from(a[2])) / 3;
+            let faded = (luma / 4) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        } else {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        }
+    }
+
+    (mismatched, diff)
+}
+
+/// `dir/name.png` -> `dir/name.<suffix>.png`
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::GlTestWindow;
+    use crate::widgets::{LabelButton, LabelButtonStyle};
+    use crate::{Anchor, AnchorOffset, ParentAnchorType, Point, RegionInfo, ScaleFactor};
+    use femtovg::Color;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_image_diff() {
+        // A 2x1 image where the second pixel is off by more than the
+        // tolerance.
+        let expected = [100, 100, 100, 255, 0, 0, 0, 0];
+        let actual = [102, 99, 100, 255, 0, 0, 10, 10];
+
+        let (mismatched, diff) = image_diff(&actual, &expected, 2);
+        assert_eq!(mismatched, 1);
+        assert_eq!(diff[4..8], [255, 0, 0, 255]);
+        assert_eq!(diff[3], 255);
+        assert_ne!(diff[0..3], [255, 0, 0]);
+
+        assert_eq!(image_diff(&actual, &expected, 10).0, 0);
+    }
+
+    #[test]
+    fn test_assert_image_matches() {
+        let size = PhysicalSize::new(2, 1);
+        let golden = [255, 0, 0, 255, 0, 0, 255, 255];
+
+        // Include the process ID so that concurrent test runs don't write
+        // to the same directory.
+        let dir = std::env::temp_dir().join(format!(
+            "firewheel_test_assert_image_matches_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let golden_path = dir.join("golden.png");
+        write_png(&golden_path, &golden, size).unwrap();
+
+        // Within tolerance.
+        assert_image_matches(&[254, 1, 0, 255, 0, 0, 255, 255], size, &golden_path, 1);
+
+        // A mismatch writes the actual and diff images next to the golden
+        // image.
+        let res = std::panic::catch_unwind(|| {
+            assert_image_matches(&[255, 0, 0, 255, 0, 255, 0, 255], size, &golden_path, 1);
+        });
+        assert!(res.is_err());
+        let diff = image::open(dir.join("golden.diff.png"))
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(diff.get_pixel(1, 0).0, [255, 0, 0, 255]);
+        assert!(dir.join("golden.actual.png").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The golden test for the button in the test app. Run it with
+    /// `FIREWHEEL_UPDATE_GOLDEN=1` after an intended change to how the
+    /// button is rendered.
+    #[test]
+    fn test_label_button_golden() {
+        const GOLDEN_PATH: &str = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/test-app/golden/button.png"
+        );
+        let window_size = PhysicalSize::new(400, 300);
+        let scale_factor = ScaleFactor(1.0);
+
+        let mut window = match GlTestWindow::new(window_size, scale_factor) {
+            Some(window) => window,
+            None => return,
+        };
+        let app_window = &mut window.app_window;
+        let font_id = app_window
+            .add_font_from_bytes(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+        let layer = app_window.add_widget_layer(
+            window_size.to_logical(scale_factor),
+            0,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.0),
+            true,
+        );
+
+        let label = "Hello World!";
+        let style = Rc::new(LabelButtonStyle::default());
+        let size = style.compute_size(label, font_id, scale_factor, app_window.vg());
+        app_window
+            .add_widget_node(
+                Box::new(LabelButton::new(label.into(), font_id, style, None, true)),
+                &layer,
+                RegionInfo {
+                    size,
+                    internal_anchor: Anchor::center(),
+                    parent_anchor: Anchor::center(),
+                    parent_anchor_type: ParentAnchorType::Layer,
+                    anchor_offset: AnchorOffset::default(),
+                    normalized_parent_anchor: None,
+                },
+                true,
+            )
+            .unwrap();
+
+        let pixels = app_window
+            .render_to_rgba(window_size, Color::rgb(30, 30, 30))
+            .unwrap();
+        assert_image_matches(&pixels, window_size, GOLDEN_PATH, 2);
+    }
+}
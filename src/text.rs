@@ -0,0 +1,276 @@
+//! Helpers for mapping between positions in a line of text and the indexes
+//! of its characters, i.e. for placing the caret and drawing the selection
+//! of a text input widget.
+//!
+//! Indexes are in characters (not bytes), where index `0` is before the first
+//! character and `text.chars().count()` is after the last one. Positions are
+//! in logical coordinates (points) relative to the point the text is drawn
+//! at with the paint used by `compute_font_bounds()`.
+//!
+//! femtovg shapes each run of right-to-left text (i.e. Arabic or Hebrew) in
+//! its own direction, so the width of the text measured with
+//! `compute_font_bounds()` is the same for any direction. In a right-to-left
+//! run the caret before a character is on its right side, so the caret
+//! positions of mixed-direction text are not in order from left to right.
+
+use unicode_bidi::{BidiInfo, Level};
+
+use crate::{font_paint, ScaleFactor, VG};
+
+/// The base direction of a paragraph of text, which decides the direction
+/// of characters that don't have one of their own (i.e. spaces and
+/// punctuation).
+///
+/// This only affects the caret positions. femtovg decides the visual order
+/// of the runs in the text on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextDirection {
+    /// Use the direction of the first character that has a strong direction
+    /// (the default of the Unicode Bidirectional Algorithm).
+    Auto,
+    /// Left-to-right.
+    Ltr,
+    /// Right-to-left.
+    Rtl,
+}
+
+impl Default for TextDirection {
+    fn default() -> Self {
+        TextDirection::Auto
+    }
+}
+
+impl TextDirection {
+    fn default_level(&self) -> Option<Level> {
+        match self {
+            TextDirection::Auto => None,
+            TextDirection::Ltr => Some(Level::ltr()),
+            TextDirection::Rtl => Some(Level::rtl()),
+        }
+    }
+}
+
+/// The index of the caret position closest to `x_pts` (i.e. where the user
+/// clicked in the text).
+pub fn caret_index_at_x(
+    text: &str,
+    font_id: femtovg::FontId,
+    font_size_pts: f32,
+    x_pts: f32,
+    direction: TextDirection,
+    scale_factor: ScaleFactor,
+    vg: &VG,
+) -> usize {
+    let font_paint = font_paint(font_id, font_size_pts, scale_factor);
+    let font_metrics = vg.measure_text(0.0, 0.0, text, &font_paint).unwrap();
+
+    nearest_caret_stop(
+        &caret_stops(text, &font_metrics, direction, scale_factor),
+        x_pts,
+    )
+}
+
+/// The x offset of the caret position before the character at `index`.
+///
+/// An `index` past the end of the text returns the offset after the last
+/// character.
+pub fn char_x_offset(
+    text: &str,
+    index: usize,
+    font_id: femtovg::FontId,
+    font_size_pts: f32,
+    direction: TextDirection,
+    scale_factor: ScaleFactor,
+    vg: &VG,
+) -> f32 {
+    let font_paint = font_paint(font_id, font_size_pts, scale_factor);
+    let font_metrics = vg.measure_text(0.0, 0.0, text, &font_paint).unwrap();
+
+    let stops = caret_stops(text, &font_metrics, direction, scale_factor);
+    stops[index.min(stops.len() - 1)]
+}
+
+/// The x offset in points of each caret position in the text, from before
+/// the first character to after the last one.
+///
+/// The caret before a character is on its leading edge, which is the right
+/// edge of characters in a right-to-left run. Characters which don't start a
+/// glyph of their own (i.e. the second character of a ligature) share the
+/// position of the previous character.
+pub(crate) fn caret_stops(
+    text: &str,
+    font_metrics: &femtovg::TextMetrics,
+    direction: TextDirection,
+    scale_factor: ScaleFactor,
+) -> Vec<f32> {
+    let num_chars = text.chars().count();
+    let mut stops: Vec<Option<f32>> = vec![None; num_chars + 1];
+    let bidi_info = BidiInfo::new(text, direction.default_level());
+
+    // The trailing edge of the last character in logical order.
+    let mut end: Option<(usize, f32)> = None;
+
+    for glyph in font_metrics.glyphs.iter() {
+        let byte_index = glyph.byte_index.min(text.len());
+        let char_index = text[..byte_index].chars().count().min(num_chars);
+        let is_rtl = bidi_info
+            .levels
+            .get(byte_index)
+            .map(|level| level.is_rtl())
+            .unwrap_or(false);
+
+        // `x` is the left edge of the glyph's bitmap, so remove the bearing
+        // to get the position of the pen.
+        let left_x = glyph.x - glyph.bearing_x;
+        let right_x = left_x + glyph.advance_x;
+        let (leading_x, trailing_x) = if is_rtl {
+            (right_x, left_x)
+        } else {
+            (left_x, right_x)
+        };
+
+        let stop = &mut stops[char_index];
+        if stop.is_none() {
+            *stop = Some(leading_x);
+        }
+        if end.map(|(i, _)| char_index >= i).unwrap_or(true) {
+            end = Some((char_index, trailing_x));
+        }
+    }
+
+    stops[num_chars] = Some(end.map(|(_, x)| x).unwrap_or(0.0));
+
+    let mut last_x = 0.0;
+    stops
+        .into_iter()
+        .map(|stop| {
+            if let Some(x) = stop {
+                last_x = x;
+            }
+            last_x / scale_factor.0
+        })
+        .collect()
+}
+
+/// The index of the caret stop closest to `x_pts`.
+fn nearest_caret_stop(stops: &[f32], x_pts: f32) -> usize {
+    let mut nearest = 0;
+    let mut nearest_dist = f32::INFINITY;
+    for (i, stop_x) in stops.iter().enumerate() {
+        let dist = (stop_x - x_pts).abs();
+        if dist < nearest_dist {
+            nearest = i;
+            nearest_dist = dist;
+        }
+    }
+
+    nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use femtovg::TextContext;
+
+    #[test]
+    fn test_caret_index_between_glyphs() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let scale_factor = ScaleFactor(2.0);
+        let paint = font_paint(font_id, 14.0, scale_factor);
+        let stops = |text: &str| {
+            let metrics = text_context.measure_text(0.0, 0.0, text, &paint).unwrap();
+            caret_stops(text, &metrics, TextDirection::Auto, scale_factor)
+        };
+
+        let stops_wm = stops("WM");
+        assert_eq!(stops_wm.len(), 3);
+        assert_eq!(stops_wm[0], 0.0);
+
+        // The caret between the glyphs is after the advance of the "W".
+        let w_width = *stops("W").last().unwrap();
+        assert!(w_width > 0.0);
+        assert!((stops_wm[1] - w_width).abs() < 0.01);
+        assert!(stops_wm[2] > stops_wm[1]);
+
+        // Clicking just to either side of the boundary between the glyphs
+        // places the caret between them.
+        assert_eq!(nearest_caret_stop(&stops_wm, stops_wm[1] - 1.0), 1);
+        assert_eq!(nearest_caret_stop(&stops_wm, stops_wm[1] + 1.0), 1);
+        // Clicking near the start of the "W" places the caret before it.
+        assert_eq!(nearest_caret_stop(&stops_wm, w_width * 0.25), 0);
+        // Clicking outside of the text places the caret at the closest end.
+        assert_eq!(nearest_caret_stop(&stops_wm, -20.0), 0);
+        assert_eq!(nearest_caret_stop(&stops_wm, stops_wm[2] + 20.0), 2);
+    }
+
+    #[test]
+    fn test_caret_stops_multibyte_chars() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let scale_factor = ScaleFactor(1.0);
+        let paint = font_paint(font_id, 14.0, scale_factor);
+        let text = "aéb";
+        let metrics = text_context.measure_text(0.0, 0.0, text, &paint).unwrap();
+        let stops = caret_stops(text, &metrics, TextDirection::Auto, scale_factor);
+
+        // There is one stop per character (not per byte).
+        assert_eq!(stops.len(), 4);
+        assert!(stops.windows(2).all(|w| w[1] > w[0]));
+
+        // Forcing the direction of left-to-right text doesn't change it.
+        assert_eq!(
+            caret_stops(text, &metrics, TextDirection::Ltr, scale_factor),
+            stops
+        );
+    }
+
+    #[test]
+    fn test_rtl_caret_stops() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let scale_factor = ScaleFactor(2.0);
+        let paint = font_paint(font_id, 14.0, scale_factor);
+        let width = |text: &str| {
+            text_context
+                .measure_text(0.0, 0.0, text, &paint)
+                .unwrap()
+                .width()
+                / scale_factor.0
+        };
+
+        // Hebrew for "shalom". Roboto has no Hebrew glyphs, but the missing
+        // glyphs are still shaped from right to left.
+        let text = "שלום";
+        let metrics = text_context.measure_text(0.0, 0.0, text, &paint).unwrap();
+        let text_width = metrics.width() / scale_factor.0;
+
+        // The text measures the same as its characters laid side by side.
+        let chars_width: f32 = text.chars().map(|c| width(&c.to_string())).sum();
+        assert!(text_width > 0.0);
+        assert!((text_width - chars_width).abs() < 0.01);
+
+        // The carets read from right to left, starting at the right edge of
+        // the text.
+        let stops = caret_stops(text, &metrics, TextDirection::Auto, scale_factor);
+        assert_eq!(stops.len(), 5);
+        assert!((stops[0] - text_width).abs() < 0.01);
+        assert!(stops[4].abs() < 0.01);
+        assert!(stops.windows(2).all(|w| w[1] < w[0]));
+
+        // Clicking near the right edge places the caret before the first
+        // character, and clicking near the left edge places it after the
+        // last one.
+        assert_eq!(nearest_caret_stop(&stops, text_width - 1.0), 0);
+        assert_eq!(nearest_caret_stop(&stops, 1.0), 4);
+    }
+}
@@ -0,0 +1,222 @@
+use femtovg::FontId;
+use fnv::FnvHashMap;
+
+use crate::{ScaleFactor, Size};
+
+/// The maximum number of entries kept by default.
+pub const DEFAULT_TEXT_LAYOUT_CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FontKey {
+    font_id: FontId,
+    font_size_bits: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CacheEntry {
+    size: Size,
+    last_used: u64,
+}
+
+/// Memoizes the measured size of text so that static labels don't need to
+/// be re-measured on every layout pass.
+///
+/// All entries are invalidated when the scale factor changes or when a font
+/// is loaded. Once the cache holds `capacity` entries, the least recently
+/// used entry is evicted to make room for a new one.
+pub struct TextLayoutCache {
+    entries: FnvHashMap<FontKey, FnvHashMap<String, CacheEntry>>,
+    len: usize,
+    capacity: usize,
+    /// Incremented on every lookup, used to find the least recently used
+    /// entry.
+    clock: u64,
+    scale_factor: ScaleFactor,
+}
+
+impl TextLayoutCache {
+    pub fn new(scale_factor: ScaleFactor) -> Self {
+        Self::with_capacity(scale_factor, DEFAULT_TEXT_LAYOUT_CACHE_CAPACITY)
+    }
+
+    pub fn with_capacity(scale_factor: ScaleFactor, capacity: usize) -> Self {
+        Self {
+            entries: FnvHashMap::default(),
+            len: 0,
+            capacity: capacity.max(1),
+            clock: 0,
+            scale_factor,
+        }
+    }
+
+    /// Get the cached size of the given text, or measure it with `measure`
+    /// and cache the result.
+    ///
+    /// Looking up text that is already cached does not allocate.
+    pub fn get_or_measure<F: FnOnce() -> Size>(
+        &mut self,
+        text: &str,
+        font_id: FontId,
+        font_size_pts: f32,
+        scale_factor: ScaleFactor,
+        measure: F,
+    ) -> Size {
+        if self.scale_factor != scale_factor {
+            self.scale_factor = scale_factor;
+            self.invalidate();
+        }
+
+        self.clock += 1;
+
+        let font_key = FontKey {
+            font_id,
+            font_size_bits: font_size_pts.to_bits(),
+        };
+
+        if let Some(entry) = self
+            .entries
+            .get_mut(&font_key)
+            .and_then(|texts| texts.get_mut(text))
+        {
+            entry.last_used = self.clock;
+            return entry.size;
+        }
+
+        if self.len >= self.capacity {
+            self.evict_least_recently_used();
+        }
+
+        let size = measure();
+        self.entries.entry(font_key).or_default().insert(
+            text.into(),
+            CacheEntry {
+                size,
+                last_used: self.clock,
+            },
+        );
+        self.len += 1;
+
+        size
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let mut oldest: Option<(FontKey, &str, u64)> = None;
+        for (font_key, texts) in self.entries.iter() {
+            for (text, entry) in texts.iter() {
+                if oldest.is_none_or(|(_, _, last_used)| entry.last_used < last_used) {
+                    oldest = Some((*font_key, text.as_str(), entry.last_used));
+                }
+            }
+        }
+
+        let (font_key, text) = match oldest {
+            Some((font_key, text, _)) => (font_key, String::from(text)),
+            None => return,
+        };
+
+        if let Some(texts) = self.entries.get_mut(&font_key) {
+            if texts.remove(&text).is_some() {
+                self.len -= 1;
+            }
+            if texts.is_empty() {
+                self.entries.remove(&font_key);
+            }
+        }
+    }
+
+    /// Remove all cached entries.
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use femtovg::TextContext;
+
+    #[test]
+    fn test_text_layout_cache() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let mut cache = TextLayoutCache::new(ScaleFactor(1.0));
+        let mut num_measurements = 0;
+
+        let mut measure = |cache: &mut TextLayoutCache, scale_factor: ScaleFactor| {
+            cache.get_or_measure("Hello", font_id, 16.0, scale_factor, || {
+                num_measurements += 1;
+                Size::new(40.0, 20.0)
+            })
+        };
+
+        // Repeated measurements of the same text should hit the cache.
+        assert_eq!(measure(&mut cache, ScaleFactor(1.0)), Size::new(40.0, 20.0));
+        assert_eq!(measure(&mut cache, ScaleFactor(1.0)), Size::new(40.0, 20.0));
+        assert_eq!(measure(&mut cache, ScaleFactor(1.0)), Size::new(40.0, 20.0));
+        assert_eq!(cache.len(), 1);
+
+        // Changing the scale factor should invalidate the cache.
+        measure(&mut cache, ScaleFactor(2.0));
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(num_measurements, 2);
+
+        // Different text is measured separately.
+        cache.get_or_measure("World", font_id, 16.0, ScaleFactor(2.0), || {
+            Size::new(42.0, 20.0)
+        });
+        assert_eq!(cache.len(), 2);
+
+        cache.invalidate();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_text_layout_cache_evicts_least_recently_used() {
+        let text_context = TextContext::default();
+        let font_id = text_context
+            .add_font_mem(include_bytes!("../examples/assets/Roboto-Regular.ttf"))
+            .unwrap();
+
+        let mut cache = TextLayoutCache::with_capacity(ScaleFactor(1.0), 2);
+        let mut measured = Vec::new();
+
+        let mut measure = |cache: &mut TextLayoutCache, text: &'static str| {
+            cache.get_or_measure(text, font_id, 16.0, ScaleFactor(1.0), || {
+                measured.push(text);
+                Size::new(10.0, 20.0)
+            });
+        };
+
+        measure(&mut cache, "a");
+        measure(&mut cache, "b");
+        // Use "a" again so that "b" becomes the least recently used entry.
+        measure(&mut cache, "a");
+        measure(&mut cache, "c");
+        assert_eq!(cache.len(), 2);
+
+        // "a" and "c" are still cached, "b" was evicted.
+        measure(&mut cache, "a");
+        measure(&mut cache, "c");
+        measure(&mut cache, "b");
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(measured, vec!["a", "b", "c", "b"]);
+    }
+}
@@ -37,6 +37,10 @@ impl<A: Clone + Send + Sync + 'static> WidgetNodeSet<A> {
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &StrongWidgetNodeEntry<A>> {
+        self.entries.iter()
+    }
+
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut StrongWidgetNodeEntry<A>> {
         self.entries.iter_mut()
     }
@@ -49,6 +53,10 @@ impl<A: Clone + Send + Sync + 'static> WidgetNodeSet<A> {
         self.entries.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
     pub fn clear(&mut self) {
         self.unique_ids.clear();
         self.entries.clear();
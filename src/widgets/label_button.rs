@@ -2,7 +2,7 @@ use crossbeam_channel::Sender;
 use std::any::Any;
 use std::rc::Rc;
 
-use crate::vg::{Color, FontId, Paint};
+use crate::vg::{Color, FontId, Paint, TextContext};
 use crate::{
     event::InputEvent, BgColor, EventCapturedStatus, GradientDirection, PaintRegionInfo, Point,
     Rect, ScaleFactor, Size, WidgetNode, WidgetNodeRequests, WidgetNodeType, VG,
@@ -56,6 +56,14 @@ pub struct LabelButtonStyle {
     pub keyboard_focus_bg_color: BgColor,
     pub keyboard_focus_border_color: Color,
     pub keyboard_focus_font_color: Color,
+
+    /// The width of the ring drawn around the button while it has keyboard
+    /// focus. Set this to `0.0` to disable the focus ring.
+    pub keyboard_focus_ring_width_pts: f32,
+    /// How far the focus ring is inset from the edges of the button's
+    /// region. A negative value draws the ring outside of the region.
+    pub keyboard_focus_ring_inset_pts: f32,
+    pub keyboard_focus_ring_color: Color,
 }
 
 impl LabelButtonStyle {
@@ -113,6 +121,10 @@ impl Default for LabelButtonStyle {
             keyboard_focus_bg_color: BgColor::Solid(Color::rgb(41, 41, 41)),
             keyboard_focus_border_color: Color::rgb(150, 150, 150),
             keyboard_focus_font_color: Color::rgb(235, 235, 235),
+
+            keyboard_focus_ring_width_pts: 0.0,
+            keyboard_focus_ring_inset_pts: 0.0,
+            keyboard_focus_ring_color: Color::rgb(150, 150, 150),
         }
     }
 }
@@ -160,6 +172,7 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
             WidgetNodeType::Painted,
             WidgetNodeRequests {
                 set_pointer_events_listen: Some(true),
+                set_focusable: Some(true),
                 ..Default::default()
             },
         )
@@ -167,6 +180,8 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
 
     fn on_visibility_hidden(&mut self, _action_tx: &mut Sender<A>) {
         self.state = ButtonState::Idle;
+        // Hidden widgets lose the keyboard focus.
+        self.keyboard_focused = false;
     }
 
     fn on_region_changed(&mut self, assigned_rect: Rect) {
@@ -231,6 +246,7 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
     fn on_input_event(
         &mut self,
         event: &InputEvent,
+        _text_context: &TextContext,
         action_tx: &mut Sender<A>,
     ) -> EventCapturedStatus {
         match event {
@@ -337,6 +353,30 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
                     }
                 }
             }
+            InputEvent::KeyboardFocused => {
+                self.keyboard_focused = true;
+
+                if let ButtonState::Idle = self.state {
+                    self.state = ButtonState::KeyboardFocus;
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests {
+                        repaint: true,
+                        ..Default::default()
+                    });
+                }
+            }
+            InputEvent::KeyboardUnfocused => {
+                self.keyboard_focused = false;
+
+                if let ButtonState::KeyboardFocus = self.state {
+                    self.state = ButtonState::Idle;
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests {
+                        repaint: true,
+                        ..Default::default()
+                    });
+                }
+            }
             _ => {}
         }
 
@@ -407,6 +447,25 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
         vg.fill_path(&mut bg_path, &bg_paint);
         vg.stroke_path(&mut bg_path, &border_paint);
 
+        if let ButtonState::KeyboardFocus = self.state {
+            if self.style.keyboard_focus_ring_width_pts > 0.0 {
+                let ring_width_px =
+                    (self.style.keyboard_focus_ring_width_pts * region.scale_factor.0).round();
+
+                // Keep the stroke within the ring's rect.
+                let mut ring_path = region.focus_ring_path(
+                    self.style.keyboard_focus_ring_inset_pts
+                        + (ring_width_px / 2.0 / region.scale_factor.0),
+                    self.style.border_radius_pts,
+                );
+
+                let mut ring_paint = Paint::color(self.style.keyboard_focus_ring_color);
+                ring_paint.set_line_width(ring_width_px);
+
+                vg.stroke_path(&mut ring_path, &ring_paint);
+            }
+        }
+
         if !self.label.is_empty() {
             let label_rect_width_px = region.physical_rect.size.width as f32
                 - (f32::from(self.style.margin_lr_pts + self.style.padding_lr_pts)
@@ -432,12 +491,34 @@ impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for LabelButton<A> {
                     label_rect_height_px,
                 );
 
-                let mut font_paint = Paint::color(*font_color);
-                font_paint.set_font(&[self.font_id]);
-                font_paint.set_font_size(self.style.font_size_pts * region.scale_factor.0);
-                font_paint.set_text_baseline(crate::vg::Baseline::Top);
+                let font_size_px = self.style.font_size_pts * region.scale_factor.0;
 
-                vg.fill_text(label_rect_x_px, label_rect_y_px, &self.label, &font_paint);
+                // Draw with the window's software text renderer, in case
+                // femtovg can't draw glyphs on this context.
+                #[cfg(feature = "software-text")]
+                let _ = crate::software_text::fill_text(
+                    vg,
+                    label_rect_x_px,
+                    label_rect_y_px,
+                    &self.label,
+                    &crate::software_text::TextPaint {
+                        font_id: self.font_id,
+                        font_size_px,
+                        color: *font_color,
+                        baseline: crate::vg::Baseline::Top,
+                    },
+                );
+
+                #[cfg(not(feature = "software-text"))]
+                {
+                    let mut font_paint = Paint::color(*font_color);
+                    font_paint.set_font(&[self.font_id]);
+                    font_paint.set_font_size(font_size_px);
+                    font_paint.set_text_baseline(crate::vg::Baseline::Top);
+
+                    let _ =
+                        vg.fill_text(label_rect_x_px, label_rect_y_px, &self.label, &font_paint);
+                }
 
                 vg.reset_scissor();
             }
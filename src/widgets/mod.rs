@@ -1,3 +1,7 @@
 mod label_button;
+mod resize_handle;
 
 pub use label_button::{LabelButton, LabelButtonEvent, LabelButtonStyle};
+pub use resize_handle::{
+    resize_container_region, ResizeEdge, ResizeHandle, ResizeHandleEvent, ResizeHandleStyle,
+};
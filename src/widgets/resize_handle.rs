@@ -0,0 +1,396 @@
+use crossbeam_channel::Sender;
+use std::any::Any;
+use std::rc::Rc;
+
+use crate::error::FirewheelError;
+use crate::vg::{Color, Paint, Path, TextContext};
+use crate::{
+    event::InputEvent, AppWindow, ContainerRegionRef, EventCapturedStatus, PaintRegionInfo, Point,
+    Rect, Size, WidgetNode, WidgetNodeRequests, WidgetNodeType, VG,
+};
+
+/// The edge of a container region that a `ResizeHandle` drags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl ResizeEdge {
+    /// Whether dragging this edge changes the width (as opposed to the
+    /// height) of the region.
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, ResizeEdge::Left | ResizeEdge::Right)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HandleState {
+    Idle,
+    Hovered,
+    Dragging {
+        start_pointer_pos: Point,
+        start_size_pts: f32,
+    },
+}
+
+pub enum ResizeHandleEvent {
+    /// Set the current size of the target region (i.e. after the region was
+    /// resized by something other than this handle).
+    SetSize(f32),
+    SetLimits {
+        min_size_pts: f32,
+        max_size_pts: f32,
+    },
+    SetStyle(Rc<ResizeHandleStyle>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ResizeHandleStyle {
+    pub idle_color: Color,
+    pub hover_color: Color,
+    pub drag_color: Color,
+}
+
+impl Default for ResizeHandleStyle {
+    fn default() -> Self {
+        Self {
+            idle_color: Color::rgb(22, 22, 22),
+            hover_color: Color::rgb(90, 90, 90),
+            drag_color: Color::rgb(150, 150, 150),
+        }
+    }
+}
+
+/// A thin handle that resizes a container region when it is dragged (i.e.
+/// the border between a side panel and the rest of the window).
+///
+/// The handle is usually placed along the dragged edge of the target region.
+/// While it is dragged, it captures the pointer and sends the action returned
+/// by `on_resize` with the new size (in points) of the target region along
+/// the axis of the edge, clamped to the handle's limits. The app then applies
+/// the new size with `resize_container_region()`.
+pub struct ResizeHandle<A> {
+    edge: ResizeEdge,
+    size_pts: f32,
+    min_size_pts: f32,
+    max_size_pts: f32,
+
+    style: Rc<ResizeHandleStyle>,
+
+    on_resize: Box<dyn Fn(f32) -> A>,
+
+    pointer_bounds: Rect,
+
+    state: HandleState,
+}
+
+impl<A: Clone + Send + Sync + 'static> ResizeHandle<A> {
+    /// * `size_pts` - The current size of the target region along the axis
+    /// of `edge`.
+    pub fn new(
+        edge: ResizeEdge,
+        size_pts: f32,
+        min_size_pts: f32,
+        max_size_pts: f32,
+        style: Rc<ResizeHandleStyle>,
+        on_resize: Box<dyn Fn(f32) -> A>,
+    ) -> Self {
+        Self {
+            edge,
+            size_pts,
+            min_size_pts,
+            max_size_pts,
+            style,
+            on_resize,
+            pointer_bounds: Rect::default(),
+            state: HandleState::Idle,
+        }
+    }
+}
+
+impl<A: Clone + Send + Sync + 'static> WidgetNode<A> for ResizeHandle<A> {
+    fn on_added(&mut self, _action_tx: &mut Sender<A>) -> (WidgetNodeType, WidgetNodeRequests) {
+        (
+            WidgetNodeType::Painted,
+            WidgetNodeRequests {
+                set_pointer_events_listen: Some(true),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn on_visibility_hidden(&mut self, _action_tx: &mut Sender<A>) {
+        self.state = HandleState::Idle;
+    }
+
+    fn on_region_changed(&mut self, assigned_rect: Rect) {
+        self.pointer_bounds = assigned_rect;
+    }
+
+    fn on_user_event(
+        &mut self,
+        event: Box<dyn Any>,
+        _action_tx: &mut Sender<A>,
+    ) -> Option<WidgetNodeRequests> {
+        if let Ok(event) = event.downcast::<ResizeHandleEvent>() {
+            match *event {
+                ResizeHandleEvent::SetSize(size_pts) => {
+                    self.size_pts = size_pts;
+                }
+                ResizeHandleEvent::SetLimits {
+                    min_size_pts,
+                    max_size_pts,
+                } => {
+                    self.min_size_pts = min_size_pts;
+                    self.max_size_pts = max_size_pts;
+                }
+                ResizeHandleEvent::SetStyle(style) => {
+                    self.style = style;
+
+                    return Some(WidgetNodeRequests {
+                        repaint: true,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn on_input_event(
+        &mut self,
+        event: &InputEvent,
+        _text_context: &TextContext,
+        action_tx: &mut Sender<A>,
+    ) -> EventCapturedStatus {
+        // TODO: Show a resize cursor icon while hovered once `InputEventResult`
+        // supports cursor icons.
+        match event {
+            InputEvent::Pointer(event) => match self.state {
+                HandleState::Idle | HandleState::Hovered => {
+                    if !self.pointer_bounds.contains_point(event.position) {
+                        if self.state == HandleState::Hovered {
+                            self.state = HandleState::Idle;
+
+                            return EventCapturedStatus::Captured(WidgetNodeRequests {
+                                repaint: true,
+                                set_pointer_leave_listen: Some(false),
+                                ..Default::default()
+                            });
+                        }
+
+                        return EventCapturedStatus::NotCaptured;
+                    }
+
+                    if event.left_button.just_pressed() {
+                        self.state = HandleState::Dragging {
+                            start_pointer_pos: event.position,
+                            start_size_pts: self.size_pts,
+                        };
+
+                        return EventCapturedStatus::Captured(WidgetNodeRequests {
+                            repaint: true,
+                            set_pointer_capture: Some(true),
+                            ..Default::default()
+                        });
+                    }
+
+                    if self.state == HandleState::Idle {
+                        self.state = HandleState::Hovered;
+
+                        return EventCapturedStatus::Captured(WidgetNodeRequests {
+                            repaint: true,
+                            // Listen to when the pointer leaves so we can reset
+                            // the state when it does.
+                            set_pointer_leave_listen: Some(true),
+                            ..Default::default()
+                        });
+                    }
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests::default());
+                }
+                HandleState::Dragging {
+                    start_pointer_pos,
+                    start_size_pts,
+                } => {
+                    let size_pts = dragged_size(
+                        self.edge,
+                        start_size_pts,
+                        start_pointer_pos,
+                        event.position,
+                        self.min_size_pts,
+                        self.max_size_pts,
+                    );
+                    if size_pts != self.size_pts {
+                        self.size_pts = size_pts;
+                        action_tx.send((self.on_resize)(size_pts)).unwrap();
+                    }
+
+                    if event.left_button.just_unpressed() {
+                        self.state = if self.pointer_bounds.contains_point(event.position) {
+                            HandleState::Hovered
+                        } else {
+                            HandleState::Idle
+                        };
+
+                        return EventCapturedStatus::Captured(WidgetNodeRequests {
+                            repaint: true,
+                            set_pointer_capture: Some(false),
+                            set_pointer_leave_listen: Some(self.state == HandleState::Hovered),
+                            ..Default::default()
+                        });
+                    }
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests::default());
+                }
+            },
+            InputEvent::PointerCaptureLost => {
+                if let HandleState::Dragging { .. } = self.state {
+                    self.state = HandleState::Idle;
+
+                    return EventCapturedStatus::Captured(WidgetNodeRequests {
+                        repaint: true,
+                        ..Default::default()
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        EventCapturedStatus::NotCaptured
+    }
+
+    fn paint(&mut self, vg: &mut VG, region: &PaintRegionInfo) {
+        let color = match self.state {
+            HandleState::Idle => self.style.idle_color,
+            HandleState::Hovered => self.style.hover_color,
+            HandleState::Dragging { .. } => self.style.drag_color,
+        };
+
+        let mut path = Path::new();
+        path.rect(
+            region.physical_rect.pos.x as f32,
+            region.physical_rect.pos.y as f32,
+            region.physical_rect.size.width as f32,
+            region.physical_rect.size.height as f32,
+        );
+
+        vg.fill_path(&mut path, &Paint::color(color));
+    }
+}
+
+/// Set the size of `region` along the axis of `edge` to `size_pts` (i.e. in
+/// response to the action sent by a `ResizeHandle`), keeping its size on the
+/// other axis.
+pub fn resize_container_region<A: Clone + Send + Sync + 'static>(
+    app_window: &mut AppWindow<A>,
+    region: &mut ContainerRegionRef<A>,
+    edge: ResizeEdge,
+    size_pts: f32,
+) -> Result<(), FirewheelError> {
+    let size = app_window.container_region_rect(region)?.size();
+    let new_size = if edge.is_horizontal() {
+        Size::new(size_pts, size.height())
+    } else {
+        Size::new(size.width(), size_pts)
+    };
+
+    app_window.modify_container_region(region, Some(new_size), None, None, None)
+}
+
+/// The size of the target region after dragging its `edge` from
+/// `start_pointer_pos` to `pointer_pos`.
+///
+/// Dragging the right or bottom edge away from the region's origin grows it,
+/// while dragging the left or top edge grows it in the opposite direction.
+fn dragged_size(
+    edge: ResizeEdge,
+    start_size_pts: f32,
+    start_pointer_pos: Point,
+    pointer_pos: Point,
+    min_size_pts: f32,
+    max_size_pts: f32,
+) -> f32 {
+    let delta = match edge {
+        ResizeEdge::Left => start_pointer_pos.x - pointer_pos.x,
+        ResizeEdge::Right => pointer_pos.x - start_pointer_pos.x,
+        ResizeEdge::Top => start_pointer_pos.y - pointer_pos.y,
+        ResizeEdge::Bottom => pointer_pos.y - start_pointer_pos.y,
+    } as f32;
+
+    (start_size_pts + delta).max(min_size_pts).min(max_size_pts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dragged_size() {
+        let start = Point::new(200.0, 100.0);
+
+        // Dragging the right edge of a side panel to the right grows it.
+        assert_eq!(
+            dragged_size(
+                ResizeEdge::Right,
+                200.0,
+                start,
+                Point::new(250.0, 120.0),
+                100.0,
+                400.0
+            ),
+            250.0
+        );
+        // Dragging the left edge to the right shrinks it.
+        assert_eq!(
+            dragged_size(
+                ResizeEdge::Left,
+                200.0,
+                start,
+                Point::new(250.0, 120.0),
+                100.0,
+                400.0
+            ),
+            150.0
+        );
+        assert_eq!(
+            dragged_size(
+                ResizeEdge::Top,
+                200.0,
+                start,
+                Point::new(250.0, 80.0),
+                100.0,
+                400.0
+            ),
+            220.0
+        );
+
+        // The size is clamped to the limits.
+        assert_eq!(
+            dragged_size(
+                ResizeEdge::Bottom,
+                200.0,
+                start,
+                Point::new(0.0, 900.0),
+                100.0,
+                400.0
+            ),
+            400.0
+        );
+        assert_eq!(
+            dragged_size(
+                ResizeEdge::Right,
+                200.0,
+                start,
+                Point::new(-50.0, 100.0),
+                100.0,
+                400.0
+            ),
+            100.0
+        );
+    }
+}